@@ -0,0 +1 @@
+../../pyoxidizer/src/pyembed/packing.rs
\ No newline at end of file
@@ -0,0 +1 @@
+../../pyoxidizer/src/pyembed/searchpaths.rs
\ No newline at end of file
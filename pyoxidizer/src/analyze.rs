@@ -4,14 +4,17 @@
 
 //! Analyze binaries for distribution compatibility.
 
-use byteorder::ReadBytesExt;
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt};
 use lazy_static::lazy_static;
-use std::collections::BTreeMap;
+use serde::Serialize;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::ffi::CStr;
 use std::fs::File;
 use std::io::{Cursor, Read};
 use std::os::raw::c_char;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 const LSB_SHARED_LIBRARIES: &[&str] = &[
     "ld-linux-x86-64.so.2",
@@ -24,6 +27,52 @@ const LSB_SHARED_LIBRARIES: &[&str] = &[
     "libutil.so.1",
 ];
 
+/// Windows DLLs that are always present, in lowercase.
+const WINDOWS_SYSTEM_LIBRARIES: &[&str] = &[
+    "advapi32.dll",
+    "bcrypt.dll",
+    "crypt32.dll",
+    "gdi32.dll",
+    "kernel32.dll",
+    "msvcrt.dll",
+    "ntdll.dll",
+    "ole32.dll",
+    "oleaut32.dll",
+    "rpcrt4.dll",
+    "shell32.dll",
+    "shlwapi.dll",
+    "user32.dll",
+    "version.dll",
+    "winmm.dll",
+    "ws2_32.dll",
+];
+
+/// DLLs providing the Microsoft Visual C++ runtime and the Visual C++
+/// version they belong to, in lowercase.
+///
+/// These aren't part of Windows and must be distributed with applications.
+const MSVC_RUNTIME_LIBRARIES: &[(&str, &str)] = &[
+    ("msvcr71.dll", "7.1"),
+    ("msvcp71.dll", "7.1"),
+    ("msvcr80.dll", "8.0"),
+    ("msvcp80.dll", "8.0"),
+    ("msvcr90.dll", "9.0"),
+    ("msvcp90.dll", "9.0"),
+    ("msvcr100.dll", "10.0"),
+    ("msvcp100.dll", "10.0"),
+    ("msvcr110.dll", "11.0"),
+    ("msvcp110.dll", "11.0"),
+    ("msvcr120.dll", "12.0"),
+    ("msvcp120.dll", "12.0"),
+    ("vcruntime140.dll", "14.0"),
+    ("vcruntime140_1.dll", "14.0"),
+    ("msvcp140.dll", "14.0"),
+    ("msvcp140_1.dll", "14.0"),
+    ("msvcp140_2.dll", "14.0"),
+    ("concrt140.dll", "14.0"),
+    ("vcomp140.dll", "14.0"),
+];
+
 type DistroVersion = Vec<(&'static str, &'static str)>;
 
 lazy_static! {
@@ -176,16 +225,29 @@ pub struct UndefinedSymbol {
     symbol: String,
     filename: Option<String>,
     version: Option<String>,
+    weak: bool,
 }
 
-pub fn analyze_file(path: PathBuf) {
-    let mut fd = File::open(path).unwrap();
+/// Analyze a binary, printing the results.
+///
+/// Returns an error if the binary violates `policy`.
+pub fn analyze_file(path: PathBuf, policy: &PortabilityPolicy) -> Result<(), String> {
+    let mut fd = File::open(&path).unwrap();
     let mut buffer = Vec::new();
     fd.read_to_end(&mut buffer).unwrap();
-    analyze_data(&buffer);
+    analyze_data(&buffer, policy)?;
+
+    if policy.verify_symbols && buffer.starts_with(goblin::elf::header::ELFMAG) {
+        let resolution = resolve_elf_symbols(&path, &policy.system_library_paths)?;
+
+        analyze_symbol_resolution(&resolution);
+        verify_symbol_resolution(&resolution)?;
+    }
+
+    Ok(())
 }
 
-pub fn analyze_data(buffer: &[u8]) {
+pub fn analyze_data(buffer: &[u8], policy: &PortabilityPolicy) -> Result<(), String> {
     match goblin::Object::parse(buffer).unwrap() {
         goblin::Object::Elf(elf) => {
             let undefined_symbols =
@@ -193,88 +255,1253 @@ pub fn analyze_data(buffer: &[u8]) {
 
             analyze_elf_libraries(&elf.libraries, &undefined_symbols);
         }
-        goblin::Object::PE(_pe) => {
-            panic!("PE not yet supported");
+        goblin::Object::PE(_) => {
+            analyze_pe(&pe_report(buffer).ok_or("unable to parse PE binary")?);
         }
-        goblin::Object::Mach(_mach) => {
-            panic!("mach not yet supported");
+        goblin::Object::Mach(_) => {
+            analyze_macho(&macho_report(buffer).ok_or("unable to parse Mach-O binary")?);
         }
         goblin::Object::Archive(_archive) => {
             panic!("archive not yet supported");
         }
         goblin::Object::Unknown(magic) => panic!("unknown magic: {:#x}", magic),
     }
+
+    verify_data_portability(buffer, policy)
 }
 
-pub fn analyze_elf_libraries(libs: &[&str], undefined_symbols: &Vec<UndefinedSymbol>) {
-    let mut latest_symbols: BTreeMap<String, version_compare::Version> = BTreeMap::new();
+/// Verify a binary satisfies a portability policy.
+///
+/// Unlike `analyze_file()`, nothing is printed. The returned error
+/// describes every violation of the first check that failed.
+pub fn verify_binary_portability(path: &Path, policy: &PortabilityPolicy) -> Result<(), String> {
+    let buffer =
+        std::fs::read(path).or_else(|e| Err(format!("error reading {}: {}", path.display(), e)))?;
+
+    verify_data_portability(&buffer, policy)
+        .and_then(|_| {
+            if policy.verify_symbols && buffer.starts_with(goblin::elf::header::ELFMAG) {
+                verify_symbol_resolution(&resolve_elf_symbols(path, &policy.system_library_paths)?)
+            } else {
+                Ok(())
+            }
+        })
+        .or_else(|e| Err(format!("{} is not portable: {}", path.display(), e)))
+}
 
-    println!("Shared Library Dependencies");
-    println!("===========================");
+fn verify_data_portability(buffer: &[u8], policy: &PortabilityPolicy) -> Result<(), String> {
+    match goblin::Object::parse(buffer) {
+        Ok(goblin::Object::Elf(elf)) => {
+            let libraries: Vec<String> = elf.libraries.iter().map(|s| s.to_string()).collect();
+            verify_library_dependencies(&libraries, policy)?;
 
-    for lib in itertools::sorted(libs) {
-        println!("{}", lib);
+            let undefined_symbols = find_undefined_elf_symbols(buffer, &elf);
+            let report = symbol_version_report(&undefined_symbols);
+            verify_symbol_versions(&report, &policy.symbol_versions)
+        }
+        Ok(goblin::Object::PE(_)) => {
+            let report = pe_report(buffer).ok_or("unable to parse PE binary")?;
 
-        if LSB_SHARED_LIBRARIES.contains(&lib) {
-            println!("  OK - Library part of Linux Shared Bass and present on most distros");
+            let dlls: Vec<String> = report.imports.keys().cloned().collect();
+            verify_library_dependencies(&dlls, policy)?;
+            verify_msvc_runtimes(&report, policy)
+        }
+        Ok(goblin::Object::Mach(_)) => {
+            let report = macho_report(buffer).ok_or("unable to parse Mach-O binary")?;
+
+            verify_library_dependencies(&report.dylibs, policy)?;
+            verify_macos_minimum_version(&report, policy)
+        }
+        _ => Err("not an ELF, Mach-O, or PE binary".to_string()),
+    }
+}
+
+/// Find the shared libraries a binary depends on.
+///
+/// Returns the names of libraries recorded in ELF ``DT_NEEDED`` entries,
+/// Mach-O load commands, or PE import tables, in the order they are
+/// recorded. Returns None if the data isn't a binary we know how to parse.
+pub fn find_shared_library_dependencies(buffer: &[u8]) -> Option<Vec<String>> {
+    match goblin::Object::parse(buffer) {
+        Ok(goblin::Object::Elf(elf)) => Some(elf.libraries.iter().map(|s| s.to_string()).collect()),
+        Ok(goblin::Object::PE(pe)) => Some(pe.libraries.iter().map(|s| s.to_string()).collect()),
+        Ok(goblin::Object::Mach(_)) => Some(macho_report(buffer)?.dylibs),
+        _ => None,
+    }
+}
+
+/// Describes the platform a binary is built for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BinaryPlatform {
+    /// The object file format. ``elf``, ``mach-o``, or ``pe``.
+    pub format: &'static str,
+
+    /// Architectures the binary contains code for, using Rust target triple
+    /// names. e.g. ``x86_64``. Multi-architecture Mach-O binaries have
+    /// multiple entries.
+    pub architectures: Vec<String>,
+
+    /// The minimum macOS version the binary runs on, as (major, minor).
+    pub macos_minimum_version: Option<(u32, u32)>,
+}
+
+fn elf_machine_name(machine: u16) -> String {
+    match machine {
+        goblin::elf::header::EM_386 => "x86".to_string(),
+        goblin::elf::header::EM_X86_64 => "x86_64".to_string(),
+        goblin::elf::header::EM_ARM => "arm".to_string(),
+        goblin::elf::header::EM_AARCH64 => "aarch64".to_string(),
+        _ => format!("elf-machine-{}", machine),
+    }
+}
+
+fn mach_cpu_type_name(cputype: u32) -> String {
+    match cputype {
+        goblin::mach::constants::cputype::CPU_TYPE_X86 => "x86".to_string(),
+        goblin::mach::constants::cputype::CPU_TYPE_X86_64 => "x86_64".to_string(),
+        goblin::mach::constants::cputype::CPU_TYPE_ARM => "arm".to_string(),
+        goblin::mach::constants::cputype::CPU_TYPE_ARM64 => "aarch64".to_string(),
+        _ => format!("mach-cpu-{}", cputype),
+    }
+}
+
+fn pe_machine_name(machine: u16) -> String {
+    match machine {
+        goblin::pe::header::COFF_MACHINE_X86 => "x86".to_string(),
+        goblin::pe::header::COFF_MACHINE_X86_64 => "x86_64".to_string(),
+        // IMAGE_FILE_MACHINE_ARM64
+        0xaa64 => "aarch64".to_string(),
+        _ => format!("pe-machine-{:#x}", machine),
+    }
+}
+
+/// Load command holding the minimum OS version in newer Mach-O binaries.
+const LC_BUILD_VERSION: u32 = 0x32;
+
+/// Find the minimum macOS version of a Mach-O binary.
+///
+/// ``LC_VERSION_MIN_MACOSX`` and ``LC_BUILD_VERSION`` encode versions as
+/// ``xxxx.yy.zz`` nibbles.
+fn macho_minimum_version(buffer: &[u8], macho: &goblin::mach::MachO) -> Option<(u32, u32)> {
+    for lc in &macho.load_commands {
+        let version = match &lc.command {
+            goblin::mach::load_command::CommandVariant::VersionMinMacosx(command) => {
+                command.version
+            }
+            goblin::mach::load_command::CommandVariant::Unimplemented(header)
+                if header.cmd == LC_BUILD_VERSION =>
+            {
+                // struct build_version_command { cmd, cmdsize, platform, minos, ... }
+                let data = buffer.get(lc.offset + 12..lc.offset + 16)?;
+                if macho.little_endian {
+                    byteorder::LittleEndian::read_u32(data)
+                } else {
+                    byteorder::BigEndian::read_u32(data)
+                }
+            }
+            _ => continue,
+        };
+
+        return Some((version >> 16, (version >> 8) & 0xff));
+    }
+
+    None
+}
+
+/// Determine the platform a binary is built for.
+///
+/// Returns None if the data isn't a binary we know how to parse.
+pub fn binary_platform(buffer: &[u8]) -> Option<BinaryPlatform> {
+    match goblin::Object::parse(buffer) {
+        Ok(goblin::Object::Elf(elf)) => Some(BinaryPlatform {
+            format: "elf",
+            architectures: vec![elf_machine_name(elf.header.e_machine)],
+            macos_minimum_version: None,
+        }),
+        Ok(goblin::Object::PE(pe)) => Some(BinaryPlatform {
+            format: "pe",
+            architectures: vec![pe_machine_name(pe.header.coff_header.machine)],
+            macos_minimum_version: None,
+        }),
+        Ok(goblin::Object::Mach(_)) => {
+            let report = macho_report(buffer)?;
+
+            Some(BinaryPlatform {
+                format: "mach-o",
+                architectures: report.architectures,
+                macos_minimum_version: report.macos_minimum_version,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Obtain each architecture of a Mach-O binary along with its data.
+///
+/// Load command offsets are relative to the architecture's slice of a fat
+/// binary, so the slice is needed to read data referenced by them.
+fn macho_slices<'a>(buffer: &'a [u8]) -> Option<Vec<(&'a [u8], goblin::mach::MachO<'a>)>> {
+    match goblin::mach::Mach::parse(buffer).ok()? {
+        goblin::mach::Mach::Binary(macho) => Some(vec![(buffer, macho)]),
+        goblin::mach::Mach::Fat(multi) => {
+            let mut res = Vec::new();
+
+            for i in 0..multi.narches {
+                let macho = multi.get(i).ok()?;
+                let arch = multi.iter_arches().nth(i)?.ok()?;
+                let slice = buffer.get(arch.offset as usize..(arch.offset + arch.size) as usize)?;
+
+                res.push((slice, macho));
+            }
+
+            Some(res)
+        }
+    }
+}
+
+/// Describes the run-time requirements of a Mach-O binary.
+///
+/// Multi-architecture binaries are reported as the union of their
+/// architectures.
+#[derive(Clone, Debug, Default)]
+pub struct MachOReport {
+    /// Architectures the binary contains code for.
+    pub architectures: Vec<String>,
+
+    /// Libraries loaded via ``LC_LOAD_DYLIB`` and related load commands.
+    pub dylibs: Vec<String>,
+
+    /// Run paths defined via ``LC_RPATH``.
+    pub rpaths: Vec<String>,
+
+    /// The minimum macOS version the binary runs on, as (major, minor).
+    ///
+    /// This is the newest version required by any architecture.
+    pub macos_minimum_version: Option<(u32, u32)>,
+}
+
+/// Find the run paths defined by a Mach-O binary.
+fn macho_rpaths(buffer: &[u8], macho: &goblin::mach::MachO) -> Vec<String> {
+    let mut res = Vec::new();
+
+    for lc in &macho.load_commands {
+        if let goblin::mach::load_command::CommandVariant::Rpath(command) = &lc.command {
+            // The path is a NUL terminated string at an offset in the command.
+            let start = lc.offset + command.path as usize;
+            let end = lc.offset + command.cmdsize as usize;
+
+            if let Some(data) = buffer.get(start..end) {
+                let data = match data.iter().position(|b| *b == 0) {
+                    Some(idx) => &data[0..idx],
+                    None => data,
+                };
+
+                res.push(String::from_utf8_lossy(data).to_string());
+            }
+        }
+    }
+
+    res
+}
+
+/// Analyze the dependencies and deployment target of a Mach-O binary.
+///
+/// Returns None if the data isn't a Mach-O binary.
+pub fn macho_report(buffer: &[u8]) -> Option<MachOReport> {
+    let mut report = MachOReport::default();
+
+    for (slice, macho) in macho_slices(buffer)? {
+        report
+            .architectures
+            .push(mach_cpu_type_name(macho.header.cputype));
+
+        // The first entry refers to the binary itself.
+        for lib in macho.libs.iter().skip(1) {
+            if !report.dylibs.iter().any(|l| l == lib) {
+                report.dylibs.push(lib.to_string());
+            }
+        }
+
+        for rpath in macho_rpaths(slice, &macho) {
+            if !report.rpaths.contains(&rpath) {
+                report.rpaths.push(rpath);
+            }
+        }
+
+        if let Some(version) = macho_minimum_version(slice, &macho) {
+            if report
+                .macos_minimum_version
+                .map(|v| version > v)
+                .unwrap_or(true)
+            {
+                report.macos_minimum_version = Some(version);
+            }
+        }
+    }
+
+    Some(report)
+}
+
+/// Magic of the superblob holding the code signature of a Mach-O binary.
+const CSMAGIC_EMBEDDED_SIGNATURE: u32 = 0xfade_0cc0;
+
+/// Magic of a code directory blob in a code signature.
+const CSMAGIC_CODEDIRECTORY: u32 = 0xfade_0c02;
+
+/// Length of a cdhash, in bytes.
+const CDHASH_LENGTH: usize = 20;
+
+/// Describes a code directory in the code signature of a Mach-O binary.
+#[derive(Clone, Debug, Serialize)]
+pub struct CodeDirectoryHash {
+    /// Architecture of the binary the code directory belongs to.
+    pub architecture: String,
+
+    /// Identifier the binary was signed with. e.g. ``com.example.myapp``.
+    pub identifier: String,
+
+    /// Hash algorithm of the code directory.
+    ///
+    /// One of ``sha1``, ``sha256``, ``sha256-truncated``, or ``sha384``.
+    pub hash_type: String,
+
+    /// Hex encoded cdhash.
+    pub cdhash: String,
+
+    /// Hex encoded digest of the code directory, before truncation.
+    pub digest: String,
+}
+
+/// Find the code signature data of a Mach-O binary.
+fn macho_code_signature<'a>(slice: &'a [u8], macho: &goblin::mach::MachO) -> Option<&'a [u8]> {
+    for lc in &macho.load_commands {
+        if let goblin::mach::load_command::CommandVariant::CodeSignature(command) = &lc.command {
+            let start = command.dataoff as usize;
+
+            return slice.get(start..start + command.datasize as usize);
+        }
+    }
+
+    None
+}
+
+/// Compute the cdhashes of a signed Mach-O binary.
+///
+/// A cdhash identifies a signed binary. It is the digest of a code
+/// directory in the binary's code signature, truncated to 20 bytes. This
+/// is what notarization tickets and code signing policies refer to.
+/// Signatures can hold code directories using different hash algorithms,
+/// so there is a cdhash for each code directory of each architecture.
+pub fn macho_cdhashes(buffer: &[u8]) -> Result<Vec<CodeDirectoryHash>, String> {
+    let slices = macho_slices(buffer).ok_or("not a Mach-O binary")?;
+
+    let mut res = Vec::new();
+
+    for (slice, macho) in slices {
+        let architecture = mach_cpu_type_name(macho.header.cputype);
+        let malformed = || format!("{} binary has a malformed code signature", architecture);
+
+        let signature = macho_code_signature(slice, &macho)
+            .ok_or_else(|| format!("{} binary is not signed", architecture))?;
+
+        // Code signatures are big endian, regardless of the architecture.
+        if signature.len() < 12
+            || BigEndian::read_u32(&signature[0..4]) != CSMAGIC_EMBEDDED_SIGNATURE
+        {
+            return Err(malformed());
+        }
+
+        let count = BigEndian::read_u32(&signature[8..12]) as usize;
+
+        for i in 0..count {
+            // Index entries are (type, offset) pairs following the header.
+            let entry = signature
+                .get(12 + i * 8..20 + i * 8)
+                .ok_or_else(malformed)?;
+            let offset = BigEndian::read_u32(&entry[4..8]) as usize;
+
+            let header = signature.get(offset..offset + 8).ok_or_else(malformed)?;
+            if BigEndian::read_u32(&header[0..4]) != CSMAGIC_CODEDIRECTORY {
+                continue;
+            }
+
+            let length = BigEndian::read_u32(&header[4..8]) as usize;
+            let blob = signature
+                .get(offset..offset + length)
+                .filter(|blob| blob.len() >= 40)
+                .ok_or_else(malformed)?;
+
+            let identifier_offset = BigEndian::read_u32(&blob[20..24]) as usize;
+            let identifier = blob
+                .get(identifier_offset..)
+                .map(|data| match data.iter().position(|b| *b == 0) {
+                    Some(idx) => &data[0..idx],
+                    None => data,
+                })
+                .map(|data| String::from_utf8_lossy(data).to_string())
+                .unwrap_or_default();
+
+            let (hash_type, digest) = match blob[37] {
+                1 => ("sha1", Sha1::digest(blob).to_vec()),
+                2 => ("sha256", Sha256::digest(blob).to_vec()),
+                3 => ("sha256-truncated", Sha256::digest(blob).to_vec()),
+                4 => ("sha384", Sha384::digest(blob).to_vec()),
+                value => {
+                    return Err(format!(
+                        "{} binary has a code directory with unknown hash type {}",
+                        architecture, value
+                    ))
+                }
+            };
+
+            res.push(CodeDirectoryHash {
+                architecture: architecture.clone(),
+                identifier,
+                hash_type: hash_type.to_string(),
+                cdhash: hex::encode(&digest[0..CDHASH_LENGTH]),
+                digest: hex::encode(&digest),
+            });
+        }
+    }
+
+    Ok(res)
+}
+
+/// Resolve the main executable of a macOS bundle.
+///
+/// Only XML property lists are supported.
+fn bundle_executable(path: &Path) -> Result<PathBuf, String> {
+    let plist_path = path.join("Contents").join("Info.plist");
+    let plist = std::fs::read_to_string(&plist_path)
+        .or_else(|e| Err(format!("error reading {}: {}", plist_path.display(), e)))?;
+
+    // The value is the element following the key.
+    let key = "<key>CFBundleExecutable</key>";
+    let value = plist
+        .find(key)
+        .map(|idx| plist[idx + key.len()..].trim_start())
+        .filter(|value| value.starts_with("<string>"))
+        .and_then(|value| value.find("</string>").map(|end| &value[8..end]))
+        .ok_or_else(|| {
+            format!(
+                "{} does not define CFBundleExecutable",
+                plist_path.display()
+            )
+        })?;
+
+    Ok(path.join("Contents").join("MacOS").join(value.trim()))
+}
+
+/// Print the cdhashes of a signed Mach-O binary or macOS bundle.
+pub fn print_cdhashes(path: &Path, json: bool) -> Result<(), String> {
+    let path = if path.is_dir() {
+        bundle_executable(path)?
+    } else {
+        path.to_path_buf()
+    };
+
+    let data = std::fs::read(&path)
+        .or_else(|e| Err(format!("error reading {}: {}", path.display(), e)))?;
+    let hashes = macho_cdhashes(&data)?;
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&hashes).or_else(|e| Err(e.to_string()))?
+        );
+
+        return Ok(());
+    }
+
+    for hash in &hashes {
+        println!(
+            "{:<8} {:<16} {} {}",
+            hash.architecture, hash.hash_type, hash.cdhash, hash.identifier
+        );
+    }
+
+    Ok(())
+}
+
+/// A Microsoft Visual C++ runtime DLL a PE binary imports.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MsvcRuntime {
+    /// Name of the DLL, as imported.
+    pub dll: String,
+
+    /// Visual C++ version of the runtime. e.g. ``14.0``.
+    ///
+    /// ``ucrt`` for the Universal C Runtime.
+    pub version: String,
+
+    /// Whether this is a debug build of the runtime.
+    ///
+    /// Debug runtimes can't be redistributed and are only present on
+    /// machines with Visual Studio installed.
+    pub debug: bool,
+}
+
+/// Identify a Microsoft Visual C++ runtime DLL from its name.
+///
+/// Returns None if the DLL isn't a Visual C++ runtime. ``msvcrt.dll`` ships
+/// with Windows and isn't considered a Visual C++ runtime.
+pub fn msvc_runtime(dll: &str) -> Option<MsvcRuntime> {
+    let lower = dll.to_lowercase();
+
+    if lower == "ucrtbase.dll" || lower == "ucrtbased.dll" {
+        return Some(MsvcRuntime {
+            dll: dll.to_string(),
+            version: "ucrt".to_string(),
+            debug: lower == "ucrtbased.dll",
+        });
+    }
+
+    let find = |name: &str| {
+        MSVC_RUNTIME_LIBRARIES
+            .iter()
+            .find(|(runtime, _)| *runtime == name)
+            .map(|(_, version)| version.to_string())
+    };
+
+    // Debug runtimes have a ``d`` suffix. e.g. ``vcruntime140d.dll``.
+    let (version, debug) = match find(&lower) {
+        Some(version) => (version, false),
+        None if lower.ends_with("d.dll") => {
+            (find(&format!("{}.dll", &lower[0..lower.len() - 5]))?, true)
+        }
+        None => return None,
+    };
+
+    Some(MsvcRuntime {
+        dll: dll.to_string(),
+        version,
+        debug,
+    })
+}
+
+/// Describes the run-time requirements of a PE binary.
+#[derive(Clone, Debug, Default)]
+pub struct PeReport {
+    /// Architecture the binary contains code for.
+    pub architecture: String,
+
+    /// Symbols imported from each DLL, keyed by DLL name.
+    ///
+    /// Symbols imported by ordinal are named ``#<ordinal>``.
+    pub imports: BTreeMap<String, Vec<String>>,
+
+    /// Microsoft Visual C++ runtime DLLs the binary imports.
+    pub msvc_runtimes: Vec<MsvcRuntime>,
+}
+
+/// Analyze the DLL imports of a PE binary.
+///
+/// Returns None if the data isn't a PE binary.
+pub fn pe_report(buffer: &[u8]) -> Option<PeReport> {
+    let pe = goblin::pe::PE::parse(buffer).ok()?;
+
+    let mut report = PeReport {
+        architecture: pe_machine_name(pe.header.coff_header.machine),
+        ..PeReport::default()
+    };
+
+    for dll in &pe.libraries {
+        report.imports.insert(dll.to_string(), Vec::new());
+
+        if let Some(runtime) = msvc_runtime(dll) {
+            report.msvc_runtimes.push(runtime);
+        }
+    }
+
+    for import in &pe.imports {
+        let name = if import.name.is_empty() {
+            format!("#{}", import.ordinal)
         } else {
-            println!("  PROBLEMATIC - Shared library dependency may not be on all machines");
+            import.name.to_string()
+        };
+
+        report
+            .imports
+            .entry(import.dll.to_string())
+            .or_default()
+            .push(name);
+    }
+
+    Some(report)
+}
+
+/// Whether a shared library dependency is provided by the operating system.
+///
+/// Libraries that aren't provided by the operating system need to be
+/// distributed with the application.
+pub fn is_system_library(name: &str) -> bool {
+    let lower = name.to_lowercase();
+
+    LSB_SHARED_LIBRARIES.contains(&name)
+        || name.starts_with("/usr/lib/")
+        || name.starts_with("/System/Library/")
+        || lower.starts_with("api-ms-win-")
+        || WINDOWS_SYSTEM_LIBRARIES.contains(&lower.as_str())
+}
+
+/// The newest version of a versioned symbol namespace required by a binary.
+#[derive(Clone, Debug)]
+pub struct SymbolVersionRequirement {
+    /// The version. e.g. ``2.17``.
+    pub version: String,
+
+    /// Symbols requiring this version.
+    pub symbols: Vec<String>,
+}
+
+/// Describes the symbol versions an ELF binary requires.
+#[derive(Clone, Debug, Default)]
+pub struct SymbolVersionReport {
+    /// Requirements keyed by symbol version namespace. e.g. ``GLIBC`` or
+    /// ``GLIBCXX``.
+    pub requirements: BTreeMap<String, SymbolVersionRequirement>,
+}
+
+/// Compute the newest symbol version required for each namespace.
+pub fn symbol_version_report(undefined_symbols: &[UndefinedSymbol]) -> SymbolVersionReport {
+    let mut report = SymbolVersionReport::default();
+
+    for symbol in undefined_symbols {
+        let version = match &symbol.version {
+            Some(version) => version,
+            None => continue,
+        };
+
+        // e.g. GLIBC_2.17. Versions without a number (e.g. GLIBC_PRIVATE)
+        // can't be compared and are ignored.
+        let parts: Vec<&str> = version.splitn(2, '_').collect();
+        if parts.len() != 2 {
+            continue;
+        }
+
+        let v = match version_compare::Version::from(parts[1]) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        let requirement = report
+            .requirements
+            .entry(parts[0].to_string())
+            .or_insert_with(|| SymbolVersionRequirement {
+                version: parts[1].to_string(),
+                symbols: Vec::new(),
+            });
+
+        let (newer, same) = {
+            let existing = version_compare::Version::from(&requirement.version)
+                .expect("unable to parse version");
+
+            (v > existing, v == existing)
+        };
+
+        if newer {
+            requirement.version = parts[1].to_string();
+            requirement.symbols.clear();
+        }
+
+        if newer || same {
+            requirement.symbols.push(symbol.symbol.clone());
+        }
+    }
+
+    report
+}
+
+/// Maximum symbol versions a binary may require.
+///
+/// This expresses policies such as "must run on CentOS 7".
+#[derive(Clone, Debug, Default)]
+pub struct SymbolVersionPolicy {
+    /// Maximum versions keyed by symbol version namespace.
+    pub max_versions: BTreeMap<String, String>,
+}
+
+impl SymbolVersionPolicy {
+    /// Add a maximum version from a ``NAMESPACE=VERSION`` string.
+    pub fn add_max_version(&mut self, value: &str) -> Result<(), String> {
+        let parts: Vec<&str> = value.splitn(2, '=').collect();
+
+        if parts.len() != 2 || version_compare::Version::from(parts[1]).is_none() {
+            return Err(format!(
+                "invalid symbol version: {}; expected NAME=VERSION, e.g. GLIBC=2.17",
+                value
+            ));
         }
 
-        let mut symbols: Vec<&UndefinedSymbol> = Vec::new();
+        self.max_versions
+            .insert(parts[0].to_string(), parts[1].to_string());
 
-        for symbol in undefined_symbols {
-            if symbol.filename == Some(lib.to_string()) {
-                symbols.push(symbol);
+        Ok(())
+    }
+
+    /// Add maximum versions to allow running on a Linux distribution.
+    ///
+    /// `value` is a distribution name and version, e.g. ``RHEL 7``. glibc
+    /// and gcc versions of known distributions are used.
+    pub fn add_distro(&mut self, value: &str) -> Result<(), String> {
+        let parts: Vec<&str> = value.splitn(2, ' ').collect();
+        if parts.len() != 2 {
+            return Err(format!(
+                "invalid distribution: {}; expected NAME VERSION, e.g. RHEL 7",
+                value
+            ));
+        }
+
+        let mut found = false;
+
+        for (namespace, table) in &[
+            ("GLIBC", &*GLIBC_VERSIONS_BY_DISTRO),
+            ("GCC", &*GCC_VERSIONS_BY_DISTRO),
+        ] {
+            let versions = match table
+                .iter()
+                .find(|(distro, _)| distro.eq_ignore_ascii_case(parts[0]))
+            {
+                Some((_, versions)) => versions,
+                None => continue,
+            };
+
+            if let Some((_, version)) = versions.iter().find(|(v, _)| *v == parts[1]) {
+                self.max_versions
+                    .insert(namespace.to_string(), version.to_string());
+                found = true;
             }
         }
 
-        /*
-        println!("");
-        println!("  Symbols");
-        println!("  -------");
-        */
+        if found {
+            Ok(())
+        } else {
+            Err(format!(
+                "unknown distribution {}; known distributions: {}",
+                value,
+                GLIBC_VERSIONS_BY_DISTRO
+                    .iter()
+                    .map(|(distro, versions)| format!(
+                        "{} ({})",
+                        distro,
+                        versions
+                            .iter()
+                            .map(|(v, _)| *v)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        }
+    }
+}
 
-        for symbol in symbols {
-            match &symbol.version {
-                Some(version) => {
-                    let parts: Vec<&str> = version.splitn(2, '_').collect();
-
-                    match parts.len() {
-                        1 => { /* TODO this is weird. Do something? */ }
-                        2 => {
-                            let v = version_compare::Version::from(parts[1])
-                                .expect("unable to parse version");
-
-                            match latest_symbols.get(parts[0]) {
-                                Some(existing) => {
-                                    if &v > existing {
-                                        latest_symbols.insert(parts[0].to_string(), v);
-                                    }
-                                }
-                                None => {
-                                    latest_symbols.insert(parts[0].to_string(), v);
-                                }
-                            }
+/// Requirements a binary must satisfy to be portable.
+#[derive(Clone, Debug, Default)]
+pub struct PortabilityPolicy {
+    /// Maximum symbol versions of ELF binaries.
+    pub symbol_versions: SymbolVersionPolicy,
+
+    /// Maximum minimum macOS version of Mach-O binaries, as (major, minor).
+    pub max_macos_version: Option<(u32, u32)>,
+
+    /// Whether all shared library dependencies must be provided by the
+    /// operating system.
+    pub system_libraries_only: bool,
+
+    /// Visual C++ runtime versions PE binaries may import. e.g. ``14.0``.
+    ///
+    /// Empty allows any version.
+    pub msvc_runtime_versions: Vec<String>,
+
+    /// Whether PE binaries may import debug builds of the Visual C++
+    /// runtime.
+    pub allow_debug_runtime: bool,
+
+    /// Whether to verify every undefined symbol of ELF binaries is provided
+    /// by a library they load.
+    pub verify_symbols: bool,
+
+    /// Directories holding the shared libraries of the target system.
+    ///
+    /// Used to resolve system libraries when verifying symbols. If empty,
+    /// system libraries are assumed to provide any symbol bound to them.
+    pub system_library_paths: Vec<PathBuf>,
+}
+
+impl PortabilityPolicy {
+    /// Set the maximum minimum macOS version from a ``MAJOR.MINOR`` string.
+    pub fn set_max_macos_version(&mut self, value: &str) -> Result<(), String> {
+        let parts: Vec<&str> = value.split('.').collect();
+
+        let version = match (
+            parts.first().and_then(|v| v.parse::<u32>().ok()),
+            parts.get(1).map_or(Some(0), |v| v.parse::<u32>().ok()),
+        ) {
+            (Some(major), Some(minor)) if parts.len() <= 3 => (major, minor),
+            _ => {
+                return Err(format!(
+                    "invalid macOS version: {}; expected MAJOR.MINOR, e.g. 10.12",
+                    value
+                ));
+            }
+        };
+
+        self.max_macos_version = Some(version);
+
+        Ok(())
+    }
+}
+
+/// Verify a binary's shared library dependencies satisfy a policy.
+pub fn verify_library_dependencies(
+    libraries: &[String],
+    policy: &PortabilityPolicy,
+) -> Result<(), String> {
+    if !policy.system_libraries_only {
+        return Ok(());
+    }
+
+    let non_system: Vec<&str> = libraries
+        .iter()
+        .filter(|lib| !is_system_library(lib))
+        .map(|lib| lib.as_str())
+        .collect();
+
+    if non_system.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "binary depends on libraries not provided by the operating system: {}",
+            non_system.join(", ")
+        ))
+    }
+}
+
+/// Verify the Visual C++ runtimes a PE binary imports satisfy a policy.
+pub fn verify_msvc_runtimes(report: &PeReport, policy: &PortabilityPolicy) -> Result<(), String> {
+    let mut violations = Vec::new();
+
+    for runtime in &report.msvc_runtimes {
+        if runtime.debug && !policy.allow_debug_runtime {
+            violations.push(format!(
+                "{} is a debug runtime and can't be redistributed",
+                runtime.dll
+            ));
+        }
+
+        if !policy.msvc_runtime_versions.is_empty()
+            && !policy.msvc_runtime_versions.contains(&runtime.version)
+        {
+            violations.push(format!(
+                "{} is from Visual C++ runtime {} but only {} allowed",
+                runtime.dll,
+                runtime.version,
+                policy.msvc_runtime_versions.join(", ")
+            ));
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "binary violates Visual C++ runtime policy: {}",
+            violations.join("; ")
+        ))
+    }
+}
+
+/// Verify a Mach-O binary's deployment target satisfies a policy.
+///
+/// Binaries not recording a minimum macOS version are assumed to satisfy
+/// the policy.
+pub fn verify_macos_minimum_version(
+    report: &MachOReport,
+    policy: &PortabilityPolicy,
+) -> Result<(), String> {
+    match (report.macos_minimum_version, policy.max_macos_version) {
+        (Some(required), Some(max)) if required > max => Err(format!(
+            "binary requires macOS {}.{} but must run on macOS {}.{}",
+            required.0, required.1, max.0, max.1
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// A symbol defined by an ELF binary and available to other binaries.
+#[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+struct DefinedSymbol {
+    symbol: String,
+    version: Option<String>,
+}
+
+/// Find defined dynamic symbols in an ELF binary, with their versions.
+fn find_defined_elf_symbols(buffer: &[u8], elf: &goblin::elf::Elf) -> BTreeSet<DefinedSymbol> {
+    let read_u16 = |offset: usize| {
+        buffer.get(offset..offset + 2).map(|v| {
+            if elf.little_endian {
+                byteorder::LittleEndian::read_u16(v)
+            } else {
+                byteorder::BigEndian::read_u16(v)
+            }
+        })
+    };
+    let read_u32 = |offset: usize| {
+        buffer.get(offset..offset + 4).map(|v| {
+            if elf.little_endian {
+                byteorder::LittleEndian::read_u32(v)
+            } else {
+                byteorder::BigEndian::read_u32(v)
+            }
+        })
+    };
+
+    let mut versym_offset = None;
+    // Version index -> name.
+    let mut versions: BTreeMap<u16, String> = BTreeMap::new();
+
+    for section_header in &elf.section_headers {
+        match section_header.sh_type {
+            goblin::elf::section_header::SHT_GNU_VERSYM => {
+                versym_offset = Some(section_header.sh_offset as usize);
+            }
+            goblin::elf::section_header::SHT_GNU_VERDEF => {
+                let names = match elf.section_headers.get(section_header.sh_link as usize) {
+                    Some(names) => names.sh_offset as usize,
+                    None => continue,
+                };
+
+                // Elf64_Verdef records are chained via vd_next. Each points
+                // to Elf64_Verdaux records, the first naming the version.
+                let mut offset = section_header.sh_offset as usize;
+
+                for _ in 0..section_header.sh_info {
+                    let (ndx, aux, next) = match (
+                        read_u16(offset + 4),
+                        read_u32(offset + 12),
+                        read_u32(offset + 16),
+                    ) {
+                        (Some(ndx), Some(aux), Some(next)) => (ndx, aux, next),
+                        _ => break,
+                    };
+
+                    if let Some(name) = read_u32(offset + aux as usize) {
+                        let start = names + name as usize;
+
+                        if let Some(data) = buffer.get(start..) {
+                            let end = data.iter().position(|b| *b == 0).unwrap_or(0);
+                            versions
+                                .insert(ndx, String::from_utf8_lossy(&data[0..end]).to_string());
                         }
-                        _ => {}
                     }
 
-                    //println!("  {}@{}", &symbol.symbol, version)
+                    if next == 0 {
+                        break;
+                    }
+
+                    offset += next as usize;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut res = BTreeSet::new();
+
+    for (i, sym) in elf.dynsyms.iter().enumerate() {
+        if sym.st_shndx == 0 {
+            continue;
+        }
+
+        let bind = sym.st_bind();
+        if bind != goblin::elf::sym::STB_GLOBAL && bind != goblin::elf::sym::STB_WEAK {
+            continue;
+        }
+
+        let name = match elf.dynstrtab.get(sym.st_name) {
+            Some(Ok(name)) if !name.is_empty() => name,
+            _ => continue,
+        };
+
+        // The high bit marks hidden (non-default) versions, which can still
+        // satisfy versioned references.
+        let version = versym_offset
+            .and_then(|offset| read_u16(offset + i * 2))
+            .and_then(|versym| versions.get(&(versym & 0x7fff)))
+            .cloned();
+
+        res.insert(DefinedSymbol {
+            symbol: name.to_string(),
+            version,
+        });
+    }
+
+    res
+}
+
+/// Find the ``DT_RPATH`` and ``DT_RUNPATH`` entries of an ELF binary.
+///
+/// ``$ORIGIN`` is expanded to the directory containing the binary.
+fn elf_search_paths(elf: &goblin::elf::Elf, origin: &Path) -> Vec<PathBuf> {
+    let mut res = Vec::new();
+
+    let dynamic = match &elf.dynamic {
+        Some(dynamic) => dynamic,
+        None => return res,
+    };
+
+    for entry in &dynamic.dyns {
+        if entry.d_tag != goblin::elf::dynamic::DT_RPATH
+            && entry.d_tag != goblin::elf::dynamic::DT_RUNPATH
+        {
+            continue;
+        }
+
+        if let Some(Ok(value)) = elf.dynstrtab.get(entry.d_val as usize) {
+            for path in value.split(':').filter(|p| !p.is_empty()) {
+                let path = path
+                    .replace("${ORIGIN}", "$ORIGIN")
+                    .replace("$ORIGIN", &origin.display().to_string());
+
+                res.push(PathBuf::from(path));
+            }
+        }
+    }
+
+    res
+}
+
+/// The result of resolving the dynamic symbols of an ELF binary.
+#[derive(Clone, Debug, Default)]
+pub struct SymbolResolution {
+    /// Names of libraries that were loaded, in load order.
+    pub loaded_libraries: Vec<String>,
+
+    /// System libraries that weren't found and are assumed to be present.
+    pub assumed_libraries: BTreeSet<String>,
+
+    /// Libraries that couldn't be found, with the binaries requiring them.
+    pub missing_libraries: BTreeMap<String, BTreeSet<String>>,
+
+    /// Symbols not provided by any loaded library, keyed by the binary
+    /// requiring them. Versioned symbols are formatted as ``name@version``.
+    pub unresolved_symbols: BTreeMap<String, Vec<String>>,
+}
+
+/// Resolve the dynamic symbols of an ELF binary against its dependencies.
+///
+/// This emulates the dynamic linker: shared library dependencies are found
+/// via the ``DT_RPATH`` and ``DT_RUNPATH`` of the binary requiring them,
+/// then `system_library_paths`. Every non-weak undefined symbol of the
+/// binary and its loaded dependencies must be defined by one of them.
+///
+/// System libraries not found in `system_library_paths` are assumed to be
+/// present and to provide every symbol bound to them. Unversioned symbols
+/// can't be attributed to a library, so they're only reported if every
+/// dependency was loaded.
+pub fn resolve_elf_symbols(
+    path: &Path,
+    system_library_paths: &[PathBuf],
+) -> Result<SymbolResolution, String> {
+    let mut resolution = SymbolResolution::default();
+
+    // (name, undefined symbols, defined symbols) of each loaded binary.
+    let mut loaded = Vec::new();
+    let mut queue = VecDeque::new();
+    let mut seen = BTreeSet::new();
+
+    queue.push_back((
+        path.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        path.to_path_buf(),
+    ));
+
+    while let Some((name, path)) = queue.pop_front() {
+        let buffer = std::fs::read(&path)
+            .or_else(|e| Err(format!("error reading {}: {}", path.display(), e)))?;
+        let elf = goblin::elf::Elf::parse(&buffer)
+            .or_else(|e| Err(format!("error parsing {}: {}", path.display(), e)))?;
+
+        let origin = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut search_paths = elf_search_paths(&elf, origin);
+        search_paths.extend(system_library_paths.iter().cloned());
+
+        for library in &elf.libraries {
+            if !seen.insert(library.to_string()) {
+                continue;
+            }
+
+            match search_paths
+                .iter()
+                .map(|p| p.join(library))
+                .find(|p| p.is_file())
+            {
+                Some(library_path) => {
+                    queue.push_back((library.to_string(), library_path));
+                }
+                None if is_system_library(library) => {
+                    resolution.assumed_libraries.insert(library.to_string());
                 }
                 None => {
-                    //println!("  {}", &symbol.symbol)
+                    resolution
+                        .missing_libraries
+                        .entry(library.to_string())
+                        .or_default()
+                        .insert(name.clone());
                 }
             }
         }
 
+        loaded.push((
+            name.clone(),
+            find_undefined_elf_symbols(&buffer, &elf),
+            find_defined_elf_symbols(&buffer, &elf),
+        ));
+
+        if loaded.len() > 1 {
+            resolution.loaded_libraries.push(name);
+        }
+    }
+
+    let all_loaded =
+        resolution.assumed_libraries.is_empty() && resolution.missing_libraries.is_empty();
+
+    for (name, undefined_symbols, _) in &loaded {
+        for symbol in undefined_symbols.iter().filter(|s| !s.weak) {
+            let defined = loaded.iter().any(|(_, _, defined)| {
+                defined.iter().any(|d| {
+                    d.symbol == symbol.symbol
+                        && (symbol.version.is_none() || d.version == symbol.version)
+                })
+            });
+
+            if defined {
+                continue;
+            }
+
+            // Versioned symbols are bound to a library by name.
+            let assumed = match &symbol.filename {
+                Some(filename) => !resolution.loaded_libraries.contains(filename),
+                None => !all_loaded,
+            };
+
+            if assumed {
+                continue;
+            }
+
+            resolution
+                .unresolved_symbols
+                .entry(name.clone())
+                .or_default()
+                .push(match &symbol.version {
+                    Some(version) => format!("{}@{}", symbol.symbol, version),
+                    None => symbol.symbol.clone(),
+                });
+        }
+    }
+
+    Ok(resolution)
+}
+
+/// Verify all libraries and symbols of a binary were resolved.
+pub fn verify_symbol_resolution(resolution: &SymbolResolution) -> Result<(), String> {
+    let mut violations = Vec::new();
+
+    for (library, consumers) in &resolution.missing_libraries {
+        violations.push(format!(
+            "library {} required by {} not found",
+            library,
+            consumers.iter().cloned().collect::<Vec<_>>().join(", ")
+        ));
+    }
+
+    for (name, symbols) in &resolution.unresolved_symbols {
+        violations.push(format!(
+            "{} requires undefined symbols {}",
+            name,
+            symbols.join(", ")
+        ));
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "binary would fail to load: {}",
+            violations.join("; ")
+        ))
+    }
+}
+
+/// Verify a binary's symbol version requirements satisfy a policy.
+///
+/// The returned error lists every namespace exceeding its maximum version
+/// along with the symbols responsible.
+pub fn verify_symbol_versions(
+    report: &SymbolVersionReport,
+    policy: &SymbolVersionPolicy,
+) -> Result<(), String> {
+    let mut violations = Vec::new();
+
+    for (namespace, max_version) in &policy.max_versions {
+        let requirement = match report.requirements.get(namespace) {
+            Some(requirement) => requirement,
+            None => continue,
+        };
+
+        let required =
+            version_compare::Version::from(&requirement.version).expect("unable to parse version");
+        let max = version_compare::Version::from(max_version).expect("unable to parse version");
+
+        if required > max {
+            violations.push(format!(
+                "{} {} required but at most {} allowed (required by {})",
+                namespace,
+                requirement.version,
+                max_version,
+                requirement.symbols.join(", ")
+            ));
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "binary violates symbol version policy: {}",
+            violations.join("; ")
+        ))
+    }
+}
+
+pub fn analyze_elf_libraries(libs: &[&str], undefined_symbols: &Vec<UndefinedSymbol>) {
+    println!("Shared Library Dependencies");
+    println!("===========================");
+
+    for lib in itertools::sorted(libs) {
+        println!("{}", lib);
+
+        if LSB_SHARED_LIBRARIES.contains(&lib) {
+            println!("  OK - Library part of Linux Shared Bass and present on most distros");
+        } else {
+            println!("  PROBLEMATIC - Shared library dependency may not be on all machines");
+        }
+
         println!();
     }
 
+    let report = symbol_version_report(undefined_symbols);
+
     println!("Symbol Versioning");
     println!("=================");
 
-    for (name, version) in &latest_symbols {
+    for (name, requirement) in &report.requirements {
+        let version =
+            version_compare::Version::from(&requirement.version).expect("unable to parse version");
+
         match name.as_str() {
             "GLIBC" => {
                 println!();
@@ -312,6 +1539,110 @@ pub fn analyze_elf_libraries(libs: &[&str], undefined_symbols: &Vec<UndefinedSym
     }
 }
 
+pub fn analyze_symbol_resolution(resolution: &SymbolResolution) {
+    println!();
+    println!("Symbol Resolution");
+    println!("=================");
+
+    for library in &resolution.loaded_libraries {
+        println!("{}", library);
+        println!("  OK - Library found and loaded");
+    }
+
+    for library in &resolution.assumed_libraries {
+        println!("{}", library);
+        println!("  OK - System library assumed present on target machines");
+    }
+
+    for (library, consumers) in &resolution.missing_libraries {
+        println!("{}", library);
+        println!(
+            "  PROBLEMATIC - Library required by {} not found",
+            consumers.iter().cloned().collect::<Vec<_>>().join(", ")
+        );
+    }
+
+    for (name, symbols) in &resolution.unresolved_symbols {
+        println!();
+        println!("Undefined symbols required by {}:", name);
+
+        for symbol in symbols {
+            println!("  {}", symbol);
+        }
+    }
+}
+
+pub fn analyze_pe(report: &PeReport) {
+    println!("Architecture: {}", report.architecture);
+    println!();
+    println!("DLL Dependencies");
+    println!("================");
+
+    for (dll, symbols) in &report.imports {
+        println!("{} ({} symbols)", dll, symbols.len());
+
+        match msvc_runtime(dll) {
+            Some(ref runtime) if runtime.debug => {
+                println!("  PROBLEMATIC - Debug Visual C++ runtime; can't be redistributed");
+            }
+            Some(runtime) => {
+                println!(
+                    "  OK - Visual C++ runtime {}; must be distributed with the binary",
+                    runtime.version
+                );
+            }
+            None if is_system_library(dll) => {
+                println!("  OK - DLL provided by Windows");
+            }
+            None => {
+                println!("  PROBLEMATIC - DLL must be distributed with the binary");
+            }
+        }
+    }
+}
+
+pub fn analyze_macho(report: &MachOReport) {
+    println!("Architectures");
+    println!("=============");
+
+    for arch in &report.architectures {
+        println!("{}", arch);
+    }
+
+    println!();
+    println!("Shared Library Dependencies");
+    println!("===========================");
+
+    for lib in itertools::sorted(&report.dylibs) {
+        println!("{}", lib);
+
+        if is_system_library(lib) {
+            println!("  OK - Library provided by macOS");
+        } else if lib.starts_with('@') {
+            println!("  PROBLEMATIC - Library must be distributed with the binary");
+        } else {
+            println!("  PROBLEMATIC - Library may not be present on all machines");
+        }
+    }
+
+    println!();
+    println!("Run Paths");
+    println!("=========");
+
+    for rpath in &report.rpaths {
+        println!("{}", rpath);
+    }
+
+    println!();
+    println!("Deployment Target");
+    println!("=================");
+
+    match report.macos_minimum_version {
+        Some((major, minor)) => println!("Minimum macOS Version: {}.{}", major, minor),
+        None => println!("Minimum macOS Version: Unknown"),
+    }
+}
+
 fn find_minimum_distro_version(
     version: &version_compare::Version,
     distro_versions: &BTreeMap<&'static str, DistroVersion>,
@@ -428,10 +1759,12 @@ pub fn find_undefined_elf_symbols(buffer: &[u8], elf: &goblin::elf::Elf) -> Vec<
     let mut versym_iter = versym.iter();
 
     for sym in elf.dynsyms.iter() {
-        let versym = *versym_iter.next().unwrap();
+        // Binaries not using symbol versioning have no versym section.
+        let versym = versym_iter.next().cloned().unwrap_or(0);
 
         if sym.is_import() {
             let name = dynstrtab.get(sym.st_name).unwrap().unwrap();
+            let weak = sym.st_bind() == goblin::elf::sym::STB_WEAK;
 
             res.push(if versym > 1 {
                 let (filename, version) =
@@ -441,12 +1774,14 @@ pub fn find_undefined_elf_symbols(buffer: &[u8], elf: &goblin::elf::Elf) -> Vec<
                     symbol: String::from(name),
                     filename,
                     version,
+                    weak,
                 }
             } else {
                 UndefinedSymbol {
                     symbol: String::from(name),
                     filename: None,
                     version: None,
+                    weak,
                 }
             });
         }
@@ -56,6 +56,78 @@ they were created with.
 On success, instructions on potential next steps are printed.
 ";
 
+const ANALYZE_ABOUT: &str = "\
+Analyze a built binary.
+
+For ELF binaries, shared library dependencies and required versions of
+versioned symbols (such as glibc's) are printed.
+
+Maximum symbol versions can be enforced. --max-symbol-version takes a
+value like GLIBC=2.17. --distro takes a Linux distribution and version,
+like \"RHEL 7\", and limits glibc and gcc symbol versions to those of that
+distribution. The command fails if the binary requires newer versions.
+
+For Mach-O binaries, dylib dependencies, run paths, and the minimum macOS
+version are printed. --max-macos-version fails the command if the binary
+requires a macOS version newer than the one given, like 10.12.
+
+For PE binaries, imported DLLs and the Visual C++ runtime versions they
+belong to are printed. The command fails if the binary depends on a debug
+Visual C++ runtime, unless --allow-debug-runtime is given.
+--msvc-runtime-version fails the command if the binary depends on a Visual
+C++ runtime other than the given versions, like 14.0.
+
+For ELF binaries, --verify-symbols resolves shared library dependencies
+the way the dynamic linker would and verifies every undefined symbol is
+defined by a loaded library. --system-library-path defines directories
+holding the target system's libraries. System libraries not found are
+assumed to be present.
+
+--system-libraries-only fails the command if the binary depends on a
+shared library not provided by the operating system.
+";
+
+const INSPECT_RESOURCES_ABOUT: &str = "\
+Show the content of packed modules or resources data.
+
+The FILE argument is a path to a file containing packed modules or
+resources data, as produced by PyOxidizer, or to an executable embedding
+such data.
+
+Each entry's type, size, and flags are printed. Only data in a versioned
+format can be found in executables.
+";
+
+const FIND_RESOURCES_ABOUT: &str = "\
+Explain how files are classified as Python resources.
+
+The PATH argument is a virtualenv, a site-packages directory, any other
+directory holding Python packages, or a wheel file.
+
+For each file, its classification (module source, bytecode, resource,
+extension module, metadata, etc.), the reason for that classification,
+and whether packaging rules would package it are printed.
+
+--include and --exclude define patterns using the syntax of the excludes
+of packaging rules. When --include is given, only files matching an
+include pattern are packaged. Files matching an --exclude pattern are
+never packaged. The patterns matching each file are reported.
+";
+
+const CDHASH_ABOUT: &str = "\
+Print the cdhashes of a signed Mach-O binary.
+
+The PATH argument is a Mach-O binary or a macOS application bundle, in
+which case the bundle's main executable is used.
+
+A cdhash identifies a signed binary. It is the digest of a code directory
+in the binary's code signature, truncated to 20 bytes. Notarization tickets
+and code signing policies refer to binaries by their cdhash.
+
+The architecture, hash type, cdhash, and signing identifier of every code
+directory of every architecture in the binary are printed.
+";
+
 const RUN_BUILD_SCRIPT_ABOUT: &str = "\
 Runs a crate build script to generate Python artifacts.
 
@@ -76,6 +148,22 @@ pub fn run_cli() -> Result<(), String> {
         .version(BUILD_SEMVER_LIGHTWEIGHT)
         .author("Gregory Szorc <gregory.szorc@gmail.com>")
         .long_about("Build and distribute Python applications")
+        .arg(
+            Arg::with_name("log_level")
+                .long("log-level")
+                .global(true)
+                .takes_value(true)
+                .possible_values(&["error", "warning", "info", "debug"])
+                .help("Minimum level of log messages to print"),
+        )
+        .arg(
+            Arg::with_name("log_format")
+                .long("log-format")
+                .global(true)
+                .takes_value(true)
+                .possible_values(&["text", "json"])
+                .help("Format of log output"),
+        )
         .subcommand(
             SubCommand::with_name("add")
                 .setting(AppSettings::ArgRequiredElseHelp)
@@ -91,8 +179,138 @@ pub fn run_cli() -> Result<(), String> {
         .subcommand(
             SubCommand::with_name("analyze")
                 .about("Analyze a built binary")
+                .long_about(ANALYZE_ABOUT)
+                .arg(
+                    Arg::with_name("max_symbol_version")
+                        .long("max-symbol-version")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("NAME=VERSION")
+                        .help("Maximum allowed version of a versioned symbol namespace"),
+                )
+                .arg(
+                    Arg::with_name("distro")
+                        .long("distro")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("DISTRO")
+                        .help("Linux distribution version the binary must run on"),
+                )
+                .arg(
+                    Arg::with_name("max_macos_version")
+                        .long("max-macos-version")
+                        .takes_value(true)
+                        .value_name("VERSION")
+                        .help("Oldest macOS version the binary must run on"),
+                )
+                .arg(
+                    Arg::with_name("system_libraries_only")
+                        .long("system-libraries-only")
+                        .help(
+                            "Require all shared libraries to be provided by the operating system",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("msvc_runtime_version")
+                        .long("msvc-runtime-version")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("VERSION")
+                        .help("Visual C++ runtime version the binary may depend on"),
+                )
+                .arg(
+                    Arg::with_name("allow_debug_runtime")
+                        .long("allow-debug-runtime")
+                        .help("Allow depending on debug builds of the Visual C++ runtime"),
+                )
+                .arg(
+                    Arg::with_name("verify_symbols")
+                        .long("verify-symbols")
+                        .help("Verify all shared libraries and undefined symbols can be resolved"),
+                )
+                .arg(
+                    Arg::with_name("system_library_path")
+                        .long("system-library-path")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("DIR")
+                        .help("Directory holding shared libraries of the target system"),
+                )
                 .arg(Arg::with_name("path").help("Path to executable to analyze")),
         )
+        .subcommand(
+            SubCommand::with_name("cdhash")
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .about("Print the cdhashes of a signed Mach-O binary")
+                .long_about(CDHASH_ABOUT)
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Print output as JSON"),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .required(true)
+                        .value_name("PATH")
+                        .help("Path to a Mach-O binary or application bundle"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("find-resources")
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .about("Explain how files are classified as Python resources")
+                .long_about(FIND_RESOURCES_ABOUT)
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Print output as JSON"),
+                )
+                .arg(
+                    Arg::with_name("include")
+                        .long("include")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("PATTERN")
+                        .help("Pattern of resources to include"),
+                )
+                .arg(
+                    Arg::with_name("exclude")
+                        .long("exclude")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("PATTERN")
+                        .help("Pattern of resources to exclude"),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .required(true)
+                        .value_name("PATH")
+                        .help("Directory or wheel to scan"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("inspect-resources")
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .about("Show the content of packed modules or resources data")
+                .long_about(INSPECT_RESOURCES_ABOUT)
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Print output as JSON"),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .required(true)
+                        .value_name("FILE")
+                        .help("Path to packed data or an executable"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("run-build-script")
                 .setting(AppSettings::ArgRequiredElseHelp)
@@ -217,6 +435,21 @@ pub fn run_cli() -> Result<(), String> {
         )
         .get_matches();
 
+    // Logging settings are passed via the environment so they are inherited
+    // by the build script invoked by `cargo build`.
+    for (arg, env_var) in &[
+        ("log_level", logging::LOG_LEVEL_ENV),
+        ("log_format", logging::LOG_FORMAT_ENV),
+    ] {
+        let value = matches
+            .value_of(arg)
+            .or_else(|| matches.subcommand().1.and_then(|args| args.value_of(arg)));
+
+        if let Some(value) = value {
+            std::env::set_var(env_var, value);
+        }
+    }
+
     let logger_context = logging::logger_from_env();
 
     match matches.subcommand() {
@@ -229,9 +462,39 @@ pub fn run_cli() -> Result<(), String> {
         ("analyze", Some(args)) => {
             let path = args.value_of("path").unwrap();
             let path = PathBuf::from(path);
-            analyze::analyze_file(path);
 
-            Ok(())
+            let mut policy = analyze::PortabilityPolicy::default();
+
+            if let Some(values) = args.values_of("distro") {
+                for value in values {
+                    policy.symbol_versions.add_distro(value)?;
+                }
+            }
+
+            if let Some(values) = args.values_of("max_symbol_version") {
+                for value in values {
+                    policy.symbol_versions.add_max_version(value)?;
+                }
+            }
+
+            if let Some(value) = args.value_of("max_macos_version") {
+                policy.set_max_macos_version(value)?;
+            }
+
+            policy.system_libraries_only = args.is_present("system_libraries_only");
+
+            if let Some(values) = args.values_of("msvc_runtime_version") {
+                policy.msvc_runtime_versions = values.map(|v| v.to_string()).collect();
+            }
+
+            policy.allow_debug_runtime = args.is_present("allow_debug_runtime");
+            policy.verify_symbols = args.is_present("verify_symbols");
+
+            if let Some(values) = args.values_of("system_library_path") {
+                policy.system_library_paths = values.map(PathBuf::from).collect();
+            }
+
+            analyze::analyze_file(path, &policy)
         }
 
         ("build-artifacts", Some(args)) => {
@@ -259,6 +522,35 @@ pub fn run_cli() -> Result<(), String> {
             projectmgmt::init(name)
         }
 
+        ("cdhash", Some(args)) => {
+            let path = args.value_of("path").unwrap();
+            let json = args.is_present("json");
+
+            analyze::print_cdhashes(Path::new(path), json)
+        }
+
+        ("find-resources", Some(args)) => {
+            let path = args.value_of("path").unwrap();
+            let includes = args
+                .values_of("include")
+                .map(|values| values.map(|v| v.to_string()).collect())
+                .unwrap_or_else(Vec::new);
+            let excludes = args
+                .values_of("exclude")
+                .map(|values| values.map(|v| v.to_string()).collect())
+                .unwrap_or_else(Vec::new);
+            let json = args.is_present("json");
+
+            projectmgmt::find_resources(path, &includes, &excludes, json)
+        }
+
+        ("inspect-resources", Some(args)) => {
+            let path = args.value_of("path").unwrap();
+            let json = args.is_present("json");
+
+            projectmgmt::inspect_resources(path, json)
+        }
+
         ("python-distribution-extract", Some(args)) => {
             let dist_path = args.value_of("dist_path").unwrap();
             let dest_path = args.value_of("dest_path").unwrap();
@@ -281,7 +573,7 @@ pub fn run_cli() -> Result<(), String> {
         ("run", Some(args)) => {
             let target = args.value_of("target");
             let release = args.is_present("release");
-            let path = args.value_of("path").unwrap();;
+            let path = args.value_of("path").unwrap();
             let extra: Vec<&str> = args.values_of("extra").unwrap_or_default().collect();
 
             projectmgmt::run(&logger_context.logger, path, target, release, &extra)
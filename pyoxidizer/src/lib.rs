@@ -13,9 +13,11 @@ This library exposes that functionality to other tools.
 
 pub mod analyze;
 pub mod environment;
+pub mod licensing;
 pub mod logging;
 pub mod projectmgmt;
 pub mod pyrepackager;
 pub mod python_distributions;
+pub mod strip;
 
 pub use pyrepackager::repackage::run_from_build;
@@ -0,0 +1,1034 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Track the licenses of components distributed with an application.
+//!
+//! Binaries produced by PyOxidizer contain code from the Python distribution,
+//! from Python packages, and from Rust crates. Many of their licenses require
+//! that a copyright notice accompany redistributed copies. This module
+//! collects license metadata for these components and renders it into a
+//! single notices document.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// The type of a licensed component.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum ComponentFlavor {
+    /// The Python distribution itself.
+    PythonDistribution,
+
+    /// An extension module or library built into the Python distribution.
+    PythonExtensionModule,
+
+    /// A Python package, typically installed from a wheel or sdist.
+    PythonPackage,
+
+    /// A Rust crate compiled into the application.
+    RustCrate,
+}
+
+impl ComponentFlavor {
+    /// Human readable description of the flavor.
+    pub fn description(self) -> &'static str {
+        match self {
+            ComponentFlavor::PythonDistribution => "Python distribution",
+            ComponentFlavor::PythonExtensionModule => "Python extension module",
+            ComponentFlavor::PythonPackage => "Python package",
+            ComponentFlavor::RustCrate => "Rust crate",
+        }
+    }
+}
+
+/// Describes the license of a component.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum LicenseFlavor {
+    /// An SPDX license expression, e.g. ``MIT OR Apache-2.0``.
+    Spdx(String),
+
+    /// The component is in the public domain.
+    PublicDomain,
+
+    /// A license that can't be expressed in SPDX, such as free-form text.
+    Other(String),
+
+    /// The license isn't known.
+    Unknown,
+}
+
+impl LicenseFlavor {
+    /// Construct an instance from a list of SPDX license identifiers that all apply.
+    pub fn from_spdx_licenses(licenses: &[String]) -> LicenseFlavor {
+        match licenses.len() {
+            0 => LicenseFlavor::Unknown,
+            1 => LicenseFlavor::Spdx(licenses[0].clone()),
+            _ => LicenseFlavor::Spdx(
+                licenses
+                    .iter()
+                    .map(|l| {
+                        if l.contains(' ') {
+                            format!("({})", l)
+                        } else {
+                            l.clone()
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" AND "),
+            ),
+        }
+    }
+
+    /// Human readable description of the license.
+    pub fn description(&self) -> String {
+        match self {
+            LicenseFlavor::Spdx(expression) => expression.clone(),
+            LicenseFlavor::PublicDomain => "Public Domain".to_string(),
+            LicenseFlavor::Other(value) => value.clone(),
+            LicenseFlavor::Unknown => "Unknown".to_string(),
+        }
+    }
+}
+
+/// A file holding the text of a license or a copyright notice.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LicenseText {
+    /// Name of the file the text came from.
+    pub filename: String,
+
+    /// Content of the file.
+    pub text: String,
+}
+
+/// A component distributed with an application, along with its license.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LicensedComponent {
+    /// The type of component.
+    pub flavor: ComponentFlavor,
+
+    /// Name of the component.
+    pub name: String,
+
+    /// Version of the component, if known.
+    pub version: Option<String>,
+
+    /// License of the component.
+    pub license: LicenseFlavor,
+
+    /// Texts of licenses and notices of the component.
+    pub license_texts: Vec<LicenseText>,
+
+    /// URL of the component's homepage, if known.
+    pub homepage: Option<String>,
+}
+
+impl LicensedComponent {
+    pub fn new(flavor: ComponentFlavor, name: &str, license: LicenseFlavor) -> LicensedComponent {
+        LicensedComponent {
+            flavor,
+            name: name.to_string(),
+            version: None,
+            license,
+            license_texts: Vec::new(),
+            homepage: None,
+        }
+    }
+
+    /// Name and version of the component.
+    pub fn display_name(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{} {}", self.name, version),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// A collection of licensed components.
+///
+/// Components are unique by flavor and name. They are iterated sorted by
+/// flavor, then by case-insensitive name.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct LicensedComponents {
+    components: BTreeMap<(ComponentFlavor, String), LicensedComponent>,
+}
+
+impl LicensedComponents {
+    /// Add a component, replacing an existing component of the same flavor and name.
+    pub fn add(&mut self, component: LicensedComponent) {
+        self.components
+            .insert((component.flavor, component.name.to_lowercase()), component);
+    }
+
+    pub fn extend<T: IntoIterator<Item = LicensedComponent>>(&mut self, components: T) {
+        for component in components {
+            self.add(component);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &LicensedComponent> {
+        self.components.values()
+    }
+}
+
+/// A parsed SPDX license expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpdxExpression {
+    /// A license identifier, optionally with an exception.
+    License {
+        id: String,
+        exception: Option<String>,
+    },
+
+    /// All of the expressions apply.
+    And(Vec<SpdxExpression>),
+
+    /// Any of the expressions may be chosen.
+    Or(Vec<SpdxExpression>),
+}
+
+impl SpdxExpression {
+    /// Parse an SPDX license expression.
+    ///
+    /// Operators are matched case-insensitively. License identifiers aren't
+    /// validated against the SPDX license list.
+    pub fn parse(value: &str) -> Result<SpdxExpression, String> {
+        let spaced = value.replace('(', " ( ").replace(')', " ) ");
+        let tokens = spaced.split_whitespace().collect::<Vec<_>>();
+
+        let mut pos = 0;
+        let expression = parse_spdx_or(&tokens, &mut pos)
+            .ok_or_else(|| format!("invalid SPDX expression: {}", value))?;
+
+        if pos != tokens.len() {
+            return Err(format!("invalid SPDX expression: {}", value));
+        }
+
+        Ok(expression)
+    }
+}
+
+fn is_spdx_operator(token: &str, operator: &str) -> bool {
+    token.eq_ignore_ascii_case(operator)
+}
+
+fn parse_spdx_or(tokens: &[&str], pos: &mut usize) -> Option<SpdxExpression> {
+    let mut terms = vec![parse_spdx_and(tokens, pos)?];
+
+    while *pos < tokens.len() && is_spdx_operator(tokens[*pos], "OR") {
+        *pos += 1;
+        terms.push(parse_spdx_and(tokens, pos)?);
+    }
+
+    Some(if terms.len() == 1 {
+        terms.remove(0)
+    } else {
+        SpdxExpression::Or(terms)
+    })
+}
+
+fn parse_spdx_and(tokens: &[&str], pos: &mut usize) -> Option<SpdxExpression> {
+    let mut terms = vec![parse_spdx_primary(tokens, pos)?];
+
+    while *pos < tokens.len() && is_spdx_operator(tokens[*pos], "AND") {
+        *pos += 1;
+        terms.push(parse_spdx_primary(tokens, pos)?);
+    }
+
+    Some(if terms.len() == 1 {
+        terms.remove(0)
+    } else {
+        SpdxExpression::And(terms)
+    })
+}
+
+fn parse_spdx_primary(tokens: &[&str], pos: &mut usize) -> Option<SpdxExpression> {
+    let token = *tokens.get(*pos)?;
+    *pos += 1;
+
+    if token == "(" {
+        let expression = parse_spdx_or(tokens, pos)?;
+
+        if tokens.get(*pos) != Some(&")") {
+            return None;
+        }
+        *pos += 1;
+
+        return Some(expression);
+    }
+
+    if token == ")"
+        || is_spdx_operator(token, "AND")
+        || is_spdx_operator(token, "OR")
+        || is_spdx_operator(token, "WITH")
+    {
+        return None;
+    }
+
+    let exception = if tokens
+        .get(*pos)
+        .map(|t| is_spdx_operator(t, "WITH"))
+        .unwrap_or(false)
+    {
+        let exception = *tokens.get(*pos + 1)?;
+        *pos += 2;
+        Some(exception.to_string())
+    } else {
+        None
+    };
+
+    Some(SpdxExpression::License {
+        id: token.to_string(),
+        exception,
+    })
+}
+
+/// Defines which licenses may be distributed with an application.
+#[derive(Clone, Debug, Default)]
+pub struct LicensePolicy {
+    /// License identifiers that are allowed.
+    ///
+    /// If empty, all licenses not denied are allowed.
+    pub allowed: Vec<String>,
+
+    /// License identifiers that are not allowed.
+    ///
+    /// These take precedence over allowed licenses.
+    pub denied: Vec<String>,
+
+    /// Whether components with unknown or non-SPDX licenses are allowed.
+    pub allow_unknown: bool,
+}
+
+/// Whether a license matches a policy pattern.
+///
+/// Patterns are compared case-insensitively against the license identifier
+/// and against the identifier with its exception, e.g.
+/// ``GPL-2.0-only WITH Classpath-exception-2.0``. A trailing ``*`` matches
+/// any suffix.
+fn license_matches(pattern: &str, id: &str, exception: Option<&str>) -> bool {
+    let pattern = pattern.split_whitespace().collect::<Vec<_>>().join(" ");
+    let pattern = pattern.to_lowercase();
+
+    let mut candidates = vec![id.to_lowercase()];
+    if let Some(exception) = exception {
+        candidates.push(format!("{} with {}", id, exception).to_lowercase());
+    }
+
+    candidates.iter().any(|candidate| {
+        if pattern.ends_with('*') {
+            candidate.starts_with(&pattern[0..pattern.len() - 1])
+        } else {
+            candidate == &pattern
+        }
+    })
+}
+
+impl LicensePolicy {
+    /// Whether a single license is allowed.
+    pub fn allows_license(&self, id: &str, exception: Option<&str>) -> bool {
+        if self
+            .denied
+            .iter()
+            .any(|p| license_matches(p, id, exception))
+        {
+            return false;
+        }
+
+        self.allowed.is_empty()
+            || self
+                .allowed
+                .iter()
+                .any(|p| license_matches(p, id, exception))
+    }
+
+    /// Whether an SPDX expression is allowed.
+    ///
+    /// Alternatives joined with ``OR`` are allowed if any of them is allowed.
+    /// Licenses joined with ``AND`` must all be allowed.
+    pub fn allows_expression(&self, expression: &SpdxExpression) -> bool {
+        match expression {
+            SpdxExpression::License { id, exception } => {
+                self.allows_license(id, exception.as_ref().map(|s| s.as_str()))
+            }
+            SpdxExpression::And(terms) => terms.iter().all(|t| self.allows_expression(t)),
+            SpdxExpression::Or(terms) => terms.iter().any(|t| self.allows_expression(t)),
+        }
+    }
+
+    /// Evaluate a component against the policy.
+    ///
+    /// Returns a description of the problem if the component isn't allowed.
+    pub fn evaluate(&self, component: &LicensedComponent) -> Result<(), String> {
+        match &component.license {
+            LicenseFlavor::PublicDomain => Ok(()),
+            LicenseFlavor::Spdx(value) => match SpdxExpression::parse(value) {
+                Ok(expression) => {
+                    if self.allows_expression(&expression) {
+                        Ok(())
+                    } else {
+                        Err(format!("license {} is not allowed", value))
+                    }
+                }
+                Err(_) if self.allow_unknown => Ok(()),
+                Err(e) => Err(e),
+            },
+            _ if self.allow_unknown => Ok(()),
+            LicenseFlavor::Other(value) => {
+                Err(format!("license {} is not an SPDX expression", value))
+            }
+            LicenseFlavor::Unknown => Err("license is unknown".to_string()),
+        }
+    }
+
+    /// Verify all components satisfy the policy.
+    ///
+    /// The error describes every component that violates the policy.
+    pub fn verify(&self, components: &LicensedComponents) -> Result<(), String> {
+        let problems = components
+            .iter()
+            .filter_map(|component| match self.evaluate(component) {
+                Ok(()) => None,
+                Err(e) => Some(format!(
+                    "{} {}: {}",
+                    component.flavor.description(),
+                    component.display_name(),
+                    e
+                )),
+            })
+            .collect::<Vec<_>>();
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "license policy violations:\n{}",
+                problems.join("\n")
+            ))
+        }
+    }
+}
+
+/// The format of a rendered notices document.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum NoticeFormat {
+    Text,
+    Markdown,
+    Html,
+}
+
+impl NoticeFormat {
+    /// Resolve a format from its name in config files.
+    pub fn from_name(value: &str) -> Result<NoticeFormat, String> {
+        match value {
+            "text" => Ok(NoticeFormat::Text),
+            "markdown" => Ok(NoticeFormat::Markdown),
+            "html" => Ok(NoticeFormat::Html),
+            _ => Err(format!(
+                "invalid notices format: {}; must be text, markdown, or html",
+                value
+            )),
+        }
+    }
+}
+
+const NOTICES_TITLE: &str = "Third-Party Software Notices";
+const NOTICES_INTRO: &str =
+    "This software contains the following third-party components, which are \
+     distributed under the terms of the licenses listed below.";
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn render_text(components: &LicensedComponents) -> String {
+    let mut lines = vec![NOTICES_TITLE.to_uppercase(), "".to_string()];
+    lines.push(NOTICES_INTRO.to_string());
+
+    for component in components.iter() {
+        lines.push("".to_string());
+        lines.push("=".repeat(79));
+        lines.push(format!(
+            "{} ({})",
+            component.display_name(),
+            component.flavor.description()
+        ));
+        lines.push(format!("License: {}", component.license.description()));
+        if let Some(homepage) = &component.homepage {
+            lines.push(format!("Homepage: {}", homepage));
+        }
+
+        for text in &component.license_texts {
+            lines.push("".to_string());
+            lines.push(format!("--- {} ---", text.filename));
+            lines.push("".to_string());
+            lines.push(text.text.trim_end().to_string());
+        }
+    }
+
+    lines.push("".to_string());
+    lines.join("\n")
+}
+
+fn render_markdown(components: &LicensedComponents) -> String {
+    let mut lines = vec![format!("# {}", NOTICES_TITLE), "".to_string()];
+    lines.push(NOTICES_INTRO.to_string());
+
+    for component in components.iter() {
+        lines.push("".to_string());
+        lines.push(format!("## {}", component.display_name()));
+        lines.push("".to_string());
+        lines.push(format!("* Type: {}", component.flavor.description()));
+        lines.push(format!("* License: {}", component.license.description()));
+        if let Some(homepage) = &component.homepage {
+            lines.push(format!("* Homepage: <{}>", homepage));
+        }
+
+        for text in &component.license_texts {
+            // The fence must be longer than any backtick run in the text.
+            let mut fence = "```".to_string();
+            while text.text.contains(&fence) {
+                fence.push('`');
+            }
+
+            lines.push("".to_string());
+            lines.push(format!("### {}", text.filename));
+            lines.push("".to_string());
+            lines.push(fence.clone());
+            lines.push(text.text.trim_end().to_string());
+            lines.push(fence);
+        }
+    }
+
+    lines.push("".to_string());
+    lines.join("\n")
+}
+
+fn render_html(components: &LicensedComponents) -> String {
+    let mut lines = vec![
+        "<!DOCTYPE html>".to_string(),
+        "<html>".to_string(),
+        "<head>".to_string(),
+        "<meta charset=\"utf-8\">".to_string(),
+        format!("<title>{}</title>", NOTICES_TITLE),
+        "</head>".to_string(),
+        "<body>".to_string(),
+        format!("<h1>{}</h1>", NOTICES_TITLE),
+        format!("<p>{}</p>", NOTICES_INTRO),
+    ];
+
+    for component in components.iter() {
+        lines.push(format!(
+            "<h2>{}</h2>",
+            escape_html(&component.display_name())
+        ));
+        lines.push("<ul>".to_string());
+        lines.push(format!("<li>Type: {}</li>", component.flavor.description()));
+        lines.push(format!(
+            "<li>License: {}</li>",
+            escape_html(&component.license.description())
+        ));
+        if let Some(homepage) = &component.homepage {
+            let homepage = escape_html(homepage);
+            lines.push(format!(
+                "<li>Homepage: <a href=\"{}\">{}</a></li>",
+                homepage, homepage
+            ));
+        }
+        lines.push("</ul>".to_string());
+
+        for text in &component.license_texts {
+            lines.push(format!("<h3>{}</h3>", escape_html(&text.filename)));
+            lines.push(format!("<pre>{}</pre>", escape_html(text.text.trim_end())));
+        }
+    }
+
+    lines.push("</body>".to_string());
+    lines.push("</html>".to_string());
+    lines.push("".to_string());
+    lines.join("\n")
+}
+
+/// Render a notices document for a set of components.
+///
+/// The document lists every component with its license and includes the
+/// license texts of each.
+pub fn render_notices(components: &LicensedComponents, format: NoticeFormat) -> String {
+    match format {
+        NoticeFormat::Text => render_text(components),
+        NoticeFormat::Markdown => render_markdown(components),
+        NoticeFormat::Html => render_html(components),
+    }
+}
+
+/// Whether a file name looks like a license or notice file.
+pub fn is_license_filename(name: &str) -> bool {
+    let name = name.to_uppercase();
+
+    name.starts_with("LICENSE")
+        || name.starts_with("LICENCE")
+        || name.starts_with("COPYING")
+        || name.starts_with("NOTICE")
+        || name.starts_with("COPYRIGHT")
+}
+
+/// Read license texts of a crate from its source directory.
+fn read_crate_license_texts(
+    crate_dir: &Path,
+    license_file: Option<&str>,
+) -> Result<Vec<LicenseText>, String> {
+    let mut paths = Vec::new();
+
+    if let Some(license_file) = license_file {
+        paths.push(crate_dir.join(license_file));
+    }
+
+    let mut entries = fs::read_dir(crate_dir)
+        .or_else(|e| Err(format!("error reading {}: {}", crate_dir.display(), e)))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|p| {
+            p.is_file()
+                && p.file_name()
+                    .and_then(|n| n.to_str())
+                    .map(is_license_filename)
+                    .unwrap_or(false)
+        })
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    for path in entries {
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+
+    let mut res = Vec::new();
+
+    for path in paths {
+        let text = fs::read_to_string(&path)
+            .or_else(|e| Err(format!("error reading {}: {}", path.display(), e)))?;
+
+        res.push(LicenseText {
+            filename: path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("LICENSE")
+                .to_string(),
+            text,
+        });
+    }
+
+    Ok(res)
+}
+
+/// Obtain the ``cfg`` values rustc defines when compiling for a target.
+///
+/// Values are formatted as printed by ``rustc --print cfg``, e.g. ``unix``
+/// or ``target_os="linux"``.
+fn target_cfgs(target_triple: &str) -> Result<Vec<String>, String> {
+    let output = Command::new("rustc")
+        .arg("--print")
+        .arg("cfg")
+        .arg("--target")
+        .arg(target_triple)
+        .output()
+        .or_else(|e| Err(format!("error running rustc: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "rustc --print cfg failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+        .collect())
+}
+
+/// Evaluate a ``cfg()`` predicate against ``cfg`` values of a target.
+fn eval_cfg(tokens: &[&str], pos: &mut usize, cfgs: &[String]) -> Option<bool> {
+    let token = *tokens.get(*pos)?;
+    *pos += 1;
+
+    match token {
+        "all" | "any" | "not" if tokens.get(*pos) == Some(&"(") => {
+            *pos += 1;
+
+            let mut values = Vec::new();
+            while tokens.get(*pos) != Some(&")") {
+                values.push(eval_cfg(tokens, pos, cfgs)?);
+
+                match tokens.get(*pos) {
+                    Some(&",") => *pos += 1,
+                    Some(&")") => {}
+                    _ => return None,
+                }
+            }
+            *pos += 1;
+
+            match token {
+                "all" => Some(values.iter().all(|v| *v)),
+                "any" => Some(values.iter().any(|v| *v)),
+                _ if values.len() == 1 => Some(!values[0]),
+                _ => None,
+            }
+        }
+        "(" | ")" | "," | "=" => None,
+        name => {
+            if tokens.get(*pos) == Some(&"=") {
+                let value = *tokens.get(*pos + 1)?;
+                *pos += 2;
+                let cfg = format!("{}={}", name, value);
+                Some(cfgs.contains(&cfg))
+            } else {
+                Some(cfgs.iter().any(|c| c == name))
+            }
+        }
+    }
+}
+
+/// Whether the ``target`` of a Cargo dependency applies to a target.
+///
+/// ``target`` is either a target triple or a ``cfg()`` expression. Invalid
+/// expressions apply, so crates are listed rather than silently dropped.
+fn dependency_target_matches(target: &str, target_triple: &str, cfgs: &[String]) -> bool {
+    if !target.starts_with("cfg(") || !target.ends_with(')') {
+        return target == target_triple;
+    }
+
+    let spaced = target[4..target.len() - 1]
+        .replace('(', " ( ")
+        .replace(')', " ) ")
+        .replace(',', " , ")
+        .replace('=', " = ");
+    let tokens = spaced.split_whitespace().collect::<Vec<_>>();
+
+    let mut pos = 0;
+    match eval_cfg(&tokens, &mut pos, cfgs) {
+        Some(value) if pos == tokens.len() => value,
+        _ => true,
+    }
+}
+
+/// Resolve Rust crates a Cargo project depends on.
+///
+/// This runs ``cargo metadata`` against the given ``Cargo.toml`` and follows
+/// the normal dependencies of the root package when building for the given
+/// target. Development and build dependencies, which don't end up in the
+/// binary, and packages that are members of the project's workspace aren't
+/// returned.
+///
+/// Whether a dependency applies is derived from the dependency declarations
+/// of each package, as ``cargo metadata`` only reports dependency kinds and
+/// filters platforms itself as of Cargo 1.41.
+pub fn rust_crate_components(
+    manifest_path: &Path,
+    target_triple: &str,
+) -> Result<Vec<LicensedComponent>, String> {
+    let cfgs = target_cfgs(target_triple)?;
+
+    let output = Command::new("cargo")
+        .arg("metadata")
+        .arg("--format-version")
+        .arg("1")
+        .arg("--manifest-path")
+        .arg(manifest_path)
+        .output()
+        .or_else(|e| Err(format!("error running cargo metadata: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .or_else(|e| Err(format!("error parsing cargo metadata: {}", e)))?;
+
+    let members = metadata["workspace_members"]
+        .as_array()
+        .map(|a| a.iter().filter_map(|v| v.as_str()).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let packages = metadata["packages"]
+        .as_array()
+        .ok_or_else(|| "cargo metadata does not define packages".to_string())?;
+
+    let packages_by_id = packages
+        .iter()
+        .filter_map(|package| package["id"].as_str().map(|id| (id, package)))
+        .collect::<BTreeMap<_, _>>();
+
+    let nodes = metadata["resolve"]["nodes"]
+        .as_array()
+        .ok_or_else(|| "cargo metadata does not define resolve nodes".to_string())?
+        .iter()
+        .filter_map(|node| node["id"].as_str().map(|id| (id, node)))
+        .collect::<BTreeMap<_, _>>();
+
+    // A virtual workspace has no root package, so start from its members.
+    let mut pending = match metadata["resolve"]["root"].as_str() {
+        Some(root) => vec![root],
+        None => members.clone(),
+    };
+    let mut used = BTreeSet::new();
+
+    while let Some(id) = pending.pop() {
+        if !used.insert(id) {
+            continue;
+        }
+
+        let deps = nodes
+            .get(id)
+            .and_then(|node| node["deps"].as_array())
+            .ok_or_else(|| format!("cargo metadata does not resolve {}", id))?;
+
+        let declared = packages_by_id
+            .get(id)
+            .and_then(|package| package["dependencies"].as_array())
+            .ok_or_else(|| format!("cargo metadata does not define package {}", id))?;
+
+        for dep in deps {
+            let pkg = dep["pkg"]
+                .as_str()
+                .ok_or_else(|| format!("cargo metadata has invalid dependency of {}", id))?;
+            let pkg_name = packages_by_id
+                .get(pkg)
+                .and_then(|package| package["name"].as_str())
+                .ok_or_else(|| format!("cargo metadata does not define package {}", pkg))?;
+
+            // Renamed dependencies are resolved under their new name, with
+            // dashes replaced like in crate names.
+            let normal = declared.iter().any(|decl| {
+                let renamed = match decl["rename"].as_str() {
+                    Some(rename) => Some(rename.replace('-', "_").as_str()) == dep["name"].as_str(),
+                    None => true,
+                };
+                let applies = match decl["target"].as_str() {
+                    Some(target) => dependency_target_matches(target, target_triple, &cfgs),
+                    None => true,
+                };
+
+                // Normal dependencies have a null kind.
+                decl["name"].as_str() == Some(pkg_name)
+                    && renamed
+                    && applies
+                    && decl["kind"].is_null()
+            });
+
+            if normal {
+                pending.push(pkg);
+            }
+        }
+    }
+
+    let mut res = Vec::new();
+
+    for package in packages {
+        let id = package["id"].as_str().unwrap_or("");
+        if members.contains(&id) || !used.contains(id) {
+            continue;
+        }
+
+        let name = package["name"]
+            .as_str()
+            .ok_or_else(|| format!("package {} does not have a name", id))?;
+
+        // Older crates used / to separate alternative licenses.
+        let license = match package["license"].as_str() {
+            Some(license) => LicenseFlavor::Spdx(license.replace('/', " OR ")),
+            None => LicenseFlavor::Unknown,
+        };
+
+        let mut component = LicensedComponent::new(ComponentFlavor::RustCrate, name, license);
+        component.version = package["version"].as_str().map(|s| s.to_string());
+        component.homepage = package["homepage"]
+            .as_str()
+            .or_else(|| package["repository"].as_str())
+            .map(|s| s.to_string());
+
+        if let Some(crate_dir) = package["manifest_path"].as_str().map(Path::new) {
+            if let Some(crate_dir) = crate_dir.parent() {
+                component.license_texts =
+                    read_crate_license_texts(crate_dir, package["license_file"].as_str())?;
+
+                if let LicenseFlavor::Unknown = component.license {
+                    if let Some(license_file) = package["license_file"].as_str() {
+                        component.license = LicenseFlavor::Other(format!("see {}", license_file));
+                    }
+                }
+            }
+        }
+
+        res.push(component);
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn license(id: &str) -> SpdxExpression {
+        SpdxExpression::License {
+            id: id.to_string(),
+            exception: None,
+        }
+    }
+
+    fn policy(allowed: &[&str], denied: &[&str]) -> LicensePolicy {
+        LicensePolicy {
+            allowed: allowed.iter().map(|s| s.to_string()).collect(),
+            denied: denied.iter().map(|s| s.to_string()).collect(),
+            allow_unknown: false,
+        }
+    }
+
+    fn evaluate(policy: &LicensePolicy, license: LicenseFlavor) -> Result<(), String> {
+        policy.evaluate(&LicensedComponent::new(
+            ComponentFlavor::PythonPackage,
+            "foo",
+            license,
+        ))
+    }
+
+    #[test]
+    fn spdx_and_binds_tighter_than_or() {
+        assert_eq!(
+            SpdxExpression::parse("MIT OR Apache-2.0 AND BSD-3-Clause").unwrap(),
+            SpdxExpression::Or(vec![
+                license("MIT"),
+                SpdxExpression::And(vec![license("Apache-2.0"), license("BSD-3-Clause")]),
+            ])
+        );
+        assert_eq!(
+            SpdxExpression::parse("MIT AND Apache-2.0 OR BSD-3-Clause").unwrap(),
+            SpdxExpression::Or(vec![
+                SpdxExpression::And(vec![license("MIT"), license("Apache-2.0")]),
+                license("BSD-3-Clause"),
+            ])
+        );
+        assert_eq!(
+            SpdxExpression::parse("MIT or Apache-2.0 and Zlib").unwrap(),
+            SpdxExpression::parse("MIT OR Apache-2.0 AND Zlib").unwrap()
+        );
+    }
+
+    #[test]
+    fn spdx_parentheses() {
+        assert_eq!(
+            SpdxExpression::parse("(MIT OR Apache-2.0) AND BSD-3-Clause").unwrap(),
+            SpdxExpression::And(vec![
+                SpdxExpression::Or(vec![license("MIT"), license("Apache-2.0")]),
+                license("BSD-3-Clause"),
+            ])
+        );
+        assert_eq!(SpdxExpression::parse("((MIT))").unwrap(), license("MIT"));
+        assert_eq!(
+            SpdxExpression::parse("(MIT OR Apache-2.0)AND(Zlib)").unwrap(),
+            SpdxExpression::And(vec![
+                SpdxExpression::Or(vec![license("MIT"), license("Apache-2.0")]),
+                license("Zlib"),
+            ])
+        );
+
+        for value in &["(MIT", "MIT)", "()", "(MIT OR)", "MIT (Apache-2.0)"] {
+            assert!(SpdxExpression::parse(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn spdx_with_exception() {
+        assert_eq!(
+            SpdxExpression::parse("GPL-2.0-only WITH Classpath-exception-2.0 OR MIT").unwrap(),
+            SpdxExpression::Or(vec![
+                SpdxExpression::License {
+                    id: "GPL-2.0-only".to_string(),
+                    exception: Some("Classpath-exception-2.0".to_string()),
+                },
+                license("MIT"),
+            ])
+        );
+
+        for value in &[
+            "GPL-2.0-only WITH",
+            "WITH Classpath-exception-2.0",
+            "MIT AND",
+        ] {
+            assert!(SpdxExpression::parse(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn policy_denied_takes_precedence() {
+        let policy = policy(&["GPL-*", "MIT"], &["GPL-3.0-only"]);
+
+        assert!(policy.allows_license("MIT", None));
+        assert!(policy.allows_license("mit", None));
+        assert!(policy.allows_license("GPL-2.0-only", None));
+        assert!(!policy.allows_license("GPL-3.0-only", None));
+        assert!(!policy.allows_license("Apache-2.0", None));
+
+        assert!(LicensePolicy::default().allows_license("Apache-2.0", None));
+    }
+
+    #[test]
+    fn policy_exceptions() {
+        let allowed = policy(&["MIT", "GPL-2.0-only WITH Classpath-exception-2.0"], &[]);
+
+        assert!(allowed.allows_license("GPL-2.0-only", Some("Classpath-exception-2.0")));
+        assert!(!allowed.allows_license("GPL-2.0-only", None));
+
+        // Denying a license also denies it with any exception.
+        let denied = policy(&[], &["GPL-2.0-only"]);
+        assert!(!denied.allows_license("GPL-2.0-only", Some("Classpath-exception-2.0")));
+    }
+
+    #[test]
+    fn policy_expressions() {
+        let policy = policy(&["MIT", "Apache-2.0"], &[]);
+        let allows = |value: &str| policy.allows_expression(&SpdxExpression::parse(value).unwrap());
+
+        assert!(allows("MIT OR GPL-3.0-only"));
+        assert!(!allows("MIT AND GPL-3.0-only"));
+        assert!(allows("MIT AND (Apache-2.0 OR GPL-3.0-only)"));
+        assert!(!allows("(MIT OR GPL-3.0-only) AND Zlib"));
+    }
+
+    #[test]
+    fn policy_evaluate() {
+        let strict = policy(&["MIT"], &[]);
+
+        assert!(evaluate(&strict, LicenseFlavor::Spdx("MIT".to_string())).is_ok());
+        assert!(evaluate(&strict, LicenseFlavor::PublicDomain).is_ok());
+        assert_eq!(
+            evaluate(&strict, LicenseFlavor::Spdx("Zlib".to_string())),
+            Err("license Zlib is not allowed".to_string())
+        );
+        assert!(evaluate(&strict, LicenseFlavor::Spdx("MIT AND".to_string())).is_err());
+        assert!(evaluate(&strict, LicenseFlavor::Other("Custom".to_string())).is_err());
+        assert!(evaluate(&strict, LicenseFlavor::Unknown).is_err());
+
+        let lenient = LicensePolicy {
+            allow_unknown: true,
+            ..strict.clone()
+        };
+
+        assert!(evaluate(&lenient, LicenseFlavor::Spdx("MIT AND".to_string())).is_ok());
+        assert!(evaluate(&lenient, LicenseFlavor::Other("Custom".to_string())).is_ok());
+        assert!(evaluate(&lenient, LicenseFlavor::Unknown).is_ok());
+        assert!(evaluate(&lenient, LicenseFlavor::Spdx("Zlib".to_string())).is_err());
+    }
+}
@@ -2,10 +2,201 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+//! Logging and progress reporting.
+//!
+//! Log records are written to stdout, either as human readable text or as
+//! one JSON object per line. The level and format are read from the
+//! environment so processes we spawn (such as the build script invoked by
+//! `cargo build`) log the same way we do.
+
 use slog::Drain;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Environment variable holding the minimum level of records to log.
+pub const LOG_LEVEL_ENV: &str = "PYOXIDIZER_LOG_LEVEL";
+
+/// Environment variable holding the format of log output.
+pub const LOG_FORMAT_ENV: &str = "PYOXIDIZER_LOG_FORMAT";
+
+/// Key of the record value holding the current progress of an operation.
+const PROGRESS_CURRENT_KEY: &str = "progress_current";
+
+/// Key of the record value holding the total progress of an operation.
+const PROGRESS_TOTAL_KEY: &str = "progress_total";
+
+/// Width of text progress bars, in characters.
+const PROGRESS_BAR_WIDTH: u64 = 30;
+
+/// Format of log output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    /// Human readable text.
+    Text,
+
+    /// A JSON object per line.
+    Json,
+}
+
+impl LogFormat {
+    /// Resolve a format from its name.
+    pub fn from_name(name: &str) -> Result<LogFormat, String> {
+        match name {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format: {}", name)),
+        }
+    }
+}
+
+/// Resolve a log level from its name.
+pub fn level_from_name(name: &str) -> Result<slog::Level, String> {
+    match name {
+        "error" => Ok(slog::Level::Error),
+        "warning" | "warn" => Ok(slog::Level::Warning),
+        "info" => Ok(slog::Level::Info),
+        "debug" => Ok(slog::Level::Debug),
+        _ => Err(format!("unknown log level: {}", name)),
+    }
+}
+
+fn level_name(level: slog::Level) -> &'static str {
+    match level {
+        slog::Level::Critical => "critical",
+        slog::Level::Error => "error",
+        slog::Level::Warning => "warning",
+        slog::Level::Info => "info",
+        slog::Level::Debug => "debug",
+        slog::Level::Trace => "trace",
+    }
+}
+
+/// Collects key-value pairs attached to a log record.
+#[derive(Default)]
+struct ValueCollector {
+    values: BTreeMap<String, serde_json::Value>,
+}
+
+impl ValueCollector {
+    fn insert(&mut self, key: slog::Key, value: serde_json::Value) -> slog::Result {
+        // Values closer to the record are serialized first and take precedence.
+        self.values.entry(key.to_string()).or_insert(value);
+        Ok(())
+    }
+
+    fn get_u64(&self, key: &str) -> Option<u64> {
+        self.values.get(key).and_then(|v| v.as_u64())
+    }
+}
+
+impl slog::Serializer for ValueCollector {
+    fn emit_arguments(&mut self, key: slog::Key, val: &fmt::Arguments) -> slog::Result {
+        self.insert(key, serde_json::Value::from(fmt::format(*val)))
+    }
+
+    fn emit_bool(&mut self, key: slog::Key, val: bool) -> slog::Result {
+        self.insert(key, serde_json::Value::from(val))
+    }
+
+    fn emit_usize(&mut self, key: slog::Key, val: usize) -> slog::Result {
+        self.insert(key, serde_json::Value::from(val as u64))
+    }
+
+    fn emit_u64(&mut self, key: slog::Key, val: u64) -> slog::Result {
+        self.insert(key, serde_json::Value::from(val))
+    }
+
+    fn emit_i64(&mut self, key: slog::Key, val: i64) -> slog::Result {
+        self.insert(key, serde_json::Value::from(val))
+    }
+
+    fn emit_str(&mut self, key: slog::Key, val: &str) -> slog::Result {
+        self.insert(key, serde_json::Value::from(val))
+    }
+
+    fn emit_none(&mut self, key: slog::Key) -> slog::Result {
+        self.insert(key, serde_json::Value::Null)
+    }
+}
+
+/// Render a text progress bar.
+fn progress_bar(current: u64, total: u64) -> String {
+    let current = std::cmp::min(current, total);
+    let filled = (current * PROGRESS_BAR_WIDTH)
+        .checked_div(total)
+        .unwrap_or(PROGRESS_BAR_WIDTH);
+    let percent = (current * 100).checked_div(total).unwrap_or(100);
+
+    format!(
+        "[{}{}] {:>3}% ({}/{})",
+        "#".repeat(filled as usize),
+        " ".repeat((PROGRESS_BAR_WIDTH - filled) as usize),
+        percent,
+        current,
+        total
+    )
+}
 
 /// A slog Drain that uses println!.
-pub struct PrintlnDrain {}
+///
+/// Records below `min_level` are discarded.
+pub struct PrintlnDrain {
+    pub min_level: slog::Level,
+    pub format: LogFormat,
+}
+
+impl PrintlnDrain {
+    fn format_text(&self, record: &slog::Record, values: &ValueCollector) -> String {
+        let mut line = String::new();
+
+        if let Some(target) = values.values.get("target").and_then(|v| v.as_str()) {
+            line.push_str(&format!("[{}] ", target));
+        }
+
+        match record.level() {
+            slog::Level::Critical | slog::Level::Error => line.push_str("error: "),
+            slog::Level::Warning => line.push_str("warning: "),
+            _ => {}
+        }
+
+        line.push_str(&record.msg().to_string());
+
+        if let (Some(current), Some(total)) = (
+            values.get_u64(PROGRESS_CURRENT_KEY),
+            values.get_u64(PROGRESS_TOTAL_KEY),
+        ) {
+            line.push(' ');
+            line.push_str(&progress_bar(current, total));
+        }
+
+        line
+    }
+
+    fn format_json(&self, record: &slog::Record, values: ValueCollector) -> String {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        let mut event = serde_json::Map::new();
+        event.insert("time_ms".to_string(), serde_json::Value::from(time));
+        event.insert(
+            "level".to_string(),
+            serde_json::Value::from(level_name(record.level())),
+        );
+        event.insert(
+            "message".to_string(),
+            serde_json::Value::from(record.msg().to_string()),
+        );
+
+        for (key, value) in values.values {
+            event.entry(key).or_insert(value);
+        }
+
+        serde_json::Value::Object(event).to_string()
+    }
+}
 
 /// slog Drain that uses println!.
 impl slog::Drain for PrintlnDrain {
@@ -15,9 +206,28 @@ impl slog::Drain for PrintlnDrain {
     fn log(
         &self,
         record: &slog::Record,
-        _values: &slog::OwnedKVList,
+        values: &slog::OwnedKVList,
     ) -> Result<Self::Ok, Self::Err> {
-        println!("{}", record.msg());
+        if !record.level().is_at_least(self.min_level) {
+            return Ok(());
+        }
+
+        let mut collector = ValueCollector::default();
+        slog::KV::serialize(&record.kv(), record, &mut collector)
+            .and_then(|_| slog::KV::serialize(values, record, &mut collector))
+            .or_else(|e| {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    e.to_string(),
+                ))
+            })?;
+
+        let line = match self.format {
+            LogFormat::Text => self.format_text(record, &collector),
+            LogFormat::Json => self.format_json(record, collector),
+        };
+
+        println!("{}", line);
         Ok(())
     }
 }
@@ -28,8 +238,61 @@ pub struct LoggerContext {
 }
 
 /// Construct a slog::Logger from settings in environment.
+///
+/// Unknown levels and formats fall back to ``info`` and ``text``.
 pub fn logger_from_env() -> LoggerContext {
+    let min_level = std::env::var(LOG_LEVEL_ENV)
+        .ok()
+        .and_then(|v| level_from_name(&v).ok())
+        .unwrap_or(slog::Level::Info);
+    let format = std::env::var(LOG_FORMAT_ENV)
+        .ok()
+        .and_then(|v| LogFormat::from_name(&v).ok())
+        .unwrap_or(LogFormat::Text);
+
     LoggerContext {
-        logger: slog::Logger::root(PrintlnDrain {}.fuse(), slog::o!()),
+        logger: slog::Logger::root(PrintlnDrain { min_level, format }.fuse(), slog::o!()),
+    }
+}
+
+/// Reports progress of a long running operation.
+///
+/// Progress is logged each time another tenth of the operation completes,
+/// so the volume of output doesn't depend on the size of the operation.
+pub struct Progress<'a> {
+    logger: &'a slog::Logger,
+    description: String,
+    current: u64,
+    total: u64,
+    reported_step: Option<u64>,
+}
+
+impl<'a> Progress<'a> {
+    pub fn new(logger: &'a slog::Logger, description: &str, total: u64) -> Progress<'a> {
+        Progress {
+            logger,
+            description: description.to_string(),
+            current: 0,
+            total,
+            reported_step: None,
+        }
+    }
+
+    /// Record that `count` more units of work have completed.
+    pub fn advance(&mut self, count: u64) {
+        self.current = std::cmp::min(self.current + count, self.total);
+
+        let step = (self.current * 10).checked_div(self.total).unwrap_or(10);
+
+        if self.reported_step.map_or(true, |reported| step > reported) {
+            self.reported_step = Some(step);
+
+            slog::info!(
+                self.logger,
+                "{}", self.description;
+                PROGRESS_CURRENT_KEY => self.current,
+                PROGRESS_TOTAL_KEY => self.total
+            );
+        }
     }
 }
@@ -30,11 +30,13 @@ a rather effective and powerful tool.
 mod analyze;
 mod cli;
 mod environment;
+mod licensing;
 mod logging;
 mod projectmgmt;
 #[allow(unused)]
 mod pyrepackager;
 mod python_distributions;
+mod strip;
 
 fn main() {
     std::process::exit(match cli::run_cli() {
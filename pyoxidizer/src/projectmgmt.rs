@@ -7,8 +7,8 @@
 use handlebars::Handlebars;
 use itertools::Itertools;
 use lazy_static::lazy_static;
-use slog::info;
-use std::collections::BTreeMap;
+use slog::{info, o};
+use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
 use std::fs::create_dir_all;
 use std::io::{Cursor, Read, Write};
@@ -18,13 +18,14 @@ use std::process;
 use super::environment::{
     canonicalize_path, PyOxidizerSource, BUILD_GIT_COMMIT, MINIMUM_RUST_VERSION, PYOXIDIZER_VERSION,
 };
-use super::pyrepackager::config::RawAllocator;
 use super::pyrepackager::dist::{analyze_python_distribution_tar_zst, python_exe_path};
+use super::pyrepackager::findresources::explain_resources;
 use super::pyrepackager::fsscan::walk_tree_files;
+use super::pyrepackager::inspect::inspect_file;
 use super::pyrepackager::repackage::{
     find_pyoxidizer_config_file_env, package_project, process_config, run_from_build, BuildContext,
 };
-use super::python_distributions::CPYTHON_BY_TRIPLE;
+use super::python_distributions::{find_distribution, VersionConstraint, CPYTHON_DISTRIBUTIONS};
 
 lazy_static! {
     static ref PYEMBED_RS_FILES: BTreeMap<&'static str, &'static [u8]> = {
@@ -34,9 +35,11 @@ lazy_static! {
         res.insert("lib.rs", include_bytes!("pyembed/lib.rs"));
         res.insert("data.rs", include_bytes!("pyembed/data.rs"));
         res.insert("importer.rs", include_bytes!("pyembed/importer.rs"));
+        res.insert("packing.rs", include_bytes!("pyembed/packing.rs"));
         res.insert("pyalloc.rs", include_bytes!("pyembed/pyalloc.rs"));
         res.insert("pyinterp.rs", include_bytes!("pyembed/pyinterp.rs"));
         res.insert("pystr.rs", include_bytes!("pyembed/pystr.rs"));
+        res.insert("searchpaths.rs", include_bytes!("pyembed/searchpaths.rs"));
 
         res
     };
@@ -187,12 +190,18 @@ pub fn write_new_pyoxidizer_config_file(
 ) -> Result<(), std::io::Error> {
     let path = project_dir.to_path_buf().join("pyoxidizer.toml");
 
-    let distributions = CPYTHON_BY_TRIPLE
+    // Pin the newest distribution for each target.
+    let any_version = VersionConstraint::parse("*").unwrap();
+    let distributions = CPYTHON_DISTRIBUTIONS
         .iter()
-        .map(|(triple, dist)| {
+        .map(|dist| dist.target_triple.as_str())
+        .collect::<BTreeSet<_>>()
+        .iter()
+        .filter_map(|triple| find_distribution(triple, &any_version, &[]))
+        .map(|dist| {
             format!(
                 "[[python_distribution]]\nbuild_target = \"{}\"\nurl = \"{}\"\nsha256 = \"{}\"\n",
-                triple.clone(),
+                dist.target_triple.clone(),
                 dist.url.clone(),
                 dist.sha256.clone()
             )
@@ -402,7 +411,7 @@ fn build_pyoxidizer_artifacts(
         .expect("unable to canonicalize artifacts directory");
 
     if !artifacts_current(logger, &context.config_path, &pyoxidizer_artifacts_path) {
-        process_config(logger, context, "0");
+        process_config(logger, context, "0")?;
     }
 
     Ok(())
@@ -447,7 +456,7 @@ fn build_project(logger: &slog::Logger, context: &mut BuildContext) -> Result<()
         args.push("--release");
     }
 
-    if context.config.raw_allocator == RawAllocator::Jemalloc {
+    if context.config.uses_jemalloc() {
         args.push("--features");
         args.push("jemalloc");
     }
@@ -566,6 +575,7 @@ pub fn build(
     release: bool,
 ) -> Result<(), String> {
     let mut context = resolve_build_context(logger, project_path, None, target, release, None)?;
+    let logger = &logger.new(o!("target" => context.target_triple.clone()));
     build_project(logger, &mut context)?;
     package_project(logger, &mut context)?;
 
@@ -593,6 +603,7 @@ pub fn build_artifacts(
         release,
         Some(dest_path),
     )?;
+    let logger = &logger.new(o!("target" => context.target_triple.clone()));
 
     build_pyoxidizer_artifacts(logger, &mut context)?;
 
@@ -607,6 +618,7 @@ pub fn run(
     extra_args: &[&str],
 ) -> Result<(), String> {
     let mut context = resolve_build_context(logger, project_path, None, target, release, None)?;
+    let logger = &logger.new(o!("target" => context.target_triple.clone()));
 
     run_project(logger, &mut context, extra_args)
 }
@@ -743,6 +755,110 @@ pub fn python_distribution_licenses(path: &str) -> Result<(), String> {
     Ok(())
 }
 
+pub fn find_resources(
+    path: &str,
+    includes: &[String],
+    excludes: &[String],
+    json: bool,
+) -> Result<(), String> {
+    let explanations = explain_resources(Path::new(path), includes, excludes)?;
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&explanations).or_else(|e| Err(e.to_string()))?
+        );
+
+        return Ok(());
+    }
+
+    for explanation in &explanations {
+        println!("{}", explanation.path);
+        println!(
+            "    {} {}: {}",
+            explanation.resource_type, explanation.name, explanation.classification
+        );
+        if let Some(distribution) = &explanation.distribution {
+            println!("    installed by {}", distribution);
+        }
+        println!("    {}", explanation.decision);
+    }
+
+    println!();
+    println!(
+        "{} files; {} would be packaged",
+        explanations.len(),
+        explanations.iter().filter(|e| e.included).count()
+    );
+
+    Ok(())
+}
+
+pub fn inspect_resources(path: &str, json: bool) -> Result<(), String> {
+    let packed = inspect_file(Path::new(path))?;
+
+    let mismatches = packed
+        .iter()
+        .flat_map(|data| data.entries.iter())
+        .filter(|entry| entry.digest_mismatch())
+        .count();
+
+    let result = if mismatches == 0 {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} entries do not match their recorded SHA-256 digest",
+            mismatches
+        ))
+    };
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&packed).or_else(|e| Err(e.to_string()))?
+        );
+
+        return result;
+    }
+
+    for data in &packed {
+        println!(
+            "{} data version {} at offset {} ({} bytes, {} entries)",
+            data.kind,
+            data.version,
+            data.offset,
+            data.length,
+            data.entries.len()
+        );
+        println!();
+
+        for entry in &data.entries {
+            let size = if entry.stored_size == entry.size {
+                format!("{}", entry.size)
+            } else {
+                format!("{} (stored {})", entry.size, entry.stored_size)
+            };
+
+            println!(
+                "{:<22} {:>20} {:<10} {}{}",
+                entry.entry_type,
+                size,
+                entry.flags.join(","),
+                entry.name,
+                if entry.digest_mismatch() {
+                    " (SHA-256 MISMATCH)"
+                } else {
+                    ""
+                }
+            );
+        }
+
+        println!();
+    }
+
+    result
+}
+
 pub fn run_build_script(logger: &slog::Logger, build_script: &str) -> Result<(), String> {
     run_from_build(logger, build_script);
 
@@ -15,6 +15,19 @@ pub enum PythonRawAllocator {
     System,
 }
 
+/// Defines which allocator to use for the mem and object domains.
+#[derive(Clone, Debug)]
+pub enum PythonMemAllocator {
+    /// Use Python's default allocator, pymalloc.
+    Pymalloc,
+    /// Use jemalloc.
+    Jemalloc,
+    /// Use the Rust global allocator.
+    Rust,
+    /// Use the system allocator.
+    System,
+}
+
 /// Defines Python code to run.
 #[derive(Clone, Debug)]
 pub enum PythonRunMode {
@@ -28,6 +41,25 @@ pub enum PythonRunMode {
     Eval { code: String },
 }
 
+/// Defines how to locate files needed at run-time that live outside the binary.
+#[derive(Clone, Debug)]
+pub enum RuntimeFileResolution {
+    /// Don't attempt to locate files.
+    None,
+    /// Search well-known locations on the current system.
+    Dynamic,
+    /// Use an explicit path.
+    ///
+    /// ``$ORIGIN`` will resolve to the directory of the application at
+    /// run-time.
+    Static(String),
+    /// Use the operating system's certificate store.
+    ///
+    /// Only supported for CA certificates. Behaves like ``Dynamic``
+    /// otherwise.
+    TrustStore,
+}
+
 /// Holds the configuration of an embedded Python interpreter.
 ///
 /// Instances of this struct can be used to construct Python interpreters.
@@ -95,6 +127,44 @@ pub struct PythonConfig {
     /// likely comes from an include_bytes!(...) of a file generated by PyOxidizer.
     pub py_resources_data: &'static [u8],
 
+    /// Directory to extract resources to when a filesystem path is requested.
+    ///
+    /// ``$ORIGIN`` will resolve to the directory of the application and
+    /// ``$TEMP`` will resolve to the system temporary directory at run-time.
+    ///
+    /// If not set, in-memory resources do not have filesystem paths.
+    pub resource_extraction_directory: Option<String>,
+
+    /// Packages whose resources are extracted on demand.
+    ///
+    /// When ``importlib.abc.ResourceReader.resource_path()`` is called for a
+    /// resource in one of these packages, the resource is written to
+    /// ``resource_extraction_directory`` on first use and its path is returned.
+    pub resource_extraction_packages: Vec<String>,
+
+    /// Directory that Python packages can be installed into at run-time.
+    ///
+    /// ``$ORIGIN`` will resolve to the directory of the application, ``$HOME``
+    /// to the home directory of the current user, and ``$TEMP`` to the system
+    /// temporary directory at run-time.
+    ///
+    /// If set, the directory is created private to the current user if
+    /// necessary and added to ``sys.path`` using ``site.addsitedir()``, so
+    /// ``.pth`` files in it are processed. Initialization fails if the
+    /// directory could be written by other users. Its path is exposed as
+    /// ``sys.oxidized_site_directory``. Requires ``filesystem_importer``.
+    pub writable_site_directory: Option<String>,
+
+    /// Directory to write bytecode of modules imported from the filesystem to.
+    ///
+    /// Supports the same tokens as ``writable_site_directory``.
+    ///
+    /// If set, the path is exposed as ``sys.oxidized_bytecode_cache_directory``,
+    /// which the frozen ``importlib._bootstrap_external`` consults instead of
+    /// ``__pycache__`` directories. Bytecode is only written if
+    /// ``dont_write_bytecode`` is false.
+    pub bytecode_cache_directory: Option<String>,
+
     /// Whether to set sys.argvb with bytes versions of process arguments.
     ///
     /// On Windows, bytes will be UTF-16. On POSIX, bytes will be raw char*
@@ -104,6 +174,12 @@ pub struct PythonConfig {
     /// Which memory allocator to use for the raw domain.
     pub raw_allocator: PythonRawAllocator,
 
+    /// Which memory allocator to use for the mem domain.
+    pub mem_allocator: PythonMemAllocator,
+
+    /// Which memory allocator to use for the object domain.
+    pub obj_allocator: PythonMemAllocator,
+
     /// Environment variable holding the directory to write a loaded modules file.
     ///
     /// If this value is set and the environment it refers to is set,
@@ -112,6 +188,56 @@ pub struct PythonConfig {
     /// loaded in ``sys.modules``.
     pub write_modules_directory_env: Option<String>,
 
+    /// Environment variable holding the directory to write import timings to.
+    ///
+    /// If this value is set and the environment variable it refers to is set
+    /// when the interpreter is initialized, the time taken to import each
+    /// module is recorded. On interpreter shutdown, an
+    /// ``import-timings-<random>.json`` file describing each import and an
+    /// ``import-timings-<random>.folded`` file containing stacks in the
+    /// format consumed by flamegraph tools are written to the directory.
+    pub write_import_timings_directory_env: Option<String>,
+
+    /// How to locate the terminfo database used by ncurses.
+    ///
+    /// When resolved, the ``TERMINFO_DIRS`` environment variable is set,
+    /// unless ``TERMINFO`` or ``TERMINFO_DIRS`` are already set.
+    pub terminfo_resolution: RuntimeFileResolution,
+
+    /// How to locate the trusted CA certificates used by OpenSSL.
+    ///
+    /// When resolved, the ``SSL_CERT_FILE`` (and, for ``TrustStore``,
+    /// ``SSL_CERT_DIR``) environment variable is set, unless ``SSL_CERT_FILE``
+    /// or ``SSL_CERT_DIR`` are already set.
+    pub ca_certificates_resolution: RuntimeFileResolution,
+
+    /// Whether to enable ``faulthandler``.
+    ///
+    /// When enabled, tracebacks of all Python threads are written if the
+    /// process crashes with a fatal signal (``SIGSEGV``, ``SIGABRT``, etc).
+    pub faulthandler: bool,
+
+    /// File to write crash tracebacks to instead of stderr.
+    ///
+    /// Supports the same tokens as ``writable_site_directory``. ``$PID``
+    /// resolves to the current process ID. The file is opened in append mode
+    /// when the interpreter is initialized and is exposed as
+    /// ``sys.oxidized_crash_report_file``. Implies ``faulthandler``.
+    pub crash_report_file: Option<String>,
+
+    /// Whether to write tracebacks of all Python threads when Rust code panics.
+    ///
+    /// Tracebacks are written to ``crash_report_file`` if set or stderr
+    /// otherwise, after the message of the previously installed panic hook.
+    pub dump_traceback_on_panic: bool,
+
+    /// Whether to allow the operating system to write a core dump if the
+    /// process crashes.
+    ///
+    /// On POSIX, the soft ``RLIMIT_CORE`` limit is raised to the hard limit.
+    /// This has no effect on Windows.
+    pub enable_core_dumps: bool,
+
     /// Defines what code to run by default.
     ///
     pub run: PythonRunMode,
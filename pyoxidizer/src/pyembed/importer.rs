@@ -10,21 +10,28 @@ for importing Python modules from memory.
 */
 
 use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 use std::ffi::CStr;
+use std::fs;
 use std::io::Cursor;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use byteorder::{LittleEndian, ReadBytesExt};
-use cpython::exc::{FileNotFoundError, ImportError, RuntimeError, ValueError};
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
+use cpython::exc::{FileNotFoundError, ImportError, OSError, RuntimeError, ValueError};
 use cpython::{
-    py_class, py_class_impl, py_coerce_item, py_fn, NoArgs, ObjectProtocol, PyClone, PyDict, PyErr,
-    PyList, PyModule, PyObject, PyResult, PyString, PyTuple, Python, PythonObject, ToPyObject,
+    py_class, py_class_impl, py_coerce_item, py_fn, NoArgs, ObjectProtocol, PyBytes, PyClone,
+    PyDict, PyErr, PyList, PyModule, PyObject, PyResult, PyString, PyTuple, Python, PythonObject,
+    ToPyObject,
 };
 use python3_sys as pyffi;
 use python3_sys::{PyBUF_READ, PyMemoryView_FromMemory};
+use sha2::{Digest, Sha256};
 
-use super::pyinterp::PYOXIDIZER_IMPORTER_NAME;
+use super::packing::pack_virtualenv;
+use super::pyinterp::{create_private_dir, PYOXIDIZER_IMPORTER_NAME};
 
 /// Obtain a Python memoryview referencing a memory slice.
 ///
@@ -37,10 +44,13 @@ fn get_memory_view(py: Python, data: &'static [u8]) -> Option<PyObject> {
 }
 
 /// Holds pointers to Python module data in memory.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 struct PythonModuleData {
     source: Option<&'static [u8]>,
     bytecode: Option<&'static [u8]>,
+    bytecode_opt1: Option<&'static [u8]>,
+    bytecode_opt2: Option<&'static [u8]>,
+    is_package: bool,
 }
 
 impl PythonModuleData {
@@ -53,24 +63,95 @@ impl PythonModuleData {
     }
 
     /// Obtain a PyMemoryView instance for bytecode data.
-    fn get_bytecode_memory_view(&self, py: Python) -> Option<PyObject> {
-        match self.bytecode {
+    ///
+    /// Bytecode for the requested optimization level is preferred. If it
+    /// isn't available, bytecode for another optimization level is used.
+    fn get_bytecode_memory_view(&self, py: Python, optimize_level: i32) -> Option<PyObject> {
+        let preferred = match optimize_level {
+            1 => self.bytecode_opt1,
+            2 => self.bytecode_opt2,
+            _ => self.bytecode,
+        };
+
+        match preferred
+            .or(self.bytecode)
+            .or(self.bytecode_opt1)
+            .or(self.bytecode_opt2)
+        {
             Some(data) => get_memory_view(py, data),
             None => None,
         }
     }
 }
 
-/// Represents Python modules data in memory.
+/// Magic bytes at the beginning of versioned packed modules data.
 ///
-/// This is essentially an index over a raw backing blob.
-struct PythonModulesData {
-    data: HashMap<&'static str, PythonModuleData>,
+/// Data without this header is in the original, unversioned format.
+pub const MODULES_MAGIC: &[u8] = b"pyoxmods";
+
+/// Version of versioned packed modules data this reader supports.
+pub const MODULES_VERSION: u32 = 3;
+
+/// Length of the versioned modules data header and module count.
+const MODULES_INDEX_OFFSET: usize = 8 + 4 + 4 + 4;
+
+/// Length of each entry in the versioned modules data index.
+const MODULES_INDEX_ENTRY_LENGTH: usize = 11 * 4;
+
+/// Module flag indicating the module is a package.
+pub const MODULE_FLAG_PACKAGE: u32 = 0x1;
+
+/// Represents Python modules data in memory.
+enum PythonModulesData {
+    /// Versioned data containing an index sorted by module name.
+    ///
+    /// Lookups binary search the index in the backing blob, so no
+    /// per-module work is performed when the data is loaded.
+    Indexed { data: &'static [u8], count: usize },
+
+    /// Unversioned data, indexed when loaded.
+    ///
+    /// Entries are sorted by module name.
+    Unindexed {
+        entries: Vec<(&'static str, PythonModuleData)>,
+    },
 }
 
 impl PythonModulesData {
     /// Construct a new instance from a memory slice.
     fn from(data: &'static [u8]) -> Result<PythonModulesData, &'static str> {
+        if data.starts_with(MODULES_MAGIC) {
+            let mut reader = Cursor::new(data);
+            reader.set_position(MODULES_MAGIC.len() as u64);
+
+            let version = reader
+                .read_u32::<LittleEndian>()
+                .or_else(|_| Err("failed reading modules data version"))?;
+            // Reserved for future use.
+            reader
+                .read_u32::<LittleEndian>()
+                .or_else(|_| Err("failed reading modules data header"))?;
+
+            if version != MODULES_VERSION {
+                return Err("unsupported modules data version");
+            }
+
+            let count = reader
+                .read_u32::<LittleEndian>()
+                .or_else(|_| Err("failed reading count"))? as usize;
+
+            if data.len() < MODULES_INDEX_OFFSET + count * MODULES_INDEX_ENTRY_LENGTH {
+                return Err("modules index out of bounds");
+            }
+
+            Ok(PythonModulesData::Indexed { data, count })
+        } else {
+            PythonModulesData::from_unversioned(data)
+        }
+    }
+
+    /// Parse the original, unversioned modules data format.
+    fn from_unversioned(data: &'static [u8]) -> Result<PythonModulesData, &'static str> {
         let mut reader = Cursor::new(data);
 
         let count = reader
@@ -100,7 +181,8 @@ impl PythonModulesData {
             total_sources_length += source_length;
         }
 
-        let mut res = HashMap::with_capacity(count as usize);
+        let mut entries = Vec::with_capacity(count as usize);
+        let mut packages = HashSet::new();
         let sources_start_offset = reader.position() as usize + total_names_length;
         let bytecodes_start_offset = sources_start_offset + total_sources_length;
 
@@ -132,22 +214,160 @@ impl PythonModulesData {
             sources_current_offset += source_length;
             bytecodes_current_offset += bytecode_length;
 
-            res.insert(name, PythonModuleData { source, bytecode });
+            populate_packages(&mut packages, name);
+
+            entries.push((
+                name,
+                PythonModuleData {
+                    source,
+                    bytecode,
+                    bytecode_opt1: None,
+                    bytecode_opt2: None,
+                    is_package: false,
+                },
+            ));
         }
 
-        Ok(PythonModulesData { data: res })
+        for (name, module_data) in entries.iter_mut() {
+            module_data.is_package = packages.contains(name);
+        }
+
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries.dedup_by(|a, b| a.0 == b.0);
+
+        Ok(PythonModulesData::Unindexed { entries })
+    }
+
+    /// Find a module by name.
+    fn get(&self, name: &str) -> Option<PythonModuleData> {
+        match self {
+            PythonModulesData::Indexed { data, count } => {
+                let mut low = 0;
+                let mut high = *count;
+
+                while low < high {
+                    let mid = low + (high - low) / 2;
+                    let offset = MODULES_INDEX_OFFSET + mid * MODULES_INDEX_ENTRY_LENGTH;
+                    let entry = &data[offset..offset + MODULES_INDEX_ENTRY_LENGTH];
+                    let field =
+                        |i: usize| LittleEndian::read_u32(&entry[i * 4..i * 4 + 4]) as usize;
+
+                    let entry_name = data.get(field(0)..field(0) + field(1))?;
+
+                    match entry_name.cmp(name.as_bytes()) {
+                        Ordering::Less => low = mid + 1,
+                        Ordering::Greater => high = mid,
+                        Ordering::Equal => {
+                            // Resolve a slice from an offset and length field.
+                            let slice = |i: usize| match field(i + 1) {
+                                0 => Some(None),
+                                length => data.get(field(i)..field(i) + length).map(Some),
+                            };
+
+                            return Some(PythonModuleData {
+                                source: slice(2)?,
+                                bytecode: slice(4)?,
+                                bytecode_opt1: slice(6)?,
+                                bytecode_opt2: slice(8)?,
+                                is_package: field(10) as u32 & MODULE_FLAG_PACKAGE != 0,
+                            });
+                        }
+                    }
+                }
+
+                None
+            }
+            PythonModulesData::Unindexed { entries } => {
+                match entries.binary_search_by(|(entry_name, _)| (*entry_name).cmp(name)) {
+                    Ok(idx) => Some(entries[idx].1),
+                    Err(_) => None,
+                }
+            }
+        }
+    }
+}
+
+/// Magic bytes at the beginning of versioned packed resources data.
+///
+/// Data without this header is in the original, unversioned format.
+pub const RESOURCES_MAGIC: &[u8] = b"pyoxrsrc";
+
+/// Version of versioned packed resources data this reader supports.
+pub const RESOURCES_VERSION: u32 = 2;
+
+/// Resource flag indicating data is zlib compressed.
+pub const RESOURCE_FLAG_ZLIB: u32 = 0x1;
+
+/// All resource flags understood by this reader.
+const RESOURCE_FLAGS_KNOWN: u32 = RESOURCE_FLAG_ZLIB;
+
+/// Holds a reference to a resource's data in memory.
+#[derive(Clone, Copy, Debug)]
+struct PythonResourceData {
+    /// Raw data, as stored.
+    data: &'static [u8],
+
+    /// Whether the data is zlib compressed.
+    compressed: bool,
+
+    /// SHA-256 of the uncompressed data, if the format records it.
+    digest: Option<&'static [u8]>,
+}
+
+impl PythonResourceData {
+    /// Obtain a Python object exposing the resource's content.
+    ///
+    /// Uncompressed data is exposed as a memoryview without copying.
+    /// Compressed data is decompressed into a new bytes instance.
+    fn get_py_object(&self, py: Python) -> PyResult<PyObject> {
+        let mv = match get_memory_view(py, self.data) {
+            Some(mv) => mv,
+            None => return Err(PyErr::fetch(py)),
+        };
+
+        if self.compressed {
+            let zlib = py.import("zlib")?;
+            zlib.call(py, "decompress", (mv,), None)
+        } else {
+            Ok(mv)
+        }
     }
 }
 
+type PackageResources = HashMap<&'static str, PythonResourceData>;
+
 /// Represents Python resources data in memory.
 ///
 /// This is essentially an index over a raw backing blob.
 struct PythonResourcesData {
-    packages: HashMap<&'static str, Arc<Box<HashMap<&'static str, &'static [u8]>>>>,
+    packages: HashMap<&'static str, Arc<Box<PackageResources>>>,
 }
 
 impl PythonResourcesData {
     fn from(data: &'static [u8]) -> Result<PythonResourcesData, &'static str> {
+        if data.starts_with(RESOURCES_MAGIC) {
+            let mut reader = Cursor::new(data);
+            reader.set_position(RESOURCES_MAGIC.len() as u64);
+
+            let version = reader
+                .read_u32::<LittleEndian>()
+                .or_else(|_| Err("failed reading resources data version"))?;
+            // Reserved for future use.
+            reader
+                .read_u32::<LittleEndian>()
+                .or_else(|_| Err("failed reading resources data header"))?;
+
+            match version {
+                RESOURCES_VERSION => PythonResourcesData::from_v2(data, reader),
+                _ => Err("unsupported resources data version"),
+            }
+        } else {
+            PythonResourcesData::from_v1(data)
+        }
+    }
+
+    /// Parse the original, unversioned resources data format.
+    fn from_v1(data: &'static [u8]) -> Result<PythonResourcesData, &'static str> {
         let mut reader = Cursor::new(data);
 
         let package_count = reader
@@ -183,37 +403,178 @@ impl PythonResourcesData {
 
                 total_names_length += resource_name_length;
 
-                package_index.push((resource_name_length, resource_data_length));
+                package_index.push((resource_name_length, 0, resource_data_length, None, None));
+            }
+
+            index.push((package_name_length, package_index));
+        }
+
+        let names_offset = reader.position() as usize;
+
+        PythonResourcesData::from_index(data, index, names_offset, total_names_length)
+    }
+
+    /// Parse version 2 of the resources data format.
+    ///
+    /// The reader should be positioned after the header.
+    fn from_v2(
+        data: &'static [u8],
+        mut reader: Cursor<&'static [u8]>,
+    ) -> Result<PythonResourcesData, &'static str> {
+        let package_count = reader
+            .read_u32::<LittleEndian>()
+            .or_else(|_| Err("failed reading package count"))? as usize;
+
+        let mut index = Vec::with_capacity(package_count);
+        let mut total_names_length = 0;
+
+        for _ in 0..package_count {
+            let package_name_length = reader
+                .read_u32::<LittleEndian>()
+                .or_else(|_| Err("failed reading package name length"))?
+                as usize;
+            let resource_count = reader
+                .read_u32::<LittleEndian>()
+                .or_else(|_| Err("failed reading resource count"))?
+                as usize;
+            // Reserved for future use.
+            reader
+                .read_u32::<LittleEndian>()
+                .or_else(|_| Err("failed reading package reserved field"))?;
+
+            total_names_length += package_name_length;
+
+            let mut package_index = Vec::with_capacity(resource_count);
+
+            for _ in 0..resource_count {
+                let resource_name_length = reader
+                    .read_u32::<LittleEndian>()
+                    .or_else(|_| Err("failed reading resource name length"))?
+                    as usize;
+                let flags = reader
+                    .read_u32::<LittleEndian>()
+                    .or_else(|_| Err("failed reading resource flags"))?;
+                let resource_data_length = reader
+                    .read_u32::<LittleEndian>()
+                    .or_else(|_| Err("failed reading resource data length"))?
+                    as usize;
+                // The uncompressed length is only needed by tools verifying data.
+                reader
+                    .read_u32::<LittleEndian>()
+                    .or_else(|_| Err("failed reading resource decoded length"))?;
+                let resource_data_offset = reader
+                    .read_u32::<LittleEndian>()
+                    .or_else(|_| Err("failed reading resource data offset"))?
+                    as usize;
+                // Reserved for future use.
+                reader
+                    .read_u32::<LittleEndian>()
+                    .or_else(|_| Err("failed reading resource reserved field"))?;
+                // The SHA-256 of the uncompressed data is used to verify
+                // extracted files.
+                let resource_digest_offset = reader.position() as usize;
+                reader.set_position(reader.position() + 32);
+
+                if flags & !RESOURCE_FLAGS_KNOWN != 0 {
+                    return Err("unsupported resource flags");
+                }
+
+                total_names_length += resource_name_length;
+
+                package_index.push((
+                    resource_name_length,
+                    flags,
+                    resource_data_length,
+                    Some(resource_data_offset),
+                    Some(resource_digest_offset),
+                ));
             }
 
             index.push((package_name_length, package_index));
         }
 
-        let mut name_offset = reader.position() as usize;
-        let data_offset = name_offset + total_names_length;
+        let names_offset = reader.position() as usize;
+
+        PythonResourcesData::from_index(data, index, names_offset, total_names_length)
+    }
+
+    /// Resolve parsed index data to references into the backing blob.
+    ///
+    /// Resources without an explicit data offset have their data stored
+    /// sequentially.
+    fn from_index(
+        data: &'static [u8],
+        index: Vec<(
+            usize,
+            Vec<(usize, u32, usize, Option<usize>, Option<usize>)>,
+        )>,
+        mut name_offset: usize,
+        total_names_length: usize,
+    ) -> Result<PythonResourcesData, &'static str> {
+        let data_start = name_offset + total_names_length;
+        let mut data_offset = data_start;
         let mut res = HashMap::new();
 
         for (package_name_length, package_index) in index {
             let package_name = unsafe {
-                std::str::from_utf8_unchecked(&data[name_offset..name_offset + package_name_length])
+                std::str::from_utf8_unchecked(
+                    data.get(name_offset..name_offset + package_name_length)
+                        .ok_or_else(|| "package name out of bounds")?,
+                )
             };
 
             name_offset += package_name_length;
 
-            let mut package_data = Box::new(HashMap::new());
+            let mut package_data = Box::new(PackageResources::new());
 
-            for (resource_name_length, resource_data_length) in package_index {
+            for (
+                resource_name_length,
+                flags,
+                resource_data_length,
+                resource_data_offset,
+                resource_digest_offset,
+            ) in package_index
+            {
                 let resource_name = unsafe {
                     std::str::from_utf8_unchecked(
-                        &data[name_offset..name_offset + resource_name_length],
+                        data.get(name_offset..name_offset + resource_name_length)
+                            .ok_or_else(|| "resource name out of bounds")?,
                     )
                 };
 
                 name_offset += resource_name_length;
 
-                let resource_data = &data[data_offset..data_offset + resource_data_length];
+                let resource_data = match resource_data_offset {
+                    Some(offset) => data
+                        .get(data_start + offset..data_start + offset + resource_data_length)
+                        .ok_or_else(|| "resource data out of bounds")?,
+                    None => {
+                        let resource_data = data
+                            .get(data_offset..data_offset + resource_data_length)
+                            .ok_or_else(|| "resource data out of bounds")?;
+
+                        data_offset += resource_data_length;
 
-                package_data.insert(resource_name, resource_data);
+                        resource_data
+                    }
+                };
+
+                let digest = match resource_digest_offset {
+                    Some(offset) => Some(
+                        data.get(offset..offset + 32)
+                            .ok_or_else(|| "resource digest out of bounds")?,
+                    ),
+                    None => None,
+                };
+
+                package_data.insert(
+                    resource_name,
+                    PythonResourceData {
+                        data: resource_data,
+                        compressed: flags & RESOURCE_FLAG_ZLIB != 0,
+                        digest,
+                    },
+                );
             }
 
             res.insert(package_name, Arc::new(package_data));
@@ -223,6 +584,78 @@ impl PythonResourcesData {
     }
 }
 
+/// Describes how long it took to import a module.
+#[derive(Clone, Debug)]
+pub struct ImportTiming {
+    /// Name of the imported module.
+    pub name: String,
+
+    /// Where the module was imported from.
+    ///
+    /// One of ``builtin``, ``frozen``, or ``memory``.
+    pub origin: &'static str,
+
+    /// Names of modules whose import triggered this import, outermost first.
+    pub parents: Vec<String>,
+
+    /// When the import started, relative to the first recorded import.
+    pub start: Duration,
+
+    /// Total time spent importing the module, including nested imports.
+    pub duration: Duration,
+
+    /// Time spent importing the module, excluding nested imports.
+    pub self_duration: Duration,
+}
+
+/// Records timings of module imports.
+///
+/// Imports nest, so a stack of in-progress imports is maintained so time
+/// spent in nested imports can be attributed to the appropriate module.
+#[derive(Debug)]
+struct ImportTimings {
+    epoch: Instant,
+    /// (name, start time, time spent in nested imports).
+    stack: Vec<(String, Instant, Duration)>,
+    records: Vec<ImportTiming>,
+}
+
+impl ImportTimings {
+    fn new() -> ImportTimings {
+        ImportTimings {
+            epoch: Instant::now(),
+            stack: Vec::new(),
+            records: Vec::new(),
+        }
+    }
+
+    /// Record that the import of a module has started.
+    fn enter(&mut self, name: &str) {
+        self.stack
+            .push((name.to_string(), Instant::now(), Duration::from_secs(0)));
+    }
+
+    /// Record that the most recently started import has finished.
+    fn exit(&mut self, origin: &'static str) {
+        if let Some((name, start, children)) = self.stack.pop() {
+            let duration = start.elapsed();
+
+            if let Some(parent) = self.stack.last_mut() {
+                parent.2 += duration;
+            }
+
+            self.records.push(ImportTiming {
+                name,
+                origin,
+                parents: self.stack.iter().map(|entry| entry.0.clone()).collect(),
+                start: start.duration_since(self.epoch),
+                duration,
+                self_duration: duration.checked_sub(children).unwrap_or_default(),
+            });
+        }
+    }
+}
+
 #[allow(unused_doc_comments)]
 /// Python type to import modules.
 ///
@@ -238,17 +671,21 @@ py_class!(class PyOxidizerFinder |py| {
     data module_spec_type: PyObject;
     data decode_source: PyObject;
     data exec_fn: PyObject;
-    data packages: HashSet<&'static str>;
     data known_modules: KnownModules;
-    data resources: HashMap<&'static str, Arc<Box<HashMap<&'static str, &'static [u8]>>>>;
+    data modules_data: PythonModulesData;
+    data optimize_level: i32;
+    data resources: HashMap<&'static str, Arc<Box<PackageResources>>>;
     data resource_readers: RefCell<Box<HashMap<String, PyObject>>>;
+    data resource_extraction_directory: Option<PathBuf>;
+    data resource_extraction_packages: HashSet<String>;
+    data import_timings: Option<RefCell<ImportTimings>>;
 
     // Start of importlib.abc.MetaPathFinder interface.
 
     def find_spec(&self, fullname: &PyString, path: &PyObject, target: Option<PyObject> = None) -> PyResult<PyObject> {
         let key = fullname.to_string(py)?;
 
-        if let Some(flavor) = self.known_modules(py).get(&*key) {
+        if let Some(flavor) = find_known_module(py, self, &key) {
             match flavor {
                 KnownModuleFlavor::Builtin => {
                     self.builtin_importer(py).call_method(py, "find_spec", (fullname, path, target), None)
@@ -256,8 +693,8 @@ py_class!(class PyOxidizerFinder |py| {
                 KnownModuleFlavor::Frozen => {
                     self.frozen_importer(py).call_method(py, "find_spec", (fullname, path, target), None)
                 }
-                KnownModuleFlavor::InMemory { .. } => {
-                    let is_package = self.packages(py).contains(&*key);
+                KnownModuleFlavor::InMemory { module_data } => {
+                    let is_package = module_data.is_package;
 
                     // TODO consider setting origin and has_location so __file__ will be
                     // populated.
@@ -295,28 +732,21 @@ py_class!(class PyOxidizerFinder |py| {
         let name = module.getattr(py, "__name__")?;
         let key = name.extract::<String>(py)?;
 
-        if let Some(flavor) = self.known_modules(py).get(&*key) {
-            match flavor {
-                KnownModuleFlavor::Builtin => {
-                    self.builtin_importer(py).call_method(py, "exec_module", (module,), None)
-                },
-                KnownModuleFlavor::Frozen => {
-                    self.frozen_importer(py).call_method(py, "exec_module", (module,), None)
-                },
-                KnownModuleFlavor::InMemory { module_data } => {
-                    match module_data.get_bytecode_memory_view(py) {
-                        Some(value) => {
-                            let code = self.marshal_loads(py).call(py, (value,), None)?;
-                            let exec_fn = self.exec_fn(py);
-                            let dict = module.getattr(py, "__dict__")?;
+        if let Some(flavor) = find_known_module(py, self, &key) {
+            if let Some(timings) = self.import_timings(py) {
+                timings.borrow_mut().enter(&key);
 
-                            self.call_with_frames_removed(py).call(py, (exec_fn, code, dict), None)
-                        },
-                        None => {
-                            Err(PyErr::new::<ImportError, _>(py, ("cannot find code in memory", name)))
-                        }
-                    }
-                },
+                let res = exec_known_module(py, self, module, &name, &flavor);
+
+                timings.borrow_mut().exit(match flavor {
+                    KnownModuleFlavor::Builtin => "builtin",
+                    KnownModuleFlavor::Frozen => "frozen",
+                    KnownModuleFlavor::InMemory { .. } => "memory",
+                });
+
+                res
+            } else {
+                exec_known_module(py, self, module, &name, &flavor)
             }
         } else {
             // Raising here might make more sense, as exec_module() shouldn't
@@ -332,7 +762,7 @@ py_class!(class PyOxidizerFinder |py| {
     def get_code(&self, fullname: &PyString) -> PyResult<PyObject> {
         let key = fullname.to_string(py)?;
 
-        if let Some(flavor) = self.known_modules(py).get(&*key) {
+        if let Some(flavor) = find_known_module(py, self, &key) {
             match flavor {
                 KnownModuleFlavor::Frozen => {
                     let imp_module = self.imp_module(py);
@@ -340,7 +770,7 @@ py_class!(class PyOxidizerFinder |py| {
                     imp_module.call(py, "get_frozen_object", (fullname,), None)
                 },
                 KnownModuleFlavor::InMemory { module_data } => {
-                    match module_data.get_bytecode_memory_view(py) {
+                    match module_data.get_bytecode_memory_view(py, *self.optimize_level(py)) {
                         Some(value) => {
                             self.marshal_loads(py).call(py, (value,), None)
                         }
@@ -361,7 +791,7 @@ py_class!(class PyOxidizerFinder |py| {
     def get_source(&self, fullname: &PyString) -> PyResult<PyObject> {
         let key = fullname.to_string(py)?;
 
-        if let Some(flavor) = self.known_modules(py).get(&*key) {
+        if let Some(flavor) = find_known_module(py, self, &key) {
             if let KnownModuleFlavor::InMemory { module_data } = flavor {
                 match module_data.get_source_memory_view(py) {
                     Some(value) => {
@@ -400,18 +830,32 @@ py_class!(class PyOxidizerFinder |py| {
         }
 
         // Only create a reader if the name is a package.
-        if self.packages(py).contains(&*key) {
+        let is_package = match self.modules_data(py).get(&key) {
+            Some(module_data) => module_data.is_package,
+            None => false,
+        };
+
+        if is_package {
 
             // Not all packages have known resources.
             let resources = match self.resources(py).get(&*key) {
                 Some(v) => v.clone(),
                 None => {
-                    let h: Box<HashMap<&'static str, &'static [u8]>> = Box::new(HashMap::new());
+                    let h: Box<PackageResources> = Box::new(HashMap::new());
                     Arc::new(h)
                 }
             };
 
-            let reader = PyOxidizerResourceReader::create_instance(py, resources)?.into_object();
+            // Resources in designated packages can be materialized on the
+            // filesystem on demand.
+            let extraction_directory = match self.resource_extraction_directory(py) {
+                Some(path) if self.resource_extraction_packages(py).contains(&*key) => {
+                    Some((path.clone(), path.join(key.replace('.', "/"))))
+                }
+                _ => None,
+            };
+
+            let reader = PyOxidizerResourceReader::create_instance(py, resources, extraction_directory)?.into_object();
             resource_readers.insert(key.to_string(), reader.clone_ref(py));
 
             Ok(reader)
@@ -426,7 +870,8 @@ py_class!(class PyOxidizerFinder |py| {
 ///
 /// Implements importlib.abc.ResourceReader.
 py_class!(class PyOxidizerResourceReader |py| {
-    data resources: Arc<Box<HashMap<&'static str, &'static [u8]>>>;
+    data resources: Arc<Box<PackageResources>>;
+    data extraction_directory: Option<(PathBuf, PathBuf)>;
 
     /// Returns an opened, file-like object for binary reading of the resource.
     ///
@@ -435,15 +880,11 @@ py_class!(class PyOxidizerResourceReader |py| {
         let key = resource.to_string(py)?;
 
         if let Some(data) = self.resources(py).get(&*key) {
-            match get_memory_view(py, data) {
-                Some(mv) => {
-                    let io_module = py.import("io")?;
-                    let bytes_io = io_module.get(py, "BytesIO")?;
+            let value = data.get_py_object(py)?;
+            let io_module = py.import("io")?;
+            let bytes_io = io_module.get(py, "BytesIO")?;
 
-                    bytes_io.call(py, (mv,), None)
-                }
-                None => Err(PyErr::fetch(py))
-            }
+            bytes_io.call(py, (value,), None)
         } else {
             Err(PyErr::new::<FileNotFoundError, _>(py, "resource not found"))
         }
@@ -453,8 +894,39 @@ py_class!(class PyOxidizerResourceReader |py| {
     ///
     /// If the resource does not concretely exist on the file system, raise
     /// FileNotFoundError.
-    def resource_path(&self, _resource: &PyString) -> PyResult<PyObject> {
-        Err(PyErr::new::<FileNotFoundError, _>(py, "in-memory resources do not have filesystem paths"))
+    ///
+    /// Resources in packages configured for extraction are written to the
+    /// extraction directory on first use and that path is returned.
+    def resource_path(&self, resource: &PyString) -> PyResult<PyObject> {
+        let key = resource.to_string(py)?;
+
+        let (root_dir, dest_dir) = match self.extraction_directory(py) {
+            Some(paths) => paths,
+            None => {
+                return Err(PyErr::new::<FileNotFoundError, _>(py, "in-memory resources do not have filesystem paths"));
+            }
+        };
+
+        let data = match self.resources(py).get(&*key) {
+            Some(data) => data,
+            None => {
+                return Err(PyErr::new::<FileNotFoundError, _>(py, "resource not found"));
+            }
+        };
+
+        let res = if data.compressed {
+            let value = data.get_py_object(py)?;
+            let value = value.cast_as::<PyBytes>(py)?;
+
+            extract_resource(root_dir, dest_dir, &key, value.data(py), data.digest)
+        } else {
+            extract_resource(root_dir, dest_dir, &key, data.data, data.digest)
+        };
+
+        match res {
+            Ok(path) => Ok(PyString::new(py, &path.display().to_string()).into_object()),
+            Err(msg) => Err(PyErr::new::<OSError, _>(py, msg)),
+        }
     }
 
     /// Returns True if the named name is considered a resource. FileNotFoundError
@@ -493,6 +965,146 @@ py_class!(class PyOxidizerResourceReader |py| {
     }
 });
 
+/// Write resource data to a file in a directory, returning the file's path.
+///
+/// `dest_dir` is a directory in the extraction directory `root_dir`. The
+/// extraction directory is created private to the current user. Extraction
+/// fails if it exists and other users could have written to it, as they
+/// could otherwise plant files for us to load.
+///
+/// Existing files are reused if their content matches `digest`, the SHA-256
+/// of `data`, or `data` itself if the digest isn't known. Otherwise data is
+/// written to a temporary file which is then renamed into place, so
+/// concurrent processes never observe partially written files.
+fn extract_resource(
+    root_dir: &Path,
+    dest_dir: &Path,
+    name: &str,
+    data: &[u8],
+    digest: Option<&[u8]>,
+) -> Result<PathBuf, String> {
+    // The build appends a directory specific to the resources data to the
+    // configured directory. Both need to be private, or the owner of the
+    // configured directory could replace the other.
+    if let Some(parent) = root_dir.parent() {
+        create_private_dir(parent)?;
+    }
+    create_private_dir(root_dir)?;
+
+    let path = dest_dir.join(name);
+
+    let is_file = fs::symlink_metadata(&path)
+        .map(|metadata| metadata.is_file())
+        .unwrap_or(false);
+
+    if is_file {
+        if let Ok(existing) = fs::read(&path) {
+            let matches = match digest {
+                Some(digest) => Sha256::digest(&existing).as_slice() == digest,
+                None => existing.as_slice() == data,
+            };
+
+            if matches {
+                return Ok(path);
+            }
+        }
+    }
+
+    let parent = path
+        .parent()
+        .ok_or_else(|| format!("unable to resolve parent directory of {}", path.display()))?;
+    fs::create_dir_all(parent)
+        .or_else(|e| Err(format!("unable to create {}: {}", parent.display(), e)))?;
+
+    let temp_path = parent.join(format!(".{}.tmp", uuid::Uuid::new_v4()));
+    fs::write(&temp_path, data)
+        .or_else(|e| Err(format!("unable to write {}: {}", temp_path.display(), e)))?;
+    fs::rename(&temp_path, &path).or_else(|e| {
+        let _ = fs::remove_file(&temp_path);
+        Err(format!("unable to write {}: {}", path.display(), e))
+    })?;
+
+    Ok(path)
+}
+
+/// Find a module known to a PyOxidizerFinder.
+///
+/// In-memory modules take precedence over built-in and frozen modules. This
+/// allows some builtins to be overwritten by .py implemented modules.
+fn find_known_module(
+    py: Python,
+    finder: &PyOxidizerFinder,
+    name: &str,
+) -> Option<KnownModuleFlavor> {
+    if let Some(module_data) = finder.modules_data(py).get(name) {
+        return Some(KnownModuleFlavor::InMemory { module_data });
+    }
+
+    match finder.known_modules(py).get(name) {
+        Some(flavor) => Some(*flavor),
+        None => None,
+    }
+}
+
+/// Execute a module known to a PyOxidizerFinder.
+fn exec_known_module(
+    py: Python,
+    finder: &PyOxidizerFinder,
+    module: &PyObject,
+    name: &PyObject,
+    flavor: &KnownModuleFlavor,
+) -> PyResult<PyObject> {
+    match flavor {
+        KnownModuleFlavor::Builtin => {
+            finder
+                .builtin_importer(py)
+                .call_method(py, "exec_module", (module,), None)
+        }
+        KnownModuleFlavor::Frozen => {
+            finder
+                .frozen_importer(py)
+                .call_method(py, "exec_module", (module,), None)
+        }
+        KnownModuleFlavor::InMemory { module_data } => {
+            match module_data.get_bytecode_memory_view(py, *finder.optimize_level(py)) {
+                Some(value) => {
+                    let code = finder.marshal_loads(py).call(py, (value,), None)?;
+                    let exec_fn = finder.exec_fn(py);
+                    let dict = module.getattr(py, "__dict__")?;
+
+                    finder
+                        .call_with_frames_removed(py)
+                        .call(py, (exec_fn, code, dict), None)
+                }
+                None => Err(PyErr::new::<ImportError, _>(
+                    py,
+                    ("cannot find code in memory", name),
+                )),
+            }
+        }
+    }
+}
+
+/// Obtain import timings recorded by the PyOxidizerFinder on sys.meta_path.
+///
+/// Returns None if no such finder is registered or if it isn't recording
+/// import timings.
+pub fn get_import_timings(py: Python) -> Option<Vec<ImportTiming>> {
+    let sys_module = py.import("sys").ok()?;
+    let meta_path = sys_module.get(py, "meta_path").ok()?;
+    let meta_path = meta_path.cast_as::<PyList>(py).ok()?;
+
+    for finder in meta_path.iter(py) {
+        if let Ok(finder) = finder.cast_as::<PyOxidizerFinder>(py) {
+            if let Some(timings) = finder.import_timings(py) {
+                return Some(timings.borrow().records.clone());
+            }
+        }
+    }
+
+    None
+}
+
 fn populate_packages(packages: &mut HashSet<&'static str>, name: &'static str) {
     let mut search = name;
 
@@ -518,6 +1130,15 @@ pub struct InitModuleState {
 
     /// Raw data constituting Python resources data.
     pub py_resources_data: &'static [u8],
+
+    /// Directory to extract resources to, with special tokens resolved.
+    pub resource_extraction_directory: Option<PathBuf>,
+
+    /// Packages whose resources can be extracted to the filesystem.
+    pub resource_extraction_packages: Vec<String>,
+
+    /// Whether to record how long module imports take.
+    pub record_import_timings: bool,
 }
 
 /// Holds reference to next module state struct.
@@ -527,7 +1148,7 @@ pub struct InitModuleState {
 pub static mut NEXT_MODULE_STATE: *const InitModuleState = std::ptr::null();
 
 /// Represents which importer to use for known modules.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 enum KnownModuleFlavor {
     Builtin,
     Frozen,
@@ -555,6 +1176,15 @@ struct ModuleState {
     /// Raw data constituting Python resources data.
     py_resources_data: &'static [u8],
 
+    /// Directory to extract resources to.
+    resource_extraction_directory: Option<PathBuf>,
+
+    /// Packages whose resources can be extracted to the filesystem.
+    resource_extraction_packages: Vec<String>,
+
+    /// Whether to record how long module imports take.
+    record_import_timings: bool,
+
     /// Whether setup() has been called.
     setup_called: bool,
 }
@@ -608,6 +1238,11 @@ fn module_init(py: Python, m: &PyModule) -> PyResult<()> {
         state.sys_paths = (*NEXT_MODULE_STATE).sys_paths.clone();
         state.py_modules_data = (*NEXT_MODULE_STATE).py_modules_data;
         state.py_resources_data = (*NEXT_MODULE_STATE).py_resources_data;
+        state.resource_extraction_directory =
+            (*NEXT_MODULE_STATE).resource_extraction_directory.clone();
+        state.resource_extraction_packages =
+            (*NEXT_MODULE_STATE).resource_extraction_packages.clone();
+        state.record_import_timings = (*NEXT_MODULE_STATE).record_import_timings;
     }
 
     state.setup_called = false;
@@ -626,6 +1261,22 @@ fn module_init(py: Python, m: &PyModule) -> PyResult<()> {
         ),
     )?;
 
+    m.add(
+        py,
+        "pack_virtualenv",
+        py_fn!(
+            py,
+            pack_virtualenv(
+                path: String,
+                modules_path: String,
+                resources_path: String,
+                optimize_level: i32 = 0,
+                include_source: bool = true,
+                compress_resources: bool = false
+            )
+        ),
+    )?;
+
     Ok(())
 }
 
@@ -677,19 +1328,17 @@ fn module_setup(
     let builtin_importer = meta_path.get_item(py, 0);
     let frozen_importer = meta_path.get_item(py, 1);
 
-    // It may seem inefficient to create a full HashMap of the parsed data instead of e.g.
-    // streaming it. But the overhead of iterators was measured to be more than building
-    // up a temporary HashMap.
+    // Versioned modules data is searched in place, so this is cheap regardless
+    // of how many modules there are.
     let modules_data = match PythonModulesData::from(state.py_modules_data) {
         Ok(v) => v,
         Err(msg) => return Err(PyErr::new::<ValueError, _>(py, msg)),
     };
 
-    // Populate our known module lookup table with entries from builtins, frozens, and
-    // finally us. Last write wins and has the same effect as registering our
-    // meta path importer first. This should be safe. If nothing else, it allows
-    // some builtins to be overwritten by .py implemented modules.
-    let mut known_modules = KnownModules::with_capacity(modules_data.data.len() + 10);
+    // Populate our known module lookup table with entries from builtins and
+    // frozens. In-memory modules are looked up in modules_data first, which has
+    // the same effect as registering our meta path importer first.
+    let mut known_modules = KnownModules::new();
 
     for i in 0.. {
         let record = unsafe { pyffi::PyImport_Inittab.offset(i) };
@@ -733,19 +1382,6 @@ fn module_setup(
         known_modules.insert(name_str, KnownModuleFlavor::Frozen);
     }
 
-    // TODO consider baking set of packages into embedded data.
-    let mut packages: HashSet<&'static str> = HashSet::with_capacity(modules_data.data.len());
-
-    for (name, record) in modules_data.data {
-        known_modules.insert(
-            name,
-            KnownModuleFlavor::InMemory {
-                module_data: record,
-            },
-        );
-        populate_packages(&mut packages, name);
-    }
-
     let resources_data = match PythonResourcesData::from(state.py_resources_data) {
         Ok(v) => v,
         Err(msg) => return Err(PyErr::new::<ValueError, _>(py, msg)),
@@ -755,6 +1391,12 @@ fn module_setup(
     let call_with_frames_removed = bootstrap_module.get(py, "_call_with_frames_removed")?;
     let module_spec_type = bootstrap_module.get(py, "ModuleSpec")?;
 
+    // Bytecode is chosen to match the interpreter's optimization level.
+    let optimize_level = sys_module
+        .get(py, "flags")?
+        .getattr(py, "optimize")?
+        .extract::<i32>(py)?;
+
     let builtins_module =
         match unsafe { PyObject::from_borrowed_ptr_opt(py, pyffi::PyEval_GetBuiltins()) } {
             Some(o) => o.cast_into::<PyDict>(py),
@@ -779,6 +1421,15 @@ fn module_setup(
     let resource_readers: RefCell<Box<HashMap<String, PyObject>>> =
         RefCell::new(Box::new(HashMap::new()));
 
+    let resource_extraction_packages: HashSet<String> =
+        state.resource_extraction_packages.iter().cloned().collect();
+
+    let import_timings = if state.record_import_timings {
+        Some(RefCell::new(ImportTimings::new()))
+    } else {
+        None
+    };
+
     let unified_importer = PyOxidizerFinder::create_instance(
         py,
         imp_module,
@@ -789,10 +1440,14 @@ fn module_setup(
         module_spec_type,
         decode_source,
         exec_fn,
-        packages,
         known_modules,
+        modules_data,
+        optimize_level,
         resources_data.packages,
         resource_readers,
+        state.resource_extraction_directory.clone(),
+        resource_extraction_packages,
+        import_timings,
     )?;
     meta_path_object.call_method(py, "clear", NoArgs, None)?;
     meta_path_object.call_method(py, "append", (unified_importer,), None)?;
@@ -20,9 +20,11 @@ running code in the interpreter.
 mod config;
 mod data;
 mod importer;
+mod packing;
 mod pyalloc;
 mod pyinterp;
 mod pystr;
+mod searchpaths;
 
 #[allow(unused_imports)]
 pub use config::PythonConfig;
@@ -0,0 +1,491 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Produce packed modules and resources data from a running interpreter.
+//!
+//! This backs ``_pyoxidizer_importer.pack_virtualenv()``. It classifies
+//! files like ``pack_virtualenv()`` in the ``pyoxidizer`` crate, but
+//! compiles bytecode with the running interpreter, which is the interpreter
+//! that will load the data.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use cpython::exc::{OSError, ValueError};
+use cpython::{
+    PyBytes, PyDict, PyErr, PyList, PyObject, PyResult, Python, PythonObject, ToPyObject,
+};
+use sha2::{Digest, Sha256};
+
+use super::importer::{
+    MODULES_MAGIC, MODULES_VERSION, MODULE_FLAG_PACKAGE, RESOURCES_MAGIC, RESOURCES_VERSION,
+    RESOURCE_FLAG_ZLIB,
+};
+
+/// A module to pack.
+struct PackedModule {
+    /// Whether the module is a package, i.e. an ``__init__.py``.
+    is_package: bool,
+    source: Option<Vec<u8>>,
+    /// Bytecode, indexed by optimization level.
+    bytecode: [Option<Vec<u8>>; 3],
+}
+
+/// Files found in a virtualenv, classified.
+#[derive(Default)]
+struct VirtualenvFiles {
+    /// Module names and paths of their source files.
+    modules: BTreeMap<String, (PathBuf, bool)>,
+
+    /// Package names, resource names, and paths of resource files.
+    resources: BTreeMap<String, BTreeMap<String, PathBuf>>,
+
+    /// Relative paths of files that can't be packed and why.
+    ignored: Vec<(String, String)>,
+}
+
+/// Obtain the site-packages directory of a virtualenv.
+///
+/// Returns the path itself if it doesn't look like a virtualenv.
+fn site_packages_path(path: &Path) -> PathBuf {
+    let windows = path.join("Lib").join("site-packages");
+    if windows.is_dir() {
+        return windows;
+    }
+
+    if let Ok(entries) = fs::read_dir(path.join("lib")) {
+        let mut candidates = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("python"))
+            .map(|entry| entry.path().join("site-packages"))
+            .filter(|path| path.is_dir())
+            .collect::<Vec<_>>();
+
+        candidates.sort();
+
+        if let Some(candidate) = candidates.into_iter().next() {
+            return candidate;
+        }
+    }
+
+    path.to_path_buf()
+}
+
+/// Find files in a directory, as lists of path components relative to it.
+///
+/// Symlinks to directories aren't followed.
+fn find_files(root: &Path, prefix: &[String], res: &mut Vec<Vec<String>>) -> Result<(), String> {
+    let dir = prefix.iter().fold(root.to_path_buf(), |p, c| p.join(c));
+
+    let mut entries = fs::read_dir(&dir)
+        .or_else(|e| Err(format!("unable to read {}: {}", dir.display(), e)))?
+        .filter_map(|entry| entry.ok())
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let mut components = prefix.to_vec();
+        components.push(entry.file_name().to_string_lossy().to_string());
+
+        match entry.file_type() {
+            Ok(t) if t.is_dir() => find_files(root, &components, res)?,
+            _ => res.push(components),
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a path component can be part of a module name.
+fn is_identifier(value: &str) -> bool {
+    let mut chars = value.chars();
+
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+
+    chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Classify files in a site-packages directory.
+fn classify_files(path: &Path) -> Result<VirtualenvFiles, String> {
+    let mut files = Vec::new();
+    find_files(path, &[], &mut files)?;
+
+    let mut res = VirtualenvFiles::default();
+    let mut resource_candidates = Vec::new();
+
+    for components in files {
+        let relative_path = components.join("/");
+        let filename = components[components.len() - 1].as_str();
+        let dirs = &components[0..components.len() - 1];
+        let top_level = components[0].as_str();
+
+        let mut ignore = |reason: &str| {
+            res.ignored
+                .push((relative_path.clone(), reason.to_string()))
+        };
+
+        if top_level.ends_with(".dist-info") || top_level.ends_with(".egg-info") {
+            // Packaging metadata isn't needed at run-time.
+            continue;
+        } else if dirs.iter().any(|d| d == "__pycache__") {
+            // Bytecode is compiled from source.
+            continue;
+        } else if dirs.is_empty()
+            && (filename.starts_with("__editable__")
+                || filename.ends_with(".egg-link")
+                || filename == "easy-install.pth")
+        {
+            ignore("editable install; its source is outside of the directory");
+        } else if dirs.is_empty() && filename.ends_with(".pth") {
+            ignore(".pth files are not processed");
+        } else if dirs.iter().any(|d| d.ends_with(".libs") || d == ".dylibs") {
+            ignore("shared library; only Python modules and resources are packed");
+        } else if filename.ends_with(".so") || filename.ends_with(".pyd") {
+            ignore("extension module; extension modules can't be loaded from memory");
+        } else if filename.ends_with(".pyc") {
+            ignore("bytecode without source");
+        } else if filename.ends_with(".py") {
+            let stem = &filename[0..filename.len() - 3];
+
+            let mut name = dirs.to_vec();
+            let is_package = stem == "__init__";
+            if !is_package {
+                name.push(stem.to_string());
+            }
+
+            if name.is_empty() || !name.iter().all(|c| is_identifier(c)) {
+                ignore("not an importable module name");
+                continue;
+            }
+
+            res.modules.insert(
+                name.join("."),
+                (path.join(components.join("/")), is_package),
+            );
+        } else {
+            resource_candidates.push(components);
+        }
+    }
+
+    // Like the packaging of a config file, resources belong to the nearest
+    // directory containing modules.
+    let mut packages = BTreeSet::new();
+    for (name, (_, is_package)) in &res.modules {
+        let mut search = name.as_str();
+
+        if *is_package {
+            packages.insert(search.to_string());
+        }
+
+        while let Some(idx) = search.rfind('.') {
+            search = &search[0..idx];
+            packages.insert(search.to_string());
+        }
+    }
+
+    for components in resource_candidates {
+        let dirs = &components[0..components.len() - 1];
+
+        let package_length = (1..=dirs.len())
+            .rev()
+            .find(|length| packages.contains(&dirs[0..*length].join(".")));
+
+        match package_length {
+            Some(length) => {
+                res.resources
+                    .entry(dirs[0..length].join("."))
+                    .or_insert_with(BTreeMap::new)
+                    .insert(
+                        components[length..].join("/"),
+                        path.join(components.join("/")),
+                    );
+            }
+            None => res
+                .ignored
+                .push((components.join("/"), "not in a Python package".to_string())),
+        }
+    }
+
+    Ok(res)
+}
+
+/// Serialize modules in the versioned packed modules data format.
+///
+/// See ``write_modules_entries()`` in the ``pyoxidizer`` crate.
+fn modules_data(modules: &BTreeMap<String, PackedModule>) -> Result<Vec<u8>, std::io::Error> {
+    let mut packages = BTreeSet::new();
+    for (name, module) in modules {
+        if module.is_package {
+            packages.insert(name.as_str());
+        }
+
+        let mut search = name.as_str();
+        while let Some(idx) = search.rfind('.') {
+            search = &search[0..idx];
+            packages.insert(search);
+        }
+    }
+
+    // Names, sources, and bytecode of each optimization level are stored in
+    // separate sections.
+    let values = |module: &PackedModule| {
+        vec![
+            module.source.clone().unwrap_or_default(),
+            module.bytecode[0].clone().unwrap_or_default(),
+            module.bytecode[1].clone().unwrap_or_default(),
+            module.bytecode[2].clone().unwrap_or_default(),
+        ]
+    };
+
+    let mut name_offset = MODULES_MAGIC.len() + 4 + 4 + 4 + modules.len() * 11 * 4;
+    let mut offsets = vec![name_offset + modules.keys().map(|n| n.len()).sum::<usize>()];
+    for i in 0..3 {
+        let length: usize = modules.values().map(|m| values(m)[i].len()).sum();
+        offsets.push(offsets[i] + length);
+    }
+
+    let mut res = Vec::new();
+    res.extend_from_slice(MODULES_MAGIC);
+    res.write_u32::<LittleEndian>(MODULES_VERSION)?;
+    // Reserved.
+    res.write_u32::<LittleEndian>(0)?;
+    res.write_u32::<LittleEndian>(modules.len() as u32)?;
+
+    for (name, module) in modules {
+        res.write_u32::<LittleEndian>(name_offset as u32)?;
+        res.write_u32::<LittleEndian>(name.len() as u32)?;
+        name_offset += name.len();
+
+        for (i, value) in values(module).iter().enumerate() {
+            res.write_u32::<LittleEndian>(offsets[i] as u32)?;
+            res.write_u32::<LittleEndian>(value.len() as u32)?;
+            offsets[i] += value.len();
+        }
+
+        res.write_u32::<LittleEndian>(if packages.contains(name.as_str()) {
+            MODULE_FLAG_PACKAGE
+        } else {
+            0
+        })?;
+    }
+
+    for name in modules.keys() {
+        res.extend_from_slice(name.as_bytes());
+    }
+
+    for i in 0..4 {
+        for module in modules.values() {
+            res.extend_from_slice(&values(module)[i]);
+        }
+    }
+
+    Ok(res)
+}
+
+/// Serialize resources in the versioned packed resources data format.
+///
+/// `stored` maps resource content to its flags and stored form. See
+/// ``write_resources_entries()`` in the ``pyoxidizer`` crate.
+fn resources_data(
+    resources: &BTreeMap<String, BTreeMap<String, Vec<u8>>>,
+    stored: &HashMap<&[u8], (u32, Vec<u8>)>,
+) -> Result<Vec<u8>, std::io::Error> {
+    // Identical values are stored once, in the order they are first seen.
+    let mut offsets: HashMap<&[u8], usize> = HashMap::new();
+    let mut values = Vec::new();
+    let mut data_length = 0;
+
+    for value in resources.values().flat_map(|r| r.values()) {
+        if !offsets.contains_key(value.as_slice()) {
+            let data = &stored[value.as_slice()].1;
+            offsets.insert(value.as_slice(), data_length);
+            data_length += data.len();
+            values.push(data);
+        }
+    }
+
+    let mut res = Vec::new();
+    res.extend_from_slice(RESOURCES_MAGIC);
+    res.write_u32::<LittleEndian>(RESOURCES_VERSION)?;
+    // Reserved.
+    res.write_u32::<LittleEndian>(0)?;
+    res.write_u32::<LittleEndian>(resources.len() as u32)?;
+
+    for (package, entries) in resources {
+        res.write_u32::<LittleEndian>(package.len() as u32)?;
+        res.write_u32::<LittleEndian>(entries.len() as u32)?;
+        // Reserved.
+        res.write_u32::<LittleEndian>(0)?;
+
+        for (name, value) in entries {
+            let (flags, data) = &stored[value.as_slice()];
+
+            res.write_u32::<LittleEndian>(name.len() as u32)?;
+            res.write_u32::<LittleEndian>(*flags)?;
+            res.write_u32::<LittleEndian>(data.len() as u32)?;
+            res.write_u32::<LittleEndian>(value.len() as u32)?;
+            res.write_u32::<LittleEndian>(offsets[value.as_slice()] as u32)?;
+            // Reserved.
+            res.write_u32::<LittleEndian>(0)?;
+            res.extend_from_slice(&Sha256::digest(value));
+        }
+    }
+
+    for (package, entries) in resources {
+        res.extend_from_slice(package.as_bytes());
+
+        for name in entries.keys() {
+            res.extend_from_slice(name.as_bytes());
+        }
+    }
+
+    for data in values {
+        res.extend_from_slice(data);
+    }
+
+    Ok(res)
+}
+
+fn os_error(py: Python, message: String) -> PyErr {
+    PyErr::new::<OSError, _>(py, message)
+}
+
+fn read_file(py: Python, path: &Path) -> PyResult<Vec<u8>> {
+    fs::read(path).or_else(|e| {
+        Err(os_error(
+            py,
+            format!("unable to read {}: {}", path.display(), e),
+        ))
+    })
+}
+
+fn write_file(py: Python, path: &str, data: &[u8]) -> PyResult<()> {
+    fs::write(path, data).or_else(|e| Err(os_error(py, format!("unable to write {}: {}", path, e))))
+}
+
+/// Produce packed modules and resources data from a virtualenv.
+///
+/// ``path`` can be the root directory of a virtualenv or a ``site-packages``
+/// directory. Packed modules and resources data is written to
+/// ``modules_path`` and ``resources_path``. Bytecode is compiled with the
+/// running interpreter at ``optimize_level``.
+///
+/// Returns a list of ``(relative_path, reason)`` tuples describing files
+/// that weren't packed, other than packaging metadata and bytecode caches.
+pub fn pack_virtualenv(
+    py: Python,
+    path: String,
+    modules_path: String,
+    resources_path: String,
+    optimize_level: i32,
+    include_source: bool,
+    compress_resources: bool,
+) -> PyResult<PyObject> {
+    match optimize_level {
+        0..=2 => {}
+        _ => {
+            return Err(PyErr::new::<ValueError, _>(
+                py,
+                "optimize_level must be 0, 1, or 2",
+            ))
+        }
+    }
+
+    let packages_path = site_packages_path(Path::new(&path));
+    let files = classify_files(&packages_path).or_else(|e| Err(os_error(py, e)))?;
+
+    let builtins = py.import("builtins")?;
+    let marshal = py.import("marshal")?;
+    let zlib = py.import("zlib")?;
+
+    let mut modules = BTreeMap::new();
+
+    for (name, (source_path, is_package)) in &files.modules {
+        let source = read_file(py, source_path)?;
+
+        let kwargs = PyDict::new(py);
+        kwargs.set_item(py, "dont_inherit", true)?;
+        kwargs.set_item(py, "optimize", optimize_level)?;
+
+        let code = builtins.call(
+            py,
+            "compile",
+            (PyBytes::new(py, &source), name, "exec"),
+            Some(&kwargs),
+        )?;
+        let bytecode = marshal.call(py, "dumps", (code,), None)?;
+        let bytecode = bytecode.cast_as::<PyBytes>(py)?.data(py).to_vec();
+
+        let mut module = PackedModule {
+            is_package: *is_package,
+            source: None,
+            bytecode: [None, None, None],
+        };
+        module.bytecode[optimize_level as usize] = Some(bytecode);
+        if include_source {
+            module.source = Some(source);
+        }
+
+        modules.insert(name.clone(), module);
+    }
+
+    let mut resources = BTreeMap::new();
+    for (package, entries) in &files.resources {
+        let mut values = BTreeMap::new();
+
+        for (name, resource_path) in entries {
+            values.insert(name.clone(), read_file(py, resource_path)?);
+        }
+
+        resources.insert(package.clone(), values);
+    }
+
+    // Resolve the stored form of each distinct value. Compressed data is only
+    // used if it is smaller.
+    let mut stored = HashMap::new();
+    for value in resources.values().flat_map(|r| r.values()) {
+        if stored.contains_key(value.as_slice()) {
+            continue;
+        }
+
+        let compressed = if compress_resources {
+            let data = zlib.call(py, "compress", (PyBytes::new(py, value), 9), None)?;
+            Some(data.cast_as::<PyBytes>(py)?.data(py).to_vec())
+        } else {
+            None
+        };
+
+        stored.insert(
+            value.as_slice(),
+            match compressed {
+                Some(data) if data.len() < value.len() => (RESOURCE_FLAG_ZLIB, data),
+                _ => (0, value.clone()),
+            },
+        );
+    }
+
+    let modules = modules_data(&modules).or_else(|e| Err(os_error(py, e.to_string())))?;
+    let resources =
+        resources_data(&resources, &stored).or_else(|e| Err(os_error(py, e.to_string())))?;
+
+    write_file(py, &modules_path, &modules)?;
+    write_file(py, &resources_path, &resources)?;
+
+    let ignored = files
+        .ignored
+        .iter()
+        .map(|(path, reason)| {
+            (path.as_str(), reason.as_str())
+                .to_py_object(py)
+                .into_object()
+        })
+        .collect::<Vec<_>>();
+
+    Ok(PyList::new(py, &ignored).into_object())
+}
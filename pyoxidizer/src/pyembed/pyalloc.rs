@@ -150,6 +150,52 @@ pub fn make_raw_rust_memory_allocator() -> RawAllocator {
     }
 }
 
+// An allocator calling the system's allocation functions.
+//
+// Python uses these functions for the raw domain by default. But its other
+// domains need to be told to use them.
+
+extern "C" fn system_malloc(_ctx: *mut c_void, size: size_t) -> *mut c_void {
+    let size = match size {
+        0 => 1,
+        val => val,
+    };
+
+    unsafe { libc::malloc(size) }
+}
+
+extern "C" fn system_calloc(_ctx: *mut c_void, nelem: size_t, elsize: size_t) -> *mut c_void {
+    let (nelem, elsize) = match nelem * elsize {
+        0 => (1, 1),
+        _ => (nelem, elsize),
+    };
+
+    unsafe { libc::calloc(nelem, elsize) }
+}
+
+extern "C" fn system_realloc(_ctx: *mut c_void, ptr: *mut c_void, new_size: size_t) -> *mut c_void {
+    let new_size = match new_size {
+        0 => 1,
+        val => val,
+    };
+
+    unsafe { libc::realloc(ptr, new_size) }
+}
+
+extern "C" fn system_free(_ctx: *mut c_void, ptr: *mut c_void) {
+    unsafe { libc::free(ptr) }
+}
+
+pub fn make_system_allocator() -> pyffi::PyMemAllocatorEx {
+    pyffi::PyMemAllocatorEx {
+        ctx: std::ptr::null_mut(),
+        malloc: Some(system_malloc),
+        calloc: Some(system_calloc),
+        realloc: Some(system_realloc),
+        free: Some(system_free),
+    }
+}
+
 // Now let's define a raw memory allocator that interfaces directly with jemalloc.
 // This avoids the overhead of going through Rust's allocation layer.
 
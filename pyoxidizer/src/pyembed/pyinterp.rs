@@ -11,7 +11,7 @@ use std::env;
 use std::ffi::CString;
 use std::fs;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::ptr::null;
 
 use cpython::exc::ValueError;
@@ -20,18 +20,37 @@ use cpython::{
     Python, PythonObject, ToPyObject,
 };
 
-use super::config::{PythonConfig, PythonRawAllocator, PythonRunMode};
-use super::importer::PyInit__pyoxidizer_importer;
+use super::config::{
+    PythonConfig, PythonMemAllocator, PythonRawAllocator, PythonRunMode, RuntimeFileResolution,
+};
+use super::importer::{get_import_timings, PyInit__pyoxidizer_importer};
 #[cfg(feature = "jemalloc-sys")]
 use super::pyalloc::make_raw_jemalloc_allocator;
-use super::pyalloc::{make_raw_rust_memory_allocator, RawAllocator};
+use super::pyalloc::{make_raw_rust_memory_allocator, make_system_allocator, RawAllocator};
 use super::pystr::{osstring_to_bytes, osstring_to_str, OwnedPyStr};
+use super::searchpaths::{CA_CERTIFICATES_SEARCH_PATHS, TERMINFO_SEARCH_PATHS};
 
 pub const PYOXIDIZER_IMPORTER_NAME: &[u8] = b"_pyoxidizer_importer\0";
 
 const FROZEN_IMPORTLIB_NAME: &[u8] = b"_frozen_importlib\0";
 const FROZEN_IMPORTLIB_EXTERNAL_NAME: &[u8] = b"_frozen_importlib_external\0";
 
+/// Common locations of hashed CA certificate directories.
+#[cfg(all(unix, not(target_os = "macos")))]
+const CA_CERTIFICATES_DIR_SEARCH_PATHS: &[&str] = &[
+    // Most Linux distributions, FreeBSD.
+    "/etc/ssl/certs",
+    // NetBSD.
+    "/etc/openssl/certs",
+];
+
+/// Keychains holding the certificates trusted by macOS.
+#[cfg(target_os = "macos")]
+const MACOS_KEYCHAINS: &[&str] = &[
+    "/System/Library/Keychains/SystemRootCertificates.keychain",
+    "/Library/Keychains/System.keychain",
+];
+
 /// Represents the results of executing Python code with exception handling.
 #[derive(Debug)]
 pub enum PythonRunResult {
@@ -43,6 +62,225 @@ pub enum PythonRunResult {
     Exit { code: i32 },
 }
 
+/// Set an environment variable pointing at files needed at run-time.
+///
+/// Nothing is done if any of `override_vars` is already set, as the user
+/// knows better than us. Existing `search_paths` found by dynamic resolution
+/// are joined with `separator`. If there is no separator, only the first
+/// is used.
+fn resolve_runtime_files(
+    resolution: &RuntimeFileResolution,
+    origin: &str,
+    var: &str,
+    override_vars: &[&str],
+    search_paths: &[&str],
+    separator: Option<&str>,
+) {
+    if override_vars.iter().any(|v| env::var_os(v).is_some()) {
+        return;
+    }
+
+    let value = match resolution {
+        RuntimeFileResolution::None => None,
+        RuntimeFileResolution::Dynamic | RuntimeFileResolution::TrustStore => {
+            let mut found = search_paths
+                .iter()
+                .filter(|p| std::path::Path::new(p).exists())
+                .cloned();
+
+            match separator {
+                Some(separator) => {
+                    Some(found.collect::<Vec<_>>().join(separator)).filter(|v| !v.is_empty())
+                }
+                None => found.next().map(|p| p.to_string()),
+            }
+        }
+        RuntimeFileResolution::Static(path) => Some(path.replace("$ORIGIN", origin)),
+    };
+
+    if let Some(value) = value {
+        env::set_var(var, value);
+    }
+}
+
+/// Whether a path is owned by the current user and only writable by them.
+///
+/// Symbolic links are not followed, so a link never passes.
+#[cfg(unix)]
+fn is_private_path(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match fs::symlink_metadata(path) {
+        Ok(metadata) => {
+            !metadata.file_type().is_symlink()
+                && metadata.uid() == unsafe { libc::geteuid() }
+                && metadata.mode() & 0o022 == 0
+        }
+        Err(_) => false,
+    }
+}
+
+/// Create a directory only accessible by the current user.
+///
+/// Missing directories are created with mode 0700. An error is returned if
+/// the directory already exists but isn't owned by the current user, could
+/// be written by other users, or is a symbolic link, as files in it could
+/// then have been planted by another user.
+#[cfg(unix)]
+pub fn create_private_dir(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    if fs::symlink_metadata(path).is_err() {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(path)
+            .or_else(|e| Err(format!("unable to create {}: {}", path.display(), e)))?;
+    }
+
+    if is_private_path(path) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} is not private to the current user",
+            path.display()
+        ))
+    }
+}
+
+/// Create a directory only accessible by the current user.
+///
+/// On platforms without Unix permissions, the directory is created with
+/// default permissions.
+#[cfg(not(unix))]
+pub fn create_private_dir(path: &Path) -> Result<(), String> {
+    fs::create_dir_all(path).or_else(|e| Err(format!("unable to create {}: {}", path.display(), e)))
+}
+
+/// Obtain the directory caching the export of keychain certificates.
+///
+/// This is a directory in the user's `~/Library/Caches`, created private to
+/// the user. `None` is returned if it can't be created or could have been
+/// written by another user.
+#[cfg(target_os = "macos")]
+fn keychain_certificates_cache_dir() -> Option<PathBuf> {
+    let path = PathBuf::from(env::var_os("HOME")?)
+        .join("Library")
+        .join("Caches")
+        .join("pyembed");
+
+    create_private_dir(&path).ok()?;
+
+    Some(path)
+}
+
+/// Export the certificates in the macOS system keychains to a PEM file.
+///
+/// OpenSSL can't read keychains. So we have the `security` tool export
+/// them and point OpenSSL at the result. The export is cached in a private
+/// per-user cache directory for a day, as it takes a while.
+///
+/// `security find-certificate` exports certificates regardless of trust
+/// settings. So certificates distrusted in Keychain Access are still
+/// trusted, and certificates only trusted by settings on other keychains,
+/// such as the login keychain, aren't.
+#[cfg(target_os = "macos")]
+fn export_keychain_certificates() -> Option<PathBuf> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let path = keychain_certificates_cache_dir()?.join("keychain-certificates.pem");
+
+    let fresh = is_private_path(&path)
+        && fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .map_or(false, |age| age.as_secs() < 86400);
+
+    if fresh {
+        return Some(path);
+    }
+
+    let output = std::process::Command::new("/usr/bin/security")
+        .args(&["find-certificate", "-a", "-p"])
+        .args(MACOS_KEYCHAINS)
+        .output()
+        .ok()?;
+
+    if !output.status.success() || output.stdout.is_empty() {
+        return None;
+    }
+
+    // Write to a process specific file and rename so concurrent processes
+    // never see a partially written file.
+    let temp_path = path.with_extension(format!("pem.{}", std::process::id()));
+    let _ = fs::remove_file(&temp_path);
+
+    let written = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&temp_path)
+        .and_then(|mut f| f.write_all(&output.stdout));
+
+    if written.is_err() || fs::rename(&temp_path, &path).is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return None;
+    }
+
+    Some(path)
+}
+
+/// Point OpenSSL at the operating system's certificate store.
+///
+/// As with `resolve_runtime_files()`, nothing is done if `SSL_CERT_FILE`
+/// or `SSL_CERT_DIR` are already set.
+fn resolve_ca_certificates_trust_store() {
+    if env::var_os("SSL_CERT_FILE").is_some() || env::var_os("SSL_CERT_DIR").is_some() {
+        return;
+    }
+
+    // Python's ssl module loads certificates from the Windows certificate
+    // store itself, so there is nothing to do there.
+
+    #[cfg(target_os = "macos")]
+    {
+        match export_keychain_certificates() {
+            Some(path) => env::set_var("SSL_CERT_FILE", path),
+            None => resolve_runtime_files(
+                &RuntimeFileResolution::Dynamic,
+                "",
+                "SSL_CERT_FILE",
+                &[],
+                CA_CERTIFICATES_SEARCH_PATHS,
+                None,
+            ),
+        }
+    }
+
+    // Certificates added by administrators may only be present in the
+    // hashed directory. So use it in addition to the bundle.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        resolve_runtime_files(
+            &RuntimeFileResolution::Dynamic,
+            "",
+            "SSL_CERT_DIR",
+            &[],
+            CA_CERTIFICATES_DIR_SEARCH_PATHS,
+            None,
+        );
+        resolve_runtime_files(
+            &RuntimeFileResolution::Dynamic,
+            "",
+            "SSL_CERT_FILE",
+            &[],
+            CA_CERTIFICATES_SEARCH_PATHS,
+            None,
+        );
+    }
+}
+
 fn make_custom_frozen_modules(config: &PythonConfig) -> [pyffi::_frozen; 3] {
     [
         pyffi::_frozen {
@@ -104,6 +342,61 @@ fn stderr_to_file() -> *mut libc::FILE {
     unsafe { libc::fdopen(libc::STDERR_FILENO, &('w' as libc::c_char)) }
 }
 
+extern "C" {
+    // Not exposed by python3-sys. This is what faulthandler uses to write
+    // tracebacks from signal handlers, so it doesn't need the GIL.
+    fn _Py_DumpTracebackThreads(
+        fd: libc::c_int,
+        interp: *mut pyffi::PyInterpreterState,
+        current_tstate: *mut pyffi::PyThreadState,
+    ) -> *const c_char;
+}
+
+/// Install a panic hook writing tracebacks of all Python threads to a file descriptor.
+///
+/// The previously installed hook runs first, so the panic message comes
+/// before the tracebacks.
+fn install_panic_traceback_hook(fd: libc::c_int) {
+    let previous = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+
+        if unsafe { pyffi::Py_IsInitialized() } == 0 {
+            return;
+        }
+
+        let header = b"\nPython tracebacks at time of panic:\n";
+
+        unsafe {
+            libc::write(
+                fd,
+                header.as_ptr() as *const libc::c_void,
+                header.len() as _,
+            );
+            _Py_DumpTracebackThreads(fd, std::ptr::null_mut(), std::ptr::null_mut());
+        }
+    }));
+}
+
+#[cfg(unix)]
+fn enable_core_dumps() {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+
+    unsafe {
+        if libc::getrlimit(libc::RLIMIT_CORE, &mut limit) == 0 {
+            limit.rlim_cur = limit.rlim_max;
+            libc::setrlimit(libc::RLIMIT_CORE, &limit);
+        }
+    }
+}
+
+#[cfg(windows)]
+fn enable_core_dumps() {}
+
 #[cfg(feature = "jemalloc-sys")]
 fn raw_jemallocator() -> pyffi::PyMemAllocatorEx {
     make_raw_jemalloc_allocator()
@@ -114,6 +407,45 @@ fn raw_jemallocator() -> pyffi::PyMemAllocatorEx {
     panic!("jemalloc is not available in this build configuration");
 }
 
+/// An allocator to install into a Python memory domain.
+enum DomainAllocator {
+    /// Allocator functions that don't need state.
+    Stateless(pyffi::PyMemAllocatorEx),
+    /// The Rust allocator, which tracks its allocations.
+    Rust(RawAllocator),
+}
+
+impl DomainAllocator {
+    fn for_mem_allocator(allocator: &PythonMemAllocator) -> Option<DomainAllocator> {
+        match allocator {
+            PythonMemAllocator::Pymalloc => None,
+            PythonMemAllocator::Jemalloc => Some(DomainAllocator::Stateless(raw_jemallocator())),
+            PythonMemAllocator::Rust => {
+                Some(DomainAllocator::Rust(make_raw_rust_memory_allocator()))
+            }
+            PythonMemAllocator::System => Some(DomainAllocator::Stateless(make_system_allocator())),
+        }
+    }
+
+    fn as_ptr(&self) -> *const pyffi::PyMemAllocatorEx {
+        match self {
+            DomainAllocator::Stateless(allocator) => allocator as *const _,
+            DomainAllocator::Rust(allocator) => &allocator.allocator as *const _,
+        }
+    }
+}
+
+/// Install an allocator into a Python memory domain.
+///
+/// The allocator must outlive the interpreter.
+fn set_domain_allocator(domain: pyffi::PyMemAllocatorDomain, allocator: &Option<DomainAllocator>) {
+    if let Some(allocator) = allocator {
+        unsafe {
+            pyffi::PyMem_SetAllocator(domain, allocator.as_ptr() as *mut _);
+        }
+    }
+}
+
 /// Manages an embedded Python interpreter.
 ///
 /// **Warning: Python interpreters have global state. There should only be a
@@ -130,8 +462,9 @@ pub struct MainPythonInterpreter<'a> {
     pub config: PythonConfig,
     frozen_modules: [pyffi::_frozen; 3],
     init_run: bool,
-    raw_allocator: Option<pyffi::PyMemAllocatorEx>,
-    raw_rust_allocator: Option<RawAllocator>,
+    raw_allocator: Option<DomainAllocator>,
+    mem_allocator: Option<DomainAllocator>,
+    obj_allocator: Option<DomainAllocator>,
     gil: Option<GILGuard>,
     py: Option<Python<'a>>,
     program_name: Option<OwnedPyStr>,
@@ -142,11 +475,15 @@ impl<'a> MainPythonInterpreter<'a> {
     ///
     /// The Python interpreter is initialized as a side-effect. The GIL is held.
     pub fn new(config: PythonConfig) -> Result<MainPythonInterpreter<'a>, &'static str> {
-        let (raw_allocator, raw_rust_allocator) = match config.raw_allocator {
-            PythonRawAllocator::Jemalloc => (Some(raw_jemallocator()), None),
-            PythonRawAllocator::Rust => (None, Some(make_raw_rust_memory_allocator())),
-            PythonRawAllocator::System => (None, None),
+        let raw_allocator = match config.raw_allocator {
+            PythonRawAllocator::Jemalloc => Some(DomainAllocator::Stateless(raw_jemallocator())),
+            PythonRawAllocator::Rust => {
+                Some(DomainAllocator::Rust(make_raw_rust_memory_allocator()))
+            }
+            PythonRawAllocator::System => None,
         };
+        let mem_allocator = DomainAllocator::for_mem_allocator(&config.mem_allocator);
+        let obj_allocator = DomainAllocator::for_mem_allocator(&config.obj_allocator);
 
         let frozen_modules = make_custom_frozen_modules(&config);
 
@@ -155,7 +492,8 @@ impl<'a> MainPythonInterpreter<'a> {
             frozen_modules,
             init_run: false,
             raw_allocator,
-            raw_rust_allocator,
+            mem_allocator,
+            obj_allocator,
             gil: None,
             py: None,
             program_name: None,
@@ -198,25 +536,79 @@ impl<'a> MainPythonInterpreter<'a> {
             .map(|path| path.replace("$ORIGIN", &origin))
             .collect();
 
-        // TODO should we call PyMem::SetupDebugHooks() if enabled?
-        if let Some(raw_allocator) = &self.raw_allocator {
-            unsafe {
-                let ptr = raw_allocator as *const _;
-                pyffi::PyMem_SetAllocator(
-                    pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_RAW,
-                    ptr as *mut _,
-                );
-            }
-        } else if let Some(raw_rust_allocator) = &self.raw_rust_allocator {
-            unsafe {
-                let ptr = &raw_rust_allocator.allocator as *const _;
-                pyffi::PyMem_SetAllocator(
-                    pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_RAW,
-                    ptr as *mut _,
-                );
-            }
+        let home = env::var("HOME")
+            .or_else(|_| env::var("USERPROFILE"))
+            .unwrap_or_default();
+        let expand_path = |path: &String| {
+            path.replace("$ORIGIN", &origin)
+                .replace("$HOME", &home)
+                .replace("$TEMP", &env::temp_dir().display().to_string())
+        };
+
+        let writable_site_directory = config.writable_site_directory.as_ref().map(expand_path);
+        let bytecode_cache_directory = config.bytecode_cache_directory.as_ref().map(expand_path);
+        let crash_report_file = config
+            .crash_report_file
+            .as_ref()
+            .map(|path| expand_path(path).replace("$PID", &std::process::id().to_string()));
+
+        if config.enable_core_dumps {
+            enable_core_dumps();
         }
 
+        // site.addsitedir() runs import lines of .pth files in the directory,
+        // so other users must not be able to write to it.
+        if let Some(path) = &writable_site_directory {
+            create_private_dir(Path::new(path)).or_else(|_| {
+                Err("unable to create writable site directory private to the current user")
+            })?;
+        }
+
+        // These need to be set before anything reads them. ncurses and OpenSSL
+        // read them lazily. But Python snapshots the environment into os.environ
+        // during initialization.
+        resolve_runtime_files(
+            &config.terminfo_resolution,
+            &origin,
+            "TERMINFO_DIRS",
+            &["TERMINFO", "TERMINFO_DIRS"],
+            TERMINFO_SEARCH_PATHS,
+            Some(":"),
+        );
+        match &config.ca_certificates_resolution {
+            RuntimeFileResolution::TrustStore => resolve_ca_certificates_trust_store(),
+            resolution => resolve_runtime_files(
+                resolution,
+                &origin,
+                "SSL_CERT_FILE",
+                &["SSL_CERT_FILE", "SSL_CERT_DIR"],
+                CA_CERTIFICATES_SEARCH_PATHS,
+                None,
+            ),
+        }
+
+        let resource_extraction_directory =
+            config.resource_extraction_directory.as_ref().map(|path| {
+                PathBuf::from(
+                    path.replace("$ORIGIN", &origin)
+                        .replace("$TEMP", &env::temp_dir().display().to_string()),
+                )
+            });
+
+        // TODO should we call PyMem::SetupDebugHooks() if enabled?
+        set_domain_allocator(
+            pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_RAW,
+            &self.raw_allocator,
+        );
+        set_domain_allocator(
+            pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_MEM,
+            &self.mem_allocator,
+        );
+        set_domain_allocator(
+            pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_OBJ,
+            &self.obj_allocator,
+        );
+
         // Module state is a bit wonky.
         //
         // Our in-memory importer relies on a special module which holds references
@@ -247,6 +639,12 @@ impl<'a> MainPythonInterpreter<'a> {
             sys_paths,
             py_modules_data: config.py_modules_data,
             py_resources_data: config.py_resources_data,
+            resource_extraction_directory,
+            resource_extraction_packages: config.resource_extraction_packages.clone(),
+            record_import_timings: match &config.write_import_timings_directory_env {
+                Some(key) => env::var(key).is_ok(),
+                None => false,
+            },
         };
 
         if config.use_custom_importlib {
@@ -406,6 +804,96 @@ impl<'a> MainPythonInterpreter<'a> {
             _ => return Err("unable to set sys.oxidized"),
         }
 
+        if let Some(path) = &writable_site_directory {
+            let site = py
+                .import("site")
+                .or_else(|_| Err("unable to import site module"))?;
+            site.call(py, "addsitedir", (path.as_str(),), None)
+                .or_else(|_| Err("unable to add writable site directory to sys.path"))?;
+
+            let name = b"oxidized_site_directory\0";
+
+            let res = path.with_borrowed_ptr(py, |value| unsafe {
+                pyffi::PySys_SetObject(name.as_ptr() as *const i8, value)
+            });
+
+            match res {
+                0 => (),
+                _ => return Err("unable to set sys.oxidized_site_directory"),
+            }
+        }
+
+        // Directories are created by the importer when bytecode is written.
+        if let Some(path) = &bytecode_cache_directory {
+            let name = b"oxidized_bytecode_cache_directory\0";
+
+            let res = path.with_borrowed_ptr(py, |value| unsafe {
+                pyffi::PySys_SetObject(name.as_ptr() as *const i8, value)
+            });
+
+            match res {
+                0 => (),
+                _ => return Err("unable to set sys.oxidized_bytecode_cache_directory"),
+            }
+        }
+
+        // Tracebacks go to stderr unless a crash report file is configured.
+        let mut crash_report_fd = 2;
+
+        if config.faulthandler || crash_report_file.is_some() {
+            let faulthandler = py
+                .import("faulthandler")
+                .or_else(|_| Err("unable to import faulthandler module"))?;
+
+            let kwargs = PyDict::new(py);
+            kwargs
+                .set_item(py, "all_threads", true)
+                .or_else(|_| Err("unable to set faulthandler arguments"))?;
+
+            if let Some(path) = &crash_report_file {
+                if let Some(parent) = PathBuf::from(path).parent() {
+                    fs::create_dir_all(parent)
+                        .or_else(|_| Err("unable to create crash report directory"))?;
+                }
+
+                let builtins = py
+                    .import("builtins")
+                    .or_else(|_| Err("unable to import builtins module"))?;
+                let file = builtins
+                    .call(py, "open", (path.as_str(), "a"), None)
+                    .or_else(|_| Err("unable to open crash report file"))?;
+
+                crash_report_fd = file
+                    .call_method(py, "fileno", NoArgs, None)
+                    .and_then(|fd| fd.extract::<i32>(py))
+                    .or_else(|_| Err("unable to obtain crash report file descriptor"))?;
+
+                kwargs
+                    .set_item(py, "file", &file)
+                    .or_else(|_| Err("unable to set faulthandler arguments"))?;
+
+                // Keeps the file object, and therefore its descriptor, alive.
+                let name = b"oxidized_crash_report_file\0";
+
+                let res = file.with_borrowed_ptr(py, |value| unsafe {
+                    pyffi::PySys_SetObject(name.as_ptr() as *const i8, value)
+                });
+
+                match res {
+                    0 => (),
+                    _ => return Err("unable to set sys.oxidized_crash_report_file"),
+                }
+            }
+
+            faulthandler
+                .call(py, "enable", NoArgs, Some(&kwargs))
+                .or_else(|_| Err("unable to enable faulthandler"))?;
+        }
+
+        if config.dump_traceback_on_panic {
+            install_panic_traceback_hook(crash_report_fd);
+        }
+
         Ok(py)
     }
 
@@ -758,6 +1246,83 @@ fn write_modules_to_directory(py: Python, path: &PathBuf) -> Result<(), &'static
     Ok(())
 }
 
+/// Escape a string for inclusion in a JSON document.
+fn json_escape(value: &str) -> String {
+    let mut res = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            c if (c as u32) < 0x20 => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
+    }
+
+    res
+}
+
+/// Write recorded module import timings to a directory.
+///
+/// Given a Python interpreter and a path to a directory, this will create
+/// ``import-timings-<UUID>.json`` and ``import-timings-<UUID>.folded`` files
+/// in that directory.
+///
+/// The JSON file contains an array of objects describing each import
+/// performed by our importer. Modules imported from the filesystem aren't
+/// imported by our importer, so they aren't listed and the time spent
+/// importing them is attributed to the module importing them.
+///
+/// The ``.folded`` file contains lines of the form ``a;b;c <microseconds>``,
+/// which can be fed into flamegraph tools.
+fn write_import_timings_to_directory(py: Python, path: &PathBuf) -> Result<(), &'static str> {
+    let timings = get_import_timings(py).ok_or_else(|| "import timings not recorded")?;
+
+    fs::create_dir_all(path).or_else(|_| Err("could not create directory for import timings"))?;
+
+    let rand = uuid::Uuid::new_v4();
+
+    let mut entries = Vec::with_capacity(timings.len());
+
+    for timing in &timings {
+        entries.push(format!(
+            "  {{\"name\": \"{}\", \"origin\": \"{}\", \"parents\": [{}], \"start_us\": {}, \"duration_us\": {}, \"self_us\": {}}}",
+            json_escape(&timing.name),
+            timing.origin,
+            timing
+                .parents
+                .iter()
+                .map(|p| format!("\"{}\"", json_escape(p)))
+                .collect::<Vec<String>>()
+                .join(", "),
+            timing.start.as_micros(),
+            timing.duration.as_micros(),
+            timing.self_duration.as_micros(),
+        ));
+    }
+
+    let json_path = path.join(format!("import-timings-{}.json", rand.to_string()));
+    fs::write(&json_path, format!("[\n{}\n]\n", entries.join(",\n")))
+        .or_else(|_| Err("could not write import timings"))?;
+
+    let mut f = fs::File::create(path.join(format!("import-timings-{}.folded", rand.to_string())))
+        .or_else(|_| Err("could not open file for writing"))?;
+
+    for timing in &timings {
+        let mut stack = timing.parents.clone();
+        stack.push(timing.name.clone());
+
+        f.write_fmt(format_args!(
+            "{} {}\n",
+            stack.join(";"),
+            timing.self_duration.as_micros()
+        ))
+        .or_else(|_| Err("could not write"))?;
+    }
+
+    Ok(())
+}
+
 impl<'a> Drop for MainPythonInterpreter<'a> {
     fn drop(&mut self) {
         if let Some(key) = &self.config.write_modules_directory_env {
@@ -771,6 +1336,17 @@ impl<'a> Drop for MainPythonInterpreter<'a> {
             }
         }
 
+        if let Some(key) = &self.config.write_import_timings_directory_env {
+            if let Ok(path) = env::var(key) {
+                let path = PathBuf::from(path);
+                let py = self.acquire_gil();
+
+                if let Err(msg) = write_import_timings_to_directory(py, &path) {
+                    eprintln!("error writing import timings: {}", msg);
+                }
+            }
+        }
+
         let _ = unsafe { pyffi::Py_FinalizeEx() };
     }
 }
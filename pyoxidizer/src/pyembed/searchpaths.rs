@@ -0,0 +1,32 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Common locations of files Python needs at run-time.
+//!
+//! This file is also compiled into the ``pyoxidizer`` crate, which bundles
+//! these files with applications, so it must not reference other modules.
+
+/// Common locations of terminfo databases.
+///
+/// Ordered by decreasing precedence, as ncurses searches them.
+pub const TERMINFO_SEARCH_PATHS: &[&str] = &[
+    "/etc/terminfo",
+    "/lib/terminfo",
+    "/usr/share/terminfo",
+    "/usr/lib/terminfo",
+];
+
+/// Common locations of CA certificate bundles.
+pub const CA_CERTIFICATES_SEARCH_PATHS: &[&str] = &[
+    // Debian, Ubuntu, Arch, Gentoo.
+    "/etc/ssl/certs/ca-certificates.crt",
+    // Fedora, RHEL.
+    "/etc/pki/tls/certs/ca-bundle.crt",
+    // CentOS, RHEL 7.
+    "/etc/pki/ca-trust/extracted/pem/tls-ca-bundle.pem",
+    // OpenSUSE.
+    "/etc/ssl/ca-bundle.pem",
+    // Alpine, macOS, BSDs.
+    "/etc/ssl/cert.pem",
+];
@@ -2,10 +2,14 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
 use std::process;
+use std::time::UNIX_EPOCH;
 
 pub const BYTECODE_COMPILER: &[u8] = include_bytes!("bytecodecompiler.py");
 
@@ -89,3 +93,159 @@ impl Drop for BytecodeCompiler {
         self.command.wait().expect("compiler process did not exit");
     }
 }
+
+/// A bytecode compiler that caches compiled bytecode on the filesystem.
+///
+/// Cache entries are keyed by a digest of the Python interpreter's identity
+/// (path, size, and modification time), the module name, the optimization
+/// level, and the source code. So changing any of them results in a new
+/// entry and unchanged modules don't need to be compiled again on rebuilds.
+///
+/// The Python process is only started once a module needs compiling.
+///
+/// Entries are never invalidated, so the cache only grows. Call `prune()`
+/// once compilation is done to bound its size.
+pub struct CachingBytecodeCompiler {
+    python: PathBuf,
+    cache_dir: PathBuf,
+    python_identity: Vec<u8>,
+    compiler: Option<BytecodeCompiler>,
+
+    /// Cache entries read or written by this instance.
+    used: HashSet<PathBuf>,
+
+    /// Number of modules found in the cache.
+    pub hits: usize,
+
+    /// Number of modules that needed compiling.
+    pub misses: usize,
+}
+
+impl CachingBytecodeCompiler {
+    pub fn new(python: &Path, cache_dir: &Path) -> CachingBytecodeCompiler {
+        let mut python_identity = python.display().to_string().into_bytes();
+
+        if let Ok(metadata) = fs::metadata(python) {
+            python_identity.extend(metadata.len().to_string().as_bytes());
+
+            if let Ok(modified) = metadata.modified() {
+                if let Ok(duration) = modified.duration_since(UNIX_EPOCH) {
+                    python_identity.extend(duration.as_nanos().to_string().as_bytes());
+                }
+            }
+        }
+
+        CachingBytecodeCompiler {
+            python: python.to_path_buf(),
+            cache_dir: cache_dir.to_path_buf(),
+            python_identity,
+            compiler: None,
+            used: HashSet::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    fn cache_path(&self, source: &[u8], filename: &str, optimize: i32) -> PathBuf {
+        let mut hasher = Sha256::new();
+
+        for part in &[
+            self.python_identity.as_slice(),
+            filename.as_bytes(),
+            optimize.to_string().as_bytes(),
+        ] {
+            hasher.input(part.len().to_string().as_bytes());
+            hasher.input(b":");
+            hasher.input(part);
+        }
+        hasher.input(source);
+
+        let digest = hex::encode(hasher.result());
+
+        self.cache_dir.join(&digest[0..2]).join(&digest)
+    }
+
+    /// Compile Python source into bytecode, using a cached result if available.
+    pub fn compile(
+        self: &mut CachingBytecodeCompiler,
+        source: &[u8],
+        filename: &str,
+        optimize: i32,
+    ) -> Result<Vec<u8>, std::io::Error> {
+        let cache_path = self.cache_path(source, filename, optimize);
+
+        if let Ok(bytecode) = fs::read(&cache_path) {
+            self.hits += 1;
+            self.used.insert(cache_path);
+            return Ok(bytecode);
+        }
+
+        if self.compiler.is_none() {
+            self.compiler = Some(BytecodeCompiler::new(&self.python));
+        }
+
+        let bytecode = self
+            .compiler
+            .as_mut()
+            .unwrap()
+            .compile(source, filename, optimize)?;
+        self.misses += 1;
+
+        // Write to a temporary file and rename so an interrupted write can't
+        // leave a truncated entry behind.
+        let parent = cache_path.parent().unwrap();
+        fs::create_dir_all(parent)?;
+        let temp_path = cache_path.with_extension("tmp");
+        fs::write(&temp_path, &bytecode)?;
+        fs::rename(&temp_path, &cache_path)?;
+        self.used.insert(cache_path);
+
+        Ok(bytecode)
+    }
+
+    /// Remove cache entries until the cache is no larger than `max_size` bytes.
+    ///
+    /// Entries used by this instance are kept, so the current build never
+    /// needs to compile anything again. Other entries are removed oldest
+    /// first. Returns the number of removed entries.
+    pub fn prune(&self, max_size: u64) -> Result<usize, std::io::Error> {
+        let mut total_size = 0;
+        let mut candidates = Vec::new();
+
+        for entry in walkdir::WalkDir::new(&self.cache_dir) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                // The cache directory doesn't exist if nothing was compiled.
+                Err(_) => continue,
+            };
+
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let metadata = entry.metadata()?;
+            total_size += metadata.len();
+
+            if !self.used.contains(entry.path()) {
+                let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+                candidates.push((modified, metadata.len(), entry.path().to_path_buf()));
+            }
+        }
+
+        candidates.sort();
+
+        let mut removed = 0;
+
+        for (_, size, path) in candidates {
+            if total_size <= max_size {
+                break;
+            }
+
+            fs::remove_file(&path)?;
+            total_size -= size;
+            removed += 1;
+        }
+
+        Ok(removed)
+    }
+}
@@ -0,0 +1,92 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Cache the results of classifying resource content across builds.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::super::analyze::find_shared_library_dependencies;
+
+/// The result of classifying the content of a resource.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Classification {
+    /// Libraries the resource links against, if it is a shared library.
+    shared_library_dependencies: Option<Vec<String>>,
+}
+
+/// Classifies resource content, reusing results of previous builds.
+///
+/// Results are keyed by the SHA-256 digest of the content and stored in a
+/// single JSON file. Entries not used by a build are dropped when the cache
+/// is saved, so the file doesn't grow across builds.
+pub struct ClassificationCache {
+    path: PathBuf,
+    entries: BTreeMap<String, Classification>,
+    used: BTreeMap<String, Classification>,
+
+    /// Number of resources found in the cache.
+    pub hits: usize,
+
+    /// Number of resources that needed classifying.
+    pub misses: usize,
+}
+
+impl ClassificationCache {
+    /// Load a cache from a file.
+    ///
+    /// A missing or unreadable file results in an empty cache.
+    pub fn load(path: &Path) -> ClassificationCache {
+        let entries = fs::read(path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+
+        ClassificationCache {
+            path: path.to_path_buf(),
+            entries,
+            used: BTreeMap::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Obtain the libraries a resource links against, if it is a shared library.
+    pub fn shared_library_dependencies(&mut self, data: &[u8]) -> Option<Vec<String>> {
+        let digest = hex::encode(Sha256::digest(data));
+
+        let classification = match self.entries.get(&digest) {
+            Some(classification) => {
+                self.hits += 1;
+                classification.clone()
+            }
+            None => {
+                self.misses += 1;
+                Classification {
+                    shared_library_dependencies: find_shared_library_dependencies(data),
+                }
+            }
+        };
+
+        let res = classification.shared_library_dependencies.clone();
+        self.used.insert(digest, classification);
+
+        res
+    }
+
+    /// Write results used since the cache was loaded to its file.
+    pub fn save(&self) -> Result<(), String> {
+        let data = serde_json::to_vec(&self.used).or_else(|e| Err(e.to_string()))?;
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).or_else(|e| Err(e.to_string()))?;
+        }
+
+        fs::write(&self.path, data)
+            .or_else(|e| Err(format!("error writing {}: {}", self.path.display(), e)))
+    }
+}
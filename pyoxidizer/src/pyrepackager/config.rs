@@ -2,8 +2,13 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use super::super::analyze::PortabilityPolicy;
 use super::super::environment::canonicalize_path;
-use serde::Deserialize;
+use super::super::licensing::{LicensePolicy, NoticeFormat};
+use super::super::python_distributions::{find_distribution, VersionConstraint};
+use super::super::strip::StripMode;
+use super::filtering::ResourceFilter;
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
 // TOML config file parsing.
@@ -21,6 +26,12 @@ enum ConfigPythonDistribution {
         url: String,
         sha256: String,
     },
+    Known {
+        build_target: String,
+        version: String,
+        #[serde(default)]
+        flavors: Vec<String>,
+    },
 }
 
 #[allow(non_snake_case)]
@@ -43,6 +54,57 @@ pub enum RawAllocator {
     System,
 }
 
+/// Memory allocator for the mem and obj memory domains.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub enum MemAllocator {
+    #[serde(rename = "pymalloc")]
+    Pymalloc,
+    #[serde(rename = "jemalloc")]
+    Jemalloc,
+    #[serde(rename = "rust")]
+    Rust,
+    #[serde(rename = "system")]
+    System,
+}
+
+/// How files needed at run-time that live outside the application are provided.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum RuntimeFilesPolicy {
+    /// Don't do anything.
+    #[serde(rename = "none")]
+    None,
+    /// Behave like `System` if the files are needed and `None` otherwise.
+    #[serde(rename = "auto")]
+    Auto,
+    /// Locate the files on the system running the application.
+    #[serde(rename = "system")]
+    System,
+    /// Copy the files from the build machine next to the application.
+    #[serde(rename = "bundle")]
+    Bundle,
+    /// Use the certificate store of the operating system running the
+    /// application. Only valid for CA certificates.
+    #[serde(rename = "trust_store")]
+    TrustStore,
+}
+
+/// A preset of standard library packages to package.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum StdlibProfile {
+    /// The entire standard library.
+    #[serde(rename = "full")]
+    Full,
+    /// Everything except test packages.
+    #[serde(rename = "no-tests")]
+    NoTests,
+    /// Everything except test packages and packages requiring Tk.
+    #[serde(rename = "no-tk")]
+    NoTk,
+    /// Only packages commonly needed by applications.
+    #[serde(rename = "minimal")]
+    Minimal,
+}
+
 #[allow(non_snake_case)]
 fn ALL() -> String {
     "all".to_string()
@@ -54,6 +116,32 @@ struct ConfigBuild {
     build_target: String,
     application_name: Option<String>,
     build_path: Option<String>,
+    compress_resources: Option<bool>,
+    strip: Option<String>,
+    split_debug: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConfigBinaryPortability {
+    #[serde(default = "ALL")]
+    build_target: String,
+    max_symbol_versions: Option<Vec<String>>,
+    distros: Option<Vec<String>>,
+    max_macos_version: Option<String>,
+    system_libraries_only: Option<bool>,
+    msvc_runtime_versions: Option<Vec<String>>,
+    allow_debug_runtime: Option<bool>,
+    verify_symbols: Option<bool>,
+    system_library_paths: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConfigLicensePolicy {
+    #[serde(default = "ALL")]
+    build_target: String,
+    allowed: Option<Vec<String>>,
+    denied: Option<Vec<String>>,
+    allow_unknown: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -71,7 +159,20 @@ struct ConfigPython {
     filesystem_importer: Option<bool>,
     sys_paths: Option<Vec<String>>,
     raw_allocator: Option<RawAllocator>,
+    mem_allocator: Option<MemAllocator>,
+    obj_allocator: Option<MemAllocator>,
     write_modules_directory_env: Option<String>,
+    write_import_timings_directory_env: Option<String>,
+    resource_extraction_directory: Option<String>,
+    resource_extraction_packages: Option<Vec<String>>,
+    terminfo_policy: Option<RuntimeFilesPolicy>,
+    ca_certificates_policy: Option<RuntimeFilesPolicy>,
+    writable_site_directory: Option<String>,
+    bytecode_cache_directory: Option<String>,
+    faulthandler: Option<bool>,
+    crash_report_file: Option<String>,
+    dump_traceback_on_panic: Option<bool>,
+    enable_core_dumps: Option<bool>,
 }
 
 #[allow(non_snake_case)]
@@ -79,6 +180,11 @@ fn EMBEDDED() -> String {
     "embedded".to_string()
 }
 
+#[allow(non_snake_case)]
+fn TEXT() -> String {
+    "text".to_string()
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type")]
 enum ConfigPythonPackaging {
@@ -93,6 +199,10 @@ enum ConfigPythonPackaging {
         include_source: bool,
         #[serde(default = "EMBEDDED")]
         install_location: String,
+        #[serde(default)]
+        data_files_location: Option<String>,
+        #[serde(default)]
+        scripts_location: Option<String>,
     },
 
     #[serde(rename = "stdlib-extensions-policy")]
@@ -127,14 +237,30 @@ enum ConfigPythonPackaging {
         variant: String,
     },
 
+    #[serde(rename = "static-extension-module")]
+    StaticExtensionModule {
+        #[serde(default = "ALL")]
+        build_target: String,
+        name: String,
+        sources: Vec<String>,
+        #[serde(default)]
+        include_dirs: Vec<String>,
+        #[serde(default)]
+        defines: Vec<String>,
+        #[serde(default)]
+        libraries: Vec<String>,
+    },
+
     #[serde(rename = "stdlib")]
     Stdlib {
         #[serde(default = "ALL")]
         build_target: String,
         #[serde(default = "ZERO")]
         optimize_level: i64,
-        #[serde(default = "TRUE")]
-        exclude_test_modules: bool,
+        #[serde(default)]
+        profile: Option<StdlibProfile>,
+        #[serde(default)]
+        exclude_test_modules: Option<bool>,
         #[serde(default = "TRUE")]
         include_source: bool,
         #[serde(default)]
@@ -172,6 +298,8 @@ enum ConfigPythonPackaging {
         include_source: bool,
         #[serde(default = "EMBEDDED")]
         install_location: String,
+        #[serde(default)]
+        respect_gitignore: bool,
     },
 
     #[serde(rename = "pip-install-simple")]
@@ -187,6 +315,10 @@ enum ConfigPythonPackaging {
         include_source: bool,
         #[serde(default = "EMBEDDED")]
         install_location: String,
+        #[serde(default)]
+        data_files_location: Option<String>,
+        #[serde(default)]
+        scripts_location: Option<String>,
     },
 
     #[serde(rename = "pip-requirements-file")]
@@ -200,6 +332,45 @@ enum ConfigPythonPackaging {
         include_source: bool,
         #[serde(default = "EMBEDDED")]
         install_location: String,
+        #[serde(default)]
+        data_files_location: Option<String>,
+        #[serde(default)]
+        scripts_location: Option<String>,
+    },
+
+    #[serde(rename = "wheel-install")]
+    WheelInstall {
+        #[serde(default = "ALL")]
+        build_target: String,
+        path: String,
+        #[serde(default = "ZERO")]
+        optimize_level: i64,
+        #[serde(default)]
+        excludes: Vec<String>,
+        #[serde(default = "TRUE")]
+        include_source: bool,
+        #[serde(default = "EMBEDDED")]
+        install_location: String,
+    },
+
+    #[serde(rename = "strip-source")]
+    StripSource {
+        #[serde(default = "ALL")]
+        build_target: String,
+
+        modules: Vec<String>,
+        #[serde(default)]
+        excludes: Vec<String>,
+    },
+
+    #[serde(rename = "filter-exclude")]
+    FilterExclude {
+        #[serde(default = "ALL")]
+        build_target: String,
+
+        patterns: Vec<String>,
+        #[serde(default)]
+        resource_types: Vec<String>,
     },
 
     #[serde(rename = "filter-include")]
@@ -218,6 +389,18 @@ enum ConfigPythonPackaging {
 
         path: String,
     },
+
+    #[serde(rename = "third-party-notices")]
+    ThirdPartyNotices {
+        #[serde(default = "ALL")]
+        build_target: String,
+
+        path: String,
+        #[serde(default = "TEXT")]
+        format: String,
+        #[serde(default = "TRUE")]
+        rust_crates: bool,
+    },
 }
 
 #[derive(Debug, Deserialize)]
@@ -251,6 +434,10 @@ enum ConfigRunMode {
 struct ParsedConfig {
     #[serde(default, rename = "build")]
     builds: Vec<ConfigBuild>,
+    #[serde(default, rename = "binary_portability")]
+    binary_portabilities: Vec<ConfigBinaryPortability>,
+    #[serde(default, rename = "license_policy")]
+    license_policies: Vec<ConfigLicensePolicy>,
     #[serde(default, rename = "python_distribution")]
     python_distributions: Vec<ConfigPythonDistribution>,
     #[serde(default, rename = "embedded_python_config")]
@@ -264,7 +451,14 @@ struct ParsedConfig {
 #[derive(Clone, Debug)]
 pub struct BuildConfig {
     pub application_name: String,
+    /// Rust target triple being built for.
+    pub target: String,
     pub build_path: PathBuf,
+    pub compress_resources: bool,
+    /// What to strip from the built application executable, if anything.
+    pub strip: Option<StripMode>,
+    /// Whether to write a separate debug file for the stripped executable.
+    pub split_debug: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -285,6 +479,8 @@ pub struct PackagingSetupPyInstall {
     pub optimize_level: i64,
     pub include_source: bool,
     pub install_location: InstallLocation,
+    pub data_files_location: Option<InstallLocation>,
+    pub scripts_location: Option<InstallLocation>,
 }
 
 #[derive(Clone, Debug)]
@@ -309,9 +505,19 @@ pub struct PackagingStdlibExtensionVariant {
     pub variant: String,
 }
 
+#[derive(Clone, Debug)]
+pub struct PackagingStaticExtensionModule {
+    pub name: String,
+    pub sources: Vec<String>,
+    pub include_dirs: Vec<String>,
+    pub defines: Vec<(String, Option<String>)>,
+    pub libraries: Vec<String>,
+}
+
 #[derive(Clone, Debug)]
 pub struct PackagingStdlib {
     pub optimize_level: i64,
+    pub profile: StdlibProfile,
     pub exclude_test_modules: bool,
     pub include_source: bool,
     pub include_resources: bool,
@@ -335,6 +541,7 @@ pub struct PackagingPackageRoot {
     pub excludes: Vec<String>,
     pub include_source: bool,
     pub install_location: InstallLocation,
+    pub respect_gitignore: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -344,6 +551,8 @@ pub struct PackagingPipInstallSimple {
     pub excludes: Vec<String>,
     pub include_source: bool,
     pub install_location: InstallLocation,
+    pub data_files_location: Option<InstallLocation>,
+    pub scripts_location: Option<InstallLocation>,
 }
 
 #[derive(Clone, Debug)]
@@ -353,6 +562,29 @@ pub struct PackagingPipRequirementsFile {
     pub optimize_level: i64,
     pub include_source: bool,
     pub install_location: InstallLocation,
+    pub data_files_location: Option<InstallLocation>,
+    pub scripts_location: Option<InstallLocation>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PackagingWheelInstall {
+    pub path: String,
+    pub optimize_level: i64,
+    pub excludes: Vec<String>,
+    pub include_source: bool,
+    pub install_location: InstallLocation,
+}
+
+#[derive(Clone, Debug)]
+pub struct PackagingStripSource {
+    pub modules: Vec<String>,
+    pub excludes: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PackagingFilterExclude {
+    pub patterns: Vec<String>,
+    pub resource_types: Vec<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -366,6 +598,13 @@ pub struct PackagingWriteLicenseFiles {
     pub path: String,
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PackagingThirdPartyNotices {
+    pub path: String,
+    pub format: NoticeFormat,
+    pub rust_crates: bool,
+}
+
 #[derive(Clone, Debug)]
 pub enum PythonPackaging {
     SetupPyInstall(PackagingSetupPyInstall),
@@ -373,13 +612,18 @@ pub enum PythonPackaging {
     StdlibExtensionsExplicitIncludes(PackagingStdlibExtensionsExplicitIncludes),
     StdlibExtensionsExplicitExcludes(PackagingStdlibExtensionsExplicitExcludes),
     StdlibExtensionVariant(PackagingStdlibExtensionVariant),
+    StaticExtensionModule(PackagingStaticExtensionModule),
     Stdlib(PackagingStdlib),
     Virtualenv(PackagingVirtualenv),
     PackageRoot(PackagingPackageRoot),
     PipInstallSimple(PackagingPipInstallSimple),
     PipRequirementsFile(PackagingPipRequirementsFile),
+    WheelInstall(PackagingWheelInstall),
+    StripSource(PackagingStripSource),
+    FilterExclude(PackagingFilterExclude),
     FilterInclude(PackagingFilterInclude),
     WriteLicenseFiles(PackagingWriteLicenseFiles),
+    ThirdPartyNotices(PackagingThirdPartyNotices),
 }
 
 #[derive(Clone, Debug)]
@@ -395,6 +639,10 @@ pub enum RunMode {
 pub struct Config {
     pub config_path: PathBuf,
     pub build_config: BuildConfig,
+    /// Policy the built application binary must satisfy, if any.
+    pub binary_portability: Option<PortabilityPolicy>,
+    /// Policy the licenses of distributed components must satisfy, if any.
+    pub license_policy: Option<LicensePolicy>,
     pub dont_write_bytecode: bool,
     pub ignore_environment: bool,
     pub no_site: bool,
@@ -410,7 +658,29 @@ pub struct Config {
     pub filesystem_importer: bool,
     pub sys_paths: Vec<String>,
     pub raw_allocator: RawAllocator,
+    pub mem_allocator: MemAllocator,
+    pub obj_allocator: MemAllocator,
     pub write_modules_directory_env: Option<String>,
+    pub write_import_timings_directory_env: Option<String>,
+    pub resource_extraction_directory: String,
+    pub resource_extraction_packages: Vec<String>,
+    pub terminfo_policy: RuntimeFilesPolicy,
+    pub ca_certificates_policy: RuntimeFilesPolicy,
+    pub writable_site_directory: Option<String>,
+    pub bytecode_cache_directory: Option<String>,
+    pub faulthandler: bool,
+    pub crash_report_file: Option<String>,
+    pub dump_traceback_on_panic: bool,
+    pub enable_core_dumps: bool,
+}
+
+impl Config {
+    /// Whether any memory domain is configured to use jemalloc.
+    pub fn uses_jemalloc(&self) -> bool {
+        self.raw_allocator == RawAllocator::Jemalloc
+            || self.mem_allocator == MemAllocator::Jemalloc
+            || self.obj_allocator == MemAllocator::Jemalloc
+    }
 }
 
 fn resolve_install_location(value: &str) -> Result<InstallLocation, String> {
@@ -425,6 +695,30 @@ fn resolve_install_location(value: &str) -> Result<InstallLocation, String> {
     }
 }
 
+/// Resolve an optional install location for files that must live on the filesystem.
+fn resolve_filesystem_location(
+    key: &str,
+    value: &Option<String>,
+) -> Result<Option<InstallLocation>, String> {
+    match value {
+        Some(value) => match resolve_install_location(value)? {
+            InstallLocation::Embedded => Err(format!("{} must be an app-relative location", key)),
+            location => Ok(Some(location)),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Ensure name patterns in a packaging rule are valid.
+///
+/// Patterns are compiled again at packaging time. Checking them here means
+/// a typo is reported when the config is loaded instead of mid-build.
+fn validate_patterns(key: &str, patterns: &[String]) -> Result<(), String> {
+    ResourceFilter::new(patterns, &[]).or_else(|e| Err(format!("invalid {}: {}", key, e)))?;
+
+    Ok(())
+}
+
 /// Parse a PyOxidizer TOML config from raw data.
 ///
 /// Configs are evaluated against a specific build target. Config entries not
@@ -446,6 +740,9 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
 
     let mut application_name = None;
     let mut build_path = PathBuf::from(&origin).join("build");
+    let mut compress_resources = false;
+    let mut strip = None;
+    let mut split_debug = false;
 
     for build_config in config
         .builds
@@ -459,6 +756,18 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
         if let Some(ref path) = build_config.build_path {
             build_path = PathBuf::from(path.replace("$ORIGIN", &origin));
         }
+
+        if let Some(value) = build_config.compress_resources {
+            compress_resources = value;
+        }
+
+        if let Some(ref value) = build_config.strip {
+            strip = StripMode::from_name(value)?;
+        }
+
+        if let Some(value) = build_config.split_debug {
+            split_debug = value;
+        }
     }
 
     if application_name.is_none() {
@@ -467,9 +776,84 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
 
     let build_config = BuildConfig {
         application_name: application_name.clone().unwrap(),
+        target: target.to_string(),
         build_path,
+        compress_resources,
+        strip,
+        split_debug,
     };
 
+    let mut binary_portability = None;
+
+    for portability in config
+        .binary_portabilities
+        .iter()
+        .filter(|c| c.build_target == "all" || c.build_target == target)
+    {
+        let policy = binary_portability.get_or_insert_with(PortabilityPolicy::default);
+
+        if let Some(ref distros) = portability.distros {
+            for distro in distros {
+                policy.symbol_versions.add_distro(distro)?;
+            }
+        }
+
+        if let Some(ref versions) = portability.max_symbol_versions {
+            for version in versions {
+                policy.symbol_versions.add_max_version(version)?;
+            }
+        }
+
+        if let Some(ref version) = portability.max_macos_version {
+            policy.set_max_macos_version(version)?;
+        }
+
+        if let Some(value) = portability.system_libraries_only {
+            policy.system_libraries_only = value;
+        }
+
+        if let Some(ref versions) = portability.msvc_runtime_versions {
+            policy.msvc_runtime_versions = versions.clone();
+        }
+
+        if let Some(value) = portability.allow_debug_runtime {
+            policy.allow_debug_runtime = value;
+        }
+
+        if let Some(value) = portability.verify_symbols {
+            policy.verify_symbols = value;
+        }
+
+        if let Some(ref paths) = portability.system_library_paths {
+            policy.system_library_paths = paths
+                .iter()
+                .map(|p| PathBuf::from(p.replace("$ORIGIN", &origin)))
+                .collect();
+        }
+    }
+
+    let mut license_policy = None;
+
+    for licensing in config
+        .license_policies
+        .iter()
+        .filter(|c| c.build_target == "all" || c.build_target == target)
+    {
+        let policy = license_policy.get_or_insert_with(LicensePolicy::default);
+
+        if let Some(ref allowed) = licensing.allowed {
+            policy.allowed.extend(allowed.iter().cloned());
+        }
+
+        if let Some(ref denied) = licensing.denied {
+            policy.denied.extend(denied.iter().cloned());
+        }
+
+        if let Some(value) = licensing.allow_unknown {
+            policy.allow_unknown = value;
+        }
+    }
+
     if config.python_distributions.is_empty() {
         return Err("no [[python_distribution]] sections".to_string());
     }
@@ -484,10 +868,10 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
                 sha256,
             } => {
                 if dist_target == target {
-                    Some(PythonDistribution::Local {
+                    Some(Ok(PythonDistribution::Local {
                         local_path: local_path.clone(),
                         sha256: sha256.clone(),
-                    })
+                    }))
                 } else {
                     None
                 }
@@ -499,10 +883,36 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
                 sha256,
             } => {
                 if dist_target == target {
-                    Some(PythonDistribution::Url {
+                    Some(Ok(PythonDistribution::Url {
                         url: url.clone(),
                         sha256: sha256.clone(),
-                    })
+                    }))
+                } else {
+                    None
+                }
+            }
+
+            ConfigPythonDistribution::Known {
+                build_target: dist_target,
+                version,
+                flavors,
+            } => {
+                if dist_target == target {
+                    Some(
+                        VersionConstraint::parse(version)
+                            .and_then(|constraint| {
+                                find_distribution(target, &constraint, flavors).ok_or_else(|| {
+                                    format!(
+                                        "no known Python distribution for target {} matches version {}",
+                                        target, version
+                                    )
+                                })
+                            })
+                            .map(|dist| PythonDistribution::Url {
+                                url: dist.url.clone(),
+                                sha256: dist.sha256.clone(),
+                            }),
+                    )
                 } else {
                     None
                 }
@@ -510,7 +920,7 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
         })
         .next()
     {
-        Some(v) => v,
+        Some(v) => v?,
         None => {
             return Err(format!(
                 "no suitable Python distributions found for target {}",
@@ -535,7 +945,20 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
     } else {
         RawAllocator::Jemalloc
     };
+    let mut mem_allocator = MemAllocator::Pymalloc;
+    let mut obj_allocator = MemAllocator::Pymalloc;
     let mut write_modules_directory_env = None;
+    let mut write_import_timings_directory_env = None;
+    let mut resource_extraction_directory = None;
+    let mut resource_extraction_packages = Vec::new();
+    let mut terminfo_policy = RuntimeFilesPolicy::Auto;
+    let mut ca_certificates_policy = RuntimeFilesPolicy::Auto;
+    let mut writable_site_directory = None;
+    let mut bytecode_cache_directory = None;
+    let mut faulthandler = false;
+    let mut crash_report_file = None;
+    let mut dump_traceback_on_panic = false;
+    let mut enable_core_dumps = false;
 
     for python_config in config
         .python_configs
@@ -598,11 +1021,68 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
             raw_allocator = v.clone();
         }
 
+        if let Some(ref v) = python_config.mem_allocator {
+            mem_allocator = v.clone();
+        }
+
+        if let Some(ref v) = python_config.obj_allocator {
+            obj_allocator = v.clone();
+        }
+
         if let Some(ref v) = python_config.write_modules_directory_env {
             write_modules_directory_env = Some(v.clone());
         }
+
+        if let Some(ref v) = python_config.write_import_timings_directory_env {
+            write_import_timings_directory_env = Some(v.clone());
+        }
+
+        if let Some(ref v) = python_config.resource_extraction_directory {
+            resource_extraction_directory = Some(v.clone());
+        }
+
+        if let Some(ref v) = python_config.resource_extraction_packages {
+            resource_extraction_packages = v.clone();
+        }
+
+        if let Some(v) = python_config.terminfo_policy {
+            terminfo_policy = v;
+        }
+
+        if let Some(v) = python_config.ca_certificates_policy {
+            ca_certificates_policy = v;
+        }
+
+        if let Some(ref v) = python_config.writable_site_directory {
+            writable_site_directory = Some(v.clone());
+        }
+
+        if let Some(ref v) = python_config.bytecode_cache_directory {
+            bytecode_cache_directory = Some(v.clone());
+        }
+
+        if let Some(v) = python_config.faulthandler {
+            faulthandler = v;
+        }
+
+        if let Some(ref v) = python_config.crash_report_file {
+            crash_report_file = Some(v.clone());
+        }
+
+        if let Some(v) = python_config.dump_traceback_on_panic {
+            dump_traceback_on_panic = v;
+        }
+
+        if let Some(v) = python_config.enable_core_dumps {
+            enable_core_dumps = v;
+        }
     }
 
+    let resource_extraction_directory = match resource_extraction_directory {
+        Some(v) => v,
+        None => format!("$TEMP/{}-resources", program_name),
+    };
+
     let mut have_stdlib_extensions_policy = false;
     let mut have_stdlib = false;
 
@@ -610,6 +1090,42 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
         .packaging_rules
         .iter()
         .map(|r| match r {
+            ConfigPythonPackaging::StripSource {
+                build_target: rule_target,
+                modules,
+                excludes,
+            } => {
+                if rule_target == "all" || rule_target == target {
+                    validate_patterns("strip-source modules", modules)?;
+                    validate_patterns("strip-source excludes", excludes)?;
+
+                    Ok(Some(PythonPackaging::StripSource(PackagingStripSource {
+                        modules: modules.clone(),
+                        excludes: excludes.clone(),
+                    })))
+                } else {
+                    Ok(None)
+                }
+            }
+            ConfigPythonPackaging::FilterExclude {
+                build_target: rule_target,
+                patterns,
+                resource_types,
+            } => {
+                if rule_target == "all" || rule_target == target {
+                    // Catch invalid patterns before packaging starts.
+                    ResourceFilter::new(patterns, resource_types)?;
+
+                    Ok(Some(PythonPackaging::FilterExclude(
+                        PackagingFilterExclude {
+                            patterns: patterns.clone(),
+                            resource_types: resource_types.clone(),
+                        },
+                    )))
+                } else {
+                    Ok(None)
+                }
+            }
             ConfigPythonPackaging::FilterInclude {
                 build_target: rule_target,
                 files,
@@ -634,8 +1150,11 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
                 excludes,
                 include_source,
                 install_location,
+                respect_gitignore,
             } => {
                 if rule_target == "all" || rule_target == target {
+                    validate_patterns("package-root excludes", excludes)?;
+
                     Ok(Some(PythonPackaging::PackageRoot(PackagingPackageRoot {
                         path: path.clone(),
                         packages: packages.clone(),
@@ -643,6 +1162,7 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
                         excludes: excludes.clone(),
                         include_source: *include_source,
                         install_location: resolve_install_location(&install_location)?,
+                        respect_gitignore: *respect_gitignore,
                     })))
                 } else {
                     Ok(None)
@@ -655,8 +1175,12 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
                 excludes,
                 include_source,
                 install_location,
+                data_files_location,
+                scripts_location,
             } => {
                 if rule_target == "all" || rule_target == target {
+                    validate_patterns("pip-install-simple excludes", excludes)?;
+
                     Ok(Some(PythonPackaging::PipInstallSimple(
                         PackagingPipInstallSimple {
                             package: package.clone(),
@@ -664,6 +1188,14 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
                             excludes: excludes.clone(),
                             include_source: *include_source,
                             install_location: resolve_install_location(&install_location)?,
+                            data_files_location: resolve_filesystem_location(
+                                "data_files_location",
+                                data_files_location,
+                            )?,
+                            scripts_location: resolve_filesystem_location(
+                                "scripts_location",
+                                scripts_location,
+                            )?,
                         },
                     )))
                 } else {
@@ -676,6 +1208,8 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
                 optimize_level,
                 include_source,
                 install_location,
+                data_files_location,
+                scripts_location,
             } => {
                 if rule_target == "all" || rule_target == target {
                     Ok(Some(PythonPackaging::PipRequirementsFile(
@@ -684,18 +1218,50 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
                             optimize_level: *optimize_level,
                             include_source: *include_source,
                             install_location: resolve_install_location(&install_location)?,
+                            data_files_location: resolve_filesystem_location(
+                                "data_files_location",
+                                data_files_location,
+                            )?,
+                            scripts_location: resolve_filesystem_location(
+                                "scripts_location",
+                                scripts_location,
+                            )?,
                         },
                     )))
                 } else {
                     Ok(None)
                 }
             }
+            ConfigPythonPackaging::WheelInstall {
+                build_target: rule_target,
+                path,
+                optimize_level,
+                excludes,
+                include_source,
+                install_location,
+            } => {
+                if rule_target == "all" || rule_target == target {
+                    validate_patterns("wheel-install excludes", excludes)?;
+
+                    Ok(Some(PythonPackaging::WheelInstall(PackagingWheelInstall {
+                        path: path.replace("$ORIGIN", &origin),
+                        optimize_level: *optimize_level,
+                        excludes: excludes.clone(),
+                        include_source: *include_source,
+                        install_location: resolve_install_location(&install_location)?,
+                    })))
+                } else {
+                    Ok(None)
+                }
+            }
             ConfigPythonPackaging::SetupPyInstall {
                 build_target: rule_target,
                 package_path,
                 optimize_level,
                 include_source,
                 install_location,
+                data_files_location,
+                scripts_location,
             } => {
                 if rule_target == "all" || rule_target == target {
                     Ok(Some(PythonPackaging::SetupPyInstall(
@@ -704,6 +1270,14 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
                             optimize_level: *optimize_level,
                             include_source: *include_source,
                             install_location: resolve_install_location(&install_location)?,
+                            data_files_location: resolve_filesystem_location(
+                                "data_files_location",
+                                data_files_location,
+                            )?,
+                            scripts_location: resolve_filesystem_location(
+                                "scripts_location",
+                                scripts_location,
+                            )?,
                         },
                     )))
                 } else {
@@ -713,6 +1287,7 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
             ConfigPythonPackaging::Stdlib {
                 build_target: rule_target,
                 optimize_level,
+                profile,
                 exclude_test_modules,
                 include_source,
                 include_resources,
@@ -721,9 +1296,13 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
                 if rule_target == "all" || rule_target == target {
                     have_stdlib = true;
 
+                    let profile = profile.unwrap_or(StdlibProfile::NoTests);
+
                     Ok(Some(PythonPackaging::Stdlib(PackagingStdlib {
                         optimize_level: *optimize_level,
-                        exclude_test_modules: *exclude_test_modules,
+                        profile,
+                        exclude_test_modules: exclude_test_modules
+                            .unwrap_or(profile != StdlibProfile::Full),
                         include_source: *include_source,
                         include_resources: *include_resources,
                         install_location: resolve_install_location(&install_location)?,
@@ -792,6 +1371,55 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
                     Ok(None)
                 }
             }
+            ConfigPythonPackaging::StaticExtensionModule {
+                build_target: rule_target,
+                name,
+                sources,
+                include_dirs,
+                defines,
+                libraries,
+            } => {
+                if rule_target == "all" || rule_target == target {
+                    if name.is_empty()
+                        || name.split('.').any(|part| {
+                            part.is_empty()
+                                || part.contains(|c: char| !c.is_alphanumeric() && c != '_')
+                        })
+                    {
+                        return Err(format!("invalid extension module name: {}", name));
+                    }
+
+                    if sources.is_empty() {
+                        return Err(format!("static-extension-module {} has no sources", name));
+                    }
+
+                    Ok(Some(PythonPackaging::StaticExtensionModule(
+                        PackagingStaticExtensionModule {
+                            name: name.clone(),
+                            sources: sources
+                                .iter()
+                                .map(|p| p.replace("$ORIGIN", &origin))
+                                .collect(),
+                            include_dirs: include_dirs
+                                .iter()
+                                .map(|p| p.replace("$ORIGIN", &origin))
+                                .collect(),
+                            defines: defines
+                                .iter()
+                                .map(|d| match d.find('=') {
+                                    Some(idx) => {
+                                        (d[0..idx].to_string(), Some(d[idx + 1..].to_string()))
+                                    }
+                                    None => (d.clone(), None),
+                                })
+                                .collect(),
+                            libraries: libraries.clone(),
+                        },
+                    )))
+                } else {
+                    Ok(None)
+                }
+            }
             ConfigPythonPackaging::Virtualenv {
                 build_target: rule_target,
                 path,
@@ -801,6 +1429,8 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
                 install_location,
             } => {
                 if rule_target == "all" || rule_target == target {
+                    validate_patterns("virtualenv excludes", excludes)?;
+
                     Ok(Some(PythonPackaging::Virtualenv(PackagingVirtualenv {
                         path: path.clone(),
                         optimize_level: *optimize_level,
@@ -824,6 +1454,24 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
                     Ok(None)
                 }
             }
+            ConfigPythonPackaging::ThirdPartyNotices {
+                build_target: rule_target,
+                path,
+                format,
+                rust_crates,
+            } => {
+                if rule_target == "all" || rule_target == target {
+                    Ok(Some(PythonPackaging::ThirdPartyNotices(
+                        PackagingThirdPartyNotices {
+                            path: path.clone(),
+                            format: NoticeFormat::from_name(format)?,
+                            rust_crates: *rust_crates,
+                        },
+                    )))
+                } else {
+                    Ok(None)
+                }
+            }
         })
         .collect();
 
@@ -893,11 +1541,28 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
         run = run_mode;
     }
 
-    filesystem_importer = filesystem_importer || !sys_paths.is_empty();
+    filesystem_importer =
+        filesystem_importer || !sys_paths.is_empty() || writable_site_directory.is_some();
+    dont_write_bytecode = dont_write_bytecode && bytecode_cache_directory.is_none();
+
+    if terminfo_policy == RuntimeFilesPolicy::TrustStore {
+        return Err("terminfo_policy cannot be trust_store".to_string());
+    }
+
+    // The jemalloc-sys crate doesn't work on Windows MSVC targets.
+    if target == "x86_64-pc-windows-msvc"
+        && (raw_allocator == RawAllocator::Jemalloc
+            || mem_allocator == MemAllocator::Jemalloc
+            || obj_allocator == MemAllocator::Jemalloc)
+    {
+        return Err(format!("jemalloc allocator is not supported on {}", target));
+    }
 
     Ok(Config {
         config_path: config_path.to_path_buf(),
         build_config,
+        binary_portability,
+        license_policy,
         dont_write_bytecode,
         ignore_environment,
         no_site,
@@ -913,6 +1578,19 @@ pub fn parse_config(data: &[u8], config_path: &Path, target: &str) -> Result<Con
         filesystem_importer,
         sys_paths,
         raw_allocator,
+        mem_allocator,
+        obj_allocator,
         write_modules_directory_env,
+        write_import_timings_directory_env,
+        resource_extraction_directory,
+        resource_extraction_packages,
+        terminfo_policy,
+        ca_certificates_policy,
+        writable_site_directory,
+        bytecode_cache_directory,
+        faulthandler,
+        crash_report_file,
+        dump_traceback_on_panic,
+        enable_core_dumps,
     })
 }
@@ -5,6 +5,7 @@
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use slog::info;
 use std::collections::BTreeMap;
 use std::fs;
 use std::fs::File;
@@ -12,6 +13,7 @@ use std::io::Read;
 use std::path::{Path, PathBuf};
 use url::Url;
 
+use super::super::logging::Progress;
 use super::config::{Config, PythonDistribution};
 use super::fsscan::{find_python_resources, walk_tree_files, PythonResourceType};
 
@@ -114,6 +116,19 @@ pub struct LibraryDepends {
     pub system: bool,
 }
 
+/// Describes how to compile an extension module from C source code.
+#[derive(Clone, Debug)]
+pub struct ExtensionModuleBuild {
+    /// C source files to compile.
+    pub sources: Vec<PathBuf>,
+
+    /// Additional directories to search for header files.
+    pub include_dirs: Vec<PathBuf>,
+
+    /// Preprocessor definitions. Names with an optional value.
+    pub defines: Vec<(String, Option<String>)>,
+}
+
 /// Describes an extension module in a Python distribution.
 #[derive(Clone, Debug)]
 pub struct ExtensionModule {
@@ -161,6 +176,12 @@ pub struct ExtensionModule {
 
     /// Whether the license for this library is in the public domain.
     pub license_public_domain: Option<bool>,
+
+    /// Source code to compile to produce the extension module.
+    ///
+    /// If defined, object files are produced when linking libpython.
+    /// `object_paths` is not used.
+    pub build: Option<ExtensionModuleBuild>,
 }
 
 fn link_entry_to_library_depends(entry: &LinkEntry, python_path: &PathBuf) -> LibraryDepends {
@@ -466,6 +487,7 @@ pub fn analyze_python_distribution_data(
                 },
                 links,
                 variant: entry.variant.clone(),
+                build: None,
             });
         }
 
@@ -608,7 +630,12 @@ pub fn get_http_client() -> reqwest::Result<reqwest::Client> {
 /// Ensure a Python distribution at a URL is available in a local directory.
 ///
 /// The path to the downloaded and validated file is returned.
-pub fn download_distribution(url: &str, sha256: &str, cache_dir: &Path) -> PathBuf {
+pub fn download_distribution(
+    logger: &slog::Logger,
+    url: &str,
+    sha256: &str,
+    cache_dir: &Path,
+) -> PathBuf {
     let expected_hash = hex::decode(sha256).expect("could not parse SHA256 hash");
     let url = Url::parse(url).expect("failed to parse URL");
 
@@ -632,15 +659,34 @@ pub fn download_distribution(url: &str, sha256: &str, cache_dir: &Path) -> PathB
 
     let mut data: Vec<u8> = Vec::new();
 
-    println!("downloading {}", url);
+    info!(logger, "downloading {}", url);
     let client = get_http_client().expect("unable to get HTTP client");
     let mut response = client
         .get(url)
         .send()
         .expect("unable to perform HTTP request");
-    response
-        .read_to_end(&mut data)
-        .expect("unable to download URL");
+
+    match response.content_length() {
+        Some(length) => {
+            let mut progress = Progress::new(logger, "downloading", length);
+            let mut buffer = [0; 32768];
+
+            loop {
+                let count = response.read(&mut buffer).expect("unable to download URL");
+                if count == 0 {
+                    break;
+                }
+
+                data.extend_from_slice(&buffer[..count]);
+                progress.advance(count as u64);
+            }
+        }
+        None => {
+            response
+                .read_to_end(&mut data)
+                .expect("unable to download URL");
+        }
+    }
 
     let mut hasher = Sha256::new();
     hasher.input(&data);
@@ -655,7 +701,12 @@ pub fn download_distribution(url: &str, sha256: &str, cache_dir: &Path) -> PathB
     cache_path
 }
 
-pub fn copy_local_distribution(path: &PathBuf, sha256: &str, cache_dir: &Path) -> PathBuf {
+pub fn copy_local_distribution(
+    logger: &slog::Logger,
+    path: &PathBuf,
+    sha256: &str,
+    cache_dir: &Path,
+) -> PathBuf {
     let expected_hash = hex::decode(sha256).expect("could not parse SHA256 hash");
     let basename = path.file_name().unwrap().to_str().unwrap().to_string();
     let cache_path = cache_dir.join(basename);
@@ -664,7 +715,8 @@ pub fn copy_local_distribution(path: &PathBuf, sha256: &str, cache_dir: &Path) -
         let file_hash = sha256_path(&cache_path);
 
         if file_hash == expected_hash {
-            println!(
+            info!(
+                logger,
                 "existing {} passes SHA-256 integrity check",
                 cache_path.display()
             );
@@ -678,7 +730,7 @@ pub fn copy_local_distribution(path: &PathBuf, sha256: &str, cache_dir: &Path) -
         panic!("sha256 of Python distribution does not validate");
     }
 
-    println!("copying {}", path.display());
+    info!(logger, "copying {}", path.display());
     std::fs::copy(path, &cache_path).unwrap();
 
     cache_path
@@ -693,12 +745,18 @@ pub fn copy_local_distribution(path: &PathBuf, sha256: &str, cache_dir: &Path) -
 /// in ``cache_dir``, it will be verified and returned.
 ///
 /// Local filesystem paths are preferred over remote URLs if both are defined.
-pub fn resolve_python_distribution_archive(config: &Config, cache_dir: &Path) -> PathBuf {
+pub fn resolve_python_distribution_archive(
+    logger: &slog::Logger,
+    config: &Config,
+    cache_dir: &Path,
+) -> PathBuf {
     match &config.python_distribution {
         PythonDistribution::Local { local_path, sha256 } => {
             let p = PathBuf::from(local_path);
-            copy_local_distribution(&p, sha256, cache_dir)
+            copy_local_distribution(logger, &p, sha256, cache_dir)
+        }
+        PythonDistribution::Url { url, sha256 } => {
+            download_distribution(logger, url, sha256, cache_dir)
         }
-        PythonDistribution::Url { url, sha256 } => download_distribution(url, sha256, cache_dir),
     }
 }
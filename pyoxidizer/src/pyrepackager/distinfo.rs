@@ -0,0 +1,674 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Parse Python package metadata in .dist-info directories.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use super::super::licensing::{
+    is_license_filename, ComponentFlavor, LicenseFlavor, LicenseText, LicensedComponent,
+    SpdxExpression,
+};
+use super::editable::is_editable_shim;
+
+/// SPDX license identifiers of license trove classifiers.
+///
+/// Classifiers that don't identify a specific license, such as
+/// ``BSD License`` or ``Apache Software License``, aren't listed.
+const LICENSE_CLASSIFIERS: &[(&str, &str)] = &[
+    ("Boost Software License 1.0 (BSL-1.0)", "BSL-1.0"),
+    (
+        "CC0 1.0 Universal (CC0 1.0) Public Domain Dedication",
+        "CC0-1.0",
+    ),
+    ("Eclipse Public License 2.0 (EPL-2.0)", "EPL-2.0"),
+    ("GNU Affero General Public License v3", "AGPL-3.0-only"),
+    (
+        "GNU Affero General Public License v3 or later (AGPLv3+)",
+        "AGPL-3.0-or-later",
+    ),
+    ("GNU General Public License v2 (GPLv2)", "GPL-2.0-only"),
+    (
+        "GNU General Public License v2 or later (GPLv2+)",
+        "GPL-2.0-or-later",
+    ),
+    ("GNU General Public License v3 (GPLv3)", "GPL-3.0-only"),
+    (
+        "GNU General Public License v3 or later (GPLv3+)",
+        "GPL-3.0-or-later",
+    ),
+    (
+        "GNU Lesser General Public License v2 (LGPLv2)",
+        "LGPL-2.0-only",
+    ),
+    (
+        "GNU Lesser General Public License v2 or later (LGPLv2+)",
+        "LGPL-2.0-or-later",
+    ),
+    (
+        "GNU Lesser General Public License v3 (LGPLv3)",
+        "LGPL-3.0-only",
+    ),
+    (
+        "GNU Lesser General Public License v3 or later (LGPLv3+)",
+        "LGPL-3.0-or-later",
+    ),
+    ("ISC License (ISCL)", "ISC"),
+    ("MIT License", "MIT"),
+    ("Mozilla Public License 1.1 (MPL 1.1)", "MPL-1.1"),
+    ("Mozilla Public License 2.0 (MPL 2.0)", "MPL-2.0"),
+    ("Python Software Foundation License", "PSF-2.0"),
+    ("The Unlicense (Unlicense)", "Unlicense"),
+    ("zlib/libpng License", "Zlib"),
+];
+
+/// SPDX license identifiers of common free-form ``License`` header values.
+///
+/// Keys are lowercase.
+const LICENSE_ALIASES: &[(&str, &str)] = &[
+    ("2-clause bsd", "BSD-2-Clause"),
+    ("3-clause bsd", "BSD-3-Clause"),
+    ("apache 2", "Apache-2.0"),
+    ("apache 2.0", "Apache-2.0"),
+    ("apache license 2.0", "Apache-2.0"),
+    ("apache license, version 2.0", "Apache-2.0"),
+    ("apache software license 2.0", "Apache-2.0"),
+    ("bsd 3-clause", "BSD-3-Clause"),
+    ("bsd 3-clause license", "BSD-3-Clause"),
+    ("isc license", "ISC"),
+    ("mit license", "MIT"),
+    ("modified bsd", "BSD-3-Clause"),
+    ("modified bsd license", "BSD-3-Clause"),
+    ("mozilla public license 2.0", "MPL-2.0"),
+    ("mpl 2.0", "MPL-2.0"),
+    ("new bsd", "BSD-3-Clause"),
+    ("new bsd license", "BSD-3-Clause"),
+    ("psf", "PSF-2.0"),
+    ("psf license", "PSF-2.0"),
+    ("python software foundation license", "PSF-2.0"),
+    ("simplified bsd", "BSD-2-Clause"),
+];
+
+/// Additional SPDX license identifiers recognized in ``License`` headers.
+const SPDX_LICENSE_IDS: &[&str] = &[
+    "0BSD",
+    "Apache-1.1",
+    "Apache-2.0",
+    "BSD-2-Clause",
+    "BSD-3-Clause",
+    "MIT-0",
+    "Python-2.0",
+];
+
+/// Resolve the canonical form of a known SPDX license identifier.
+fn known_spdx_license_id(value: &str) -> Option<&'static str> {
+    LICENSE_CLASSIFIERS
+        .iter()
+        .map(|(_, id)| *id)
+        .chain(LICENSE_ALIASES.iter().map(|(_, id)| *id))
+        .chain(SPDX_LICENSE_IDS.iter().cloned())
+        .find(|id| id.eq_ignore_ascii_case(value))
+}
+
+/// Resolve a ``License`` header value to an SPDX expression.
+///
+/// The value must be a common alias of a license or an SPDX expression
+/// consisting of known license identifiers. Free-form values can't be
+/// reliably interpreted and yield None.
+fn license_header_expression(value: &str) -> Option<String> {
+    let lower = value.trim().to_lowercase();
+
+    if let Some((_, id)) = LICENSE_ALIASES.iter().find(|(alias, _)| *alias == lower) {
+        return Some(id.to_string());
+    }
+
+    fn canonicalize(expression: &SpdxExpression) -> Option<String> {
+        match expression {
+            SpdxExpression::License { id, exception } => {
+                let id = known_spdx_license_id(id)?;
+
+                Some(match exception {
+                    Some(exception) => format!("{} WITH {}", id, exception),
+                    None => id.to_string(),
+                })
+            }
+            SpdxExpression::And(terms) | SpdxExpression::Or(terms) => {
+                let operator = if let SpdxExpression::And(_) = expression {
+                    " AND "
+                } else {
+                    " OR "
+                };
+
+                let terms = terms
+                    .iter()
+                    .map(|term| {
+                        let value = canonicalize(term)?;
+
+                        Some(match term {
+                            SpdxExpression::License { .. } => value,
+                            _ => format!("({})", value),
+                        })
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+
+                Some(terms.join(operator))
+            }
+        }
+    }
+
+    canonicalize(&SpdxExpression::parse(value).ok()?)
+}
+
+/// Represents a METADATA (or PKG-INFO) file.
+///
+/// See https://packaging.python.org/specifications/core-metadata/.
+#[derive(Clone, Debug)]
+pub struct PackageMetadata {
+    /// All headers, in the order they occur. Headers can be repeated.
+    pub headers: Vec<(String, String)>,
+
+    /// The message body, which holds the long description.
+    pub body: String,
+}
+
+impl PackageMetadata {
+    /// Parse metadata from its RFC 822 style representation.
+    pub fn parse(data: &str) -> Result<PackageMetadata, String> {
+        let mut headers: Vec<(String, String)> = Vec::new();
+        let mut lines = data.lines();
+
+        while let Some(line) = lines.next() {
+            if line.is_empty() {
+                break;
+            }
+
+            // Continuation lines begin with whitespace.
+            if line.starts_with(' ') || line.starts_with('\t') {
+                match headers.last_mut() {
+                    Some((_, value)) => {
+                        value.push('\n');
+                        value.push_str(line.trim());
+                    }
+                    None => return Err(format!("unexpected continuation line: {}", line)),
+                }
+
+                continue;
+            }
+
+            let idx = line
+                .find(':')
+                .ok_or_else(|| format!("malformed metadata line: {}", line))?;
+
+            headers.push((line[0..idx].to_string(), line[idx + 1..].trim().to_string()));
+        }
+
+        let body = lines.collect::<Vec<&str>>().join("\n");
+
+        Ok(PackageMetadata { headers, body })
+    }
+
+    /// Obtain the first value of a header.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Obtain all values of a header.
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.headers
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    /// The distribution's name.
+    pub fn name(&self) -> Option<&str> {
+        self.get("Name")
+    }
+
+    /// The distribution's version.
+    pub fn version(&self) -> Option<&str> {
+        self.get("Version")
+    }
+
+    /// Requirements of the distribution, from ``Requires-Dist`` headers.
+    pub fn requires_dist(&self) -> Vec<&str> {
+        self.get_all("Requires-Dist")
+    }
+}
+
+/// Represents an entry in a RECORD file.
+#[derive(Clone, Debug)]
+pub struct RecordEntry {
+    /// Path of the file, relative to the ``site-packages`` directory.
+    pub path: String,
+
+    /// Hash algorithm and digest, if recorded.
+    ///
+    /// The digest is in urlsafe base64 encoding without padding.
+    pub hash: Option<(String, String)>,
+
+    /// Size of the file in bytes, if recorded.
+    pub size: Option<u64>,
+}
+
+/// Split a line of a CSV file into its fields.
+///
+/// Fields containing commas or quotes are quoted.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::replace(&mut field, String::new())),
+            c => field.push(c),
+        }
+    }
+
+    fields.push(field);
+
+    fields
+}
+
+/// Parse a RECORD file.
+pub fn parse_record(data: &str) -> Result<Vec<RecordEntry>, String> {
+    let mut res = Vec::new();
+
+    for line in data.lines() {
+        if line.is_empty() {
+            continue;
+        }
+
+        let fields = split_csv_line(line);
+        if fields.len() != 3 {
+            return Err(format!("malformed RECORD line: {}", line));
+        }
+
+        let hash = if fields[1].is_empty() {
+            None
+        } else {
+            let idx = fields[1]
+                .find('=')
+                .ok_or_else(|| format!("malformed RECORD hash: {}", fields[1]))?;
+
+            Some((
+                fields[1][0..idx].to_string(),
+                fields[1][idx + 1..].to_string(),
+            ))
+        };
+
+        let size = if fields[2].is_empty() {
+            None
+        } else {
+            Some(
+                fields[2]
+                    .parse::<u64>()
+                    .or_else(|_| Err(format!("malformed RECORD size: {}", fields[2])))?,
+            )
+        };
+
+        res.push(RecordEntry {
+            path: fields[0].clone(),
+            hash,
+            size,
+        });
+    }
+
+    Ok(res)
+}
+
+/// Entry points, keyed by group and then by name.
+pub type EntryPoints = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Parse an entry_points.txt file.
+pub fn parse_entry_points(data: &str) -> Result<EntryPoints, String> {
+    let mut res = EntryPoints::new();
+    let mut group: Option<String> = None;
+
+    for line in data.lines() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            let name = line[1..line.len() - 1].trim().to_string();
+            res.entry(name.clone()).or_insert_with(BTreeMap::new);
+            group = Some(name);
+            continue;
+        }
+
+        let idx = line
+            .find('=')
+            .ok_or_else(|| format!("malformed entry point: {}", line))?;
+
+        match group {
+            Some(ref group) => {
+                res.get_mut(group).unwrap().insert(
+                    line[0..idx].trim().to_string(),
+                    line[idx + 1..].trim().to_string(),
+                );
+            }
+            None => return Err(format!("entry point outside of a group: {}", line)),
+        }
+    }
+
+    Ok(res)
+}
+
+/// Resolve the license of a distribution from its metadata.
+///
+/// In order of preference, this uses the PEP 639 ``License-Expression``
+/// header, a ``License`` header holding a recognized license, and license
+/// trove classifiers. Multiple license classifiers are interpreted as all
+/// licenses applying, as classifiers don't say whether they are a choice.
+pub fn metadata_license(metadata: &PackageMetadata) -> LicenseFlavor {
+    if let Some(value) = metadata.get("License-Expression") {
+        return LicenseFlavor::Spdx(value.to_string());
+    }
+
+    let header = metadata
+        .get("License")
+        .filter(|v| !v.is_empty() && *v != "UNKNOWN");
+
+    if let Some(expression) = header.and_then(license_header_expression) {
+        return LicenseFlavor::Spdx(expression);
+    }
+
+    let classifiers = metadata
+        .get_all("Classifier")
+        .into_iter()
+        .filter(|c| c.starts_with("License :: ") && *c != "License :: OSI Approved")
+        .filter_map(|c| c.rsplit(" :: ").next())
+        .collect::<Vec<_>>();
+
+    if classifiers == vec!["Public Domain"] {
+        return LicenseFlavor::PublicDomain;
+    }
+
+    let ids = classifiers
+        .iter()
+        .map(|c| {
+            LICENSE_CLASSIFIERS
+                .iter()
+                .find(|(classifier, _)| classifier == c)
+                .map(|(_, id)| id.to_string())
+        })
+        .collect::<Option<Vec<_>>>();
+
+    match (ids, header) {
+        (Some(ref ids), _) if !ids.is_empty() => LicenseFlavor::from_spdx_licenses(ids),
+        (_, Some(value)) => {
+            LicenseFlavor::Other(value.lines().next().unwrap_or("").trim().to_string())
+        }
+        _ if !classifiers.is_empty() => LicenseFlavor::Other(classifiers.join(", ")),
+        _ => LicenseFlavor::Unknown,
+    }
+}
+
+/// Represents the parsed content of a .dist-info directory.
+#[derive(Clone, Debug)]
+pub struct DistInfo {
+    /// Path to the .dist-info directory.
+    pub path: PathBuf,
+
+    /// Content of the METADATA file.
+    pub metadata: PackageMetadata,
+
+    /// Content of the RECORD file, if present.
+    pub record: Option<Vec<RecordEntry>>,
+
+    /// Content of the entry_points.txt file.
+    pub entry_points: EntryPoints,
+}
+
+impl DistInfo {
+    /// Parse a .dist-info directory.
+    pub fn from_path(path: &Path) -> Result<DistInfo, String> {
+        let read = |name: &str| -> Result<Option<String>, String> {
+            let file_path = path.join(name);
+
+            if file_path.exists() {
+                let data = fs::read(&file_path)
+                    .or_else(|e| Err(format!("error reading {}: {}", file_path.display(), e)))?;
+
+                Ok(Some(String::from_utf8_lossy(&data).to_string()))
+            } else {
+                Ok(None)
+            }
+        };
+
+        let metadata = match read("METADATA")? {
+            Some(data) => PackageMetadata::parse(&data)?,
+            None => return Err(format!("{} has no METADATA", path.display())),
+        };
+
+        let record = match read("RECORD")? {
+            Some(data) => Some(parse_record(&data)?),
+            None => None,
+        };
+
+        let entry_points = match read("entry_points.txt")? {
+            Some(data) => parse_entry_points(&data)?,
+            None => EntryPoints::new(),
+        };
+
+        Ok(DistInfo {
+            path: path.to_path_buf(),
+            metadata,
+            record,
+            entry_points,
+        })
+    }
+
+    /// Obtain license files of the distribution.
+    ///
+    /// Files listed in ``License-File`` headers are used if present. These
+    /// live in a ``licenses`` directory per PEP 639 or, with older tools, in
+    /// the root of the ``.dist-info`` directory. Otherwise, all files in the
+    /// ``.dist-info`` directory that look like license files are used.
+    pub fn license_files(&self) -> Vec<PathBuf> {
+        let declared = self.metadata.get_all("License-File");
+
+        if !declared.is_empty() {
+            return declared
+                .iter()
+                .filter_map(|name| {
+                    let candidates = [self.path.join("licenses").join(name), self.path.join(name)];
+                    candidates.iter().find(|p| p.is_file()).cloned()
+                })
+                .collect();
+        }
+
+        let mut res = WalkDir::new(&self.path)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry.file_type().is_file()
+                    && entry
+                        .file_name()
+                        .to_str()
+                        .map(is_license_filename)
+                        .unwrap_or(false)
+            })
+            .map(|entry| entry.path().to_path_buf())
+            .collect::<Vec<_>>();
+        res.sort();
+
+        res
+    }
+
+    /// Obtain names of the top-level packages and modules the distribution installed.
+    ///
+    /// Names are derived from the paths in ``RECORD``. Files installed outside
+    /// of ``site-packages``, metadata directories, ``.pth`` files and editable
+    /// install shims aren't packages.
+    pub fn top_level_names(&self) -> BTreeSet<String> {
+        let mut res = BTreeSet::new();
+
+        for entry in self.record.iter().flatten() {
+            let path = entry.path.replace('\\', "/");
+            let mut parts = path.splitn(2, '/');
+            let first = parts.next().unwrap_or("");
+            let is_dir = parts.next().is_some();
+
+            if first == ".."
+                || first == "__pycache__"
+                || first.ends_with(".dist-info")
+                || first.ends_with(".egg-info")
+                || first.ends_with(".data")
+                || is_editable_shim(first)
+                || (!is_dir && first.ends_with(".pth"))
+            {
+                continue;
+            }
+
+            // Modules and extension modules are named up to the first dot.
+            let name = if is_dir {
+                first
+            } else {
+                first.split('.').next().unwrap_or("")
+            };
+
+            if !name.is_empty() {
+                res.insert(name.to_string());
+            }
+        }
+
+        res
+    }
+
+    /// Describe the distribution as a licensed component.
+    pub fn licensed_component(&self) -> Result<LicensedComponent, String> {
+        let name = self
+            .metadata
+            .name()
+            .ok_or_else(|| format!("{} does not define a name", self.path.display()))?;
+
+        let mut component = LicensedComponent::new(
+            ComponentFlavor::PythonPackage,
+            name,
+            metadata_license(&self.metadata),
+        );
+        component.version = self.metadata.version().map(|s| s.to_string());
+        component.homepage = self
+            .metadata
+            .get("Home-page")
+            .filter(|v| !v.is_empty() && *v != "UNKNOWN")
+            .map(|s| s.to_string())
+            .or_else(|| {
+                self.metadata
+                    .get_all("Project-URL")
+                    .into_iter()
+                    .filter_map(|v| {
+                        let idx = v.find(',')?;
+                        if v[0..idx].trim().eq_ignore_ascii_case("homepage") {
+                            Some(v[idx + 1..].trim().to_string())
+                        } else {
+                            None
+                        }
+                    })
+                    .next()
+            });
+
+        for path in self.license_files() {
+            let data = fs::read(&path)
+                .or_else(|e| Err(format!("error reading {}: {}", path.display(), e)))?;
+
+            component.license_texts.push(LicenseText {
+                filename: path
+                    .strip_prefix(&self.path)
+                    .unwrap_or(&path)
+                    .iter()
+                    .map(|p| p.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+                text: String::from_utf8_lossy(&data).to_string(),
+            });
+        }
+
+        // Some distributions put the full license text in the License header.
+        if let Some(value) = self.metadata.get("License") {
+            if component.license_texts.is_empty() && value.contains('\n') {
+                component.license_texts.push(LicenseText {
+                    filename: "METADATA".to_string(),
+                    text: value.to_string(),
+                });
+            }
+        }
+
+        Ok(component)
+    }
+}
+
+/// A .dist-info directory that couldn't be parsed.
+#[derive(Clone, Debug)]
+pub struct InvalidDistInfo {
+    /// Path to the .dist-info directory.
+    pub path: PathBuf,
+
+    /// Why the directory couldn't be parsed.
+    pub error: String,
+}
+
+/// The .dist-info directories in a directory.
+#[derive(Clone, Debug, Default)]
+pub struct DistInfos {
+    /// Successfully parsed directories.
+    pub distributions: Vec<DistInfo>,
+
+    /// Directories that couldn't be parsed, such as those left behind by
+    /// an interrupted install.
+    pub invalid: Vec<InvalidDistInfo>,
+}
+
+/// Find and parse all .dist-info directories in a directory.
+///
+/// A directory that fails to parse doesn't prevent others from being
+/// found. It is recorded in ``DistInfos::invalid`` instead.
+pub fn find_dist_infos(path: &Path) -> DistInfos {
+    let mut res = DistInfos::default();
+
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return res,
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|p| {
+            p.is_dir()
+                && p.file_name()
+                    .and_then(|name| name.to_str())
+                    .map(|name| name.ends_with(".dist-info"))
+                    .unwrap_or(false)
+        })
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        match DistInfo::from_path(&path) {
+            Ok(dist_info) => res.distributions.push(dist_info),
+            Err(error) => res.invalid.push(InvalidDistInfo { path, error }),
+        }
+    }
+
+    res
+}
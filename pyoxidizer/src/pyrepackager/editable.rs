@@ -0,0 +1,273 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Resolve packages installed in editable/development mode.
+//!
+//! Editable installs (``pip install -e``, ``setup.py develop``, PEP 660)
+//! don't copy a package's files into ``site-packages``. Instead, they install
+//! a shim pointing at the package's source tree. This module follows those
+//! shims so the source tree can be packaged.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+lazy_static! {
+    /// Matches entries in the ``MAPPING`` of a PEP 660 finder module.
+    static ref FINDER_MAPPING_ENTRY: Regex =
+        Regex::new(r#"['"]([A-Za-z_][A-Za-z0-9_.]*)['"]\s*:\s*['"]([^'"]+)['"]"#).unwrap();
+}
+
+/// Describes a package installed in editable mode.
+#[derive(Clone, Debug)]
+pub struct EditableInstall {
+    /// The file in ``site-packages`` defining the editable install.
+    pub shim: PathBuf,
+
+    /// Directory containing the top-level packages. This is the directory
+    /// that would be added to ``sys.path``.
+    pub root: PathBuf,
+
+    /// Names of top-level packages and modules provided by the install.
+    pub packages: Vec<String>,
+}
+
+impl EditableInstall {
+    /// Paths of the packages and modules provided by the install.
+    ///
+    /// Only these paths belong to the install. Other content of ``root``,
+    /// such as tests or the rest of a repository, must not be packaged.
+    pub fn package_paths(&self) -> Vec<PathBuf> {
+        let mut res = Vec::new();
+
+        for package in &self.packages {
+            let mut path = self.root.clone();
+            path.extend(package.split('.'));
+
+            if path.is_dir() {
+                res.push(path);
+            } else {
+                path.set_extension("py");
+
+                if path.is_file() {
+                    res.push(path);
+                }
+            }
+        }
+
+        res
+    }
+}
+
+/// An editable install shim that couldn't be resolved.
+#[derive(Clone, Debug)]
+pub struct InvalidEditableInstall {
+    /// The file in ``site-packages`` defining the editable install.
+    pub shim: PathBuf,
+
+    /// Why the shim couldn't be resolved.
+    pub error: String,
+}
+
+/// Editable installs found in a ``site-packages`` directory.
+#[derive(Clone, Debug, Default)]
+pub struct EditableInstalls {
+    pub installs: Vec<EditableInstall>,
+
+    /// Shims that couldn't be resolved. These don't prevent resolving others.
+    pub invalid: Vec<InvalidEditableInstall>,
+}
+
+/// Whether a file in ``site-packages`` is part of an editable install shim.
+///
+/// These files only have meaning to the Python import machinery at run-time
+/// and shouldn't be packaged.
+pub fn is_editable_shim(name: &str) -> bool {
+    name.starts_with("__editable__") || name.ends_with(".egg-link") || name == "easy-install.pth"
+}
+
+/// Read the names of top-level packages from a ``top_level.txt`` file.
+fn read_top_level(path: &Path) -> Option<Vec<String>> {
+    let data = fs::read_to_string(path).ok()?;
+
+    Some(
+        data.lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .map(|l| l.to_string())
+            .collect(),
+    )
+}
+
+/// Find top-level packages of a source tree from its ``.egg-info`` directory.
+fn egg_info_top_level(root: &Path) -> Option<Vec<String>> {
+    for entry in fs::read_dir(root).ok()? {
+        let path = entry.ok()?.path();
+
+        if path.is_dir()
+            && path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.ends_with(".egg-info"))
+                .unwrap_or(false)
+        {
+            if let Some(packages) = read_top_level(&path.join("top_level.txt")) {
+                return Some(packages);
+            }
+        }
+    }
+
+    None
+}
+
+/// Resolve a ``.egg-link`` file written by ``setup.py develop``.
+///
+/// The first line holds the path to the directory containing the packages.
+fn resolve_egg_link(path: &Path) -> Result<EditableInstall, String> {
+    let data = fs::read_to_string(path)
+        .or_else(|e| Err(format!("error reading {}: {}", path.display(), e)))?;
+
+    let root = data
+        .lines()
+        .next()
+        .map(|l| PathBuf::from(l.trim()))
+        .ok_or_else(|| format!("{} is empty", path.display()))?;
+
+    let packages = egg_info_top_level(&root).ok_or_else(|| {
+        format!(
+            "unable to determine packages of {}; no .egg-info/top_level.txt",
+            root.display()
+        )
+    })?;
+
+    Ok(EditableInstall {
+        shim: path.to_path_buf(),
+        root,
+        packages,
+    })
+}
+
+/// Resolve a PEP 660 ``__editable__.<name>.pth`` file.
+///
+/// setuptools writes either a path to add to ``sys.path`` or an import of a
+/// finder module mapping package names to their source directories.
+fn resolve_editable_pth(site_packages: &Path, path: &Path) -> Result<Vec<EditableInstall>, String> {
+    let data = fs::read_to_string(path)
+        .or_else(|e| Err(format!("error reading {}: {}", path.display(), e)))?;
+
+    let mut res = Vec::new();
+
+    for line in data.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+        if line.starts_with("import ") {
+            let module = line["import ".len()..]
+                .split(|c: char| c == ';' || c.is_whitespace())
+                .next()
+                .unwrap_or("");
+            let finder_path = site_packages.join(format!("{}.py", module));
+            let finder = fs::read_to_string(&finder_path)
+                .or_else(|e| Err(format!("error reading {}: {}", finder_path.display(), e)))?;
+
+            // The finder defines a MAPPING dict of package names to paths.
+            let mapping = match finder.find("MAPPING") {
+                Some(idx) => {
+                    let rest = &finder[idx..];
+                    &rest[0..rest.find('}').unwrap_or_else(|| rest.len())]
+                }
+                None => {
+                    return Err(format!("no MAPPING found in {}", finder_path.display()));
+                }
+            };
+
+            for captures in FINDER_MAPPING_ENTRY.captures_iter(mapping) {
+                // Paths are Python string literals, so backslashes are escaped.
+                let package_path = PathBuf::from(captures[2].replace("\\\\", "\\"));
+                let root = package_path
+                    .parent()
+                    .ok_or_else(|| format!("invalid package path: {}", package_path.display()))?
+                    .to_path_buf();
+
+                res.push(EditableInstall {
+                    shim: path.to_path_buf(),
+                    root,
+                    packages: vec![captures[1].to_string()],
+                });
+            }
+        } else {
+            let root = PathBuf::from(line);
+
+            // The .dist-info installed alongside the shim describes the
+            // packages. Fall back to the source tree's .egg-info.
+            let dist_name = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .map(|s| s.trim_start_matches("__editable__."))
+                .unwrap_or("");
+            let packages = read_top_level(
+                &site_packages
+                    .join(format!("{}.dist-info", dist_name))
+                    .join("top_level.txt"),
+            )
+            .or_else(|| egg_info_top_level(&root))
+            .ok_or_else(|| {
+                format!(
+                    "unable to determine packages of editable install {}",
+                    path.display()
+                )
+            })?;
+
+            res.push(EditableInstall {
+                shim: path.to_path_buf(),
+                root,
+                packages,
+            });
+        }
+    }
+
+    Ok(res)
+}
+
+/// Find editable installs in a ``site-packages`` directory.
+///
+/// A shim that can't be resolved is recorded as invalid instead of
+/// preventing other installs from being found.
+pub fn find_editable_installs(site_packages: &Path) -> EditableInstalls {
+    let mut res = EditableInstalls::default();
+
+    let entries = match fs::read_dir(site_packages) {
+        Ok(entries) => entries,
+        Err(_) => return res,
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|p| p.is_file())
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let name = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+
+        let resolved = if name.ends_with(".egg-link") {
+            resolve_egg_link(&path).map(|install| vec![install])
+        } else if name.starts_with("__editable__") && name.ends_with(".pth") {
+            resolve_editable_pth(site_packages, &path)
+        } else {
+            continue;
+        };
+
+        match resolved {
+            Ok(installs) => res.installs.extend(installs),
+            Err(error) => res
+                .invalid
+                .push(InvalidEditableInstall { shim: path, error }),
+        }
+    }
+
+    res
+}
@@ -0,0 +1,196 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Filter Python resources by name using rules.
+
+use regex::Regex;
+use slog::{info, warn};
+use std::collections::BTreeSet;
+
+use super::fsscan::PythonResourceType;
+
+/// Names of resource types that filter rules can be restricted to.
+pub const RESOURCE_TYPES: &[&str] = &["source", "bytecode", "resource", "extension"];
+
+/// Obtain the name of a resource type, as used by filter rules.
+pub fn resource_type_name(flavor: &PythonResourceType) -> &'static str {
+    match flavor {
+        PythonResourceType::Source => "source",
+        PythonResourceType::Bytecode
+        | PythonResourceType::BytecodeOpt1
+        | PythonResourceType::BytecodeOpt2 => "bytecode",
+        PythonResourceType::Resource => "resource",
+        PythonResourceType::Other => "other",
+    }
+}
+
+#[derive(Debug)]
+enum Matcher {
+    /// Matches a name and all names within it. e.g. ``foo`` matches
+    /// ``foo`` and ``foo.bar``.
+    Prefix(String),
+    Glob(glob::Pattern),
+    Regex(Regex),
+}
+
+impl Matcher {
+    /// Parse a pattern string.
+    ///
+    /// Patterns beginning with ``re:`` are regular expressions matched
+    /// against the full name. Patterns beginning with ``glob:`` are glob
+    /// patterns. Other patterns are treated as glob patterns if they contain
+    /// glob syntax and are otherwise exact names.
+    fn parse(pattern: &str) -> Result<Matcher, String> {
+        if pattern.starts_with("re:") {
+            let re = Regex::new(&format!("^(?:{})$", &pattern[3..]))
+                .or_else(|e| Err(format!("invalid regular expression {}: {}", pattern, e)))?;
+
+            Ok(Matcher::Regex(re))
+        } else if pattern.starts_with("glob:") {
+            Matcher::parse_glob(&pattern[5..])
+        } else if pattern.contains(|c| c == '*' || c == '?' || c == '[') {
+            Matcher::parse_glob(pattern)
+        } else {
+            Ok(Matcher::Prefix(pattern.to_string()))
+        }
+    }
+
+    fn parse_glob(pattern: &str) -> Result<Matcher, String> {
+        let glob = glob::Pattern::new(pattern)
+            .or_else(|e| Err(format!("invalid glob pattern {}: {}", pattern, e)))?;
+
+        Ok(Matcher::Glob(glob))
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Matcher::Prefix(prefix) => {
+                name == prefix
+                    || (name.starts_with(prefix.as_str()) && name[prefix.len()..].starts_with('.'))
+            }
+            Matcher::Glob(glob) => glob.matches(name),
+            Matcher::Regex(re) => re.is_match(name),
+        }
+    }
+}
+
+/// A rule in a `ResourceFilter`.
+#[derive(Debug)]
+struct FilterRule {
+    pattern: String,
+    matcher: Matcher,
+
+    /// Names matched by this rule.
+    matched: BTreeSet<String>,
+}
+
+/// Matches resource names against a set of rules and records what matched.
+///
+/// The record of matches can be used to explain why a resource was or
+/// wasn't packaged and to find rules that don't do anything.
+#[derive(Debug)]
+pub struct ResourceFilter {
+    rules: Vec<FilterRule>,
+
+    /// Resource types the filter applies to. Empty means all types.
+    resource_types: BTreeSet<String>,
+}
+
+impl ResourceFilter {
+    /// Construct an instance from pattern strings.
+    ///
+    /// If `resource_types` is non-empty, only resources of the named types
+    /// are matched.
+    pub fn new(patterns: &[String], resource_types: &[String]) -> Result<ResourceFilter, String> {
+        let mut rules = Vec::with_capacity(patterns.len());
+
+        for pattern in patterns {
+            rules.push(FilterRule {
+                pattern: pattern.clone(),
+                matcher: Matcher::parse(pattern)?,
+                matched: BTreeSet::new(),
+            });
+        }
+
+        for resource_type in resource_types {
+            if !RESOURCE_TYPES.contains(&resource_type.as_str()) {
+                return Err(format!(
+                    "unknown resource type: {}; must be one of {}",
+                    resource_type,
+                    RESOURCE_TYPES.join(", ")
+                ));
+            }
+        }
+
+        Ok(ResourceFilter {
+            rules,
+            resource_types: resource_types.iter().cloned().collect(),
+        })
+    }
+
+    /// Whether a resource of the given type matches any rule.
+    ///
+    /// All matching rules record the match.
+    pub fn matches(&mut self, name: &str, resource_type: &str) -> bool {
+        if !self.resource_types.is_empty() && !self.resource_types.contains(resource_type) {
+            return false;
+        }
+
+        let mut res = false;
+
+        for rule in &mut self.rules {
+            if rule.matcher.matches(name) {
+                rule.matched.insert(name.to_string());
+                res = true;
+            }
+        }
+
+        res
+    }
+
+    /// Obtain the patterns of rules matching a resource.
+    ///
+    /// Unlike `matches()`, matches aren't recorded.
+    pub fn matching_patterns(&self, name: &str, resource_type: &str) -> Vec<String> {
+        if !self.resource_types.is_empty() && !self.resource_types.contains(resource_type) {
+            return Vec::new();
+        }
+
+        self.rules
+            .iter()
+            .filter(|rule| rule.matcher.matches(name))
+            .map(|rule| rule.pattern.clone())
+            .collect()
+    }
+
+    /// Log what each rule matched.
+    ///
+    /// `action` describes what happens to matched resources. e.g.
+    /// ``excluded``. Rules that didn't match anything are warned about, as
+    /// they are likely mistakes.
+    pub fn log_report(&self, logger: &slog::Logger, context: &str, action: &str) {
+        for rule in &self.rules {
+            if rule.matched.is_empty() {
+                warn!(
+                    logger,
+                    "{}: pattern {} did not match any resources", context, rule.pattern
+                );
+            } else {
+                info!(
+                    logger,
+                    "{}: pattern {} {} {} resources: {}",
+                    context,
+                    rule.pattern,
+                    action,
+                    rule.matched.len(),
+                    rule.matched
+                        .iter()
+                        .cloned()
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,268 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Explain how files are classified as Python resources.
+//!
+//! This is useful for figuring out why packaging rules do or don't package
+//! a file without having to run a build.
+
+use serde::Serialize;
+use std::path::Path;
+
+use super::filtering::ResourceFilter;
+use super::repackage::resolve_site_packages_path;
+use super::sitepackages::{scan_site_packages, SitePackagesResource, SitePackagesResourceType};
+use super::wheel::extract_wheel;
+
+/// Describes how a file was classified and whether it would be packaged.
+#[derive(Clone, Debug, Serialize)]
+pub struct ResourceExplanation {
+    /// Path of the file relative to the scanned directory or wheel.
+    pub path: String,
+
+    /// The classification of the file.
+    ///
+    /// One of ``source``, ``bytecode``, ``resource``, ``extension``,
+    /// ``metadata``, ``editable-shim``, ``path-configuration``, or
+    /// ``unknown``.
+    pub resource_type: String,
+
+    /// Name the file would be packaged under. Names of resources are
+    /// prefixed by their package.
+    pub name: String,
+
+    /// Python package the file belongs to. Empty if none.
+    pub package: String,
+
+    /// Name of the distribution that installed the file, if known.
+    pub distribution: Option<String>,
+
+    /// Why the file was classified the way it was.
+    pub classification: String,
+
+    /// Whether the file would be packaged.
+    pub included: bool,
+
+    /// Why the file would or wouldn't be packaged.
+    pub decision: String,
+
+    /// Include patterns matching the file.
+    pub matched_includes: Vec<String>,
+
+    /// Exclude patterns matching the file.
+    pub matched_excludes: Vec<String>,
+}
+
+fn resource_type_name(flavor: &SitePackagesResourceType) -> &'static str {
+    match flavor {
+        SitePackagesResourceType::Source => "source",
+        SitePackagesResourceType::Bytecode(_) => "bytecode",
+        SitePackagesResourceType::Resource => "resource",
+        SitePackagesResourceType::ExtensionModule => "extension",
+        SitePackagesResourceType::Metadata => "metadata",
+        SitePackagesResourceType::EditableShim => "editable-shim",
+        SitePackagesResourceType::PathConfiguration => "path-configuration",
+        SitePackagesResourceType::DataFile => "data-file",
+        SitePackagesResourceType::Unknown => "unknown",
+    }
+}
+
+/// Describe why a file was classified the way it was.
+fn classification_reason(resource: &SitePackagesResource) -> String {
+    match resource.flavor {
+        SitePackagesResourceType::Source if resource.relative_path.ends_with("__init__.py") => {
+            format!("__init__.py file defining package {}", resource.name)
+        }
+        SitePackagesResourceType::Source => format!(".py file defining module {}", resource.name),
+        SitePackagesResourceType::Bytecode(level) => format!(
+            ".pyc file in __pycache__ holding bytecode of module {} at optimization level {}",
+            resource.name, level
+        ),
+        SitePackagesResourceType::Resource => {
+            format!("non-module file in package {}", resource.package)
+        }
+        SitePackagesResourceType::ExtensionModule => format!(
+            "file name has an extension module suffix; defines module {}",
+            resource.name
+        ),
+        SitePackagesResourceType::Metadata => {
+            "file in a .dist-info or .egg-info directory".to_string()
+        }
+        SitePackagesResourceType::EditableShim => {
+            "top-level file pointing at a package installed in editable mode".to_string()
+        }
+        SitePackagesResourceType::PathConfiguration => {
+            "top-level .pth file adding entries to sys.path".to_string()
+        }
+        SitePackagesResourceType::DataFile => {
+            "file in a directory of vendored shared libraries (*.libs or .dylibs)".to_string()
+        }
+        SitePackagesResourceType::Unknown if resource.relative_path.ends_with(".pyc") => {
+            ".pyc file outside a __pycache__ directory (possibly Python 2 bytecode)".to_string()
+        }
+        SitePackagesResourceType::Unknown => {
+            "file isn't in a directory holding Python modules".to_string()
+        }
+    }
+}
+
+/// Describe why a file of a type that is never packaged is ignored.
+fn ignored_reason(flavor: &SitePackagesResourceType) -> Option<&'static str> {
+    match flavor {
+        SitePackagesResourceType::Source | SitePackagesResourceType::Resource => None,
+        SitePackagesResourceType::Bytecode(_) => {
+            Some("ignored: bytecode is compiled from source instead")
+        }
+        SitePackagesResourceType::ExtensionModule => {
+            Some("ignored: extension modules can't be loaded from memory")
+        }
+        SitePackagesResourceType::Metadata => Some("ignored: packaging metadata isn't packaged"),
+        SitePackagesResourceType::EditableShim => {
+            Some("ignored: the source tree of the editable package is packaged instead")
+        }
+        SitePackagesResourceType::PathConfiguration => {
+            Some("ignored: sys.path entries aren't processed")
+        }
+        SitePackagesResourceType::DataFile => {
+            Some("ignored: the file isn't a Python resource and can't be loaded from memory")
+        }
+        SitePackagesResourceType::Unknown => Some("ignored: the file couldn't be imported"),
+    }
+}
+
+fn explain_resource(
+    resource: &SitePackagesResource,
+    includes: &ResourceFilter,
+    excludes: &ResourceFilter,
+    has_includes: bool,
+) -> ResourceExplanation {
+    let resource_type = resource_type_name(&resource.flavor);
+
+    // Filter rules match resources by their dotted path.
+    let name = match resource.flavor {
+        SitePackagesResourceType::Resource => resource.relative_path.replace('/', "."),
+        _ => resource.name.clone(),
+    };
+
+    let matched_includes = includes.matching_patterns(&name, resource_type);
+    let matched_excludes = excludes.matching_patterns(&name, resource_type);
+
+    let (included, decision) = if let Some(reason) = ignored_reason(&resource.flavor) {
+        (false, reason.to_string())
+    } else if !matched_excludes.is_empty() {
+        (
+            false,
+            format!("excluded by {}", matched_excludes.join(", ")),
+        )
+    } else if has_includes && matched_includes.is_empty() {
+        (false, "not matched by any include pattern".to_string())
+    } else if has_includes {
+        (true, format!("included by {}", matched_includes.join(", ")))
+    } else {
+        (true, "included".to_string())
+    };
+
+    ResourceExplanation {
+        path: resource.relative_path.clone(),
+        resource_type: resource_type.to_string(),
+        name,
+        package: resource.package.clone(),
+        distribution: resource.distribution.clone(),
+        classification: classification_reason(resource),
+        included,
+        decision,
+        matched_includes,
+        matched_excludes,
+    }
+}
+
+/// Classify the files in a directory or wheel and explain each one.
+///
+/// ``path`` can be a virtualenv, a ``site-packages`` directory, any other
+/// directory holding Python packages, or a ``.whl`` file. Files are
+/// classified as described by ``scan_site_packages()``.
+///
+/// Patterns in ``includes`` and ``excludes`` use the syntax of the
+/// ``excludes`` of packaging rules. If ``includes`` is not empty, only
+/// files matching an include pattern are packaged. Files matching an
+/// exclude pattern are never packaged.
+pub fn explain_resources(
+    path: &Path,
+    includes: &[String],
+    excludes: &[String],
+) -> Result<Vec<ResourceExplanation>, String> {
+    let includes_filter = ResourceFilter::new(includes, &[])?;
+    let excludes_filter = ResourceFilter::new(excludes, &[])?;
+
+    let is_wheel = path.is_file()
+        && path
+            .extension()
+            .map_or(false, |extension| extension == "whl");
+
+    let temp_dir = tempdir::TempDir::new("pyoxidizer-find-resources")
+        .or_else(|e| Err(format!("could not create temp directory: {}", e)))?;
+
+    let (scan_path, wheel_install) = if is_wheel {
+        let install = extract_wheel(path, temp_dir.path())?;
+        (temp_dir.path().to_path_buf(), Some(install))
+    } else {
+        (resolve_site_packages_path(path), None)
+    };
+
+    let site_packages = scan_site_packages(&scan_path)?;
+
+    let mut res: Vec<ResourceExplanation> = site_packages
+        .resources
+        .iter()
+        .map(|resource| {
+            explain_resource(
+                resource,
+                &includes_filter,
+                &excludes_filter,
+                !includes.is_empty(),
+            )
+        })
+        .collect();
+
+    // Files in other install schemes of a wheel aren't extracted. But they
+    // are part of the wheel, so account for them.
+    if let Some(install) = wheel_install {
+        for (scheme, paths, decision) in &[
+            (
+                "scripts",
+                &install.scripts,
+                "ignored unless the packaging rule defines scripts_location",
+            ),
+            (
+                "data",
+                &install.data,
+                "ignored unless the packaging rule defines data_files_location",
+            ),
+            (
+                "headers",
+                &install.headers,
+                "ignored: headers aren't packaged",
+            ),
+        ] {
+            for path in paths.iter() {
+                res.push(ResourceExplanation {
+                    path: path.clone(),
+                    resource_type: "unknown".to_string(),
+                    name: path.clone(),
+                    package: "".to_string(),
+                    distribution: None,
+                    classification: format!("file in the {} install scheme of the wheel", scheme),
+                    included: false,
+                    decision: decision.to_string(),
+                    matched_includes: Vec::new(),
+                    matched_excludes: Vec::new(),
+                });
+            }
+        }
+
+        res.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+
+    Ok(res)
+}
@@ -88,7 +88,7 @@ pub struct PythonResourceIterator {
 }
 
 impl PythonResourceIterator {
-    fn new(path: &Path) -> PythonResourceIterator {
+    fn new(root_path: &Path, path: &Path) -> PythonResourceIterator {
         let res = walkdir::WalkDir::new(path);
 
         let filtered = res.into_iter().filter_map(|entry| {
@@ -104,7 +104,7 @@ impl PythonResourceIterator {
         });
 
         PythonResourceIterator {
-            root_path: path.to_path_buf(),
+            root_path: root_path.to_path_buf(),
             walkdir_result: Box::new(filtered),
             seen_packages: HashSet::new(),
             resources: Vec::new(),
@@ -365,7 +365,61 @@ impl Iterator for PythonResourceIterator {
 ///
 /// Returns an iterator of ``PythonResource`` instances.
 pub fn find_python_resources(root_path: &Path) -> PythonResourceIterator {
-    PythonResourceIterator::new(root_path)
+    PythonResourceIterator::new(root_path, root_path)
+}
+
+/// Find Python resources in part of a directory tree.
+///
+/// Only ``path``, a file or directory under ``root_path``, is walked.
+/// Resource names are resolved relative to ``root_path``, so e.g. a single
+/// package directory can be scanned.
+pub fn find_python_resources_in(root_path: &Path, path: &Path) -> PythonResourceIterator {
+    PythonResourceIterator::new(root_path, path)
+}
+
+/// Find files in a directory that aren't ignored by git.
+///
+/// This runs ``git ls-files`` in the directory, which reports files tracked
+/// by git plus untracked files not matched by ``.gitignore`` rules. Returned
+/// paths are prefixed by ``root_path``, like paths from ``walk_tree_files()``.
+///
+/// ``git ls-files`` reports submodules and nested repositories as a single
+/// entry, so files in them are found by running it in them as well.
+pub fn find_git_visible_files(root_path: &Path) -> Result<HashSet<PathBuf>, String> {
+    let output = std::process::Command::new("git")
+        .arg("ls-files")
+        .arg("-z")
+        .arg("--cached")
+        .arg("--others")
+        .arg("--exclude-standard")
+        .current_dir(root_path)
+        .output()
+        .or_else(|e| Err(format!("error running git ls-files: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "git ls-files failed in {}: {}",
+            root_path.display(),
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    let mut res = HashSet::new();
+
+    for path in output.stdout.split(|c| *c == 0).filter(|p| !p.is_empty()) {
+        let path = root_path.join(String::from_utf8_lossy(path).as_ref());
+
+        if path.is_dir() {
+            // Submodules that aren't checked out are empty directories.
+            if path.join(".git").exists() {
+                res.extend(find_git_visible_files(&path)?);
+            }
+        } else {
+            res.insert(path);
+        }
+    }
+
+    Ok(res)
 }
 
 pub fn find_python_modules(root_path: &Path) -> Result<BTreeMap<String, Vec<u8>>, &'static str> {
@@ -0,0 +1,586 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Inspect packed modules and resources data.
+//!
+//! See the documentation in the `pyembed` crate for the data formats.
+
+use byteorder::{ByteOrder, LittleEndian};
+use flate2::read::ZlibDecoder;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use super::repackage::{
+    MODULES_MAGIC, MODULES_VERSION, MODULE_FLAG_PACKAGE, RESOURCES_MAGIC, RESOURCES_VERSION,
+    RESOURCE_FLAG_ZLIB,
+};
+
+/// Describes an entry in packed data.
+#[derive(Clone, Debug, Serialize)]
+pub struct PackedEntry {
+    /// Full name of the entry.
+    ///
+    /// For resources, this is the package and resource name joined by ``:``.
+    pub name: String,
+
+    /// The type of the entry.
+    ///
+    /// One of ``module-source``, ``module-bytecode``,
+    /// ``module-bytecode-opt1``, ``module-bytecode-opt2``, or ``resource``.
+    pub entry_type: String,
+
+    /// Size of the entry's data, as stored.
+    pub stored_size: usize,
+
+    /// Size of the entry's data after decoding.
+    pub size: usize,
+
+    /// Names of flags set on the entry.
+    pub flags: Vec<String>,
+
+    /// Hex encoded SHA-256 of the decoded data, if recorded.
+    pub sha256: Option<String>,
+
+    /// Whether the decoded data matches ``sha256``, if recorded.
+    pub sha256_valid: Option<bool>,
+}
+
+impl PackedEntry {
+    /// Whether the entry has a recorded digest its data doesn't match.
+    pub fn digest_mismatch(&self) -> bool {
+        self.sha256_valid == Some(false)
+    }
+}
+
+/// Describes a blob of packed data.
+#[derive(Clone, Debug, Serialize)]
+pub struct PackedData {
+    /// The kind of data. ``modules`` or ``resources``.
+    pub kind: String,
+
+    /// Offset of the data within the file it was found in.
+    pub offset: usize,
+
+    /// Length of the data.
+    pub length: usize,
+
+    /// Format version of the data.
+    pub version: u32,
+
+    pub entries: Vec<PackedEntry>,
+}
+
+/// Obtain a range of data, with bounds and overflow checking.
+fn get_range(data: &[u8], offset: usize, length: usize) -> Option<&[u8]> {
+    data.get(offset..offset.checked_add(length)?)
+}
+
+/// Read a little endian u32 at an offset, with bounds checking.
+fn read_u32(data: &[u8], offset: usize) -> Result<usize, String> {
+    match get_range(data, offset, 4) {
+        Some(v) => Ok(LittleEndian::read_u32(v) as usize),
+        None => Err(format!("data truncated at offset {}", offset)),
+    }
+}
+
+/// Whether stored resource data decodes to data having a SHA-256 digest.
+///
+/// Data that fails to decode doesn't match.
+fn resource_digest_matches(stored: &[u8], flags: u32, size: usize, sha256: &str) -> bool {
+    let decoded = if flags & RESOURCE_FLAG_ZLIB != 0 {
+        // Sizes come from the data being inspected, so don't trust them for
+        // allocations and stop decoding once the data is known to be larger.
+        let mut decoded = Vec::new();
+
+        match ZlibDecoder::new(stored)
+            .take(size as u64 + 1)
+            .read_to_end(&mut decoded)
+        {
+            Ok(_) => decoded,
+            Err(_) => return false,
+        }
+    } else {
+        stored.to_vec()
+    };
+
+    decoded.len() == size && hex::encode(Sha256::digest(&decoded)) == sha256
+}
+
+/// Parse versioned packed modules data.
+///
+/// `data` must begin with the magic. It may extend past the end of the
+/// packed data.
+pub fn parse_packed_modules(data: &[u8]) -> Result<PackedData, String> {
+    if !data.starts_with(MODULES_MAGIC) {
+        return Err("data is not versioned packed modules data".to_string());
+    }
+
+    let version = read_u32(data, 8)? as u32;
+    if version != MODULES_VERSION {
+        return Err(format!("unsupported modules data version: {}", version));
+    }
+
+    if read_u32(data, 12)? != 0 {
+        return Err("reserved modules header field is not 0".to_string());
+    }
+
+    let count = read_u32(data, 16)?;
+    let mut entries = Vec::new();
+    let mut length = count
+        .checked_mul(44)
+        .and_then(|n| n.checked_add(20))
+        .filter(|n| *n <= data.len())
+        .ok_or_else(|| format!("module count {} exceeds data", count))?;
+
+    const TYPES: &[(usize, &str)] = &[
+        (2, "module-source"),
+        (4, "module-bytecode"),
+        (6, "module-bytecode-opt1"),
+        (8, "module-bytecode-opt2"),
+    ];
+
+    for i in 0..count {
+        let entry_offset = 20 + i * 44;
+        let field = |n: usize| read_u32(data, entry_offset + n * 4);
+
+        let name_offset = field(0)?;
+        let name_length = field(1)?;
+        let name = get_range(data, name_offset, name_length)
+            .ok_or_else(|| format!("module name {} out of bounds", i))?;
+        let name = String::from_utf8(name.to_vec())
+            .or_else(|_| Err(format!("module name {} is not valid UTF-8", i)))?;
+
+        length = std::cmp::max(length, name_offset + name_length);
+
+        let flags = if field(10)? as u32 & MODULE_FLAG_PACKAGE != 0 {
+            vec!["package".to_string()]
+        } else {
+            Vec::new()
+        };
+
+        for (field_index, entry_type) in TYPES {
+            let offset = field(*field_index)?;
+            let size = field(*field_index + 1)?;
+
+            if size == 0 {
+                continue;
+            }
+
+            if get_range(data, offset, size).is_none() {
+                return Err(format!("data for module {} out of bounds", name));
+            }
+
+            length = std::cmp::max(length, offset + size);
+
+            entries.push(PackedEntry {
+                name: name.clone(),
+                entry_type: entry_type.to_string(),
+                stored_size: size,
+                size,
+                flags: flags.clone(),
+                sha256: None,
+                sha256_valid: None,
+            });
+        }
+    }
+
+    Ok(PackedData {
+        kind: "modules".to_string(),
+        offset: 0,
+        length,
+        version,
+        entries,
+    })
+}
+
+/// Parse versioned packed resources data.
+///
+/// `data` must begin with the magic. It may extend past the end of the
+/// packed data.
+pub fn parse_packed_resources(data: &[u8]) -> Result<PackedData, String> {
+    if !data.starts_with(RESOURCES_MAGIC) {
+        return Err("data is not versioned packed resources data".to_string());
+    }
+
+    let version = read_u32(data, 8)? as u32;
+    if version != RESOURCES_VERSION {
+        return Err(format!("unsupported resources data version: {}", version));
+    }
+
+    if read_u32(data, 12)? != 0 {
+        return Err("reserved resources header field is not 0".to_string());
+    }
+
+    let package_count = read_u32(data, 16)?;
+    let mut offset = 20;
+
+    // Counts come from the data being inspected. Reject counts of entries
+    // that can't fit in the remaining data before iterating over them.
+    if package_count > (data.len() - offset) / 12 {
+        return Err(format!("package count {} exceeds data", package_count));
+    }
+
+    // (package name length, [(name length, flags, stored size, size, data offset, digest)])
+    let mut index = Vec::new();
+
+    for _ in 0..package_count {
+        let package_name_length = read_u32(data, offset)?;
+        let resource_count = read_u32(data, offset + 4)?;
+        offset += 12;
+
+        if resource_count > data.len().saturating_sub(offset) / 56 {
+            return Err(format!("resource count {} exceeds data", resource_count));
+        }
+
+        let mut resources = Vec::new();
+
+        for _ in 0..resource_count {
+            let digest = get_range(data, offset + 24, 32)
+                .ok_or_else(|| "resource digest out of bounds".to_string())?;
+
+            if read_u32(data, offset + 20)? != 0 {
+                return Err("reserved resource field is not 0".to_string());
+            }
+
+            resources.push((
+                read_u32(data, offset)?,
+                read_u32(data, offset + 4)? as u32,
+                read_u32(data, offset + 8)?,
+                read_u32(data, offset + 12)?,
+                read_u32(data, offset + 16)?,
+                hex::encode(digest),
+            ));
+
+            offset += 56;
+        }
+
+        index.push((package_name_length, resources));
+    }
+
+    let data_start = index
+        .iter()
+        .flat_map(|(length, resources)| {
+            std::iter::once(*length).chain(resources.iter().map(|r| r.0))
+        })
+        .try_fold(offset, |total, length| total.checked_add(length))
+        .filter(|start| *start <= data.len())
+        .ok_or_else(|| "resource names out of bounds".to_string())?;
+    let mut length = data_start;
+    let mut entries = Vec::new();
+
+    for (package_name_length, resources) in index {
+        let package = get_range(data, offset, package_name_length)
+            .ok_or_else(|| "package name out of bounds".to_string())?;
+        let package = String::from_utf8_lossy(package).to_string();
+        offset += package_name_length;
+
+        for (name_length, flags, stored_size, size, resource_offset, sha256) in resources {
+            let name = get_range(data, offset, name_length)
+                .ok_or_else(|| "resource name out of bounds".to_string())?;
+            let name = String::from_utf8_lossy(name).to_string();
+            offset += name_length;
+
+            let out_of_bounds = || format!("data for resource {}:{} out of bounds", package, name);
+            let start = data_start
+                .checked_add(resource_offset)
+                .ok_or_else(out_of_bounds)?;
+            let stored = get_range(data, start, stored_size).ok_or_else(out_of_bounds)?;
+
+            length = std::cmp::max(length, start + stored_size);
+
+            let sha256_valid = resource_digest_matches(stored, flags, size, &sha256);
+
+            let mut flag_names = Vec::new();
+            if flags & RESOURCE_FLAG_ZLIB != 0 {
+                flag_names.push("zlib".to_string());
+            }
+            if flags & !RESOURCE_FLAG_ZLIB != 0 {
+                flag_names.push(format!("unknown({:#x})", flags & !RESOURCE_FLAG_ZLIB));
+            }
+
+            entries.push(PackedEntry {
+                name: format!("{}:{}", package, name),
+                entry_type: "resource".to_string(),
+                stored_size,
+                size,
+                flags: flag_names,
+                sha256: Some(sha256),
+                sha256_valid: Some(sha256_valid),
+            });
+        }
+    }
+
+    Ok(PackedData {
+        kind: "resources".to_string(),
+        offset: 0,
+        length,
+        version,
+        entries,
+    })
+}
+
+/// Find packed modules and resources data in arbitrary data.
+///
+/// This can be used to find data embedded in an executable. Only versioned
+/// data can be found, as unversioned data has no identifying header.
+pub fn find_packed_data(data: &[u8]) -> Vec<PackedData> {
+    let mut res = Vec::new();
+    let mut offset = 0;
+
+    while offset + 8 <= data.len() {
+        let candidate = &data[offset..];
+
+        let parsed = if candidate.starts_with(MODULES_MAGIC) {
+            parse_packed_modules(candidate).ok()
+        } else if candidate.starts_with(RESOURCES_MAGIC) {
+            parse_packed_resources(candidate).ok()
+        } else {
+            None
+        };
+
+        match parsed {
+            Some(mut packed) => {
+                packed.offset = offset;
+                offset += packed.length;
+                res.push(packed);
+            }
+            None => {
+                offset += 1;
+            }
+        }
+    }
+
+    res
+}
+
+/// Inspect a file containing packed data or an executable embedding it.
+pub fn inspect_file(path: &Path) -> Result<Vec<PackedData>, String> {
+    let data =
+        fs::read(path).or_else(|e| Err(format!("error reading {}: {}", path.display(), e)))?;
+
+    if data.starts_with(MODULES_MAGIC) {
+        Ok(vec![parse_packed_modules(&data)?])
+    } else if data.starts_with(RESOURCES_MAGIC) {
+        Ok(vec![parse_packed_resources(&data)?])
+    } else {
+        let res = find_packed_data(&data);
+
+        if res.is_empty() {
+            Err(format!(
+                "no versioned packed modules or resources data found in {}",
+                path.display()
+            ))
+        } else {
+            Ok(res)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::repackage::{write_modules_entries, write_resources_entries, ModuleEntry};
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn resources() -> BTreeMap<String, BTreeMap<String, Vec<u8>>> {
+        let mut foo = BTreeMap::new();
+        foo.insert("bar".to_string(), b"shared content".to_vec());
+        foo.insert("empty".to_string(), Vec::new());
+
+        let mut acme = BTreeMap::new();
+        acme.insert("hello".to_string(), vec![b'a'; 4096]);
+        acme.insert("world".to_string(), b"shared content".to_vec());
+
+        let mut res = BTreeMap::new();
+        res.insert("foo".to_string(), foo);
+        res.insert("acme".to_string(), acme);
+
+        res
+    }
+
+    fn pack_resources(compress: bool) -> Vec<u8> {
+        let mut data = Vec::new();
+        write_resources_entries(&mut data, &resources(), compress).unwrap();
+
+        data
+    }
+
+    /// Obtain the entries of packed data by name.
+    fn entries_by_name(packed: &PackedData) -> BTreeMap<String, Vec<&PackedEntry>> {
+        let mut res: BTreeMap<String, Vec<&PackedEntry>> = BTreeMap::new();
+
+        for entry in &packed.entries {
+            res.entry(entry.name.clone())
+                .or_insert_with(Vec::new)
+                .push(entry);
+        }
+
+        res
+    }
+
+    #[test]
+    fn resources_round_trip() {
+        for compress in &[false, true] {
+            let data = pack_resources(*compress);
+            let packed = parse_packed_resources(&data).unwrap();
+
+            assert_eq!(packed.kind, "resources");
+            assert_eq!(packed.version, RESOURCES_VERSION);
+            assert_eq!(packed.length, data.len());
+
+            let entries = entries_by_name(&packed);
+            let mut expected_names = Vec::new();
+
+            for (package, resources) in resources() {
+                for (name, value) in resources {
+                    let name = format!("{}:{}", package, name);
+                    let entry = entries[&name][0];
+
+                    assert_eq!(entries[&name].len(), 1);
+                    assert_eq!(entry.entry_type, "resource");
+                    assert_eq!(entry.size, value.len());
+                    assert_eq!(entry.sha256, Some(hex::encode(Sha256::digest(&value))));
+                    assert_eq!(entry.sha256_valid, Some(true));
+
+                    expected_names.push(name);
+                }
+            }
+
+            assert_eq!(entries.keys().cloned().collect::<Vec<_>>(), expected_names);
+
+            let hello = entries["acme:hello"][0];
+            if *compress {
+                assert_eq!(hello.flags, vec!["zlib".to_string()]);
+                assert!(hello.stored_size < hello.size);
+            } else {
+                assert!(hello.flags.is_empty());
+                assert_eq!(hello.stored_size, hello.size);
+            }
+        }
+    }
+
+    #[test]
+    fn resources_identical_data_stored_once() {
+        let data = pack_resources(false);
+        let packed = parse_packed_resources(&data).unwrap();
+
+        // Header, package headers, resource entries, and names.
+        let index_length = 20 + 2 * 12 + 4 * 56 + "fooacmebaremptyhelloworld".len();
+        let values_length = "shared content".len() + 4096;
+
+        assert_eq!(data.len(), index_length + values_length);
+        assert_eq!(packed.length, data.len());
+
+        let entries = entries_by_name(&packed);
+        assert_eq!(
+            entries["foo:bar"][0].sha256,
+            entries["acme:world"][0].sha256
+        );
+        assert_eq!(entries["acme:world"][0].sha256_valid, Some(true));
+    }
+
+    #[test]
+    fn resources_corrupt_data_detected() {
+        let mut data = pack_resources(false);
+        // Values are stored in index order, so the last byte of acme:hello
+        // precedes the shared value.
+        let offset = data.len() - "shared content".len() - 1;
+        data[offset] ^= 0xff;
+
+        let packed = parse_packed_resources(&data).unwrap();
+        let entries = entries_by_name(&packed);
+
+        assert!(entries["acme:hello"][0].digest_mismatch());
+        assert!(!entries["foo:bar"][0].digest_mismatch());
+    }
+
+    #[test]
+    fn resources_reject_reserved_field() {
+        let mut data = pack_resources(false);
+        // Reserved field of the first resource of the first package.
+        data[20 + 12 + 20] = 1;
+
+        assert!(parse_packed_resources(&data).is_err());
+    }
+
+    #[test]
+    fn modules_round_trip() {
+        let entries = vec![
+            ModuleEntry {
+                name: "foo.bar".to_string(),
+                source: Some(b"print('hello')".to_vec()),
+                bytecode: Some(b"bytecode".to_vec()),
+                bytecode_opt1: None,
+                bytecode_opt2: Some(b"opt2".to_vec()),
+            },
+            ModuleEntry {
+                name: "foo".to_string(),
+                source: None,
+                bytecode: Some(b"package bytecode".to_vec()),
+                bytecode_opt1: Some(b"opt1".to_vec()),
+                bytecode_opt2: None,
+            },
+        ];
+
+        let mut data = Vec::new();
+        write_modules_entries(&mut data, &entries).unwrap();
+
+        let packed = parse_packed_modules(&data).unwrap();
+
+        assert_eq!(packed.kind, "modules");
+        assert_eq!(packed.version, MODULES_VERSION);
+        assert_eq!(packed.length, data.len());
+
+        let found = packed
+            .entries
+            .iter()
+            .map(|e| {
+                (
+                    e.name.as_str(),
+                    e.entry_type.as_str(),
+                    e.size,
+                    e.flags.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let package = vec!["package".to_string()];
+        assert_eq!(
+            found,
+            vec![
+                ("foo", "module-bytecode", 16, package.clone()),
+                ("foo", "module-bytecode-opt1", 4, package),
+                ("foo.bar", "module-source", 14, Vec::new()),
+                ("foo.bar", "module-bytecode", 8, Vec::new()),
+                ("foo.bar", "module-bytecode-opt2", 4, Vec::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn find_embedded_packed_data() {
+        let mut modules = Vec::new();
+        write_modules_entries(&mut modules, &[]).unwrap();
+        let resources = pack_resources(true);
+
+        let mut data = b"leading garbage".to_vec();
+        data.extend_from_slice(&modules);
+        data.extend_from_slice(b"more garbage");
+        let resources_offset = data.len();
+        data.extend_from_slice(&resources);
+
+        let found = find_packed_data(&data);
+
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].kind, "modules");
+        assert_eq!(found[0].offset, 15);
+        assert_eq!(found[0].length, modules.len());
+        assert_eq!(found[1].kind, "resources");
+        assert_eq!(found[1].offset, resources_offset);
+        assert_eq!(found[1].entries.len(), 4);
+    }
+}
@@ -0,0 +1,250 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Track files written into the application directory across builds.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// A file written into the application directory.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct InstalledFile {
+    /// SHA-256 digest of the content, in hex.
+    digest: String,
+
+    /// Unix permission bits the file was given, if any.
+    mode: Option<u32>,
+
+    /// Size of the file after it was written.
+    size: u64,
+
+    /// Modification time of the file after it was written, as seconds and
+    /// nanoseconds since the Unix epoch.
+    modified: (u64, u32),
+}
+
+/// Obtain the size and modification time of a file on disk.
+fn file_stat(path: &Path) -> Option<(u64, (u64, u32))> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+
+    if !metadata.is_file() {
+        return None;
+    }
+
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    let modified = (modified.as_secs(), modified.subsec_nanos());
+
+    Some((metadata.len(), modified))
+}
+
+/// Writes files into the application directory, skipping unchanged ones.
+///
+/// The files written by a build are recorded in a JSON file. A file is only
+/// written if its content or mode differs from what the previous build wrote,
+/// or if it was changed on disk since. Files written by the previous build
+/// but not by the current one are removed by `finish()`.
+///
+/// The record only exists between the end of a build and the start of the
+/// next one. Files written by a build failing before `finish()` are thus
+/// never left untracked: without a record, callers start from scratch.
+pub struct InstallManifest {
+    path: PathBuf,
+    root: PathBuf,
+    previous: Option<BTreeMap<String, InstalledFile>>,
+    current: BTreeMap<String, InstalledFile>,
+
+    /// Number of files written.
+    pub written: usize,
+
+    /// Number of files left as-is because they were unchanged.
+    pub unchanged: usize,
+}
+
+impl InstallManifest {
+    /// Load the record of a previous build installing into `root`.
+    ///
+    /// A missing or unreadable record results in a manifest without
+    /// previous files. The record is removed before any file is written and
+    /// is saved again by `finish()`.
+    pub fn load(path: &Path, root: &Path) -> Result<InstallManifest, String> {
+        let previous = fs::read(path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok());
+
+        if fs::symlink_metadata(path).is_ok() {
+            fs::remove_file(path)
+                .or_else(|e| Err(format!("failed to remove {}: {}", path.display(), e)))?;
+        }
+
+        Ok(InstallManifest {
+            path: path.to_path_buf(),
+            root: root.to_path_buf(),
+            previous,
+            current: BTreeMap::new(),
+            written: 0,
+            unchanged: 0,
+        })
+    }
+
+    /// Whether files written by a previous build are known.
+    pub fn has_previous(&self) -> bool {
+        self.previous.is_some()
+    }
+
+    fn key(&self, dest: &Path) -> Result<String, String> {
+        let rel_path = dest.strip_prefix(&self.root).or_else(|_| {
+            Err(format!(
+                "{} is not in {}",
+                dest.display(),
+                self.root.display()
+            ))
+        })?;
+
+        Ok(rel_path.to_string_lossy().replace('\\', "/"))
+    }
+
+    /// Write a file, unless the previous build wrote the same file.
+    ///
+    /// If `mode` is defined, the file is given these Unix permission bits.
+    pub fn write(&mut self, dest: &Path, data: &[u8], mode: Option<u32>) -> Result<(), String> {
+        let key = self.key(dest)?;
+        let digest = hex::encode(Sha256::digest(data));
+
+        let previous = self.previous.as_ref().and_then(|p| p.get(&key)).cloned();
+
+        if let Some(previous) = previous {
+            if previous.digest == digest
+                && previous.mode == mode
+                && file_stat(dest) == Some((previous.size, previous.modified))
+            {
+                self.current.insert(key, previous);
+                self.unchanged += 1;
+                return Ok(());
+            }
+        }
+
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).or_else(|e| Err(e.to_string()))?;
+        }
+
+        // A previous build may have installed the file read-only.
+        if fs::symlink_metadata(dest).is_ok() {
+            fs::remove_file(dest)
+                .or_else(|e| Err(format!("failed to remove {}: {}", dest.display(), e)))?;
+        }
+
+        fs::write(dest, data)
+            .or_else(|e| Err(format!("failed to write {}: {}", dest.display(), e)))?;
+
+        if let Some(mode) = mode {
+            set_file_mode(dest, mode)?;
+        }
+
+        let (size, modified) =
+            file_stat(dest).ok_or_else(|| format!("unable to stat {}", dest.display()))?;
+
+        self.current.insert(
+            key,
+            InstalledFile {
+                digest,
+                mode,
+                size,
+                modified,
+            },
+        );
+        self.written += 1;
+
+        Ok(())
+    }
+
+    /// Copy a file, unless the previous build copied the same file.
+    ///
+    /// The Unix permission bits of the source file are preserved.
+    pub fn copy(&mut self, source: &Path, dest: &Path) -> Result<(), String> {
+        let data = fs::read(source)
+            .or_else(|e| Err(format!("unable to copy {}: {}", source.display(), e)))?;
+
+        self.write(dest, &data, file_mode(source))
+    }
+
+    /// Remove files only written by the previous build and save the manifest.
+    ///
+    /// Directories left empty are removed as well. Returns the number of
+    /// removed files.
+    pub fn finish(self) -> Result<usize, String> {
+        let mut removed = 0;
+
+        for key in self.previous.iter().flat_map(|p| p.keys()) {
+            if self.current.contains_key(key) {
+                continue;
+            }
+
+            let path = self.root.join(key);
+
+            if fs::symlink_metadata(&path).is_ok() {
+                fs::remove_file(&path)
+                    .or_else(|e| Err(format!("failed to remove {}: {}", path.display(), e)))?;
+                removed += 1;
+            }
+
+            // Fails for directories that aren't empty, which is fine.
+            let mut dir = path.parent();
+            while let Some(p) = dir {
+                if p == self.root || fs::remove_dir(p).is_err() {
+                    break;
+                }
+                dir = p.parent();
+            }
+        }
+
+        let data = serde_json::to_vec(&self.current).or_else(|e| Err(e.to_string()))?;
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).or_else(|e| Err(e.to_string()))?;
+        }
+
+        fs::write(&self.path, data)
+            .or_else(|e| Err(format!("error writing {}: {}", self.path.display(), e)))?;
+
+        Ok(removed)
+    }
+}
+
+/// Obtain the Unix permission bits of a file.
+#[cfg(unix)]
+pub fn file_mode(path: &Path) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path)
+        .ok()
+        .map(|metadata| metadata.permissions().mode() & 0o777)
+}
+
+/// Obtain the Unix permission bits of a file.
+///
+/// Always `None` on platforms without Unix permissions.
+#[cfg(not(unix))]
+pub fn file_mode(_path: &Path) -> Option<u32> {
+    None
+}
+
+/// Set the Unix permission bits of a file.
+#[cfg(unix)]
+fn set_file_mode(path: &Path, mode: u32) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(mode)).or_else(|e| Err(e.to_string()))
+}
+
+/// Set the Unix permission bits of a file.
+///
+/// This is a no-op on platforms without Unix permissions.
+#[cfg(not(unix))]
+fn set_file_mode(_path: &Path, _mode: u32) -> Result<(), String> {
+    Ok(())
+}
@@ -12,3 +12,34 @@ def _install(_bootstrap_module):
     # These lines magically register the PyOxidizer importer.
     import _pyoxidizer_importer
     _pyoxidizer_importer._setup(_pyoxidizer_importer, _bootstrap_module, marshal, decode_source)
+
+
+# Python 3.7 doesn't support sys.pycache_prefix. So we wrap
+# cache_from_source() to write bytecode for filesystem modules to the
+# directory in sys.oxidized_bytecode_cache_directory, if set. Since the
+# importers and importlib.util resolve cache_from_source when called, this
+# wrapper is used everywhere.
+_stdlib_cache_from_source = cache_from_source
+
+def cache_from_source(path, debug_override=None, *, optimization=None):
+    cache_path = _stdlib_cache_from_source(path, debug_override,
+                                           optimization=optimization)
+
+    cache_directory = getattr(sys, 'oxidized_bytecode_cache_directory', None)
+    if cache_directory is None:
+        return cache_path
+
+    # Strip the __pycache__ directory.
+    head, filename = _path_split(cache_path)
+    head, _ = _path_split(head)
+
+    # Mirror the absolute path of the source directory so modules with the
+    # same relative path in different sys.path entries don't collide.
+    if not (head[:1] and head[:1] in path_separators) and head[1:2] != ':':
+        head = _path_join(_os.getcwd(), head)
+
+    # Strip the drive from Windows paths.
+    if head[1:2] == ':':
+        head = head[2:]
+
+    return _path_join(cache_directory, head.lstrip(path_separators), filename)
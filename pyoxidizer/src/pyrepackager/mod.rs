@@ -3,10 +3,21 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 pub mod bytecode;
+pub mod classification;
 pub mod config;
 pub mod dist;
+pub mod distinfo;
+pub mod editable;
+pub mod filtering;
+pub mod findresources;
 pub mod fsscan;
+pub mod inspect;
+pub mod installmanifest;
 pub mod repackage;
+#[path = "../pyembed/searchpaths.rs"]
+pub mod searchpaths;
+pub mod sitepackages;
+pub mod wheel;
 
 #[allow(unused)]
 const STDLIB_NONTEST_IGNORE_DIRS: &[&str] = &[
@@ -3,31 +3,54 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use byteorder::{LittleEndian, WriteBytesExt};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
 use glob::glob as findglob;
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use slog::info;
-use std::collections::{BTreeMap, BTreeSet};
+use sha2::{Digest, Sha256};
+use slog::{info, warn};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::env;
 use std::fs;
 use std::fs::create_dir_all;
 use std::io::{BufRead, BufReader, Cursor, Error as IOError, Read, Write};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
-use super::bytecode::BytecodeCompiler;
+use super::super::analyze::{is_system_library, verify_binary_portability};
+use super::super::licensing::{
+    render_notices, rust_crate_components, ComponentFlavor, LicenseFlavor, LicenseText,
+    LicensedComponent, LicensedComponents,
+};
+use super::super::logging::Progress;
+use super::super::strip::{debug_file, strip_binary, StripMode};
+use super::bytecode::{BytecodeCompiler, CachingBytecodeCompiler};
+use super::classification::ClassificationCache;
 use super::config::{
-    parse_config, Config, InstallLocation, PackagingPackageRoot, PackagingPipInstallSimple,
-    PackagingPipRequirementsFile, PackagingSetupPyInstall, PackagingStdlib,
-    PackagingStdlibExtensionVariant, PackagingStdlibExtensionsExplicitExcludes,
-    PackagingStdlibExtensionsExplicitIncludes, PackagingStdlibExtensionsPolicy,
-    PackagingVirtualenv, PythonDistribution, PythonPackaging, RawAllocator, RunMode,
+    parse_config, Config, InstallLocation, MemAllocator, PackagingPackageRoot,
+    PackagingPipInstallSimple, PackagingPipRequirementsFile, PackagingSetupPyInstall,
+    PackagingStaticExtensionModule, PackagingStdlib, PackagingStdlibExtensionVariant,
+    PackagingStdlibExtensionsExplicitExcludes, PackagingStdlibExtensionsExplicitIncludes,
+    PackagingStdlibExtensionsPolicy, PackagingThirdPartyNotices, PackagingVirtualenv,
+    PackagingWheelInstall, PythonDistribution, PythonPackaging, RawAllocator, RunMode,
+    RuntimeFilesPolicy, StdlibProfile,
 };
 use super::dist::{
     analyze_python_distribution_tar_zst, resolve_python_distribution_archive, ExtensionModule,
-    LicenseInfo, PythonDistributionInfo,
+    ExtensionModuleBuild, LibraryDepends, LicenseInfo, PythonDistributionInfo,
+};
+use super::distinfo::{find_dist_infos, metadata_license, DistInfo, InvalidDistInfo};
+use super::editable::{find_editable_installs, is_editable_shim};
+use super::filtering::{resource_type_name, ResourceFilter};
+use super::fsscan::{
+    find_git_visible_files, find_python_resources, find_python_resources_in, walk_tree_files,
+    PythonResource as FsPythonResource, PythonResourceType,
 };
-use super::fsscan::{find_python_resources, PythonResourceType};
+use super::installmanifest::{file_mode, InstallManifest};
+use super::searchpaths::{CA_CERTIFICATES_SEARCH_PATHS, TERMINFO_SEARCH_PATHS};
+use super::sitepackages::{scan_site_packages, SitePackagesResourceType};
+use super::wheel::{extract_wheel, validate_wheel_target};
 
 pub const PYTHON_IMPORTER: &[u8] = include_bytes!("memoryimporter.py");
 
@@ -60,6 +83,36 @@ const STDLIB_TEST_PACKAGES: &[&str] = &[
     "unittest.test",
 ];
 
+/// Standard library packages requiring Tk.
+const STDLIB_TK_PACKAGES: &[&str] = &["idlelib", "tkinter", "turtle", "turtledemo"];
+
+/// Standard library extension modules requiring Tk.
+const STDLIB_TK_EXTENSIONS: &[&str] = &["_tkinter"];
+
+/// Standard library packages excluded by the ``minimal`` profile.
+///
+/// These are development tools, GUI toolkits, and other packages rarely
+/// imported by applications. This is in addition to test and Tk packages.
+const STDLIB_MINIMAL_EXCLUDED_PACKAGES: &[&str] = &[
+    "curses",
+    "dbm",
+    "distutils",
+    "ensurepip",
+    "lib2to3",
+    "msilib",
+    "pydoc_data",
+    "sqlite3",
+    "venv",
+    "wsgiref",
+    "xmlrpc",
+];
+
+/// Standard library extension modules excluded by the ``minimal`` profile.
+///
+/// These back packages in ``STDLIB_MINIMAL_EXCLUDED_PACKAGES``.
+const STDLIB_MINIMAL_EXCLUDED_EXTENSIONS: &[&str] =
+    &["_curses", "_curses_panel", "_dbm", "_gdbm", "_sqlite3"];
+
 lazy_static! {
     /// Libraries provided by the host that we can ignore in Python module library dependencies.
     ///
@@ -111,8 +164,9 @@ lazy_static! {
 
 pub const HOST: &str = env!("HOST");
 
-pub fn is_stdlib_test_package(name: &str) -> bool {
-    for package in STDLIB_TEST_PACKAGES {
+/// Whether a resource name is one of the packages or inside of one of them.
+fn is_in_packages(name: &str, packages: &[&str]) -> bool {
+    for package in packages {
         let prefix = format!("{}.", package);
 
         if &name == package || name.starts_with(&prefix) {
@@ -123,6 +177,27 @@ pub fn is_stdlib_test_package(name: &str) -> bool {
     false
 }
 
+pub fn is_stdlib_test_package(name: &str) -> bool {
+    is_in_packages(name, STDLIB_TEST_PACKAGES)
+}
+
+/// Whether a stdlib profile excludes a package, module, or extension module.
+///
+/// Test packages are governed by the rule's ``exclude_test_modules`` and not
+/// by the profile.
+fn is_stdlib_profile_excluded(profile: StdlibProfile, name: &str) -> bool {
+    let tk = is_in_packages(name, STDLIB_TK_PACKAGES) || STDLIB_TK_EXTENSIONS.contains(&name);
+
+    match profile {
+        StdlibProfile::Full | StdlibProfile::NoTests => false,
+        StdlibProfile::NoTk => tk,
+        StdlibProfile::Minimal => {
+            tk || is_in_packages(name, STDLIB_MINIMAL_EXCLUDED_PACKAGES)
+                || STDLIB_MINIMAL_EXCLUDED_EXTENSIONS.contains(&name)
+        }
+    }
+}
+
 /// Represents environment for a build.
 pub struct BuildContext {
     /// Path to Rust project.
@@ -292,6 +367,8 @@ pub struct ModuleEntry {
     pub name: String,
     pub source: Option<Vec<u8>>,
     pub bytecode: Option<Vec<u8>>,
+    pub bytecode_opt1: Option<Vec<u8>>,
+    pub bytecode_opt2: Option<Vec<u8>>,
 }
 
 /// Represents an ordered collection of module entries.
@@ -318,6 +395,19 @@ pub enum PythonResource {
         name: String,
         data: Vec<u8>,
     },
+    /// A file installed at a path relative to an install location.
+    ///
+    /// Only app-relative install locations are supported.
+    File {
+        path: String,
+        data: Vec<u8>,
+        /// Unix permission bits to install the file with.
+        mode: u32,
+    },
+    /// License metadata of a Python distribution providing resources.
+    License {
+        component: LicensedComponent,
+    },
 }
 
 #[derive(Debug)]
@@ -358,7 +448,8 @@ pub struct PythonResourceAction {
 #[derive(Debug)]
 pub struct EmbeddedPythonResources {
     pub module_sources: BTreeMap<String, Vec<u8>>,
-    pub module_bytecodes: BTreeMap<String, Vec<u8>>,
+    /// Module bytecode, keyed by module name and then optimization level.
+    pub module_bytecodes: BTreeMap<String, BTreeMap<i32, Vec<u8>>>,
     pub all_modules: BTreeSet<String>,
     pub resources: BTreeMap<String, BTreeMap<String, Vec<u8>>>,
     pub extension_modules: BTreeMap<String, ExtensionModule>,
@@ -371,7 +462,10 @@ impl EmbeddedPythonResources {
 
         for name in &self.all_modules {
             let source = self.module_sources.get(name);
-            let bytecode = self.module_bytecodes.get(name);
+            let bytecode = |level: i32| match self.module_bytecodes.get(name) {
+                Some(levels) => levels.get(&level).cloned(),
+                None => None,
+            };
 
             records.push(ModuleEntry {
                 name: name.clone(),
@@ -379,10 +473,9 @@ impl EmbeddedPythonResources {
                     Some(value) => Some(value.clone()),
                     None => None,
                 },
-                bytecode: match bytecode {
-                    Some(value) => Some(value.clone()),
-                    None => None,
-                },
+                bytecode: bytecode(0),
+                bytecode_opt1: bytecode(1),
+                bytecode_opt2: bytecode(2),
             });
         }
 
@@ -394,6 +487,7 @@ impl EmbeddedPythonResources {
         module_names_path: &PathBuf,
         modules_path: &PathBuf,
         resources_path: &PathBuf,
+        compress_resources: bool,
     ) {
         let mut fh = fs::File::create(module_names_path).expect("error creating file");
         for name in &self.all_modules {
@@ -405,7 +499,37 @@ impl EmbeddedPythonResources {
         write_modules_entries(&fh, &self.modules_records()).unwrap();
 
         let fh = fs::File::create(resources_path).unwrap();
-        write_resources_entries(&fh, &self.resources).unwrap();
+        write_resources_entries(&fh, &self.resources, compress_resources).unwrap();
+    }
+}
+
+/// Represents a file to install in an app-relative location.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AppRelativeFile {
+    pub data: Vec<u8>,
+
+    /// Unix permission bits of the installed file.
+    pub mode: u32,
+}
+
+/// Normalize a shared library name for matching dependencies to libraries.
+///
+/// Only filenames are compared, as Mach-O dependencies are typically
+/// referenced through ``@rpath/``, ``@loader_path/`` or
+/// ``@executable_path/``. Windows finds DLLs case-insensitively, so names of
+/// PE libraries are lowercased.
+fn shared_library_key(name: &str) -> String {
+    let filename = match name.rfind(|c| c == '/' || c == '\\') {
+        Some(idx) => &name[idx + 1..],
+        None => name,
+    };
+
+    let lower = filename.to_lowercase();
+
+    if lower.ends_with(".dll") || lower.ends_with(".pyd") {
+        lower
+    } else {
+        filename.to_string()
     }
 }
 
@@ -415,6 +539,17 @@ pub struct AppRelativeResources {
     pub module_sources: BTreeMap<String, Vec<u8>>,
     pub module_bytecodes: BTreeMap<String, Vec<u8>>,
     pub resources: BTreeMap<String, BTreeMap<String, Vec<u8>>>,
+
+    /// Files not associated with Python packages, keyed by relative path.
+    ///
+    /// These are data files and scripts installed by Python packaging.
+    pub files: BTreeMap<String, AppRelativeFile>,
+
+    /// Shared libraries this resources collection depends on.
+    ///
+    /// Keyed by package and resource name, like `resources`. Values are the
+    /// names of libraries the resource links against.
+    pub shared_library_dependencies: BTreeMap<String, BTreeMap<String, Vec<String>>>,
 }
 
 impl AppRelativeResources {
@@ -423,7 +558,110 @@ impl AppRelativeResources {
             module_sources: BTreeMap::new(),
             module_bytecodes: BTreeMap::new(),
             resources: BTreeMap::new(),
+            files: BTreeMap::new(),
+            shared_library_dependencies: BTreeMap::new(),
+        }
+    }
+
+    /// Add a resource, recording shared library dependencies if it is a binary.
+    pub fn add_resource(
+        &mut self,
+        package: &str,
+        name: &str,
+        data: Vec<u8>,
+        classifications: &mut ClassificationCache,
+    ) {
+        if let Some(depends) = classifications.shared_library_dependencies(&data) {
+            self.shared_library_dependencies
+                .entry(package.to_string())
+                .or_insert_with(BTreeMap::new)
+                .insert(name.to_string(), depends);
+        }
+
+        self.resources
+            .entry(package.to_string())
+            .or_insert_with(BTreeMap::new)
+            .insert(name.to_string(), data);
+    }
+
+    /// Obtain the order shared libraries in this collection need to be loaded in.
+    ///
+    /// Libraries are ordered so that bundled libraries appear before the
+    /// libraries depending on them. Values are (package, name) tuples.
+    pub fn shared_library_load_order(&self) -> Vec<(String, String)> {
+        let mut libraries: BTreeMap<String, (&str, &str, &Vec<String>)> = BTreeMap::new();
+
+        for (package, entries) in &self.shared_library_dependencies {
+            for (name, depends) in entries {
+                libraries.insert(shared_library_key(name), (package, name, depends));
+            }
+        }
+
+        let mut order = Vec::new();
+        let mut visited = BTreeSet::new();
+
+        fn visit(
+            key: &str,
+            libraries: &BTreeMap<String, (&str, &str, &Vec<String>)>,
+            visited: &mut BTreeSet<String>,
+            order: &mut Vec<(String, String)>,
+        ) {
+            if !visited.insert(key.to_string()) {
+                return;
+            }
+
+            if let Some((package, name, depends)) = libraries.get(key) {
+                for depend in depends.iter() {
+                    let depend = shared_library_key(depend);
+
+                    if libraries.contains_key(&depend) {
+                        visit(&depend, libraries, visited, order);
+                    }
+                }
+
+                order.push((package.to_string(), name.to_string()));
+            }
+        }
+
+        for key in libraries.keys() {
+            visit(key, &libraries, &mut visited, &mut order);
+        }
+
+        order
+    }
+
+    /// Obtain shared library dependencies not provided by this collection.
+    ///
+    /// Libraries provided by the operating system are ignored. Returns a
+    /// mapping of library name to (package, name) tuples of resources
+    /// requiring it.
+    pub fn missing_shared_libraries(&self) -> BTreeMap<String, Vec<(String, String)>> {
+        let mut bundled = BTreeSet::new();
+
+        for entries in self.shared_library_dependencies.values() {
+            for name in entries.keys() {
+                bundled.insert(shared_library_key(name));
+            }
+        }
+
+        let mut missing: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+
+        for (package, entries) in &self.shared_library_dependencies {
+            for (name, depends) in entries {
+                for depend in depends {
+                    if bundled.contains(&shared_library_key(depend)) || is_system_library(depend) {
+                        continue;
+                    }
+
+                    missing
+                        .entry(depend.clone())
+                        .or_insert_with(Vec::new)
+                        .push((package.clone(), name.clone()));
+                }
+            }
         }
+
+        missing
     }
 
     pub fn package_names(&self) -> BTreeSet<String> {
@@ -451,6 +689,12 @@ pub struct PythonResources {
 
     /// Path where to write license files.
     pub license_files_path: Option<String>,
+
+    /// Settings for writing a third-party notices file.
+    pub third_party_notices: Option<PackagingThirdPartyNotices>,
+
+    /// Python packages providing resources, with their licenses.
+    pub licensed_components: LicensedComponents,
 }
 
 fn read_resource_names_file(path: &Path) -> Result<BTreeSet<String>, IOError> {
@@ -471,8 +715,25 @@ fn read_resource_names_file(path: &Path) -> Result<BTreeSet<String>, IOError> {
     Ok(res)
 }
 
-fn bytecode_compiler(dist: &PythonDistributionInfo) -> BytecodeCompiler {
-    BytecodeCompiler::new(&dist.python_exe)
+/// Maximum size of the bytecode cache in the build directory, in bytes.
+///
+/// Entries not used by the current build are removed beyond this size.
+const BYTECODE_CACHE_MAX_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Obtain a bytecode compiler caching its results in the build directory.
+fn bytecode_compiler(config: &Config, dist: &PythonDistributionInfo) -> CachingBytecodeCompiler {
+    let cache_dir = config.build_config.build_path.join("bytecode-cache");
+
+    CachingBytecodeCompiler::new(&dist.python_exe, &cache_dir)
+}
+
+/// Bound the size of the bytecode cache after compiling.
+fn prune_bytecode_cache(logger: &slog::Logger, compiler: &CachingBytecodeCompiler) {
+    match compiler.prune(BYTECODE_CACHE_MAX_SIZE) {
+        Ok(0) => {}
+        Ok(count) => info!(logger, "removed {} stale bytecode cache entries", count),
+        Err(e) => warn!(logger, "error pruning bytecode cache: {}", e),
+    }
 }
 
 fn filter_btreemap<V>(logger: &slog::Logger, m: &mut BTreeMap<String, V>, f: &BTreeSet<String>) {
@@ -486,6 +747,72 @@ fn filter_btreemap<V>(logger: &slog::Logger, m: &mut BTreeMap<String, V>, f: &BT
     }
 }
 
+/// Remove entries whose key matches a filter.
+fn exclude_btreemap<V>(
+    logger: &slog::Logger,
+    m: &mut BTreeMap<String, V>,
+    filter: &mut ResourceFilter,
+    resource_type: &str,
+) {
+    let keys: Vec<String> = m.keys().cloned().collect();
+
+    for key in keys {
+        if filter.matches(&key, resource_type) {
+            info!(logger, "removing {} {}", resource_type, key);
+            m.remove(&key);
+        }
+    }
+}
+
+/// Remove resources matching a filter.
+///
+/// Resources are matched by their package and name, joined with a ``.``.
+fn exclude_resources(
+    logger: &slog::Logger,
+    m: &mut BTreeMap<String, BTreeMap<String, Vec<u8>>>,
+    filter: &mut ResourceFilter,
+) {
+    for (package, resources) in m.iter_mut() {
+        let names: Vec<String> = resources.keys().cloned().collect();
+
+        for name in names {
+            if filter.matches(&format!("{}.{}", package, name), "resource") {
+                info!(logger, "removing resource {} from {}", name, package);
+                resources.remove(&name);
+            }
+        }
+    }
+
+    let empty: Vec<String> = m
+        .iter()
+        .filter(|(_, resources)| resources.is_empty())
+        .map(|(package, _)| package.clone())
+        .collect();
+
+    for package in empty {
+        m.remove(&package);
+    }
+}
+
+/// Remove module sources matched by a strip-source rule.
+///
+/// Sources matched by `excludes` are kept.
+fn strip_module_sources(
+    logger: &slog::Logger,
+    sources: &mut BTreeMap<String, Vec<u8>>,
+    modules: &mut ResourceFilter,
+    excludes: &mut ResourceFilter,
+) {
+    let names: Vec<String> = sources.keys().cloned().collect();
+
+    for name in names {
+        if !excludes.matches(&name, "source") && modules.matches(&name, "source") {
+            info!(logger, "stripping source of {}", name);
+            sources.remove(&name);
+        }
+    }
+}
+
 fn packages_from_module_names<I>(names: I) -> BTreeSet<String>
 where
     I: Iterator<Item = String>,
@@ -689,22 +1016,82 @@ fn resolve_stdlib_extension_variant(
     res
 }
 
+fn resolve_static_extension_module(
+    rule: &PackagingStaticExtensionModule,
+) -> Vec<PythonResourceAction> {
+    let init_name = rule.name.rsplit('.').next().unwrap();
+
+    let links = rule
+        .libraries
+        .iter()
+        .map(|name| LibraryDepends {
+            name: name.clone(),
+            static_path: None,
+            dynamic_path: None,
+            framework: false,
+            system: true,
+        })
+        .collect();
+
+    let module = ExtensionModule {
+        module: rule.name.clone(),
+        init_fn: Some(format!("PyInit_{}", init_name)),
+        builtin_default: false,
+        disableable: true,
+        object_paths: Vec::new(),
+        static_library: None,
+        links,
+        required: false,
+        variant: "source".to_string(),
+        licenses: None,
+        license_paths: None,
+        license_public_domain: None,
+        build: Some(ExtensionModuleBuild {
+            sources: rule.sources.iter().map(PathBuf::from).collect(),
+            include_dirs: rule.include_dirs.iter().map(PathBuf::from).collect(),
+            defines: rule.defines.clone(),
+        }),
+    };
+
+    vec![PythonResourceAction {
+        action: ResourceAction::Add,
+        location: ResourceLocation::Embedded,
+        resource: PythonResource::ExtensionModule {
+            name: rule.name.clone(),
+            module,
+        },
+    }]
+}
+
 fn resolve_stdlib(
     logger: &slog::Logger,
     dist: &PythonDistributionInfo,
     rule: &PackagingStdlib,
 ) -> Vec<PythonResourceAction> {
     let mut res = Vec::new();
+    let mut sizes = StdlibSizes::default();
 
     let location = ResourceLocation::new(&rule.install_location);
 
     for (name, fs_path) in &dist.py_modules {
+        let source = fs::read(fs_path).expect("error reading source file");
+
         if is_stdlib_test_package(&name) && rule.exclude_test_modules {
             info!(logger, "skipping test stdlib module: {}", name);
+            sizes.record(name, source.len(), false);
             continue;
         }
 
-        let source = fs::read(fs_path).expect("error reading source file");
+        if is_stdlib_profile_excluded(rule.profile, &name) {
+            info!(
+                logger,
+                "skipping stdlib module excluded by profile: {}", name
+            );
+            sizes.record(name, source.len(), false);
+            continue;
+        }
+
+        sizes.record(name, source.len(), true);
 
         if rule.include_source {
             res.push(PythonResourceAction {
@@ -730,17 +1117,31 @@ fn resolve_stdlib(
 
     if rule.include_resources {
         for (package, resources) in &dist.resources {
-            if is_stdlib_test_package(package) && rule.exclude_test_modules {
+            let excluded = if is_stdlib_test_package(package) && rule.exclude_test_modules {
                 info!(
                     logger,
                     "skipping resources associated with test package: {}", package
                 );
-                continue;
-            }
+                true
+            } else if is_stdlib_profile_excluded(rule.profile, package) {
+                info!(
+                    logger,
+                    "skipping resources associated with package excluded by profile: {}", package
+                );
+                true
+            } else {
+                false
+            };
 
             for (name, fs_path) in resources {
                 let data = fs::read(fs_path).expect("error reading resource file");
 
+                sizes.record(&format!("{}.{}", package, name), data.len(), !excluded);
+
+                if excluded {
+                    continue;
+                }
+
                 res.push(PythonResourceAction {
                     action: ResourceAction::Add,
                     location: location.clone(),
@@ -754,97 +1155,745 @@ fn resolve_stdlib(
         }
     }
 
+    // Extension modules are added by the stdlib-extensions-* rules, which
+    // typically precede this rule.
+    for (name, variants) in &dist.extension_modules {
+        if is_stdlib_profile_excluded(rule.profile, name) {
+            info!(
+                logger,
+                "excluding stdlib extension module excluded by profile: {}", name
+            );
+
+            res.push(PythonResourceAction {
+                action: ResourceAction::Remove,
+                location: ResourceLocation::Embedded,
+                resource: PythonResource::ExtensionModule {
+                    name: name.clone(),
+                    module: variants[0].clone(),
+                },
+            });
+        }
+    }
+
+    sizes.log_report(logger);
+
     res
 }
 
-fn resolve_virtualenv(
+/// Size of the object files and static library providing an extension module.
+fn extension_module_size(module: &ExtensionModule) -> usize {
+    module
+        .object_paths
+        .iter()
+        .chain(module.static_library.iter())
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|metadata| metadata.len() as usize)
+        .sum()
+}
+
+/// Log how much each standard library extension module contributes.
+///
+/// Sizes are of object files and static libraries of the packaged variant,
+/// or of the default variant if the extension module isn't packaged.
+fn log_stdlib_extension_sizes(
+    logger: &slog::Logger,
     dist: &PythonDistributionInfo,
-    rule: &PackagingVirtualenv,
-) -> Vec<PythonResourceAction> {
-    let mut res = Vec::new();
+    embedded: &BTreeMap<String, ExtensionModule>,
+) {
+    let mut sizes = dist
+        .extension_modules
+        .iter()
+        .map(|(name, variants)| match embedded.get(name) {
+            Some(module) => (name, extension_module_size(module), true),
+            None => (name, extension_module_size(&variants[0]), false),
+        })
+        .collect::<Vec<_>>();
 
-    let location = ResourceLocation::new(&rule.install_location);
+    sizes.sort_by(|a, b| b.1.cmp(&a.1));
 
-    let mut packages_path = PathBuf::from(&rule.path);
+    let included: usize = sizes.iter().filter(|s| s.2).map(|s| s.1).sum();
+    let excluded: usize = sizes.iter().filter(|s| !s.2).map(|s| s.1).sum();
 
-    if dist.os == "windows" {
-        packages_path.push("Lib");
-    } else {
-        packages_path.push("lib");
+    info!(
+        logger,
+        "stdlib extension modules: {} KiB packaged, {} KiB excluded",
+        included / 1024,
+        excluded / 1024
+    );
+
+    for (name, size, packaged) in sizes {
+        info!(
+            logger,
+            "stdlib extension module {}: {} KiB {}",
+            name,
+            size / 1024,
+            if packaged { "packaged" } else { "excluded" }
+        );
     }
+}
 
-    packages_path.push("python".to_owned() + &dist.version[0..3]);
-    packages_path.push("site-packages");
+/// Source and resource sizes of a standard library package.
+#[derive(Default)]
+struct StdlibPackageSize {
+    is_package: bool,
+    included: usize,
+    excluded: usize,
+}
 
-    for resource in find_python_resources(&packages_path) {
-        let mut relevant = true;
+/// Tracks how much each standard library package contributes.
+///
+/// Sizes are of source and resource files. Packaged bytecode is typically
+/// proportional to source size.
+#[derive(Default)]
+struct StdlibSizes {
+    entries: BTreeMap<String, StdlibPackageSize>,
+}
 
-        for exclude in &rule.excludes {
-            let prefix = exclude.clone() + ".";
+impl StdlibSizes {
+    fn record(&mut self, name: &str, size: usize, included: bool) {
+        let top_level = name.split('.').next().unwrap_or(name);
 
-            if &resource.full_name == exclude || resource.full_name.starts_with(&prefix) {
-                relevant = false;
-            }
-        }
+        let entry = self
+            .entries
+            .entry(top_level.to_string())
+            .or_insert_with(StdlibPackageSize::default);
 
-        if !relevant {
-            continue;
-        }
+        entry.is_package |= name.contains('.');
 
-        match resource.flavor {
-            PythonResourceType::Source => {
-                let source = fs::read(resource.path).expect("error reading source file");
+        if included {
+            entry.included += size;
+        } else {
+            entry.excluded += size;
+        }
+    }
 
-                if rule.include_source {
-                    res.push(PythonResourceAction {
-                        action: ResourceAction::Add,
-                        location: location.clone(),
-                        resource: PythonResource::ModuleSource {
-                            name: resource.full_name.clone(),
-                            source: source.clone(),
-                        },
-                    });
-                }
+    fn log_report(&self, logger: &slog::Logger) {
+        // Top-level modules that aren't packages are too numerous to report
+        // individually, so they are aggregated.
+        let mut modules = StdlibPackageSize::default();
+        let mut packages = Vec::new();
 
-                res.push(PythonResourceAction {
-                    action: ResourceAction::Add,
-                    location: location.clone(),
-                    resource: PythonResource::ModuleBytecode {
-                        name: resource.full_name.clone(),
-                        source,
-                        optimize_level: rule.optimize_level as i32,
-                    },
-                });
+        for (name, size) in &self.entries {
+            if size.is_package {
+                packages.push((name, size));
+            } else {
+                modules.included += size.included;
+                modules.excluded += size.excluded;
             }
+        }
 
-            PythonResourceType::Resource => {
-                let data = fs::read(resource.path).expect("error reading resource file");
+        packages.sort_by(|(_, a), (_, b)| b.included.cmp(&a.included));
 
-                res.push(PythonResourceAction {
-                    action: ResourceAction::Add,
-                    location: location.clone(),
-                    resource: PythonResource::Resource {
-                        package: resource.package.clone(),
-                        name: resource.stem.clone(),
-                        data,
-                    },
-                });
-            }
+        let included: usize = self.entries.values().map(|size| size.included).sum();
+        let excluded: usize = self.entries.values().map(|size| size.excluded).sum();
 
-            _ => {}
+        info!(
+            logger,
+            "stdlib source and resources: {} KiB packaged, {} KiB excluded",
+            included / 1024,
+            excluded / 1024
+        );
+
+        for (name, size) in packages {
+            info!(
+                logger,
+                "stdlib package {}: {} KiB packaged, {} KiB excluded",
+                name,
+                size.included / 1024,
+                size.excluded / 1024
+            );
         }
-    }
 
-    res
+        info!(
+            logger,
+            "stdlib top-level modules: {} KiB packaged, {} KiB excluded",
+            modules.included / 1024,
+            modules.excluded / 1024
+        );
+    }
 }
 
-fn resolve_package_root(rule: &PackagingPackageRoot) -> Vec<PythonResourceAction> {
-    let mut res = Vec::new();
+/// Log the distributions installed in a directory and their licenses.
+///
+/// Returns the parsed distributions.
+fn log_installed_distributions(logger: &slog::Logger, path: &Path) -> Vec<DistInfo> {
+    let dist_infos = find_dist_infos(path);
 
-    let location = ResourceLocation::new(&rule.install_location);
-    let path = PathBuf::from(&rule.path);
+    warn_invalid_dist_infos(logger, &dist_infos.invalid);
+
+    for dist_info in &dist_infos.distributions {
+        let metadata = &dist_info.metadata;
+
+        info!(
+            logger,
+            "found distribution {} {} (license: {})",
+            metadata.name().unwrap_or("<unknown>"),
+            metadata.version().unwrap_or("<unknown>"),
+            metadata_license(metadata).description()
+        );
+    }
+
+    dist_infos.distributions
+}
+
+/// Warn about .dist-info directories that couldn't be parsed.
+fn warn_invalid_dist_infos(logger: &slog::Logger, invalid: &[InvalidDistInfo]) {
+    for dist_info in invalid {
+        warn!(
+            logger,
+            "ignoring unreadable package metadata in {}: {}",
+            dist_info.path.display(),
+            dist_info.error
+        );
+    }
+}
+
+/// Name of the top-level package a module or resource belongs to.
+fn resource_top_level_name(resource: &PythonResource) -> Option<&str> {
+    let name = match resource {
+        PythonResource::ModuleSource { name, .. }
+        | PythonResource::ModuleBytecode { name, .. }
+        | PythonResource::ExtensionModule { name, .. } => name,
+        PythonResource::Resource { package, .. } => package,
+        _ => return None,
+    };
+
+    name.split('.').next()
+}
+
+/// Obtain actions recording the licenses of packaged distributions.
+///
+/// Modules and resources are attributed to the distributions installing
+/// their top-level package. Only distributions with at least one packaged
+/// resource are recorded. Top-level packages no distribution installed are
+/// recorded with an unknown license, so license policies can catch them.
+/// Scripts and data files aren't attributed.
+fn distribution_license_actions(
+    logger: &slog::Logger,
+    site_packages: &Path,
+    dist_infos: &[DistInfo],
+    location: &ResourceLocation,
+    actions: &[PythonResourceAction],
+) -> Vec<PythonResourceAction> {
+    let mut top_levels: Vec<BTreeSet<String>> =
+        dist_infos.iter().map(DistInfo::top_level_names).collect();
+
+    // The RECORD of an editable install only lists its shim.
+    for install in find_editable_installs(site_packages).installs {
+        let shim = install
+            .shim
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("");
+
+        for (dist_info, names) in dist_infos.iter().zip(top_levels.iter_mut()) {
+            if dist_info.record.iter().flatten().any(|e| e.path == shim) {
+                names.extend(install.packages.iter().cloned());
+            }
+        }
+    }
+
+    let mut used = BTreeSet::new();
+    let mut unknown = BTreeSet::new();
+
+    for action in actions {
+        if let ResourceAction::Remove = action.action {
+            continue;
+        }
+
+        let name = match resource_top_level_name(&action.resource) {
+            Some(name) => name,
+            None => continue,
+        };
+
+        // Namespace packages can be installed by several distributions.
+        let owners = top_levels
+            .iter()
+            .enumerate()
+            .filter(|(_, names)| names.contains(name))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        if owners.is_empty() {
+            unknown.insert(name.to_string());
+        }
+
+        used.extend(owners);
+    }
+
+    let mut res = Vec::new();
+
+    for (i, dist_info) in dist_infos.iter().enumerate() {
+        if !used.contains(&i) {
+            info!(
+                logger,
+                "not recording license of {}; none of its resources are packaged",
+                dist_info.path.display()
+            );
+            continue;
+        }
+
+        match dist_info.licensed_component() {
+            Ok(component) => res.push(PythonResourceAction {
+                action: ResourceAction::Add,
+                location: location.clone(),
+                resource: PythonResource::License { component },
+            }),
+            Err(msg) => warn!(
+                logger,
+                "unable to resolve license of {}: {}",
+                dist_info.path.display(),
+                msg
+            ),
+        }
+    }
+
+    for name in unknown {
+        warn!(
+            logger,
+            "no distribution metadata found for package {}; its license is unknown", name
+        );
+
+        res.push(PythonResourceAction {
+            action: ResourceAction::Add,
+            location: location.clone(),
+            resource: PythonResource::License {
+                component: LicensedComponent::new(
+                    ComponentFlavor::PythonPackage,
+                    &name,
+                    LicenseFlavor::Unknown,
+                ),
+            },
+        });
+    }
+
+    res
+}
+
+/// Files installed by Python packaging outside of package directories.
+#[derive(Debug, Default)]
+struct InstalledFiles {
+    /// Scripts, keyed by path relative to the scripts directory.
+    scripts: BTreeMap<String, PathBuf>,
+
+    /// Data files, keyed by path relative to the installation prefix.
+    data_files: BTreeMap<String, PathBuf>,
+}
+
+impl InstalledFiles {
+    fn contains_path(&self, path: &Path) -> bool {
+        self.scripts.values().any(|p| p == path) || self.data_files.values().any(|p| p == path)
+    }
+}
+
+/// Directories scripts are installed into, relative to an installation prefix.
+const SCRIPTS_DIRS: &[&str] = &["bin", "Scripts"];
+
+/// Resolve the installed files for a path relative to an installation prefix.
+///
+/// Paths that are absolute or refer to a parent directory are rejected, as
+/// they could reference files outside of the installation prefix.
+fn add_installed_file(
+    files: &mut InstalledFiles,
+    prefix: &Path,
+    rel_path: &str,
+) -> Result<(), String> {
+    let escapes = Path::new(rel_path).components().any(|c| match c {
+        Component::Normal(_) | Component::CurDir => false,
+        Component::ParentDir | Component::RootDir | Component::Prefix(_) => true,
+    });
+
+    if escapes {
+        return Err(format!(
+            "installed file {} is outside of the installation prefix",
+            rel_path
+        ));
+    }
+
+    let path = prefix.join(rel_path);
+
+    if !path.is_file() {
+        return Ok(());
+    }
+
+    let mut parts = rel_path.splitn(2, '/');
+    let first = parts.next().unwrap_or("");
+
+    match parts.next() {
+        Some(rest) if SCRIPTS_DIRS.contains(&first) => {
+            files.scripts.insert(rest.to_string(), path);
+        }
+        _ => {
+            files.data_files.insert(rel_path.to_string(), path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Find files installed outside of package directories by ``pip install --target``.
+///
+/// pip installs data files and scripts relative to the target directory but
+/// records them in ``RECORD`` files relative to a ``lib/python``
+/// directory within it. e.g. ``../../bin/script``.
+fn find_pip_target_installed_files(
+    path: &Path,
+    dist_infos: &[DistInfo],
+) -> Result<InstalledFiles, String> {
+    const PREFIX: &str = "../../";
+
+    let mut res = InstalledFiles::default();
+
+    for dist_info in dist_infos {
+        for entry in dist_info.record.iter().flatten() {
+            let entry_path = entry.path.replace('\\', "/");
+
+            if !entry_path.starts_with(PREFIX) {
+                continue;
+            }
+
+            add_installed_file(&mut res, path, &entry_path[PREFIX.len()..]).or_else(|e| {
+                Err(format!(
+                    "invalid RECORD in {}: {}",
+                    dist_info.path.display(),
+                    e
+                ))
+            })?;
+        }
+    }
+
+    Ok(res)
+}
+
+/// Find files installed outside of package directories in an installation prefix.
+///
+/// This is used for ``setup.py install --prefix``, which installs packages
+/// into a ``lib`` directory under the prefix. Header files are ignored.
+fn find_prefix_installed_files(prefix: &Path) -> Result<InstalledFiles, String> {
+    let mut res = InstalledFiles::default();
+
+    for entry in walk_tree_files(prefix) {
+        let rel_path = match entry.path().strip_prefix(prefix) {
+            Ok(p) => p.to_string_lossy().replace('\\', "/"),
+            Err(_) => continue,
+        };
+
+        let first = rel_path.splitn(2, '/').next().unwrap_or("");
+
+        if first == "lib" || first == "Lib" || first == "include" || first == "Include" {
+            continue;
+        }
+
+        add_installed_file(&mut res, prefix, &rel_path)?;
+    }
+
+    Ok(res)
+}
+
+/// Obtain the Python interpreter a script's shebang line references.
+///
+/// Scripts generated by Python packaging, such as console scripts, run the
+/// interpreter they were installed with. That interpreter doesn't exist
+/// where the application is installed.
+fn python_script_interpreter(data: &[u8]) -> Option<String> {
+    if !data.starts_with(b"#!") {
+        return None;
+    }
+
+    let line = data[2..].split(|c| *c == b'\n').next().unwrap_or(&[]);
+    let line = String::from_utf8_lossy(line).trim().to_string();
+
+    if line.contains("python") {
+        Some(line)
+    } else {
+        None
+    }
+}
+
+/// Produce actions to install data files and scripts.
+///
+/// Files for which no install location is defined are not packaged and a
+/// warning is logged.
+fn resolve_installed_files(
+    logger: &slog::Logger,
+    files: &InstalledFiles,
+    data_files_location: &Option<InstallLocation>,
+    scripts_location: &Option<InstallLocation>,
+) -> Vec<PythonResourceAction> {
+    let mut res = Vec::new();
+
+    for (kind, entries, location, key, executable) in &[
+        (
+            "data file",
+            &files.data_files,
+            data_files_location,
+            "data_files_location",
+            false,
+        ),
+        (
+            "script",
+            &files.scripts,
+            scripts_location,
+            "scripts_location",
+            true,
+        ),
+    ] {
+        for (rel_path, path) in entries.iter() {
+            match location {
+                Some(location) => {
+                    let data = fs::read(path).expect("error reading installed file");
+
+                    if *executable {
+                        if let Some(interpreter) = python_script_interpreter(&data) {
+                            warn!(
+                                logger,
+                                "not packaging script {}; it runs the build-time Python interpreter {}",
+                                rel_path,
+                                interpreter
+                            );
+                            continue;
+                        }
+                    }
+
+                    res.push(PythonResourceAction {
+                        action: ResourceAction::Add,
+                        location: ResourceLocation::new(location),
+                        resource: PythonResource::File {
+                            path: rel_path.clone(),
+                            data,
+                            mode: installed_file_mode(path, *executable),
+                        },
+                    });
+                }
+                None => {
+                    warn!(
+                        logger,
+                        "not packaging {} {}; define {} to package it", kind, rel_path, key
+                    );
+                }
+            }
+        }
+    }
+
+    res
+}
+
+/// Produce actions to package a resource found on the filesystem.
+fn filesystem_resource_actions(
+    resource: &FsPythonResource,
+    location: &ResourceLocation,
+    optimize_level: i64,
+    include_source: bool,
+) -> Vec<PythonResourceAction> {
+    let mut res = Vec::new();
+
+    match resource.flavor {
+        PythonResourceType::Source => {
+            let source = fs::read(&resource.path).expect("error reading source file");
+
+            if include_source {
+                res.push(PythonResourceAction {
+                    action: ResourceAction::Add,
+                    location: location.clone(),
+                    resource: PythonResource::ModuleSource {
+                        name: resource.full_name.clone(),
+                        source: source.clone(),
+                    },
+                });
+            }
+
+            res.push(PythonResourceAction {
+                action: ResourceAction::Add,
+                location: location.clone(),
+                resource: PythonResource::ModuleBytecode {
+                    name: resource.full_name.clone(),
+                    source,
+                    optimize_level: optimize_level as i32,
+                },
+            });
+        }
+
+        PythonResourceType::Resource => {
+            let data = fs::read(&resource.path).expect("error reading resource file");
+
+            res.push(PythonResourceAction {
+                action: ResourceAction::Add,
+                location: location.clone(),
+                resource: PythonResource::Resource {
+                    package: resource.package.clone(),
+                    name: resource.stem.clone(),
+                    data,
+                },
+            });
+        }
+
+        _ => {}
+    }
+
+    res
+}
+
+/// Whether a resource is part of an editable install shim.
+fn is_editable_shim_resource(resource: &FsPythonResource) -> bool {
+    resource
+        .path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(is_editable_shim)
+        .unwrap_or(false)
+}
+
+/// Produce actions to package the packages of editable installs.
+///
+/// Editable installs only have a shim in site-packages. The packages they
+/// point to are packaged instead. Shims that can't be resolved are ignored
+/// with a warning.
+fn resolve_editable_installs(
+    logger: &slog::Logger,
+    site_packages: &Path,
+    location: &ResourceLocation,
+    optimize_level: i64,
+    include_source: bool,
+    mut excludes: Option<&mut ResourceFilter>,
+) -> Vec<PythonResourceAction> {
+    let mut res = Vec::new();
+
+    let editable = find_editable_installs(site_packages);
+
+    for invalid in &editable.invalid {
+        warn!(
+            logger,
+            "ignoring editable install {}: {}",
+            invalid.shim.display(),
+            invalid.error
+        );
+    }
+
+    for install in editable.installs {
+        info!(
+            logger,
+            "packaging editable install of {} from {}",
+            install.packages.join(", "),
+            install.root.display()
+        );
+
+        let package_paths = install.package_paths();
+
+        if package_paths.is_empty() {
+            warn!(
+                logger,
+                "editable install {} provides no packages in {}",
+                install.shim.display(),
+                install.root.display()
+            );
+        }
+
+        for package_path in package_paths {
+            for resource in find_python_resources_in(&install.root, &package_path) {
+                if let Some(excludes) = excludes.as_mut() {
+                    if excludes.matches(&resource.full_name, resource_type_name(&resource.flavor)) {
+                        continue;
+                    }
+                }
+
+                res.extend(filesystem_resource_actions(
+                    &resource,
+                    location,
+                    optimize_level,
+                    include_source,
+                ));
+            }
+        }
+    }
+
+    res
+}
+
+fn resolve_virtualenv(
+    logger: &slog::Logger,
+    dist: &PythonDistributionInfo,
+    rule: &PackagingVirtualenv,
+) -> Vec<PythonResourceAction> {
+    let mut res = Vec::new();
+
+    let location = ResourceLocation::new(&rule.install_location);
+
+    let mut packages_path = PathBuf::from(&rule.path);
+
+    if dist.os == "windows" {
+        packages_path.push("Lib");
+    } else {
+        packages_path.push("lib");
+    }
+
+    packages_path.push("python".to_owned() + &dist.version[0..3]);
+    packages_path.push("site-packages");
+
+    let dist_infos = log_installed_distributions(logger, &packages_path);
+
+    let mut excludes = ResourceFilter::new(&rule.excludes, &[])
+        .unwrap_or_else(|e| panic!("invalid excludes: {}", e));
+
+    for resource in find_python_resources(&packages_path) {
+        if is_editable_shim_resource(&resource)
+            || excludes.matches(&resource.full_name, resource_type_name(&resource.flavor))
+        {
+            continue;
+        }
+
+        res.extend(filesystem_resource_actions(
+            &resource,
+            &location,
+            rule.optimize_level,
+            rule.include_source,
+        ));
+    }
+
+    res.extend(resolve_editable_installs(
+        logger,
+        &packages_path,
+        &location,
+        rule.optimize_level,
+        rule.include_source,
+        Some(&mut excludes),
+    ));
+
+    excludes.log_report(logger, "virtualenv excludes", "excluded");
+
+    let licenses =
+        distribution_license_actions(logger, &packages_path, &dist_infos, &location, &res);
+    res.extend(licenses);
+    res
+}
+
+fn resolve_package_root(
+    logger: &slog::Logger,
+    rule: &PackagingPackageRoot,
+) -> Vec<PythonResourceAction> {
+    let mut res = Vec::new();
+
+    let location = ResourceLocation::new(&rule.install_location);
+    let path = PathBuf::from(&rule.path);
+
+    let mut excludes = ResourceFilter::new(&rule.excludes, &[])
+        .unwrap_or_else(|e| panic!("invalid excludes: {}", e));
+
+    let git_visible = if rule.respect_gitignore {
+        Some(find_git_visible_files(&path).unwrap_or_else(|e| panic!("{}", e)))
+    } else {
+        None
+    };
+
+    let mut git_ignored_count = 0;
 
     for resource in find_python_resources(&path) {
+        if let Some(visible) = &git_visible {
+            if !visible.contains(&resource.path) {
+                git_ignored_count += 1;
+                continue;
+            }
+        }
+
         let mut relevant = false;
 
         for package in &rule.packages {
@@ -855,15 +1904,8 @@ fn resolve_package_root(rule: &PackagingPackageRoot) -> Vec<PythonResourceAction
             }
         }
 
-        for exclude in &rule.excludes {
-            let prefix = exclude.clone() + ".";
-
-            if &resource.full_name == exclude || resource.full_name.starts_with(&prefix) {
-                relevant = false;
-            }
-        }
-
-        if !relevant {
+        if !relevant || excludes.matches(&resource.full_name, resource_type_name(&resource.flavor))
+        {
             continue;
         }
 
@@ -911,6 +1953,17 @@ fn resolve_package_root(rule: &PackagingPackageRoot) -> Vec<PythonResourceAction
         }
     }
 
+    if git_ignored_count > 0 {
+        info!(
+            logger,
+            "package-root skipped {} files ignored by git in {}",
+            git_ignored_count,
+            path.display()
+        );
+    }
+
+    excludes.log_report(logger, "package-root excludes", "excluded");
+
     res
 }
 
@@ -959,18 +2012,18 @@ fn resolve_pip_install_simple(
         panic!("error running pip");
     }
 
-    for resource in find_python_resources(&temp_dir_path) {
-        let mut relevant = true;
-
-        for exclude in &rule.excludes {
-            let prefix = exclude.clone() + ".";
+    let dist_infos = log_installed_distributions(logger, &temp_dir_path);
+    let installed_files = find_pip_target_installed_files(&temp_dir_path, &dist_infos)
+        .unwrap_or_else(|e| panic!("{}", e));
 
-            if &resource.full_name == exclude || resource.full_name.starts_with(&prefix) {
-                relevant = false;
-            }
-        }
+    let mut excludes = ResourceFilter::new(&rule.excludes, &[])
+        .unwrap_or_else(|e| panic!("invalid excludes: {}", e));
 
-        if !relevant {
+    for resource in find_python_resources(&temp_dir_path) {
+        if installed_files.contains_path(&resource.path)
+            || is_editable_shim_resource(&resource)
+            || excludes.matches(&resource.full_name, resource_type_name(&resource.flavor))
+        {
             continue;
         }
 
@@ -1018,6 +2071,27 @@ fn resolve_pip_install_simple(
         }
     }
 
+    res.extend(resolve_editable_installs(
+        logger,
+        &temp_dir_path,
+        &location,
+        rule.optimize_level,
+        rule.include_source,
+        Some(&mut excludes),
+    ));
+
+    excludes.log_report(logger, "pip-install-simple excludes", "excluded");
+
+    res.extend(resolve_installed_files(
+        logger,
+        &installed_files,
+        &rule.data_files_location,
+        &rule.scripts_location,
+    ));
+
+    let licenses =
+        distribution_license_actions(logger, &temp_dir_path, &dist_infos, &location, &res);
+    res.extend(licenses);
     res
 }
 
@@ -1070,7 +2144,15 @@ fn resolve_pip_requirements_file(
         panic!("error running pip");
     }
 
+    let dist_infos = log_installed_distributions(logger, &temp_dir_path);
+    let installed_files = find_pip_target_installed_files(&temp_dir_path, &dist_infos)
+        .unwrap_or_else(|e| panic!("{}", e));
+
     for resource in find_python_resources(&temp_dir_path) {
+        if installed_files.contains_path(&resource.path) || is_editable_shim_resource(&resource) {
+            continue;
+        }
+
         match resource.flavor {
             PythonResourceType::Source => {
                 let source = fs::read(resource.path).expect("error reading source file");
@@ -1115,6 +2197,25 @@ fn resolve_pip_requirements_file(
         }
     }
 
+    res.extend(resolve_editable_installs(
+        logger,
+        &temp_dir_path,
+        &location,
+        rule.optimize_level,
+        rule.include_source,
+        None,
+    ));
+
+    res.extend(resolve_installed_files(
+        logger,
+        &installed_files,
+        &rule.data_files_location,
+        &rule.scripts_location,
+    ));
+
+    let licenses =
+        distribution_license_actions(logger, &temp_dir_path, &dist_infos, &location, &res);
+    res.extend(licenses);
     res
 }
 
@@ -1160,21 +2261,149 @@ fn resolve_setup_py_install(
         panic!("error running setup.py");
     }
 
-    let mut packages_path = temp_dir_path.to_path_buf();
+    let mut packages_path = temp_dir_path.to_path_buf();
+
+    if dist.os == "windows" {
+        packages_path.push("Lib");
+    } else {
+        packages_path.push("lib");
+    }
+
+    packages_path.push("python".to_owned() + &dist.version[0..3]);
+    packages_path.push("site-packages");
+
+    let dist_infos = log_installed_distributions(logger, &packages_path);
+    let installed_files =
+        find_prefix_installed_files(&temp_dir_path).unwrap_or_else(|e| panic!("{}", e));
+
+    for resource in find_python_resources(&packages_path) {
+        if is_editable_shim_resource(&resource) {
+            continue;
+        }
+
+        match resource.flavor {
+            PythonResourceType::Source => {
+                let source = fs::read(resource.path).expect("error reading source");
+
+                if rule.include_source {
+                    res.push(PythonResourceAction {
+                        action: ResourceAction::Add,
+                        location: location.clone(),
+                        resource: PythonResource::ModuleSource {
+                            name: resource.full_name.clone(),
+                            source: source.clone(),
+                        },
+                    });
+                }
+
+                res.push(PythonResourceAction {
+                    action: ResourceAction::Add,
+                    location: location.clone(),
+                    resource: PythonResource::ModuleBytecode {
+                        name: resource.full_name.clone(),
+                        source,
+                        optimize_level: rule.optimize_level as i32,
+                    },
+                });
+            }
+
+            PythonResourceType::Resource => {
+                let data = fs::read(resource.path).expect("error reading resource file");
+
+                res.push(PythonResourceAction {
+                    action: ResourceAction::Add,
+                    location: location.clone(),
+                    resource: PythonResource::Resource {
+                        package: resource.package.clone(),
+                        name: resource.stem.clone(),
+                        data,
+                    },
+                });
+            }
+
+            _ => {}
+        }
+    }
+
+    res.extend(resolve_editable_installs(
+        logger,
+        &packages_path,
+        &location,
+        rule.optimize_level,
+        rule.include_source,
+        None,
+    ));
+
+    res.extend(resolve_installed_files(
+        logger,
+        &installed_files,
+        &rule.data_files_location,
+        &rule.scripts_location,
+    ));
+
+    let licenses =
+        distribution_license_actions(logger, &packages_path, &dist_infos, &location, &res);
+    res.extend(licenses);
+    res
+}
+
+/// Install a wheel without pip and obtain resources to package from it.
+fn resolve_wheel_install(
+    logger: &slog::Logger,
+    rule: &PackagingWheelInstall,
+    target: &str,
+) -> Vec<PythonResourceAction> {
+    let mut res = Vec::new();
+
+    let location = ResourceLocation::new(&rule.install_location);
+
+    let temp_dir =
+        tempdir::TempDir::new("pyoxidizer-wheel-install").expect("could not create temp directory");
+
+    let temp_dir_path = temp_dir.path();
+    info!(
+        logger,
+        "extracting wheel {} to {}",
+        rule.path,
+        temp_dir_path.display()
+    );
+
+    let install = match extract_wheel(Path::new(&rule.path), &temp_dir_path) {
+        Ok(install) => install,
+        Err(msg) => panic!("error installing wheel {}: {}", rule.path, msg),
+    };
+
+    if let Err(msg) = validate_wheel_target(&install, &temp_dir_path, target) {
+        panic!("error installing wheel {}: {}", rule.path, msg);
+    }
+
+    // There is no place for these files in an application. So ignore them.
+    for (scheme, names) in &[
+        ("scripts", &install.scripts),
+        ("data", &install.data),
+        ("headers", &install.headers),
+    ] {
+        for name in names.iter() {
+            info!(
+                logger,
+                "ignoring {} file in {}: {}", scheme, install.dist_info, name
+            );
+        }
+    }
+
+    let dist_infos = log_installed_distributions(logger, &temp_dir_path);
 
-    if dist.os == "windows" {
-        packages_path.push("Lib");
-    } else {
-        packages_path.push("lib");
-    }
+    let mut excludes = ResourceFilter::new(&rule.excludes, &[])
+        .unwrap_or_else(|e| panic!("invalid excludes: {}", e));
 
-    packages_path.push("python".to_owned() + &dist.version[0..3]);
-    packages_path.push("site-packages");
+    for resource in find_python_resources(&temp_dir_path) {
+        if excludes.matches(&resource.full_name, resource_type_name(&resource.flavor)) {
+            continue;
+        }
 
-    for resource in find_python_resources(&packages_path) {
         match resource.flavor {
             PythonResourceType::Source => {
-                let source = fs::read(resource.path).expect("error reading source");
+                let source = fs::read(resource.path).expect("error reading source file");
 
                 if rule.include_source {
                     res.push(PythonResourceAction {
@@ -1216,6 +2445,11 @@ fn resolve_setup_py_install(
         }
     }
 
+    excludes.log_report(logger, "wheel-install excludes", "excluded");
+
+    let licenses =
+        distribution_license_actions(logger, &temp_dir_path, &dist_infos, &location, &res);
+    res.extend(licenses);
     res
 }
 
@@ -1224,6 +2458,7 @@ fn resolve_python_packaging(
     logger: &slog::Logger,
     package: &PythonPackaging,
     dist: &PythonDistributionInfo,
+    target: &str,
 ) -> Vec<PythonResourceAction> {
     match package {
         PythonPackaging::StdlibExtensionsPolicy(rule) => {
@@ -1242,11 +2477,13 @@ fn resolve_python_packaging(
             resolve_stdlib_extension_variant(dist, rule)
         }
 
+        PythonPackaging::StaticExtensionModule(rule) => resolve_static_extension_module(&rule),
+
         PythonPackaging::Stdlib(rule) => resolve_stdlib(logger, dist, &rule),
 
-        PythonPackaging::Virtualenv(rule) => resolve_virtualenv(dist, &rule),
+        PythonPackaging::Virtualenv(rule) => resolve_virtualenv(logger, dist, &rule),
 
-        PythonPackaging::PackageRoot(rule) => resolve_package_root(&rule),
+        PythonPackaging::PackageRoot(rule) => resolve_package_root(logger, &rule),
 
         PythonPackaging::PipInstallSimple(rule) => resolve_pip_install_simple(logger, dist, &rule),
 
@@ -1256,10 +2493,16 @@ fn resolve_python_packaging(
 
         PythonPackaging::SetupPyInstall(rule) => resolve_setup_py_install(logger, dist, &rule),
 
+        PythonPackaging::WheelInstall(rule) => resolve_wheel_install(logger, &rule, target),
+
         PythonPackaging::WriteLicenseFiles(_) => Vec::new(),
 
-        // This is a no-op because it can only be handled at a higher level.
+        PythonPackaging::ThirdPartyNotices(_) => Vec::new(),
+
+        // These are no-ops because they can only be handled at a higher level.
         PythonPackaging::FilterInclude(_) => Vec::new(),
+        PythonPackaging::StripSource(_) => Vec::new(),
+        PythonPackaging::FilterExclude(_) => Vec::new(),
     }
 }
 
@@ -1277,7 +2520,7 @@ pub fn resolve_python_resources(
 
     let mut embedded_extension_modules: BTreeMap<String, ExtensionModule> = BTreeMap::new();
     let mut embedded_sources: BTreeMap<String, Vec<u8>> = BTreeMap::new();
-    let mut embedded_bytecode_requests: BTreeMap<String, (Vec<u8>, i32)> = BTreeMap::new();
+    let mut embedded_bytecode_requests: BTreeMap<String, BTreeMap<i32, Vec<u8>>> = BTreeMap::new();
     let mut embedded_resources: BTreeMap<String, BTreeMap<String, Vec<u8>>> = BTreeMap::new();
 
     let mut app_relative: BTreeMap<String, AppRelativeResources> = BTreeMap::new();
@@ -1285,11 +2528,21 @@ pub fn resolve_python_resources(
         BTreeMap::new();
 
     let mut read_files: Vec<PathBuf> = Vec::new();
+
+    let mut classifications = ClassificationCache::load(
+        &config
+            .build_config
+            .build_path
+            .join("resource-classification.json"),
+    );
     let mut license_files_path = None;
+    let mut third_party_notices = None;
+    let mut licensed_components = LicensedComponents::default();
 
     for packaging in packages {
         info!(logger, "processing packaging rule: {:?}", packaging);
-        for entry in resolve_python_packaging(logger, packaging, dist) {
+        for entry in resolve_python_packaging(logger, packaging, dist, &config.build_config.target)
+        {
             match (entry.action, entry.location, entry.resource) {
                 (
                     ResourceAction::Add,
@@ -1297,6 +2550,9 @@ pub fn resolve_python_resources(
                     PythonResource::ExtensionModule { name, module },
                 ) => {
                     info!(logger, "adding embedded extension module: {}", name);
+                    if let Some(build) = &module.build {
+                        read_files.extend(build.sources.iter().cloned());
+                    }
                     embedded_extension_modules.insert(name, module);
                 }
                 (
@@ -1359,7 +2615,10 @@ pub fn resolve_python_resources(
                     },
                 ) => {
                     info!(logger, "adding embedded module bytecode: {}", name);
-                    embedded_bytecode_requests.insert(name.clone(), (source, optimize_level));
+                    embedded_bytecode_requests
+                        .entry(name.clone())
+                        .or_insert_with(BTreeMap::new)
+                        .insert(optimize_level, source);
                 }
                 (
                     ResourceAction::Add,
@@ -1427,19 +2686,12 @@ pub fn resolve_python_resources(
                         app_relative.insert(path.clone(), AppRelativeResources::new());
                     }
 
-                    let app_relative = app_relative.get_mut(&path).unwrap();
-
-                    if !app_relative.resources.contains_key(&package) {
-                        app_relative
-                            .resources
-                            .insert(package.clone(), BTreeMap::new());
-                    }
-
-                    app_relative
-                        .resources
-                        .get_mut(&package)
-                        .unwrap()
-                        .insert(name, data);
+                    app_relative.get_mut(&path).unwrap().add_resource(
+                        &package,
+                        &name,
+                        data,
+                        &mut classifications,
+                    );
                 }
                 (
                     ResourceAction::Remove,
@@ -1449,6 +2701,32 @@ pub fn resolve_python_resources(
                     info!(logger, "removing embedded resource: {}", name);
                     embedded_resources.remove(&name);
                 }
+                (
+                    ResourceAction::Add,
+                    ResourceLocation::AppRelative { path: location },
+                    PythonResource::File { path, data, mode },
+                ) => {
+                    info!(logger, "adding app-relative file to {}: {}", location, path);
+
+                    if !app_relative.contains_key(&location) {
+                        app_relative.insert(location.clone(), AppRelativeResources::new());
+                    }
+
+                    app_relative
+                        .get_mut(&location)
+                        .unwrap()
+                        .files
+                        .insert(path, AppRelativeFile { data, mode });
+                }
+                (ResourceAction::Add, _, PythonResource::License { component }) => {
+                    licensed_components.add(component);
+                }
+                (ResourceAction::Remove, _, PythonResource::License { .. }) => {
+                    panic!("should not have gotten an action to remove a license");
+                }
+                (_, ResourceLocation::Embedded, PythonResource::File { .. }) => {
+                    panic!("should not have gotten an embedded file");
+                }
                 (ResourceAction::Remove, ResourceLocation::AppRelative { .. }, _) => {
                     panic!("should not have gotten an action to remove an app-relative resource");
                 }
@@ -1459,6 +2737,62 @@ pub fn resolve_python_resources(
             license_files_path = Some(rule.path.clone());
         }
 
+        if let PythonPackaging::ThirdPartyNotices(rule) = packaging {
+            third_party_notices = Some(rule.clone());
+        }
+
+        if let PythonPackaging::StripSource(rule) = packaging {
+            let mut modules = ResourceFilter::new(&rule.modules, &[])
+                .unwrap_or_else(|e| panic!("invalid strip-source modules: {}", e));
+            let mut excludes = ResourceFilter::new(&rule.excludes, &[])
+                .unwrap_or_else(|e| panic!("invalid strip-source excludes: {}", e));
+
+            strip_module_sources(logger, &mut embedded_sources, &mut modules, &mut excludes);
+
+            for value in app_relative.values_mut() {
+                strip_module_sources(
+                    logger,
+                    &mut value.module_sources,
+                    &mut modules,
+                    &mut excludes,
+                );
+            }
+
+            modules.log_report(logger, "strip-source modules", "stripped source of");
+            excludes.log_report(logger, "strip-source excludes", "kept source of");
+        }
+
+        if let PythonPackaging::FilterExclude(rule) = packaging {
+            let mut filter = ResourceFilter::new(&rule.patterns, &rule.resource_types)
+                .unwrap_or_else(|e| panic!("invalid filter-exclude rule: {}", e));
+
+            exclude_btreemap(
+                logger,
+                &mut embedded_extension_modules,
+                &mut filter,
+                "extension",
+            );
+            exclude_btreemap(logger, &mut embedded_sources, &mut filter, "source");
+            exclude_btreemap(
+                logger,
+                &mut embedded_bytecode_requests,
+                &mut filter,
+                "bytecode",
+            );
+            exclude_resources(logger, &mut embedded_resources, &mut filter);
+
+            for value in app_relative.values_mut() {
+                exclude_btreemap(logger, &mut value.module_sources, &mut filter, "source");
+                exclude_resources(logger, &mut value.resources, &mut filter);
+            }
+
+            for value in app_relative_bytecode_requests.values_mut() {
+                exclude_btreemap(logger, value, &mut filter, "bytecode");
+            }
+
+            filter.log_report(logger, "filter-exclude", "excluded");
+        }
+
         if let PythonPackaging::FilterInclude(rule) = packaging {
             let mut include_names: BTreeSet<String> = BTreeSet::new();
 
@@ -1559,22 +2893,78 @@ pub fn resolve_python_resources(
         embedded_extension_modules.remove(&String::from(*e));
     }
 
-    let mut embedded_bytecodes: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+    log_stdlib_extension_sizes(logger, dist, &embedded_extension_modules);
+
+    let mut embedded_bytecodes: BTreeMap<String, BTreeMap<i32, Vec<u8>>> = BTreeMap::new();
 
     {
-        let mut compiler = bytecode_compiler(&dist);
+        let mut compiler = bytecode_compiler(config, dist);
+        let mut progress = Progress::new(
+            logger,
+            "compiling bytecode",
+            embedded_bytecode_requests
+                .values()
+                .map(|requests| requests.len() as u64)
+                .sum::<u64>()
+                + app_relative_bytecode_requests
+                    .values()
+                    .map(|requests| requests.len() as u64)
+                    .sum::<u64>(),
+        );
 
-        for (name, (source, optimize_level)) in embedded_bytecode_requests {
-            let bytecode = match compiler.compile(&source, &name, optimize_level) {
-                Ok(res) => res,
-                Err(msg) => panic!("error compiling bytecode for {}: {}", name, msg),
-            };
+        for (name, requests) in embedded_bytecode_requests {
+            for (optimize_level, source) in requests {
+                let bytecode = match compiler.compile(&source, &name, optimize_level) {
+                    Ok(res) => res,
+                    Err(msg) => panic!("error compiling bytecode for {}: {}", name, msg),
+                };
+
+                embedded_bytecodes
+                    .entry(name.clone())
+                    .or_insert_with(BTreeMap::new)
+                    .insert(optimize_level, bytecode);
+
+                progress.advance(1);
+            }
+        }
 
-            embedded_bytecodes.insert(name.clone(), bytecode);
+        for (path, requests) in app_relative_bytecode_requests {
+            let entry = app_relative
+                .entry(path)
+                .or_insert_with(AppRelativeResources::new);
+
+            for (name, (source, optimize_level)) in requests {
+                let bytecode = match compiler.compile(&source, &name, optimize_level) {
+                    Ok(res) => res,
+                    Err(msg) => panic!("error compiling bytecode for {}: {}", name, msg),
+                };
+
+                entry.module_bytecodes.insert(name, bytecode);
+
+                progress.advance(1);
+            }
         }
+
+        info!(
+            logger,
+            "bytecode for {} modules reused from cache; {} modules compiled",
+            compiler.hits,
+            compiler.misses
+        );
+
+        prune_bytecode_cache(logger, &compiler);
     }
 
-    // TODO compile app-relative bytecode too.
+    info!(
+        logger,
+        "classification of {} resources reused from cache; {} resources classified",
+        classifications.hits,
+        classifications.misses
+    );
+
+    if let Err(e) = classifications.save() {
+        warn!(logger, "error saving resource classification cache: {}", e);
+    }
 
     let mut all_embedded_modules: BTreeSet<String> = BTreeSet::new();
     for name in embedded_sources.keys() {
@@ -1616,7 +3006,208 @@ pub fn resolve_python_resources(
         app_relative,
         read_files,
         license_files_path,
+        third_party_notices,
+        licensed_components,
+    }
+}
+
+/// Resolve the site-packages directory for a virtualenv.
+///
+/// Both POSIX (``lib/pythonX.Y/site-packages``) and Windows
+/// (``Lib/site-packages``) layouts are recognized. If neither layout is
+/// present, the path is assumed to be a site-packages directory itself.
+pub fn resolve_site_packages_path(path: &Path) -> PathBuf {
+    let windows_path = path.join("Lib").join("site-packages");
+    if windows_path.is_dir() {
+        return windows_path;
+    }
+
+    let pattern = path.join("lib").join("python*").join("site-packages");
+
+    if let Ok(paths) = findglob(&pattern.display().to_string()) {
+        if let Some(Ok(candidate)) = paths.into_iter().next() {
+            return candidate;
+        }
+    }
+
+    path.to_path_buf()
+}
+
+/// Produce embeddable resources from a virtualenv or site-packages directory.
+///
+/// This is a one-call interface to obtain the packed modules and resources
+/// data consumed by the ``pyembed`` crate without going through a PyOxidizer
+/// config file. It is useful for building resource blobs with custom tooling.
+///
+/// ``path`` can be the root directory of a virtualenv or a ``site-packages``
+/// directory. All Python modules and resource files in it are collected.
+/// Source modules are compiled to bytecode using the Python interpreter at
+/// ``python_exe``, which should be the same Python version as the interpreter
+/// that will load the data. Existing ``.pyc`` files and files in ``.dist-info``
+/// directories are ignored. Resources belonging to directories that are not
+/// Python packages are ignored, as they couldn't be loaded. Extension modules
+/// are also ignored, as they can't be loaded from memory. See
+/// ``scan_site_packages()`` for how files are classified.
+///
+/// If ``modules_path`` and ``resources_path`` are defined, the packed modules
+/// and resources data is written to those files. ``compress_resources``
+/// controls whether resources data is zlib compressed.
+pub fn pack_virtualenv(
+    logger: &slog::Logger,
+    python_exe: &Path,
+    path: &Path,
+    optimize_level: i32,
+    include_source: bool,
+    modules_path: Option<&Path>,
+    resources_path: Option<&Path>,
+    compress_resources: bool,
+) -> Result<EmbeddedPythonResources, String> {
+    let packages_path = resolve_site_packages_path(path);
+
+    if !packages_path.is_dir() {
+        return Err(format!("{} is not a directory", packages_path.display()));
+    }
+
+    info!(
+        logger,
+        "collecting Python resources from {}",
+        packages_path.display()
+    );
+
+    let mut module_sources = BTreeMap::new();
+    let mut module_bytecodes = BTreeMap::new();
+    let mut resources: BTreeMap<String, BTreeMap<String, Vec<u8>>> = BTreeMap::new();
+
+    let mut compiler = BytecodeCompiler::new(python_exe);
+    let mut ignored_bytecode_count = 0;
+
+    log_installed_distributions(logger, &packages_path);
+
+    let site_packages = scan_site_packages(&packages_path)?;
+
+    for resource in &site_packages.resources {
+        match resource.flavor {
+            SitePackagesResourceType::Source => {
+                let source = fs::read(&resource.path).or_else(|e| {
+                    Err(format!("error reading {}: {}", resource.path.display(), e))
+                })?;
+
+                let bytecode = compiler
+                    .compile(&source, &resource.name, optimize_level)
+                    .or_else(|e| {
+                        Err(format!(
+                            "error compiling bytecode for {}: {}",
+                            resource.name, e
+                        ))
+                    })?;
+
+                module_bytecodes
+                    .entry(resource.name.clone())
+                    .or_insert_with(BTreeMap::new)
+                    .insert(optimize_level, bytecode);
+
+                if include_source {
+                    module_sources.insert(resource.name.clone(), source);
+                }
+            }
+
+            SitePackagesResourceType::Resource => {
+                let data = fs::read(&resource.path).or_else(|e| {
+                    Err(format!("error reading {}: {}", resource.path.display(), e))
+                })?;
+
+                resources
+                    .entry(resource.package.clone())
+                    .or_insert_with(BTreeMap::new)
+                    .insert(resource.name.clone(), data);
+            }
+
+            SitePackagesResourceType::ExtensionModule => {
+                warn!(
+                    logger,
+                    "extension module {} cannot be loaded from memory; ignoring", resource.name
+                );
+            }
+
+            SitePackagesResourceType::DataFile => {
+                info!(
+                    logger,
+                    "{} is not a Python resource; ignoring", resource.relative_path
+                );
+            }
+
+            SitePackagesResourceType::Unknown => {
+                info!(
+                    logger,
+                    "{} does not belong to a Python package; ignoring", resource.relative_path
+                );
+            }
+
+            SitePackagesResourceType::Bytecode(_) => {
+                ignored_bytecode_count += 1;
+            }
+
+            SitePackagesResourceType::EditableShim => {
+                warn!(
+                    logger,
+                    "{} is an editable install whose source is outside of {}; ignoring",
+                    resource.relative_path,
+                    packages_path.display()
+                );
+            }
+
+            SitePackagesResourceType::PathConfiguration => {
+                warn!(
+                    logger,
+                    "{} modifies sys.path, which is not supported by packed data; ignoring",
+                    resource.relative_path
+                );
+            }
+
+            // Packaging metadata isn't needed at run-time.
+            SitePackagesResourceType::Metadata => {}
+        }
+    }
+
+    if ignored_bytecode_count > 0 {
+        info!(
+            logger,
+            "ignoring {} existing bytecode files; bytecode is compiled from source",
+            ignored_bytecode_count
+        );
+    }
+
+    let mut all_modules = BTreeSet::new();
+    all_modules.extend(module_sources.keys().cloned());
+    all_modules.extend(module_bytecodes.keys().cloned());
+
+    let embedded = EmbeddedPythonResources {
+        module_sources,
+        module_bytecodes,
+        all_modules,
+        resources,
+        extension_modules: BTreeMap::new(),
+    };
+
+    info!(
+        logger,
+        "collected {} modules and resources for {} packages",
+        embedded.all_modules.len(),
+        embedded.resources.len()
+    );
+
+    if let Some(modules_path) = modules_path {
+        let fh = fs::File::create(modules_path).or_else(|e| Err(e.to_string()))?;
+        write_modules_entries(&fh, &embedded.modules_records()).or_else(|e| Err(e.to_string()))?;
+    }
+
+    if let Some(resources_path) = resources_path {
+        let fh = fs::File::create(resources_path).or_else(|e| Err(e.to_string()))?;
+        write_resources_entries(&fh, &embedded.resources, compress_resources)
+            .or_else(|e| Err(e.to_string()))?;
     }
+
+    Ok(embedded)
 }
 
 pub struct ImportlibData {
@@ -1633,8 +3224,8 @@ pub struct ImportlibData {
 /// importlib._bootstrap_external is modified. We take the original Python
 /// source and concatenate with code that provides the memory importer.
 /// Bytecode is then derived from it.
-pub fn derive_importlib(dist: &PythonDistributionInfo) -> ImportlibData {
-    let mut compiler = bytecode_compiler(&dist);
+pub fn derive_importlib(config: &Config, dist: &PythonDistributionInfo) -> ImportlibData {
+    let mut compiler = bytecode_compiler(config, dist);
 
     let mod_bootstrap_path = &dist.py_modules["importlib._bootstrap"];
     let mod_bootstrap_external_path = &dist.py_modules["importlib._bootstrap_external"];
@@ -1662,91 +3253,301 @@ pub fn derive_importlib(dist: &PythonDistributionInfo) -> ImportlibData {
     }
 }
 
+/// Magic bytes identifying versioned packed modules data.
+pub const MODULES_MAGIC: &[u8] = b"pyoxmods";
+
+/// Version of packed modules data written by `write_modules_entries()`.
+///
+/// Version 2 index entries lacked optimized bytecode and are no longer
+/// supported.
+pub const MODULES_VERSION: u32 = 3;
+
+/// Module flag indicating the module is a package.
+pub const MODULE_FLAG_PACKAGE: u32 = 0x1;
+
 /// Serialize a ModulesEntries to a writer.
 ///
+/// Entries are written sorted by name so readers can search the index
+/// without parsing it.
+///
 /// See the documentation in the `pyembed` crate for the data format.
 pub fn write_modules_entries<W: Write>(
     mut dest: W,
     entries: &[ModuleEntry],
 ) -> std::io::Result<()> {
+    let mut entries: Vec<&ModuleEntry> = entries.iter().collect();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    entries.dedup_by(|a, b| a.name == b.name);
+
+    let package_names = packages_from_module_names(entries.iter().map(|e| e.name.clone()));
+
+    let source_length = |entry: &ModuleEntry| match entry.source {
+        Some(ref v) => v.len(),
+        None => 0,
+    };
+    let bytecode_length = |entry: &ModuleEntry| match entry.bytecode {
+        Some(ref v) => v.len(),
+        None => 0,
+    };
+    let bytecode_opt1_length = |entry: &ModuleEntry| match entry.bytecode_opt1 {
+        Some(ref v) => v.len(),
+        None => 0,
+    };
+    let bytecode_opt2_length = |entry: &ModuleEntry| match entry.bytecode_opt2 {
+        Some(ref v) => v.len(),
+        None => 0,
+    };
+
+    let index_length = MODULES_MAGIC.len() + 4 + 4 + 4 + entries.len() * 11 * 4;
+    let mut name_offset = index_length;
+    let mut source_offset = name_offset + entries.iter().map(|e| e.name.len()).sum::<usize>();
+    let mut bytecode_offset =
+        source_offset + entries.iter().map(|e| source_length(e)).sum::<usize>();
+    let mut bytecode_opt1_offset =
+        bytecode_offset + entries.iter().map(|e| bytecode_length(e)).sum::<usize>();
+    let mut bytecode_opt2_offset = bytecode_opt1_offset
+        + entries
+            .iter()
+            .map(|e| bytecode_opt1_length(e))
+            .sum::<usize>();
+
+    dest.write_all(MODULES_MAGIC)?;
+    dest.write_u32::<LittleEndian>(MODULES_VERSION)?;
+    // Reserved.
+    dest.write_u32::<LittleEndian>(0)?;
+
     dest.write_u32::<LittleEndian>(entries.len() as u32)?;
 
-    for entry in entries.iter() {
-        let name_bytes = entry.name.as_bytes();
-        dest.write_u32::<LittleEndian>(name_bytes.len() as u32)?;
-        dest.write_u32::<LittleEndian>(if let Some(ref v) = entry.source {
-            v.len() as u32
+    for entry in &entries {
+        let flags = if package_names.contains(&entry.name) {
+            MODULE_FLAG_PACKAGE
         } else {
             0
-        })?;
-        dest.write_u32::<LittleEndian>(if let Some(ref v) = entry.bytecode {
-            v.len() as u32
-        } else {
-            0
-        })?;
+        };
+
+        dest.write_u32::<LittleEndian>(name_offset as u32)?;
+        dest.write_u32::<LittleEndian>(entry.name.len() as u32)?;
+        dest.write_u32::<LittleEndian>(source_offset as u32)?;
+        dest.write_u32::<LittleEndian>(source_length(entry) as u32)?;
+        dest.write_u32::<LittleEndian>(bytecode_offset as u32)?;
+        dest.write_u32::<LittleEndian>(bytecode_length(entry) as u32)?;
+        dest.write_u32::<LittleEndian>(bytecode_opt1_offset as u32)?;
+        dest.write_u32::<LittleEndian>(bytecode_opt1_length(entry) as u32)?;
+        dest.write_u32::<LittleEndian>(bytecode_opt2_offset as u32)?;
+        dest.write_u32::<LittleEndian>(bytecode_opt2_length(entry) as u32)?;
+        dest.write_u32::<LittleEndian>(flags)?;
+
+        name_offset += entry.name.len();
+        source_offset += source_length(entry);
+        bytecode_offset += bytecode_length(entry);
+        bytecode_opt1_offset += bytecode_opt1_length(entry);
+        bytecode_opt2_offset += bytecode_opt2_length(entry);
     }
 
-    for entry in entries.iter() {
-        let name_bytes = entry.name.as_bytes();
-        dest.write_all(name_bytes)?;
+    for entry in &entries {
+        dest.write_all(entry.name.as_bytes())?;
     }
 
-    for entry in entries.iter() {
+    for entry in &entries {
         if let Some(ref v) = entry.source {
             dest.write_all(v.as_slice())?;
         }
     }
 
-    for entry in entries.iter() {
+    for entry in &entries {
         if let Some(ref v) = entry.bytecode {
             dest.write_all(v.as_slice())?;
         }
     }
 
+    for entry in &entries {
+        if let Some(ref v) = entry.bytecode_opt1 {
+            dest.write_all(v.as_slice())?;
+        }
+    }
+
+    for entry in &entries {
+        if let Some(ref v) = entry.bytecode_opt2 {
+            dest.write_all(v.as_slice())?;
+        }
+    }
+
     Ok(())
 }
 
+/// Magic bytes identifying versioned packed resources data.
+pub const RESOURCES_MAGIC: &[u8] = b"pyoxrsrc";
+
+/// Version of packed resources data written by `write_resources_entries()`.
+pub const RESOURCES_VERSION: u32 = 2;
+
+/// Resource flag indicating data is zlib compressed.
+pub const RESOURCE_FLAG_ZLIB: u32 = 0x1;
+
 /// Serializes resource data to a writer.
 ///
+/// If `compress` is true, resources are zlib compressed when doing so
+/// makes them smaller.
+///
+/// Resources having identical content are only stored once.
+///
 /// See the documentation in the `pyembed` crate for the data format.
 pub fn write_resources_entries<W: Write>(
     mut dest: W,
     entries: &BTreeMap<String, BTreeMap<String, Vec<u8>>>,
+    compress: bool,
 ) -> std::io::Result<()> {
+    // Resolve the stored form of each distinct resource value up front, as
+    // the index needs to know its offset and length. Values are keyed by
+    // their content so identical values share storage.
+    let mut stored: Vec<(u32, Vec<u8>)> = Vec::new();
+    let mut stored_offsets: Vec<usize> = Vec::new();
+    let mut stored_indices: HashMap<&[u8], usize> = HashMap::new();
+    let mut data_length = 0;
+
+    for resources in entries.values() {
+        for value in resources.values() {
+            if stored_indices.contains_key(value.as_slice()) {
+                continue;
+            }
+
+            let compressed = if compress {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+                encoder.write_all(value)?;
+                Some(encoder.finish()?)
+            } else {
+                None
+            };
+
+            let (flags, data) = match compressed {
+                Some(data) if data.len() < value.len() => (RESOURCE_FLAG_ZLIB, data),
+                _ => (0, value.clone()),
+            };
+
+            stored_indices.insert(value.as_slice(), stored.len());
+            stored_offsets.push(data_length);
+            data_length += data.len();
+            stored.push((flags, data));
+        }
+    }
+
+    dest.write_all(RESOURCES_MAGIC)?;
+    dest.write_u32::<LittleEndian>(RESOURCES_VERSION)?;
+    // Reserved.
+    dest.write_u32::<LittleEndian>(0)?;
+
     dest.write_u32::<LittleEndian>(entries.len() as u32)?;
 
-    // All the numeric index data is written in pass 1.
-    for (package, resources) in entries {
-        let package_bytes = package.as_bytes();
+    // All the numeric index data is written in pass 1.
+    for (package, resources) in entries {
+        let package_bytes = package.as_bytes();
+
+        dest.write_u32::<LittleEndian>(package_bytes.len() as u32)?;
+        dest.write_u32::<LittleEndian>(resources.len() as u32)?;
+        // Reserved.
+        dest.write_u32::<LittleEndian>(0)?;
+
+        for (name, value) in resources {
+            let name_bytes = name.as_bytes();
+            let index = stored_indices[value.as_slice()];
+            let (flags, data) = &stored[index];
+
+            dest.write_u32::<LittleEndian>(name_bytes.len() as u32)?;
+            dest.write_u32::<LittleEndian>(*flags)?;
+            dest.write_u32::<LittleEndian>(data.len() as u32)?;
+            dest.write_u32::<LittleEndian>(value.len() as u32)?;
+            dest.write_u32::<LittleEndian>(stored_offsets[index] as u32)?;
+            // Reserved.
+            dest.write_u32::<LittleEndian>(0)?;
+            dest.write_all(&Sha256::digest(value))?;
+        }
+    }
+
+    // All the name strings are written in pass 2.
+    for (package, resources) in entries {
+        dest.write_all(package.as_bytes())?;
+
+        for name in resources.keys() {
+            dest.write_all(name.as_bytes())?;
+        }
+    }
+
+    // All the distinct resource data is written in pass 3.
+    for (_, data) in &stored {
+        dest.write_all(data.as_slice())?;
+    }
+
+    Ok(())
+}
+
+/// Compile the C sources of an extension module to object files.
+///
+/// `build` holds compiler settings common to all extension modules. Sources
+/// are compiled like extensions built into libpython, so the module
+/// initialization function is not exported.
+fn compile_extension_module(
+    logger: &slog::Logger,
+    name: &str,
+    build_info: &ExtensionModuleBuild,
+    build: &cc::Build,
+    out_dir: &Path,
+) -> Vec<PathBuf> {
+    let mut build = build.clone();
+    build.define("Py_BUILD_CORE_BUILTIN", None);
+
+    for path in &build_info.include_dirs {
+        build.include(path);
+    }
+
+    for (key, value) in &build_info.defines {
+        build.define(key, value.as_ref().map(|v| v.as_str()));
+    }
+
+    let compiler = build.get_compiler();
 
-        dest.write_u32::<LittleEndian>(package_bytes.len() as u32)?;
-        dest.write_u32::<LittleEndian>(resources.len() as u32)?;
+    let objects_path = out_dir.join("extensions").join(name);
+    create_dir_all(&objects_path).expect("unable to create extension object directory");
 
-        for (name, value) in resources {
-            let name_bytes = name.as_bytes();
+    let mut res = Vec::new();
 
-            dest.write_u32::<LittleEndian>(name_bytes.len() as u32)?;
-            dest.write_u32::<LittleEndian>(value.len() as u32)?;
-        }
-    }
+    for (i, source) in build_info.sources.iter().enumerate() {
+        let stem = source
+            .file_stem()
+            .expect("unable to get file stem")
+            .to_string_lossy();
+        let object_path = objects_path.join(format!("{}-{}.o", i, stem));
 
-    // All the name strings are written in pass 2.
-    for (package, resources) in entries {
-        dest.write_all(package.as_bytes())?;
+        info!(
+            logger,
+            "compiling {} for extension module {}",
+            source.display(),
+            name
+        );
 
-        for name in resources.keys() {
-            dest.write_all(name.as_bytes())?;
+        let mut command = compiler.to_command();
+        if compiler.is_like_msvc() {
+            command.arg("/c");
+            command.arg(format!("/Fo{}", object_path.display()));
+        } else {
+            command.arg("-c");
+            command.arg("-o");
+            command.arg(&object_path);
         }
-    }
-
-    // All the resource data is written in pass 3.
-    for resources in entries.values() {
-        for value in resources.values() {
-            dest.write_all(value.as_slice())?;
+        command.arg(source);
+
+        let status = command.status().expect("unable to run C compiler");
+        if !status.success() {
+            panic!(
+                "error compiling {} for extension module {}",
+                source.display(),
+                name
+            );
         }
+
+        res.push(object_path);
     }
 
-    Ok(())
+    res
 }
 
 /// Produce the content of the config.c file containing built-in extensions.
@@ -1791,6 +3592,67 @@ pub struct LibpythonInfo {
     path: PathBuf,
     cargo_metadata: Vec<String>,
     license_infos: BTreeMap<String, Vec<LicenseInfo>>,
+    licensed_components: LicensedComponents,
+}
+
+fn license_texts(infos: &[LicenseInfo]) -> Vec<LicenseText> {
+    infos
+        .iter()
+        .map(|li| LicenseText {
+            filename: li.license_filename.clone(),
+            text: li.license_text.clone(),
+        })
+        .collect()
+}
+
+/// Resolve licensed components of the Python distribution and its extension modules.
+///
+/// Extension modules without license metadata of their own are covered by
+/// the license of the Python distribution.
+fn python_licensed_components(
+    dist: &PythonDistributionInfo,
+    extension_modules: &BTreeMap<String, ExtensionModule>,
+) -> LicensedComponents {
+    let mut components = LicensedComponents::default();
+
+    let infos = dist
+        .license_infos
+        .get("python")
+        .cloned()
+        .unwrap_or_default();
+    let licenses = infos
+        .first()
+        .map(|li| li.licenses.clone())
+        .unwrap_or_default();
+
+    let mut component = LicensedComponent::new(
+        ComponentFlavor::PythonDistribution,
+        "Python",
+        LicenseFlavor::from_spdx_licenses(&licenses),
+    );
+    component.version = Some(dist.version.clone());
+    component.homepage = Some("https://www.python.org/".to_string());
+    component.license_texts = license_texts(&infos);
+    components.add(component);
+
+    for (name, em) in extension_modules {
+        let license = if em.license_public_domain == Some(true) {
+            LicenseFlavor::PublicDomain
+        } else if let Some(licenses) = &em.licenses {
+            LicenseFlavor::from_spdx_licenses(licenses)
+        } else {
+            continue;
+        };
+
+        let mut component =
+            LicensedComponent::new(ComponentFlavor::PythonExtensionModule, name, license);
+        if let Some(infos) = dist.license_infos.get(name) {
+            component.license_texts = license_texts(infos);
+        }
+        components.add(component);
+    }
+
+    components
 }
 
 /// Create a static libpython from a Python distribution.
@@ -1921,19 +3783,35 @@ pub fn link_libpython(
         "resolving inputs for {} extension modules...",
         extension_modules.len()
     );
+
+    // Used to compile extension modules defined by source code.
+    let mut extension_build = cc::Build::new();
+    extension_build.host(host);
+    extension_build.target(target);
+    extension_build.opt_level_str(opt_level);
+    extension_build.include(temp_dir_path);
+    extension_build.define("NDEBUG", None);
+    extension_build.cargo_metadata(false);
     for (name, em) in extension_modules {
         if em.builtin_default {
             continue;
         }
 
+        let object_paths = match &em.build {
+            Some(build_info) => {
+                compile_extension_module(logger, name, build_info, &extension_build, out_dir)
+            }
+            None => em.object_paths.clone(),
+        };
+
         info!(
             logger,
             "adding {} object files for {} extension module: {:#?}",
-            em.object_paths.len(),
+            object_paths.len(),
             name,
-            em.object_paths
+            object_paths
         );
-        for path in &em.object_paths {
+        for path in &object_paths {
             build.object(path);
         }
 
@@ -2025,6 +3903,7 @@ pub fn link_libpython(
         path: out_dir.join("libpythonXY.a"),
         cargo_metadata,
         license_infos,
+        licensed_components: python_licensed_components(dist, extension_modules),
     }
 }
 
@@ -2035,8 +3914,30 @@ pub fn derive_python_config(
     importlib_bootstrap_external_path: &PathBuf,
     py_modules_path: &PathBuf,
     py_resources_path: &PathBuf,
-) -> String {
-    format!(
+    runtime_files: &RuntimeFiles,
+) -> Result<String, String> {
+    let resource_extraction_directory = if config.resource_extraction_packages.is_empty() {
+        "None".to_owned()
+    } else {
+        // Extract to a directory specific to the resources data so
+        // different builds of an application don't share files.
+        let data = fs::read(py_resources_path).or_else(|e| {
+            Err(format!(
+                "unable to read resources data {}: {}",
+                py_resources_path.display(),
+                e
+            ))
+        })?;
+        let digest = hex::encode(Sha256::digest(&data));
+
+        format!(
+            "Some(r#\"{}/{}\"#.to_string())",
+            config.resource_extraction_directory,
+            &digest[0..16]
+        )
+    };
+
+    Ok(format!(
         "PythonConfig {{\n    \
          program_name: \"{}\".to_string(),\n    \
          standard_io_encoding: {},\n    \
@@ -2054,9 +3955,22 @@ pub fn derive_python_config(
          frozen_importlib_external_data: include_bytes!(r#\"{}\"#),\n    \
          py_modules_data: include_bytes!(r#\"{}\"#),\n    \
          py_resources_data: include_bytes!(r#\"{}\"#),\n    \
+         resource_extraction_directory: {},\n    \
+         resource_extraction_packages: [{}].to_vec(),\n    \
+         writable_site_directory: {},\n    \
+         bytecode_cache_directory: {},\n    \
          argvb: false,\n    \
          raw_allocator: {},\n    \
+         mem_allocator: {},\n    \
+         obj_allocator: {},\n    \
          write_modules_directory_env: {},\n    \
+         write_import_timings_directory_env: {},\n    \
+         terminfo_resolution: {},\n    \
+         ca_certificates_resolution: {},\n    \
+         faulthandler: {},\n    \
+         crash_report_file: {},\n    \
+         dump_traceback_on_panic: {},\n    \
+         enable_core_dumps: {},\n    \
          run: {},\n\
          }}",
         config.program_name,
@@ -2085,15 +3999,45 @@ pub fn derive_python_config(
         importlib_bootstrap_external_path.display(),
         py_modules_path.display(),
         py_resources_path.display(),
+        resource_extraction_directory,
+        &config
+            .resource_extraction_packages
+            .iter()
+            .map(|p| "\"".to_owned() + p + "\".to_string()")
+            .collect::<Vec<String>>()
+            .join(", "),
+        match &config.writable_site_directory {
+            Some(path) => format!("Some(r#\"{}\"#.to_string())", path),
+            None => "None".to_owned(),
+        },
+        match &config.bytecode_cache_directory {
+            Some(path) => format!("Some(r#\"{}\"#.to_string())", path),
+            None => "None".to_owned(),
+        },
         match config.raw_allocator {
             RawAllocator::Jemalloc => "PythonRawAllocator::Jemalloc",
             RawAllocator::Rust => "PythonRawAllocator::Rust",
             RawAllocator::System => "PythonRawAllocator::System",
         },
+        mem_allocator_rust(&config.mem_allocator),
+        mem_allocator_rust(&config.obj_allocator),
         match &config.write_modules_directory_env {
             Some(path) => "Some(\"".to_owned() + &path + "\".to_string())",
             _ => "None".to_owned(),
         },
+        match &config.write_import_timings_directory_env {
+            Some(key) => "Some(\"".to_owned() + &key + "\".to_string())",
+            _ => "None".to_owned(),
+        },
+        runtime_files.terminfo_resolution.to_rust(),
+        runtime_files.ca_certificates_resolution.to_rust(),
+        config.faulthandler,
+        match &config.crash_report_file {
+            Some(path) => format!("Some(r#\"{}\"#.to_string())", path),
+            None => "None".to_owned(),
+        },
+        config.dump_traceback_on_panic,
+        config.enable_core_dumps,
         match config.run {
             RunMode::Noop => "PythonRunMode::None".to_owned(),
             RunMode::Repl => "PythonRunMode::Repl".to_owned(),
@@ -2104,14 +4048,256 @@ pub fn derive_python_config(
                 "PythonRunMode::Eval { code: \"".to_owned() + code + "\".to_string() }"
             }
         },
-    )
+    ))
+}
+
+/// How an application locates files needed at run-time.
+///
+/// Mirrors `pyembed::RuntimeFileResolution`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuntimeFileResolution {
+    None,
+    Dynamic,
+    Static(String),
+    TrustStore,
+}
+
+impl RuntimeFileResolution {
+    /// Obtain Rust code constructing the pyembed equivalent of this value.
+    fn to_rust(&self) -> String {
+        match self {
+            RuntimeFileResolution::None => "RuntimeFileResolution::None".to_string(),
+            RuntimeFileResolution::Dynamic => "RuntimeFileResolution::Dynamic".to_string(),
+            RuntimeFileResolution::Static(path) => {
+                format!("RuntimeFileResolution::Static(r#\"{}\"#.to_string())", path)
+            }
+            RuntimeFileResolution::TrustStore => "RuntimeFileResolution::TrustStore".to_string(),
+        }
+    }
+}
+
+/// Files needed at run-time that live outside the application.
+#[derive(Clone, Debug)]
+pub struct RuntimeFiles {
+    pub terminfo_resolution: RuntimeFileResolution,
+    pub ca_certificates_resolution: RuntimeFileResolution,
+
+    /// Files and directories to copy next to the application.
+    ///
+    /// See `PackagingState.bundled_files`.
+    pub bundled: Vec<(PathBuf, String)>,
+}
+
+/// Obtain the operating system of a target triple.
+fn target_triple_os(triple: &str) -> &str {
+    // Triples have the form <arch>-<vendor>-<os>[-<env>], although some omit
+    // the vendor.
+    let parts = triple.split('-').collect::<Vec<_>>();
+
+    match parts.len() {
+        0 | 1 => triple,
+        2 => parts[1],
+        _ => parts[2],
+    }
+}
+
+/// Resolve how files needed at run-time are provided.
+///
+/// The terminfo database is needed by the ``_curses`` and ``readline``
+/// extensions. CA certificates are needed by the ``_ssl`` extension. With
+/// the ``auto`` policy, files are only resolved when these are packaged.
+///
+/// Files from the host are only bundled when the target has the same
+/// operating system, as they may not be compatible with the target.
+pub fn resolve_runtime_files(
+    logger: &slog::Logger,
+    config: &Config,
+    resources: &PythonResources,
+    dest_dir: &Path,
+    host_triple: &str,
+    target_triple: &str,
+) -> RuntimeFiles {
+    let host_files_usable = target_triple_os(host_triple) == target_triple_os(target_triple);
+    let extension_modules = &resources.embedded.extension_modules;
+    let uses_curses = ["_curses", "readline"]
+        .iter()
+        .any(|name| extension_modules.contains_key(*name));
+    let uses_ssl = extension_modules.contains_key("_ssl");
+
+    let mut bundled = Vec::new();
+
+    let terminfo_resolution = match (config.terminfo_policy, uses_curses) {
+        (RuntimeFilesPolicy::None, _) | (RuntimeFilesPolicy::Auto, false) => {
+            RuntimeFileResolution::None
+        }
+        (RuntimeFilesPolicy::Auto, true) | (RuntimeFilesPolicy::System, _) => {
+            info!(logger, "terminfo database will be resolved at run-time");
+            RuntimeFileResolution::Dynamic
+        }
+        (RuntimeFilesPolicy::TrustStore, _) => {
+            panic!("trust_store terminfo policy should have been rejected by config parsing")
+        }
+        (RuntimeFilesPolicy::Bundle, _) if !host_files_usable => {
+            warn!(
+                logger,
+                "not bundling the terminfo database of {} for {}; it will be resolved at run-time",
+                host_triple,
+                target_triple
+            );
+            RuntimeFileResolution::Dynamic
+        }
+        (RuntimeFilesPolicy::Bundle, _) => {
+            // Copy in order of increasing precedence so entries in databases
+            // ncurses would search first win.
+            for path in TERMINFO_SEARCH_PATHS.iter().rev().map(Path::new) {
+                if path.is_dir() {
+                    info!(logger, "bundling terminfo database {}", path.display());
+                    bundled.push((path.to_path_buf(), "terminfo".to_string()));
+                }
+            }
+
+            if bundled.is_empty() {
+                warn!(
+                    logger,
+                    "no terminfo database found; it will be resolved at run-time"
+                );
+                RuntimeFileResolution::Dynamic
+            } else {
+                RuntimeFileResolution::Static("$ORIGIN/terminfo".to_string())
+            }
+        }
+    };
+
+    let ca_certificates_resolution = match (config.ca_certificates_policy, uses_ssl) {
+        (RuntimeFilesPolicy::None, _) | (RuntimeFilesPolicy::Auto, false) => {
+            RuntimeFileResolution::None
+        }
+        (RuntimeFilesPolicy::Auto, true) | (RuntimeFilesPolicy::System, _) => {
+            info!(logger, "CA certificates will be resolved at run-time");
+            RuntimeFileResolution::Dynamic
+        }
+        (RuntimeFilesPolicy::TrustStore, _) => {
+            info!(
+                logger,
+                "CA certificates will be loaded from the operating system trust store"
+            );
+            RuntimeFileResolution::TrustStore
+        }
+        (RuntimeFilesPolicy::Bundle, _) => {
+            // Prefer the bundle from certifi, as it is what the application
+            // would get from `certifi.where()`.
+            let certifi = |resources: &BTreeMap<String, BTreeMap<String, Vec<u8>>>| {
+                resources
+                    .get("certifi")
+                    .and_then(|package| package.get("cacert.pem"))
+                    .cloned()
+            };
+            let certifi = certifi(&resources.embedded.resources).or_else(|| {
+                resources
+                    .app_relative
+                    .values()
+                    .filter_map(|r| certifi(&r.resources))
+                    .next()
+            });
+
+            let source = match certifi {
+                Some(data) => {
+                    let path = dest_dir.join("cacert.pem");
+                    fs::write(&path, data).expect("unable to write cacert.pem");
+                    info!(logger, "bundling CA certificates from certifi");
+                    Some(path)
+                }
+                None if !host_files_usable => {
+                    warn!(
+                        logger,
+                        "not bundling CA certificates of {} for {}", host_triple, target_triple
+                    );
+                    None
+                }
+                None => CA_CERTIFICATES_SEARCH_PATHS
+                    .iter()
+                    .map(PathBuf::from)
+                    .find(|p| p.is_file())
+                    .map(|path| {
+                        info!(logger, "bundling CA certificates {}", path.display());
+                        path
+                    }),
+            };
+
+            match source {
+                Some(path) => {
+                    bundled.push((path, "cacert.pem".to_string()));
+                    RuntimeFileResolution::Static("$ORIGIN/cacert.pem".to_string())
+                }
+                None => {
+                    warn!(
+                        logger,
+                        "no CA certificates found; they will be resolved at run-time"
+                    );
+                    RuntimeFileResolution::Dynamic
+                }
+            }
+        }
+    };
+
+    RuntimeFiles {
+        terminfo_resolution,
+        ca_certificates_resolution,
+        bundled,
+    }
+}
+
+/// Copy a file or directory tree, creating parent directories as needed.
+///
+/// Existing files are overwritten.
+fn copy_bundled_file(
+    installed: &mut InstallManifest,
+    source: &Path,
+    dest: &Path,
+) -> Result<(), String> {
+    if source.is_dir() {
+        for entry in walkdir::WalkDir::new(source).follow_links(true) {
+            let entry = entry.or_else(|e| Err(e.to_string()))?;
+
+            if entry.file_type().is_file() {
+                let rel_path = entry
+                    .path()
+                    .strip_prefix(source)
+                    .or_else(|e| Err(e.to_string()))?;
+                copy_bundled_file(installed, entry.path(), &dest.join(rel_path))?;
+            }
+        }
+
+        Ok(())
+    } else {
+        installed.copy(source, dest)
+    }
 }
 
-pub fn write_data_rs(path: &PathBuf, python_config_rs: &str) {
+fn mem_allocator_rust(allocator: &MemAllocator) -> &'static str {
+    match allocator {
+        MemAllocator::Pymalloc => "PythonMemAllocator::Pymalloc",
+        MemAllocator::Jemalloc => "PythonMemAllocator::Jemalloc",
+        MemAllocator::Rust => "PythonMemAllocator::Rust",
+        MemAllocator::System => "PythonMemAllocator::System",
+    }
+}
+
+pub fn write_data_rs(path: &PathBuf, config: &Config, python_config_rs: &str) {
     let mut f = fs::File::create(&path).unwrap();
 
-    f.write_all(b"use super::config::{PythonConfig, PythonRawAllocator, PythonRunMode};\n\n")
+    f.write_all(b"use super::config::{PythonConfig, PythonMemAllocator, PythonRawAllocator, PythonRunMode, RuntimeFileResolution};\n\n")
+        .unwrap();
+
+    // Catch a pyembed crate built without jemalloc at build time instead of
+    // panicking when the interpreter starts.
+    if config.uses_jemalloc() {
+        f.write_all(
+            b"#[cfg(not(feature = \"jemalloc-sys\"))]\n\
+              compile_error!(\"jemalloc allocator configured but the jemalloc-sys feature is not enabled\");\n\n",
+        )
         .unwrap();
+    }
 
     // Ideally we would have a const struct, but we need to do some
     // dynamic allocations. Using a function avoids having to pull in a
@@ -2142,12 +4328,50 @@ pub struct PackagingState {
     pub app_relative_resources: BTreeMap<String, AppRelativeResources>,
     pub license_files_path: Option<String>,
     pub license_infos: BTreeMap<String, Vec<LicenseInfo>>,
+    pub third_party_notices: Option<PackagingThirdPartyNotices>,
+    pub licensed_components: LicensedComponents,
+
+    /// Files and directories to copy next to the application.
+    ///
+    /// Pairs of source path and app-relative destination path. Later
+    /// entries overwrite files installed by earlier ones.
+    pub bundled_files: Vec<(PathBuf, String)>,
+}
+
+/// Obtain the Unix permission bits to install a file found on disk with.
+///
+/// The permission bits of the file are kept, except for the setuid, setgid
+/// and sticky bits. Executables are always executable.
+#[cfg(unix)]
+fn installed_file_mode(path: &Path, executable: bool) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(path)
+        .map(|metadata| metadata.permissions().mode() & 0o777)
+        .unwrap_or(0o644);
+
+    if executable {
+        mode | 0o111
+    } else {
+        mode
+    }
+}
+
+/// Obtain the Unix permission bits to install a file found on disk with.
+#[cfg(not(unix))]
+fn installed_file_mode(_path: &Path, executable: bool) -> u32 {
+    if executable {
+        0o755
+    } else {
+        0o644
+    }
 }
 
 /// Install all app-relative files next to the generated binary.
 fn install_app_relative(
     logger: &slog::Logger,
     context: &BuildContext,
+    installed: &mut InstallManifest,
     path: &str,
     app_relative: &AppRelativeResources,
 ) -> Result<(), String> {
@@ -2186,16 +4410,7 @@ fn install_app_relative(
             module_path.display()
         );
 
-        let parent_dir = module_path.parent().unwrap();
-        create_dir_all(&parent_dir).or_else(|_| {
-            Err(format!(
-                "failed to create directory {}",
-                parent_dir.display()
-            ))
-        })?;
-
-        fs::write(&module_path, module_data)
-            .or_else(|_| Err(format!("failed to write {}", module_path.display())))?;
+        installed.write(&module_path, module_data, None)?;
     }
 
     // TODO implement.
@@ -2238,19 +4453,100 @@ fn install_app_relative(
                 dest_path.display()
             );
 
-            create_dir_all(dest_path.parent().unwrap()).or_else(|e| Err(e.to_string()))?;
-
-            fs::write(&dest_path, data)
-                .or_else(|_| Err(format!("failed to write {}", dest_path.display())))?;
+            installed.write(&dest_path, data, None)?;
         }
     }
 
+    for (rel_path, file) in &app_relative.files {
+        let file_path = dest_path.join(rel_path);
+
+        info!(
+            logger,
+            "installing app-relative file {} to {}",
+            rel_path,
+            file_path.display()
+        );
+
+        installed.write(&file_path, &file.data, Some(file.mode))?;
+    }
+
+    let load_order = app_relative.shared_library_load_order();
+
+    if !load_order.is_empty() {
+        info!(
+            logger,
+            "app-relative shared libraries in {} in load order: {:#?}", path, load_order
+        );
+    }
+
+    for (library, consumers) in app_relative.missing_shared_libraries() {
+        warn!(
+            logger,
+            "shared library {} is required by {:?} but is not distributed with the application; it must be present on target machines",
+            library,
+            consumers
+        );
+    }
+
     Ok(())
 }
 
+/// Strip the packaged application executable.
+///
+/// If split debug files are enabled, the debug information is written next
+/// to the packaged executable, where debuggers look for it.
+fn strip_app_exe(
+    logger: &slog::Logger,
+    context: &BuildContext,
+    installed: &mut InstallManifest,
+    mode: StripMode,
+    data: &[u8],
+) -> Result<Vec<u8>, String> {
+    let debug = if context.config.build_config.split_debug {
+        let file_name = format!(
+            "{}.debug",
+            context.app_exe_path.file_name().unwrap().to_string_lossy()
+        );
+
+        Some((
+            context.app_exe_path.with_file_name(&file_name),
+            file_name,
+            debug_file(data)?,
+        ))
+    } else {
+        None
+    };
+
+    let stripped = strip_binary(
+        data,
+        mode,
+        debug
+            .as_ref()
+            .map(|(_, name, content)| (name.as_str(), content.as_slice())),
+    )?;
+
+    info!(
+        logger,
+        "stripped {} from {} to {} bytes",
+        context.app_exe_path.display(),
+        data.len(),
+        stripped.len()
+    );
+
+    if let Some((path, _, content)) = debug {
+        info!(logger, "writing debug file {}", path.display());
+        installed.write(&path, &content, None)?;
+    }
+
+    Ok(stripped)
+}
+
 /// Package a built Rust project into its packaging directory.
 ///
-/// This will delete all content in the application's package directory.
+/// Files written by the previous build are only rewritten if they changed,
+/// and files the previous build wrote but this one doesn't are removed.
+/// Without a record of the previous build, all content in the application's
+/// package directory is deleted.
 pub fn package_project(logger: &slog::Logger, context: &mut BuildContext) -> Result<(), String> {
     info!(
         logger,
@@ -2258,7 +4554,17 @@ pub fn package_project(logger: &slog::Logger, context: &mut BuildContext) -> Res
         context.app_path.display()
     );
 
-    if context.app_path.exists() {
+    // Applications are packaged into directories named after them, so the
+    // record of installed files is kept per application too.
+    let mut installed = InstallManifest::load(
+        &context
+            .build_path
+            .join("installed-files")
+            .join(format!("{}.json", context.app_name)),
+        &context.app_path,
+    )?;
+
+    if context.app_path.exists() && !installed.has_previous() {
         info!(logger, "purging {}", context.app_path.display());
         std::fs::remove_dir_all(&context.app_path).or_else(|e| Err(e.to_string()))?;
     }
@@ -2271,12 +4577,52 @@ pub fn package_project(logger: &slog::Logger, context: &mut BuildContext) -> Res
         context.app_exe_target_path.display(),
         context.app_exe_path.display()
     );
-    std::fs::copy(&context.app_exe_target_path, &context.app_exe_path)
+    let exe_data = fs::read(&context.app_exe_target_path)
         .or_else(|_| Err("failed to copy built application"))?;
 
+    let exe_data = if let Some(mode) = context.config.build_config.strip {
+        strip_app_exe(logger, context, &mut installed, mode, &exe_data)?
+    } else {
+        exe_data
+    };
+
+    installed.write(
+        &context.app_exe_path,
+        &exe_data,
+        file_mode(&context.app_exe_target_path),
+    )?;
+
     info!(logger, "resolving packaging state...");
     let state = context.get_packaging_state()?;
 
+    // Resolving Rust crates runs cargo, so only do it when needed.
+    let rust_crates = if context.config.license_policy.is_some()
+        || state
+            .third_party_notices
+            .as_ref()
+            .map(|n| n.rust_crates)
+            .unwrap_or(false)
+    {
+        rust_crate_components(
+            &context.project_path.join("Cargo.toml"),
+            &context.target_triple,
+        )?
+    } else {
+        Vec::new()
+    };
+
+    if let Some(policy) = &context.config.license_policy {
+        let mut components = state.licensed_components.clone();
+        components.extend(rust_crates.iter().cloned());
+
+        info!(
+            logger,
+            "verifying licenses of {} components",
+            components.iter().count()
+        );
+        policy.verify(&components)?;
+    }
+
     if let Some(licenses_path) = state.license_files_path {
         let licenses_path = if licenses_path.is_empty() {
             context.app_path.clone()
@@ -2288,11 +4634,32 @@ pub fn package_project(logger: &slog::Logger, context: &mut BuildContext) -> Res
             for li in lis {
                 let path = licenses_path.join(&li.license_filename);
                 info!(logger, "writing license for {} to {}", name, path.display());
-                fs::write(&path, li.license_text.as_bytes()).or_else(|e| Err(e.to_string()))?;
+                installed.write(&path, li.license_text.as_bytes(), None)?;
             }
         }
     }
 
+    if let Some(notices) = &state.third_party_notices {
+        let mut components = state.licensed_components.clone();
+
+        if notices.rust_crates {
+            components.extend(rust_crates.iter().cloned());
+        }
+
+        let path = context.app_path.join(&notices.path);
+        info!(
+            logger,
+            "writing notices for {} third-party components to {}",
+            components.iter().count(),
+            path.display()
+        );
+        installed.write(
+            &path,
+            render_notices(&components, notices.format).as_bytes(),
+            None,
+        )?;
+    }
+
     if !state.app_relative_resources.is_empty() {
         info!(
             logger,
@@ -2302,7 +4669,40 @@ pub fn package_project(logger: &slog::Logger, context: &mut BuildContext) -> Res
     }
 
     for (path, v) in &state.app_relative_resources {
-        install_app_relative(logger, context, path.as_str(), v).unwrap();
+        install_app_relative(logger, context, &mut installed, path.as_str(), v).unwrap();
+    }
+
+    for (source, dest) in &state.bundled_files {
+        let dest_path = context.app_exe_path.parent().unwrap().join(dest);
+        info!(
+            logger,
+            "installing {} to {}",
+            source.display(),
+            dest_path.display()
+        );
+        copy_bundled_file(&mut installed, source, &dest_path)?;
+    }
+
+    let written = installed.written;
+    let unchanged = installed.unchanged;
+    let removed = installed.finish()?;
+    info!(
+        logger,
+        "wrote {} files, left {} unchanged files, removed {} stale files",
+        written,
+        unchanged,
+        removed
+    );
+
+    // Verify after app-relative files are installed so libraries distributed
+    // with the application can be found.
+    if let Some(policy) = &context.config.binary_portability {
+        info!(
+            logger,
+            "verifying portability of {}",
+            context.app_exe_path.display()
+        );
+        verify_binary_portability(&context.app_exe_path, policy)?;
     }
 
     info!(
@@ -2381,7 +4781,7 @@ pub fn process_config(
     logger: &slog::Logger,
     context: &mut BuildContext,
     opt_level: &str,
-) -> EmbeddedPythonConfig {
+) -> Result<EmbeddedPythonConfig, String> {
     let mut cargo_metadata: Vec<String> = Vec::new();
 
     let config = &context.config;
@@ -2408,7 +4808,7 @@ pub fn process_config(
 
     // Obtain the configured Python distribution and parse it to a data structure.
     info!(logger, "resolving Python distribution...");
-    let python_distribution_path = resolve_python_distribution_archive(&config, &dest_dir);
+    let python_distribution_path = resolve_python_distribution_archive(logger, &config, &dest_dir);
     info!(
         logger,
         "Python distribution available at {}",
@@ -2429,7 +4829,7 @@ pub fn process_config(
         logger,
         "compiling custom importlib modules to support in-memory importing"
     );
-    let importlib = derive_importlib(&dist);
+    let importlib = derive_importlib(&config, &dist);
 
     let importlib_bootstrap_path = Path::new(&dest_dir).join("importlib_bootstrap");
     let mut fh = fs::File::create(&importlib_bootstrap_path).unwrap();
@@ -2497,9 +4897,12 @@ pub fn process_config(
     let module_names_path = Path::new(&dest_dir).join("py-module-names");
     let py_modules_path = Path::new(&dest_dir).join("py-modules");
     let resources_path = Path::new(&dest_dir).join("python-resources");
-    resources
-        .embedded
-        .write_blobs(&module_names_path, &py_modules_path, &resources_path);
+    resources.embedded.write_blobs(
+        &module_names_path,
+        &py_modules_path,
+        &resources_path,
+        config.build_config.compress_resources,
+    );
 
     info!(
         logger,
@@ -2534,16 +4937,26 @@ pub fn process_config(
         cargo_metadata.push(format!("cargo:rerun-if-changed={}", p.display()));
     }
 
+    let runtime_files = resolve_runtime_files(
+        logger,
+        &config,
+        &resources,
+        dest_dir,
+        &context.host_triple,
+        &context.target_triple,
+    );
+
     let python_config_rs = derive_python_config(
         &config,
         &importlib_bootstrap_path,
         &importlib_bootstrap_external_path,
         &py_modules_path,
         &resources_path,
-    );
+        &runtime_files,
+    )?;
 
     let dest_path = Path::new(&dest_dir).join("data.rs");
-    write_data_rs(&dest_path, &python_config_rs);
+    write_data_rs(&dest_path, &config, &python_config_rs);
     // Define the path to the written file in an environment variable so it can
     // be anywhere.
     cargo_metadata.push(format!(
@@ -2558,10 +4971,16 @@ pub fn process_config(
     fs::write(&cargo_metadata_path, cargo_metadata.join("\n").as_bytes())
         .expect("unable to write cargo_metadata.txt");
 
+    let mut licensed_components = libpython_info.licensed_components;
+    licensed_components.extend(resources.licensed_components.iter().cloned());
+
     let packaging_state = PackagingState {
         license_files_path: resources.license_files_path,
         license_infos: libpython_info.license_infos,
+        third_party_notices: resources.third_party_notices,
+        licensed_components,
         app_relative_resources: resources.app_relative,
+        bundled_files: runtime_files.bundled,
     };
 
     let packaging_state_path = dest_dir.join("packaging_state.cbor");
@@ -2577,7 +4996,7 @@ pub fn process_config(
 
     context.packaging_state = Some(packaging_state);
 
-    EmbeddedPythonConfig {
+    Ok(EmbeddedPythonConfig {
         config: config.clone(),
         python_distribution_path,
         importlib_bootstrap_path,
@@ -2589,7 +5008,7 @@ pub fn process_config(
         cargo_metadata,
         python_config_rs,
         packaging_state_path,
-    }
+    })
 }
 
 /// Find a pyoxidizer.toml configuration file by walking directory ancestry.
@@ -2643,6 +5062,7 @@ pub fn run_from_build(logger: &slog::Logger, build_script: &str) {
 
     let host = env::var("HOST").expect("HOST not defined");
     let target = env::var("TARGET").expect("TARGET not defined");
+    let logger = &logger.new(slog::o!("target" => target.clone()));
     let opt_level = env::var("OPT_LEVEL").expect("OPT_LEVEL not defined");
     let manifest_dir = env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR not found");
     let profile = env::var("PROFILE").expect("PROFILE not defined");
@@ -2674,7 +5094,10 @@ pub fn run_from_build(logger: &slog::Logger, build_script: &str) {
     )
     .unwrap();
 
-    for line in process_config(logger, &mut context, &opt_level).cargo_metadata {
+    let embedded_config = process_config(logger, &mut context, &opt_level)
+        .unwrap_or_else(|e| panic!("error processing config: {}", e));
+
+    for line in embedded_config.cargo_metadata {
         println!("{}", line);
     }
 }
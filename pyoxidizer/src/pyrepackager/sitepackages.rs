@@ -0,0 +1,368 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Scan existing site-packages directories.
+//!
+//! This allows packaging Python environments that weren't populated by
+//! PyOxidizer, such as a virtualenv a developer already uses. Every file in
+//! the directory is classified and attributed to the distribution that
+//! installed it, if known.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use super::distinfo::{find_dist_infos, DistInfo, InvalidDistInfo};
+use super::editable::{
+    find_editable_installs, is_editable_shim, EditableInstall, InvalidEditableInstall,
+};
+use super::fsscan::{find_python_resources, walk_tree_files, PythonResourceType};
+
+/// The classification of a file in a site-packages directory.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SitePackagesResourceType {
+    /// Python module source code.
+    Source,
+
+    /// Python module bytecode at the given optimization level.
+    Bytecode(i32),
+
+    /// A non-module file in a Python package.
+    Resource,
+
+    /// A compiled extension module.
+    ExtensionModule,
+
+    /// Packaging metadata in a ``.dist-info`` or ``.egg-info`` directory.
+    Metadata,
+
+    /// A shim pointing at a package installed in editable mode.
+    EditableShim,
+
+    /// A ``.pth`` file adding entries to ``sys.path``.
+    PathConfiguration,
+
+    /// A file that isn't a Python resource but is used by one.
+    ///
+    /// e.g. shared libraries vendored by ``auditwheel`` or ``delocate``,
+    /// which extension modules load at run-time.
+    DataFile,
+
+    /// A file that isn't part of a Python package and can't be imported.
+    Unknown,
+}
+
+/// A classified file in a site-packages directory.
+#[derive(Clone, Debug)]
+pub struct SitePackagesResource {
+    /// Name of the resource.
+    ///
+    /// For modules and extension modules, this is the full module name. For
+    /// resources, this is the name relative to `package`. For other types,
+    /// this is the path relative to the site-packages directory.
+    pub name: String,
+
+    /// Python package the resource belongs to.
+    ///
+    /// Empty for resources not belonging to a package.
+    pub package: String,
+
+    /// Path relative to the site-packages directory, with ``/`` separators.
+    pub relative_path: String,
+
+    /// Filesystem path to the file.
+    pub path: PathBuf,
+
+    /// The type of the resource.
+    pub flavor: SitePackagesResourceType,
+
+    /// Name of the distribution that installed the file, if known.
+    pub distribution: Option<String>,
+}
+
+/// The classified content of a site-packages directory.
+#[derive(Debug)]
+pub struct SitePackages {
+    /// Path to the site-packages directory.
+    pub path: PathBuf,
+
+    /// Parsed ``.dist-info`` directories.
+    pub distributions: Vec<DistInfo>,
+
+    /// ``.dist-info`` directories that couldn't be parsed.
+    ///
+    /// Their files are classified as metadata but files they installed
+    /// aren't attributed to any distribution.
+    pub invalid_distributions: Vec<InvalidDistInfo>,
+
+    /// Packages installed in editable mode.
+    ///
+    /// The source trees of these aren't scanned.
+    pub editable_installs: Vec<EditableInstall>,
+
+    /// Editable install shims that couldn't be resolved.
+    pub invalid_editable_installs: Vec<InvalidEditableInstall>,
+
+    /// All files, sorted by relative path.
+    pub resources: Vec<SitePackagesResource>,
+}
+
+impl SitePackages {
+    /// Obtain resources of a given type.
+    pub fn resources_of_type<'a>(
+        &'a self,
+        flavor: &'a SitePackagesResourceType,
+    ) -> impl Iterator<Item = &'a SitePackagesResource> {
+        self.resources.iter().filter(move |r| &r.flavor == flavor)
+    }
+
+    /// Obtain resources installed by a named distribution.
+    ///
+    /// Names are compared using their normalized form.
+    pub fn distribution_resources(&self, name: &str) -> Vec<&SitePackagesResource> {
+        let name = normalize_distribution_name(name);
+
+        self.resources
+            .iter()
+            .filter(|r| match &r.distribution {
+                Some(distribution) => normalize_distribution_name(distribution) == name,
+                None => false,
+            })
+            .collect()
+    }
+
+    /// Obtain importable resources not attributed to any distribution.
+    ///
+    /// These were likely copied into the directory by hand or installed by a
+    /// tool that doesn't write a ``RECORD``.
+    pub fn unowned_resources(&self) -> Vec<&SitePackagesResource> {
+        self.resources
+            .iter()
+            .filter(|r| {
+                r.distribution.is_none()
+                    && match r.flavor {
+                        SitePackagesResourceType::Source
+                        | SitePackagesResourceType::Bytecode(_)
+                        | SitePackagesResourceType::Resource
+                        | SitePackagesResourceType::ExtensionModule => true,
+                        _ => false,
+                    }
+            })
+            .collect()
+    }
+}
+
+/// Normalize a distribution name, per PEP 503.
+fn normalize_distribution_name(name: &str) -> String {
+    let mut res = String::with_capacity(name.len());
+
+    for c in name.chars() {
+        if c == '-' || c == '_' || c == '.' {
+            if !res.ends_with('-') {
+                res.push('-');
+            }
+        } else {
+            res.extend(c.to_lowercase());
+        }
+    }
+
+    res
+}
+
+/// Whether a file name is that of a compiled extension module.
+pub fn is_extension_module_file(name: &str) -> bool {
+    name.ends_with(".so") || name.ends_with(".pyd")
+}
+
+/// Whether a relative path is in a directory of vendored shared libraries.
+///
+/// ``auditwheel`` copies the shared libraries extension modules depend on
+/// into a ``<distribution>.libs`` directory (``<package>/.libs`` in older
+/// versions). ``delocate`` uses ``<package>/.dylibs``. These libraries
+/// aren't extension modules even though they have the same suffixes.
+fn is_vendored_libs_path(relative_path: &str) -> bool {
+    let mut parts: Vec<&str> = relative_path.split('/').collect();
+    parts.pop();
+
+    parts
+        .iter()
+        .any(|part| part.ends_with(".libs") || *part == ".dylibs")
+}
+
+/// Obtain the module name of an extension module from its relative path.
+///
+/// e.g. ``foo/_speedups.cpython-37m-x86_64-linux-gnu.so`` is
+/// ``foo._speedups``.
+fn extension_module_name(relative_path: &str) -> String {
+    let mut parts: Vec<&str> = relative_path.split('/').collect();
+    let file_name = parts.pop().unwrap_or("");
+    parts.push(file_name.split('.').next().unwrap_or(""));
+
+    parts.join(".")
+}
+
+fn relative_path_string(root: &Path, path: &Path) -> String {
+    let rel_path = path
+        .strip_prefix(root)
+        .expect("unable to strip path prefix");
+
+    rel_path
+        .iter()
+        .map(|p| p.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Scan a site-packages directory and classify its content.
+///
+/// Files are attributed to distributions using the ``RECORD`` of each
+/// ``.dist-info`` directory. Packages installed in editable mode are
+/// recorded but their source trees aren't scanned.
+pub fn scan_site_packages(path: &Path) -> Result<SitePackages, String> {
+    if !path.is_dir() {
+        return Err(format!("{} is not a directory", path.display()));
+    }
+
+    let dist_infos = find_dist_infos(path);
+    let distributions = dist_infos.distributions;
+    let invalid_distributions = dist_infos.invalid;
+    let editable = find_editable_installs(path);
+    let editable_installs = editable.installs;
+    let invalid_editable_installs = editable.invalid;
+
+    // Map of relative paths to the name of the distribution owning them.
+    let mut owners: HashMap<String, String> = HashMap::new();
+
+    for dist_info in &distributions {
+        let name = dist_info.metadata.name().unwrap_or("<unknown>").to_string();
+
+        if let Some(record) = &dist_info.record {
+            for entry in record {
+                let entry_path = entry.path.trim_start_matches("./").replace('\\', "/");
+                owners.insert(entry_path, name.clone());
+            }
+        }
+    }
+
+    let mut resources = Vec::new();
+
+    let metadata_dirs = distributions
+        .iter()
+        .map(|d| (&d.path, d.metadata.name().map(|s| s.to_string())))
+        .chain(invalid_distributions.iter().map(|d| (&d.path, None)));
+
+    for (dist_info_path, distribution) in metadata_dirs {
+        for entry in walk_tree_files(dist_info_path) {
+            let relative_path = relative_path_string(path, entry.path());
+
+            resources.push(SitePackagesResource {
+                name: relative_path.clone(),
+                package: "".to_string(),
+                relative_path,
+                path: entry.path().to_path_buf(),
+                flavor: SitePackagesResourceType::Metadata,
+                distribution: distribution.clone(),
+            });
+        }
+    }
+
+    // Resources are emitted after all modules. So by the time we see a
+    // resource, we know every package.
+    let mut packages = BTreeSet::new();
+
+    for resource in find_python_resources(path) {
+        let relative_path = relative_path_string(path, &resource.path);
+        let top_level = relative_path.split('/').next().unwrap_or("").to_string();
+        let is_top_level_file = !relative_path.contains('/');
+
+        let (flavor, name, package) = if top_level.ends_with(".egg-info") {
+            (
+                SitePackagesResourceType::Metadata,
+                relative_path.clone(),
+                "".to_string(),
+            )
+        } else if is_top_level_file && is_editable_shim(&relative_path) {
+            (
+                SitePackagesResourceType::EditableShim,
+                relative_path.clone(),
+                "".to_string(),
+            )
+        } else if is_top_level_file && relative_path.ends_with(".pth") {
+            (
+                SitePackagesResourceType::PathConfiguration,
+                relative_path.clone(),
+                "".to_string(),
+            )
+        } else if is_vendored_libs_path(&relative_path) {
+            (
+                SitePackagesResourceType::DataFile,
+                relative_path.clone(),
+                "".to_string(),
+            )
+        } else {
+            match resource.flavor {
+                PythonResourceType::Source
+                | PythonResourceType::Bytecode
+                | PythonResourceType::BytecodeOpt1
+                | PythonResourceType::BytecodeOpt2 => {
+                    let mut search: &str = &resource.full_name;
+                    packages.insert(search.to_string());
+                    while let Some(idx) = search.rfind('.') {
+                        search = &search[0..idx];
+                        packages.insert(search.to_string());
+                    }
+
+                    let flavor = match resource.flavor {
+                        PythonResourceType::Source => SitePackagesResourceType::Source,
+                        PythonResourceType::Bytecode => SitePackagesResourceType::Bytecode(0),
+                        PythonResourceType::BytecodeOpt1 => SitePackagesResourceType::Bytecode(1),
+                        _ => SitePackagesResourceType::Bytecode(2),
+                    };
+
+                    (flavor, resource.full_name.clone(), resource.package.clone())
+                }
+                PythonResourceType::Resource if is_extension_module_file(&relative_path) => {
+                    let name = extension_module_name(&relative_path);
+                    let package = match name.rfind('.') {
+                        Some(idx) => name[0..idx].to_string(),
+                        None => "".to_string(),
+                    };
+
+                    (SitePackagesResourceType::ExtensionModule, name, package)
+                }
+                PythonResourceType::Resource if packages.contains(&resource.package) => (
+                    SitePackagesResourceType::Resource,
+                    resource.stem.clone(),
+                    resource.package.clone(),
+                ),
+                _ => (
+                    SitePackagesResourceType::Unknown,
+                    relative_path.clone(),
+                    "".to_string(),
+                ),
+            }
+        };
+
+        let distribution = owners.get(&relative_path).cloned();
+
+        resources.push(SitePackagesResource {
+            name,
+            package,
+            relative_path,
+            path: resource.path.clone(),
+            flavor,
+            distribution,
+        });
+    }
+
+    resources.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+
+    Ok(SitePackages {
+        path: path.to_path_buf(),
+        distributions,
+        invalid_distributions,
+        editable_installs,
+        invalid_editable_installs,
+        resources,
+    })
+}
@@ -0,0 +1,666 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Install Python wheels without pip.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use super::super::analyze::binary_platform;
+use super::distinfo::parse_record;
+use super::fsscan::walk_tree_files;
+
+/// Describes the result of extracting a wheel.
+#[derive(Debug)]
+pub struct WheelInstall {
+    /// Name of the .dist-info directory in the wheel.
+    pub dist_info: String,
+
+    /// Compatibility tags from the WHEEL file. e.g. ``cp37-cp37m-manylinux1_x86_64``.
+    pub tags: Vec<String>,
+
+    /// Files that would be installed into the scripts directory.
+    pub scripts: Vec<String>,
+
+    /// Files that would be installed into the data directory.
+    pub data: Vec<String>,
+
+    /// Files that would be installed into the headers directory.
+    pub headers: Vec<String>,
+}
+
+/// Ensure a path in a wheel doesn't escape the directory it is installed to.
+fn validate_wheel_path(path: &str) -> Result<(), String> {
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(_) => {}
+            _ => return Err(format!("illegal path in wheel: {}", path)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Extract a wheel's importable files into a directory.
+///
+/// Files in the wheel's root and in its ``purelib`` and ``platlib``
+/// install schemes are written to `dest` as if it were a ``site-packages``
+/// directory. The wheel's ``.dist-info`` directory is written as well.
+/// Files in other install schemes aren't extracted and are instead
+/// reported in the returned value.
+///
+/// Every file in the wheel must be listed in its RECORD and its content
+/// must match the digest recorded there.
+pub fn extract_wheel(path: &Path, dest: &Path) -> Result<WheelInstall, String> {
+    let fh = fs::File::open(path).or_else(|e| Err(e.to_string()))?;
+    let mut archive =
+        zip::ZipArchive::new(fh).or_else(|e| Err(format!("{}: {}", path.display(), e)))?;
+
+    let mut files: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).or_else(|e| Err(e.to_string()))?;
+        let name = file.name().to_string();
+
+        if name.ends_with('/') {
+            continue;
+        }
+
+        validate_wheel_path(&name)?;
+
+        let mut data = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut data)
+            .or_else(|e| Err(format!("error reading {}: {}", name, e)))?;
+
+        files.insert(name, data);
+    }
+
+    let dist_infos: Vec<&str> = files
+        .keys()
+        .filter_map(|name| {
+            let mut parts = name.splitn(2, '/');
+            let dir = parts.next()?;
+
+            if dir.ends_with(".dist-info") && parts.next() == Some("WHEEL") {
+                Some(dir)
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let dist_info = match dist_infos.as_slice() {
+        [dist_info] => dist_info.to_string(),
+        [] => return Err(format!("{} has no .dist-info/WHEEL", path.display())),
+        _ => {
+            return Err(format!(
+                "{} has multiple .dist-info directories",
+                path.display()
+            ))
+        }
+    };
+
+    let wheel = String::from_utf8_lossy(&files[&format!("{}/WHEEL", dist_info)]).to_string();
+    let wheel_version = wheel
+        .lines()
+        .filter_map(|line| {
+            if line.starts_with("Wheel-Version:") {
+                Some(line["Wheel-Version:".len()..].trim())
+            } else {
+                None
+            }
+        })
+        .next()
+        .ok_or_else(|| format!("{} does not define Wheel-Version", path.display()))?;
+
+    if !wheel_version.starts_with("1.") {
+        return Err(format!("unsupported Wheel-Version: {}", wheel_version));
+    }
+
+    let record_path = format!("{}/RECORD", dist_info);
+    let record: BTreeMap<String, _> = match files.get(&record_path) {
+        Some(data) => parse_record(&String::from_utf8_lossy(data))?
+            .into_iter()
+            .map(|entry| (entry.path, entry.hash))
+            .collect(),
+        None => return Err(format!("{} has no RECORD", path.display())),
+    };
+
+    let data_prefix = format!(
+        "{}.data/",
+        &dist_info[0..dist_info.len() - ".dist-info".len()]
+    );
+
+    let tags = wheel
+        .lines()
+        .filter(|line| line.starts_with("Tag:"))
+        .map(|line| line["Tag:".len()..].trim().to_string())
+        .collect();
+
+    let mut res = WheelInstall {
+        dist_info: dist_info.clone(),
+        tags,
+        scripts: Vec::new(),
+        data: Vec::new(),
+        headers: Vec::new(),
+    };
+
+    for (name, data) in &files {
+        // RECORD can't contain its own digest. Signatures of RECORD are
+        // similarly unlisted.
+        let unlisted = name == &record_path
+            || name == &format!("{}.jws", record_path)
+            || name == &format!("{}.p7s", record_path);
+
+        match record.get(name) {
+            _ if unlisted => {}
+            Some(Some((algorithm, expected))) => {
+                // PEP 427 allows any hashlib algorithm at least as strong
+                // as sha256.
+                let digest = match algorithm.as_str() {
+                    "sha256" => Sha256::digest(data).to_vec(),
+                    "sha384" => Sha384::digest(data).to_vec(),
+                    "sha512" => Sha512::digest(data).to_vec(),
+                    _ => {
+                        return Err(format!(
+                            "unsupported RECORD hash for {}: {}",
+                            name, algorithm
+                        ));
+                    }
+                };
+
+                let digest = base64::encode_config(&digest, base64::URL_SAFE_NO_PAD);
+
+                if &digest != expected {
+                    return Err(format!("digest mismatch for {} in RECORD", name));
+                }
+            }
+            Some(None) => {
+                return Err(format!("{} has no digest in RECORD", name));
+            }
+            None => {
+                return Err(format!("{} is not listed in RECORD", name));
+            }
+        }
+
+        let install_path = if name.starts_with(&data_prefix) {
+            let mut parts = name[data_prefix.len()..].splitn(2, '/');
+            let scheme = parts.next().unwrap_or("");
+            let rest = parts.next().unwrap_or("");
+
+            match scheme {
+                "purelib" | "platlib" => rest,
+                "scripts" => {
+                    res.scripts.push(rest.to_string());
+                    continue;
+                }
+                "data" => {
+                    res.data.push(rest.to_string());
+                    continue;
+                }
+                "headers" => {
+                    res.headers.push(rest.to_string());
+                    continue;
+                }
+                _ => return Err(format!("unknown install scheme in wheel: {}", name)),
+            }
+        } else {
+            name.as_str()
+        };
+
+        let dest_path: PathBuf = dest.join(install_path);
+        let parent = dest_path
+            .parent()
+            .ok_or_else(|| format!("unable to resolve parent of {}", dest_path.display()))?;
+        fs::create_dir_all(parent).or_else(|e| Err(e.to_string()))?;
+        fs::write(&dest_path, data)
+            .or_else(|e| Err(format!("unable to write {}: {}", dest_path.display(), e)))?;
+    }
+
+    Ok(res)
+}
+
+/// Describes the platform of a Rust target triple in wheel terms.
+struct TargetPlatform {
+    /// Architecture. e.g. ``x86_64``.
+    arch: String,
+
+    /// ``linux``, ``macos``, or ``windows``.
+    os: &'static str,
+
+    /// Whether the target uses musl libc.
+    musl: bool,
+}
+
+impl TargetPlatform {
+    fn from_triple(target: &str) -> Result<TargetPlatform, String> {
+        let arch = normalize_arch(target.split('-').next().unwrap_or(""));
+
+        let os = if target.contains("-linux-") {
+            "linux"
+        } else if target.contains("-apple-darwin") {
+            "macos"
+        } else if target.contains("-windows-") {
+            "windows"
+        } else {
+            return Err(format!(
+                "unsupported target for wheel validation: {}",
+                target
+            ));
+        };
+
+        Ok(TargetPlatform {
+            arch,
+            os,
+            // e.g. ``-musl``, ``-musleabi``, ``-musleabihf``.
+            musl: target.contains("-musl"),
+        })
+    }
+
+    fn object_format(&self) -> &'static str {
+        match self.os {
+            "linux" => "elf",
+            "macos" => "mach-o",
+            _ => "pe",
+        }
+    }
+}
+
+/// Normalize an architecture name to Rust target triple naming.
+fn normalize_arch(arch: &str) -> String {
+    match arch {
+        "i386" | "i586" | "i686" | "x86" => "x86".to_string(),
+        "amd64" => "x86_64".to_string(),
+        "arm64" => "aarch64".to_string(),
+        _ if arch.starts_with("armv7") => "arm".to_string(),
+        _ => arch.to_string(),
+    }
+}
+
+/// Obtain the macOS deployment target being built for, if known.
+fn macos_deployment_target() -> Option<(u32, u32)> {
+    parse_macos_version(&env::var("MACOSX_DEPLOYMENT_TARGET").ok()?)
+}
+
+fn parse_macos_version(value: &str) -> Option<(u32, u32)> {
+    let mut parts = value.split(|c| c == '.' || c == '_');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().unwrap_or("0").parse().ok()?;
+
+    Some((major, minor))
+}
+
+/// Determine whether a wheel platform tag is compatible with a target.
+///
+/// Returns an error message describing the incompatibility if not.
+fn check_platform_tag(platform: &str, target: &TargetPlatform) -> Result<(), String> {
+    if platform == "any" {
+        return Ok(());
+    }
+
+    let (os, arches) = if platform == "win32" {
+        ("windows", vec!["x86".to_string()])
+    } else if platform.starts_with("win_") {
+        ("windows", vec![normalize_arch(&platform[4..])])
+    } else if platform.starts_with("macosx_") {
+        // macosx_<major>_<minor>_<arch>
+        let parts: Vec<&str> = platform.splitn(4, '_').collect();
+        if parts.len() != 4 {
+            return Err(format!("malformed platform tag {}", platform));
+        }
+
+        let version = parse_macos_version(&format!("{}.{}", parts[1], parts[2]))
+            .ok_or_else(|| format!("malformed platform tag {}", platform))?;
+
+        if let Some(deployment_target) = macos_deployment_target() {
+            if version > deployment_target {
+                return Err(format!(
+                    "{} requires macOS {}.{} but MACOSX_DEPLOYMENT_TARGET is {}.{}",
+                    platform, version.0, version.1, deployment_target.0, deployment_target.1
+                ));
+            }
+        }
+
+        let arches = match parts[3] {
+            "universal2" => vec!["x86_64".to_string(), "aarch64".to_string()],
+            "intel" => vec!["x86_64".to_string(), "x86".to_string()],
+            arch => vec![normalize_arch(arch)],
+        };
+
+        ("macos", arches)
+    } else if platform.starts_with("manylinux") || platform.starts_with("linux_") {
+        if target.musl {
+            return Err(format!(
+                "{} requires glibc but the target uses musl libc",
+                platform
+            ));
+        }
+
+        ("linux", vec![normalize_arch(platform_arch(platform))])
+    } else if platform.starts_with("musllinux_") {
+        if !target.musl {
+            return Err(format!(
+                "{} requires musl libc but the target uses glibc",
+                platform
+            ));
+        }
+
+        ("linux", vec![normalize_arch(platform_arch(platform))])
+    } else {
+        return Err(format!("unknown platform tag {}", platform));
+    };
+
+    if os != target.os {
+        return Err(format!("{} is for {}, not {}", platform, os, target.os));
+    }
+
+    if !arches.contains(&target.arch) {
+        return Err(format!(
+            "{} is for {}, not {}",
+            platform,
+            arches.join("/"),
+            target.arch
+        ));
+    }
+
+    Ok(())
+}
+
+/// Obtain the architecture component of a Linux platform tag.
+///
+/// e.g. ``manylinux_2_17_x86_64`` is ``x86_64``.
+fn platform_arch(platform: &str) -> &str {
+    for arch in &[
+        "x86_64", "i686", "aarch64", "armv7l", "ppc64le", "ppc64", "s390x",
+    ] {
+        if platform.ends_with(arch) {
+            return &platform[platform.len() - arch.len()..];
+        }
+    }
+
+    platform.rsplit('_').next().unwrap_or("")
+}
+
+/// Verify a wheel's content can be used on a target.
+///
+/// The wheel's platform tags are checked against the Rust target triple
+/// being built. Then native binaries extracted to `dest` are checked for
+/// the right object file format, architecture, and minimum macOS version.
+/// Errors describe what is wrong so a suitable wheel can be found.
+///
+/// Pure Python wheels (only ``any`` platform tags and no native binaries)
+/// are compatible with every target, including ones whose platform can't be
+/// resolved.
+pub fn validate_wheel_target(
+    install: &WheelInstall,
+    dest: &Path,
+    target: &str,
+) -> Result<(), String> {
+    // A tag may hold multiple compressed platforms. e.g.
+    // ``manylinux_2_17_x86_64.manylinux2014_x86_64``.
+    let platforms: Vec<&str> = install
+        .tags
+        .iter()
+        .filter_map(|tag| tag.splitn(3, '-').nth(2))
+        .flat_map(|platforms| platforms.split('.'))
+        .collect();
+
+    let native_paths: Vec<PathBuf> = walk_tree_files(dest)
+        .map(|entry| entry.path().to_path_buf())
+        .filter(|path| {
+            let name = path.to_string_lossy();
+            name.ends_with(".so") || name.ends_with(".pyd") || name.ends_with(".dylib")
+        })
+        .collect();
+
+    if platforms.iter().all(|p| *p == "any") && native_paths.is_empty() {
+        return Ok(());
+    }
+
+    let target_platform = TargetPlatform::from_triple(target)?;
+
+    if !platforms.is_empty() {
+        let errors: Vec<String> = platforms
+            .iter()
+            .filter_map(|p| check_platform_tag(p, &target_platform).err())
+            .collect();
+
+        if errors.len() == platforms.len() {
+            return Err(format!(
+                "{} is not compatible with target {}: {}; use a wheel built for the target",
+                install.dist_info,
+                target,
+                errors.join("; ")
+            ));
+        }
+    }
+
+    for path in &native_paths {
+        let data =
+            fs::read(path).or_else(|e| Err(format!("error reading {}: {}", path.display(), e)))?;
+        let rel_path = path.strip_prefix(dest).unwrap_or(path).display();
+
+        let platform = match binary_platform(&data) {
+            Some(platform) => platform,
+            None => {
+                return Err(format!(
+                    "{} in {} is not a recognized binary",
+                    rel_path, install.dist_info
+                ))
+            }
+        };
+
+        if platform.format != target_platform.object_format() {
+            return Err(format!(
+                "{} in {} is a {} binary but target {} needs {} binaries",
+                rel_path,
+                install.dist_info,
+                platform.format,
+                target,
+                target_platform.object_format()
+            ));
+        }
+
+        if !platform.architectures.contains(&target_platform.arch) {
+            return Err(format!(
+                "{} in {} is built for {} but target {} is {}",
+                rel_path,
+                install.dist_info,
+                platform.architectures.join("/"),
+                target,
+                target_platform.arch
+            ));
+        }
+
+        if let (Some(version), Some(deployment_target)) =
+            (platform.macos_minimum_version, macos_deployment_target())
+        {
+            if version > deployment_target {
+                return Err(format!(
+                    "{} in {} requires macOS {}.{} but MACOSX_DEPLOYMENT_TARGET is {}.{}; \
+                     use a wheel built for an older macOS or raise the deployment target",
+                    rel_path,
+                    install.dist_info,
+                    version.0,
+                    version.1,
+                    deployment_target.0,
+                    deployment_target.1
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const WHEEL: &[u8] =
+        b"Wheel-Version: 1.0\nGenerator: test\nRoot-Is-Purelib: true\nTag: py3-none-any\n";
+
+    /// Obtain the RECORD hash of data.
+    fn record_hash(data: &[u8]) -> String {
+        format!(
+            "sha256={}",
+            base64::encode_config(&Sha256::digest(data), base64::URL_SAFE_NO_PAD)
+        )
+    }
+
+    /// Write a wheel with the given files and RECORD content.
+    fn write_wheel(dir: &Path, files: &[(&str, &[u8])], record: &str) -> PathBuf {
+        let path = dir.join("foo-1.0-py3-none-any.whl");
+        let mut zf = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+
+        let mut entries = files.to_vec();
+        entries.push(("foo-1.0.dist-info/WHEEL", WHEEL));
+        entries.push(("foo-1.0.dist-info/RECORD", record.as_bytes()));
+
+        for (name, data) in entries {
+            zf.start_file(name, zip::write::FileOptions::default())
+                .unwrap();
+            zf.write_all(data).unwrap();
+        }
+
+        zf.finish().unwrap();
+
+        path
+    }
+
+    /// Produce a valid RECORD for files, listing paths as given.
+    fn record(files: &[(&str, &[u8])]) -> String {
+        let mut lines = files
+            .iter()
+            .map(|(path, data)| format!("{},{},{}", path, record_hash(data), data.len()))
+            .collect::<Vec<_>>();
+
+        lines.push(format!(
+            "foo-1.0.dist-info/WHEEL,{},{}",
+            record_hash(WHEEL),
+            WHEEL.len()
+        ));
+        lines.push("foo-1.0.dist-info/RECORD,,".to_string());
+
+        lines.join("\n") + "\n"
+    }
+
+    /// Write a wheel to a directory and extract it to its ``site-packages``.
+    fn extract(dir: &Path, files: &[(&str, &[u8])], record: &str) -> Result<WheelInstall, String> {
+        let wheel = write_wheel(dir, files, record);
+
+        extract_wheel(&wheel, &dir.join("site-packages"))
+    }
+
+    #[test]
+    fn extract_valid_wheel() {
+        let files: &[(&str, &[u8])] = &[
+            ("foo/__init__.py", b"import os\n"),
+            ("foo-1.0.data/purelib/foo/extra.py", b"X = 1\n"),
+            ("foo-1.0.data/scripts/foo", b"#!python\n"),
+            ("foo-1.0.data/data/share/foo.txt", b"data"),
+        ];
+
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-wheel-test").unwrap();
+        let install = extract(temp_dir.path(), files, &record(files)).unwrap();
+        let dest = temp_dir.path().join("site-packages");
+
+        assert_eq!(install.dist_info, "foo-1.0.dist-info");
+        assert_eq!(install.tags, vec!["py3-none-any"]);
+        assert_eq!(install.scripts, vec!["foo"]);
+        assert_eq!(install.data, vec!["share/foo.txt"]);
+        assert!(install.headers.is_empty());
+
+        assert_eq!(
+            fs::read(dest.join("foo/__init__.py")).unwrap(),
+            b"import os\n"
+        );
+        assert_eq!(fs::read(dest.join("foo/extra.py")).unwrap(), b"X = 1\n");
+        assert!(dest.join("foo-1.0.dist-info/RECORD").is_file());
+        assert!(!dest.join("foo-1.0.data").exists());
+    }
+
+    #[test]
+    fn extract_quoted_record_paths() {
+        let files: &[(&str, &[u8])] =
+            &[("foo/a,b.txt", b"comma"), ("foo/say \"hi\".txt", b"quote")];
+
+        let record = record(&[
+            ("\"foo/a,b.txt\"", files[0].1),
+            ("\"foo/say \"\"hi\"\".txt\"", files[1].1),
+        ]);
+
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-wheel-test").unwrap();
+        extract(temp_dir.path(), files, &record).unwrap();
+        let dest = temp_dir.path().join("site-packages");
+
+        assert_eq!(fs::read(dest.join("foo/a,b.txt")).unwrap(), b"comma");
+        assert_eq!(fs::read(dest.join("foo/say \"hi\".txt")).unwrap(), b"quote");
+    }
+
+    #[test]
+    fn extract_bad_record_rows() {
+        let files: &[(&str, &[u8])] = &[("foo/__init__.py", b"")];
+        let hash = record_hash(b"");
+
+        for (line, error) in &[
+            (format!("foo/__init__.py,{}", hash), "malformed RECORD line"),
+            (
+                format!("foo/__init__.py,{},0,extra", hash),
+                "malformed RECORD line",
+            ),
+            (
+                "foo/__init__.py,sha256,0".to_string(),
+                "malformed RECORD hash",
+            ),
+            (
+                format!("foo/__init__.py,{},zero", hash),
+                "malformed RECORD size",
+            ),
+            (
+                "foo/__init__.py,,".to_string(),
+                "foo/__init__.py has no digest in RECORD",
+            ),
+            (
+                "foo/__init__.py,sha256=AAAA,0".to_string(),
+                "digest mismatch for foo/__init__.py in RECORD",
+            ),
+            (
+                "foo/__init__.py,md5=1B2M2Y8AsgTpgAmY7PhCfg,0".to_string(),
+                "unsupported RECORD hash for foo/__init__.py: md5",
+            ),
+            (
+                format!("foo/other.py,{},0", hash),
+                "foo/__init__.py is not listed in RECORD",
+            ),
+        ] {
+            let temp_dir = tempdir::TempDir::new("pyoxidizer-wheel-test").unwrap();
+            let record = format!("{}\n{}", line, record(&[]));
+
+            match extract(temp_dir.path(), files, &record) {
+                Ok(_) => panic!("{} should be rejected", line),
+                Err(e) => assert!(e.starts_with(error), "{}: {}", line, e),
+            }
+        }
+    }
+
+    #[test]
+    fn extract_rejects_escaping_paths() {
+        for name in &["../evil.py", "/evil.py", "foo/../../evil.py"] {
+            let temp_dir = tempdir::TempDir::new("pyoxidizer-wheel-test").unwrap();
+            let files: &[(&str, &[u8])] = &[(name, b"")];
+
+            assert_eq!(
+                extract(temp_dir.path(), files, &record(files)).err(),
+                Some(format!("illegal path in wheel: {}", name))
+            );
+        }
+    }
+}
@@ -5,41 +5,341 @@
 //! Defines known Python distributions.
 
 use lazy_static::lazy_static;
-use std::collections::BTreeMap;
+use std::cmp::Ordering;
 
 /// Describes a Python distribution available at a URL.
 pub struct HostedDistribution {
+    /// Rust target triple the distribution is built for.
+    pub target_triple: String,
+
     pub url: String,
     pub sha256: String,
+
+    /// Version of Python in the distribution.
+    pub python_version: String,
+
+    /// The kind of distribution.
+    ///
+    /// Distributions produced by python-build-standalone are ``standalone``.
+    pub flavor: String,
 }
 
 lazy_static! {
-    pub static ref CPYTHON_BY_TRIPLE: BTreeMap<&'static str, HostedDistribution> = {
-        let mut res: BTreeMap<&'static str, HostedDistribution> = BTreeMap::new();
-
-        res.insert("x86_64-unknown-linux-gnu", HostedDistribution {
+    pub static ref CPYTHON_DISTRIBUTIONS: Vec<HostedDistribution> = vec![
+        HostedDistribution {
+            target_triple: String::from("x86_64-unknown-linux-gnu"),
             url: String::from("https://github.com/indygreg/python-build-standalone/releases/download/20190617/cpython-3.7.3-linux64-20190618T0324.tar.zst"),
             sha256: String::from("d6b80a9723c124d6d193f8816fdb874ba6d56abfb35cbfcc2b27de53176d0620"),
-        });
-
-        res.insert("x86_64-unknown-linux-musl", HostedDistribution {
+            python_version: String::from("3.7.3"),
+            flavor: String::from("standalone"),
+        },
+        HostedDistribution {
+            target_triple: String::from("x86_64-unknown-linux-musl"),
             url: String::from("https://github.com/indygreg/python-build-standalone/releases/download/20190617/cpython-3.7.3-linux64-musl-20190618T0400.tar.zst"),
             sha256: String::from("2be2d109b82634b36685b89800887501b619ef946dda182e5a8ab5c7029a8136"),
-        });
-
-        res.insert(
-            "x86_64-pc-windows-msvc",
-            HostedDistribution {
-                url: String::from("https://github.com/indygreg/python-build-standalone/releases/download/20190617/cpython-3.7.3-windows-amd64-20190618T0516.tar.zst"),
-                sha256: String::from("fd43554b5654a914846cf1c251d1ad366f46c7c4d20b7c44572251b533351221"),
-            },
-        );
-
-        res.insert("x86_64-apple-darwin", HostedDistribution {
+            python_version: String::from("3.7.3"),
+            flavor: String::from("standalone"),
+        },
+        HostedDistribution {
+            target_triple: String::from("x86_64-pc-windows-msvc"),
+            url: String::from("https://github.com/indygreg/python-build-standalone/releases/download/20190617/cpython-3.7.3-windows-amd64-20190618T0516.tar.zst"),
+            sha256: String::from("fd43554b5654a914846cf1c251d1ad366f46c7c4d20b7c44572251b533351221"),
+            python_version: String::from("3.7.3"),
+            flavor: String::from("standalone"),
+        },
+        HostedDistribution {
+            target_triple: String::from("x86_64-apple-darwin"),
             url: String::from("https://github.com/indygreg/python-build-standalone/releases/download/20190617/cpython-3.7.3-macos-20190618T0523.tar.zst"),
             sha256: String::from("6668202a3225892ce252eff4bb53a58ac058b6a413ab9d37c026a500c2a561ee"),
-        });
+            python_version: String::from("3.7.3"),
+            flavor: String::from("standalone"),
+        },
+    ];
+}
+
+/// Parse a dotted version string into its numeric components.
+fn parse_version(s: &str) -> Result<Vec<u64>, String> {
+    s.split('.')
+        .map(|part| {
+            part.parse::<u64>()
+                .or_else(|_| Err(format!("invalid version: {}", s)))
+        })
+        .collect()
+}
+
+/// Compare versions, treating missing components as 0.
+fn compare_versions(a: &[u64], b: &[u64]) -> Ordering {
+    for i in 0..std::cmp::max(a.len(), b.len()) {
+        let ordering = a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0));
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    Ordering::Equal
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum VersionOperator {
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Compatible,
+    Prefix,
+}
+
+/// A clause of a version constraint.
+#[derive(Clone, Debug)]
+struct VersionClause {
+    operator: VersionOperator,
+    version: Vec<u64>,
+    wildcard: bool,
+}
+
+impl VersionClause {
+    fn parse(s: &str) -> Result<VersionClause, String> {
+        let s = s.trim();
+
+        let operators = [
+            ("~=", VersionOperator::Compatible),
+            ("==", VersionOperator::Equal),
+            ("!=", VersionOperator::NotEqual),
+            (">=", VersionOperator::GreaterEqual),
+            ("<=", VersionOperator::LessEqual),
+            (">", VersionOperator::Greater),
+            ("<", VersionOperator::Less),
+        ];
+
+        let (operator, version) = operators
+            .iter()
+            .find(|(prefix, _)| s.starts_with(prefix))
+            .map(|(prefix, operator)| (operator.clone(), s[prefix.len()..].trim()))
+            .unwrap_or((VersionOperator::Prefix, s));
+
+        let (version, wildcard) = if version.ends_with(".*") {
+            (&version[0..version.len() - 2], true)
+        } else {
+            (version, false)
+        };
+
+        if wildcard
+            && operator != VersionOperator::Equal
+            && operator != VersionOperator::NotEqual
+            && operator != VersionOperator::Prefix
+        {
+            return Err(format!("wildcard not allowed in version clause: {}", s));
+        }
+
+        let version = parse_version(version)?;
+
+        if operator == VersionOperator::Compatible && version.len() < 2 {
+            return Err(format!(
+                "~= requires a version with at least 2 components: {}",
+                s
+            ));
+        }
+
+        Ok(VersionClause {
+            operator,
+            version,
+            wildcard,
+        })
+    }
+
+    fn matches_prefix(&self, version: &[u64]) -> bool {
+        version.len() >= self.version.len() && version[0..self.version.len()] == self.version[..]
+    }
+
+    fn matches(&self, version: &[u64]) -> bool {
+        let ordering = compare_versions(version, &self.version);
 
-        res
+        match self.operator {
+            VersionOperator::Equal if self.wildcard => self.matches_prefix(version),
+            VersionOperator::Equal => ordering == Ordering::Equal,
+            VersionOperator::NotEqual if self.wildcard => !self.matches_prefix(version),
+            VersionOperator::NotEqual => ordering != Ordering::Equal,
+            VersionOperator::Greater => ordering == Ordering::Greater,
+            VersionOperator::GreaterEqual => ordering != Ordering::Less,
+            VersionOperator::Less => ordering == Ordering::Less,
+            VersionOperator::LessEqual => ordering != Ordering::Greater,
+            VersionOperator::Compatible => {
+                ordering != Ordering::Less
+                    && version.len() >= self.version.len() - 1
+                    && version[0..self.version.len() - 1] == self.version[0..self.version.len() - 1]
+            }
+            VersionOperator::Prefix => self.matches_prefix(version),
+        }
+    }
+}
+
+/// A constraint on the Python version of a distribution.
+///
+/// Constraints are comma-delimited clauses, all of which must match. Clauses
+/// use the operators from PEP 440 (``==``, ``!=``, ``<``, ``<=``, ``>``,
+/// ``>=``, and ``~=``). ``==`` and ``!=`` accept a trailing ``.*`` to match
+/// versions by prefix. A version without an operator also matches by prefix,
+/// so ``3.7`` matches ``3.7.3``. ``*`` matches all versions.
+#[derive(Clone, Debug)]
+pub struct VersionConstraint {
+    clauses: Vec<VersionClause>,
+}
+
+impl VersionConstraint {
+    pub fn parse(s: &str) -> Result<VersionConstraint, String> {
+        if s.trim() == "*" {
+            return Ok(VersionConstraint {
+                clauses: Vec::new(),
+            });
+        }
+
+        let clauses = s
+            .split(',')
+            .map(VersionClause::parse)
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(VersionConstraint { clauses })
+    }
+
+    /// Whether a version string satisfies this constraint.
+    ///
+    /// Versions that can't be parsed never match.
+    pub fn matches(&self, version: &str) -> bool {
+        match parse_version(version) {
+            Ok(version) => self.clauses.iter().all(|c| c.matches(&version)),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Find the best known distribution for a target.
+///
+/// The distribution with the newest Python version satisfying `constraint`
+/// is chosen. If `flavors` is not empty, only distributions of the listed
+/// flavors are considered and, between distributions of the same version,
+/// flavors listed earlier are preferred.
+pub fn find_distribution(
+    target_triple: &str,
+    constraint: &VersionConstraint,
+    flavors: &[String],
+) -> Option<&'static HostedDistribution> {
+    let flavor_rank = |dist: &HostedDistribution| {
+        if flavors.is_empty() {
+            Some(0)
+        } else {
+            flavors.iter().position(|f| f == &dist.flavor)
+        }
     };
+
+    CPYTHON_DISTRIBUTIONS
+        .iter()
+        .filter(|dist| dist.target_triple == target_triple)
+        .filter(|dist| constraint.matches(&dist.python_version))
+        .filter_map(|dist| flavor_rank(dist).map(|rank| (dist, rank)))
+        .max_by(|(a, a_rank), (b, b_rank)| {
+            compare_versions(
+                &parse_version(&a.python_version).unwrap_or_default(),
+                &parse_version(&b.python_version).unwrap_or_default(),
+            )
+            .then(b_rank.cmp(a_rank))
+        })
+        .map(|(dist, _)| dist)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Obtain the versions a constraint matches.
+    fn matching(constraint: &str, versions: &[&'static str]) -> Vec<&'static str> {
+        let constraint = VersionConstraint::parse(constraint).unwrap();
+
+        versions
+            .iter()
+            .filter(|v| constraint.matches(v))
+            .cloned()
+            .collect()
+    }
+
+    const VERSIONS: &[&str] = &["3.6.8", "3.7", "3.7.0", "3.7.3", "3.7.10", "3.8.0", "4.0"];
+
+    #[test]
+    fn version_constraint_parse_errors() {
+        for value in &[
+            "",
+            "3.x",
+            "3..7",
+            ">= 3.7,",
+            "=>3.7",
+            ">=3.7.*",
+            "~=3.7.*",
+            "~=3",
+            "3.7.3-rc1",
+        ] {
+            assert!(VersionConstraint::parse(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn version_constraint_prefix() {
+        assert_eq!(matching("*", VERSIONS), VERSIONS.to_vec());
+        assert_eq!(matching(" * ", VERSIONS), VERSIONS.to_vec());
+        assert_eq!(
+            matching("3.7", VERSIONS),
+            vec!["3.7", "3.7.0", "3.7.3", "3.7.10"]
+        );
+        assert_eq!(matching("3.7.1", VERSIONS), Vec::<&str>::new());
+        assert_eq!(
+            matching("3", VERSIONS),
+            vec!["3.6.8", "3.7", "3.7.0", "3.7.3", "3.7.10", "3.8.0"]
+        );
+        assert_eq!(matching("3.7.*", VERSIONS), matching("3.7", VERSIONS));
+    }
+
+    #[test]
+    fn version_constraint_operators() {
+        // Missing components compare as 0.
+        assert_eq!(matching("==3.7", VERSIONS), vec!["3.7", "3.7.0"]);
+        assert_eq!(
+            matching("==3.7.*", VERSIONS),
+            vec!["3.7", "3.7.0", "3.7.3", "3.7.10"]
+        );
+        assert_eq!(matching("!=3.7.*", VERSIONS), vec!["3.6.8", "3.8.0", "4.0"]);
+        assert_eq!(
+            matching("!=3.7.3", VERSIONS),
+            vec!["3.6.8", "3.7", "3.7.0", "3.7.10", "3.8.0", "4.0"]
+        );
+        assert_eq!(matching(">3.7.3", VERSIONS), vec!["3.7.10", "3.8.0", "4.0"]);
+        assert_eq!(
+            matching(">=3.7.3", VERSIONS),
+            vec!["3.7.3", "3.7.10", "3.8.0", "4.0"]
+        );
+        assert_eq!(matching("<3.7", VERSIONS), vec!["3.6.8"]);
+        assert_eq!(matching("<=3.7", VERSIONS), vec!["3.6.8", "3.7", "3.7.0"]);
+        assert_eq!(matching("~=3.7.2", VERSIONS), vec!["3.7.3", "3.7.10"]);
+        assert_eq!(
+            matching("~=3.7", VERSIONS),
+            vec!["3.7", "3.7.0", "3.7.3", "3.7.10", "3.8.0"]
+        );
+    }
+
+    #[test]
+    fn version_constraint_clauses() {
+        assert_eq!(
+            matching(">=3.7, <3.8", VERSIONS),
+            vec!["3.7", "3.7.0", "3.7.3", "3.7.10"]
+        );
+        assert_eq!(
+            matching(">= 3.7 , != 3.7.3,<4", VERSIONS),
+            vec!["3.7", "3.7.0", "3.7.10", "3.8.0"]
+        );
+        assert_eq!(matching(">3.8, <3.7", VERSIONS), Vec::<&str>::new());
+
+        let constraint = VersionConstraint::parse(">=3.7").unwrap();
+        assert!(!constraint.matches("3.8.0rc1"));
+        assert!(!constraint.matches(""));
+    }
 }
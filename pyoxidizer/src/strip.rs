@@ -0,0 +1,1343 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Strip symbols and debug information from binaries.
+//!
+//! This is implemented without external tools so stripping doesn't depend on
+//! ``strip`` or ``objcopy`` being installed or supporting the target
+//! platform, which is often not the case when cross-compiling.
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::collections::{BTreeSet, HashMap};
+
+/// What to remove from a binary.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StripMode {
+    /// Remove debug information. Symbol tables are retained.
+    Debug,
+
+    /// Remove debug information and symbols not needed at run-time.
+    All,
+}
+
+impl StripMode {
+    /// Resolve a mode from its name in config files.
+    ///
+    /// Returns None for ``none``.
+    pub fn from_name(value: &str) -> Result<Option<StripMode>, String> {
+        match value {
+            "none" => Ok(None),
+            "debug" => Ok(Some(StripMode::Debug)),
+            "all" => Ok(Some(StripMode::All)),
+            _ => Err(format!(
+                "invalid strip value: {}; must be none, debug, or all",
+                value
+            )),
+        }
+    }
+}
+
+/// Reads and writes integers of a binary's byte order.
+#[derive(Clone, Copy)]
+struct Endian {
+    little: bool,
+}
+
+impl Endian {
+    fn read(self, data: &[u8], offset: usize, size: usize) -> Result<u64, String> {
+        let v = data
+            .get(offset..offset + size)
+            .ok_or_else(|| format!("data truncated at offset {}", offset))?;
+
+        Ok(if self.little {
+            LittleEndian::read_uint(v, size)
+        } else {
+            BigEndian::read_uint(v, size)
+        })
+    }
+
+    fn write(self, data: &mut [u8], offset: usize, size: usize, value: u64) {
+        let v = &mut data[offset..offset + size];
+
+        if self.little {
+            LittleEndian::write_uint(v, value, size);
+        } else {
+            BigEndian::write_uint(v, value, size);
+        }
+    }
+
+    fn push(self, data: &mut Vec<u8>, size: usize, value: u64) {
+        let offset = data.len();
+        data.resize(offset + size, 0);
+        self.write(data, offset, size, value);
+    }
+}
+
+fn pad_to(data: &mut Vec<u8>, alignment: usize) {
+    if alignment > 1 && data.len() % alignment != 0 {
+        let len = data.len() + alignment - data.len() % alignment;
+        data.resize(len, 0);
+    }
+}
+
+/// Strip a binary.
+///
+/// ELF, Mach-O (including universal binaries), and PE binaries are
+/// supported. Binaries that are code signed can't be stripped, as doing so
+/// would invalidate the signature.
+///
+/// If `debug_file` is defined, ELF binaries get a ``.gnu_debuglink``
+/// section referring to a debug file with the given name and content, as
+/// produced by `debug_file()`. Debuggers use this to find debug
+/// information. Callers are responsible for writing that file.
+pub fn strip_binary(
+    data: &[u8],
+    mode: StripMode,
+    debug_file: Option<(&str, &[u8])>,
+) -> Result<Vec<u8>, String> {
+    let debug_link = debug_file.map(|(name, content)| (name, crc32(content)));
+
+    match goblin::Object::parse(data) {
+        Ok(goblin::Object::Elf(elf)) => strip_elf(data, &elf, mode, debug_link),
+        Ok(goblin::Object::PE(_)) => strip_pe(data, mode),
+        Ok(goblin::Object::Mach(goblin::mach::Mach::Binary(_))) => strip_macho(data, mode),
+        Ok(goblin::Object::Mach(goblin::mach::Mach::Fat(_))) => strip_fat_macho(data, mode),
+        _ => Err("not an ELF, Mach-O, or PE binary".to_string()),
+    }
+}
+
+/// Produce the content of a split debug file for a binary.
+///
+/// For ELF binaries, this is the equivalent of ``objcopy --only-keep-debug``:
+/// sections loaded at run-time keep their headers but lose their data,
+/// except for notes, which hold the build ID. Debug sections and symbol
+/// tables are retained.
+///
+/// Mach-O and PE binaries are returned unchanged. Their debug information
+/// normally lives in separate ``.dSYM`` bundles or PDB files that the
+/// binary refers to, so there is little to separate and the debug file is
+/// only useful as a copy of the unstripped binary.
+pub fn debug_file(data: &[u8]) -> Result<Vec<u8>, String> {
+    match goblin::Object::parse(data) {
+        Ok(goblin::Object::Elf(elf)) => elf_debug_file(data, &elf),
+        Ok(_) => Ok(data.to_vec()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Compute the CRC-32 of data, as used by ``.gnu_debuglink``.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = flate2::Crc::new();
+    crc.update(data);
+    crc.sum()
+}
+
+fn strip_elf(
+    data: &[u8],
+    elf: &goblin::elf::Elf,
+    mode: StripMode,
+    debug_link: Option<(&str, u32)>,
+) -> Result<Vec<u8>, String> {
+    use goblin::elf::section_header::{SHF_ALLOC, SHT_NOBITS, SHT_REL, SHT_RELA, SHT_SYMTAB};
+
+    let e = Endian {
+        little: elf.little_endian,
+    };
+    let sections = &elf.section_headers;
+    let shstrndx = elf.header.e_shstrndx as usize;
+
+    if sections.is_empty() || shstrndx >= sections.len() {
+        return Err("ELF binary has no section headers".to_string());
+    }
+
+    let section_name = |index: usize| match elf.shdr_strtab.get(sections[index].sh_name) {
+        Some(Ok(name)) => name,
+        _ => "",
+    };
+
+    // Only sections not loaded at run-time can be removed.
+    let mut remove = vec![false; sections.len()];
+
+    for (i, sh) in sections.iter().enumerate().skip(1) {
+        if sh.sh_flags & u64::from(SHF_ALLOC) != 0 {
+            continue;
+        }
+
+        let name = section_name(i);
+
+        if name.starts_with(".debug")
+            || name.starts_with(".zdebug")
+            || (name == ".gnu_debuglink" && debug_link.is_some())
+        {
+            remove[i] = true;
+        }
+
+        if mode == StripMode::All && sh.sh_type == SHT_SYMTAB {
+            remove[i] = true;
+
+            let link = sh.sh_link as usize;
+            if link != shstrndx
+                && link < sections.len()
+                && sections[link].sh_flags & u64::from(SHF_ALLOC) == 0
+            {
+                remove[link] = true;
+            }
+        }
+    }
+
+    // Relocations against removed sections and symbol tables go with them.
+    for (i, sh) in sections.iter().enumerate() {
+        if (sh.sh_type == SHT_REL || sh.sh_type == SHT_RELA)
+            && sh.sh_flags & u64::from(SHF_ALLOC) == 0
+            && (remove.get(sh.sh_info as usize) == Some(&true)
+                || remove.get(sh.sh_link as usize) == Some(&true))
+        {
+            remove[i] = true;
+        }
+    }
+
+    if !remove.contains(&true) && debug_link.is_none() {
+        return Ok(data.to_vec());
+    }
+
+    // Everything loaded at run-time is retained verbatim.
+    let mut end = elf.header.e_ehsize as usize;
+    end = std::cmp::max(
+        end,
+        elf.header.e_phoff as usize + elf.header.e_phnum as usize * elf.header.e_phentsize as usize,
+    );
+
+    for ph in &elf.program_headers {
+        end = std::cmp::max(end, (ph.p_offset + ph.p_filesz) as usize);
+    }
+
+    for (i, sh) in sections.iter().enumerate() {
+        if !remove[i] && sh.sh_flags & u64::from(SHF_ALLOC) != 0 && sh.sh_type != SHT_NOBITS {
+            end = std::cmp::max(end, (sh.sh_offset + sh.sh_size) as usize);
+        }
+    }
+
+    let mut out = data
+        .get(0..end)
+        .ok_or_else(|| "ELF data truncated".to_string())?
+        .to_vec();
+
+    let mut mapping = vec![None; sections.len()];
+    let mut headers = Vec::new();
+    let mut debug_link_name = None;
+
+    for (i, sh) in sections.iter().enumerate() {
+        if remove[i] {
+            continue;
+        }
+
+        let mut sh = sh.clone();
+        let start = sh.sh_offset as usize;
+        let size = sh.sh_size as usize;
+
+        if i == shstrndx && debug_link.is_some() {
+            // The new section needs a name.
+            let mut names = data
+                .get(start..start + size)
+                .ok_or_else(|| "section data out of bounds".to_string())?
+                .to_vec();
+            debug_link_name = Some(names.len());
+            names.extend(b".gnu_debuglink\0");
+
+            sh.sh_offset = out.len() as u64;
+            sh.sh_size = names.len() as u64;
+            out.extend(names);
+        } else if i != 0 && sh.sh_type != SHT_NOBITS && size > 0 && start + size > end {
+            pad_to(&mut out, sh.sh_addralign as usize);
+
+            sh.sh_offset = out.len() as u64;
+            out.extend(
+                data.get(start..start + size)
+                    .ok_or_else(|| "section data out of bounds".to_string())?,
+            );
+        }
+
+        mapping[i] = Some(headers.len());
+        headers.push(sh);
+    }
+
+    if let (Some((link, crc)), Some(name)) = (debug_link, debug_link_name) {
+        // The file name, NUL padded to 4 bytes, followed by the CRC-32 of
+        // the debug file.
+        let mut section = link.as_bytes().to_vec();
+        section.push(0);
+        pad_to(&mut section, 4);
+        e.push(&mut section, 4, u64::from(crc));
+
+        pad_to(&mut out, 4);
+
+        let mut sh = goblin::elf::SectionHeader::new();
+        sh.sh_name = name;
+        sh.sh_type = goblin::elf::section_header::SHT_PROGBITS;
+        sh.sh_offset = out.len() as u64;
+        sh.sh_size = section.len() as u64;
+        sh.sh_addralign = 4;
+        headers.push(sh);
+
+        out.extend(section);
+    }
+
+    write_elf_section_headers(&mut out, elf, &headers, &mapping);
+
+    Ok(out)
+}
+
+/// Append ELF section headers and point the ELF header at them.
+///
+/// `mapping` maps indices of sections in `elf` to indices in `headers`.
+/// Section references in the headers are rewritten accordingly.
+fn write_elf_section_headers(
+    out: &mut Vec<u8>,
+    elf: &goblin::elf::Elf,
+    headers: &[goblin::elf::SectionHeader],
+    mapping: &[Option<usize>],
+) {
+    use goblin::elf::section_header::{SHT_REL, SHT_RELA};
+
+    // SHF_INFO_LINK: sh_info holds a section index.
+    const SHF_INFO_LINK: u64 = 0x40;
+
+    let e = Endian {
+        little: elf.little_endian,
+    };
+    let shstrndx = elf.header.e_shstrndx as usize;
+    let (word, header_size) = if elf.is_64 { (8, 64) } else { (4, 40) };
+
+    pad_to(out, word);
+    let shoff = out.len();
+
+    for sh in headers {
+        let remap = |index: u32| {
+            mapping
+                .get(index as usize)
+                .and_then(|v| *v)
+                .map(|v| v as u32)
+                .unwrap_or(0)
+        };
+
+        let link = remap(sh.sh_link);
+        let info = if sh.sh_type == SHT_REL
+            || sh.sh_type == SHT_RELA
+            || sh.sh_flags & SHF_INFO_LINK != 0
+        {
+            remap(sh.sh_info)
+        } else {
+            sh.sh_info
+        };
+
+        let start = out.len();
+        e.push(out, 4, sh.sh_name as u64);
+        e.push(out, 4, u64::from(sh.sh_type));
+        e.push(out, word, sh.sh_flags);
+        e.push(out, word, sh.sh_addr);
+        e.push(out, word, sh.sh_offset);
+        e.push(out, word, sh.sh_size);
+        e.push(out, 4, u64::from(link));
+        e.push(out, 4, u64::from(info));
+        e.push(out, word, sh.sh_addralign);
+        e.push(out, word, sh.sh_entsize);
+        assert_eq!(out.len() - start, header_size);
+    }
+
+    let (shoff_offset, shentsize_offset) = if elf.is_64 {
+        (0x28, 0x3a)
+    } else {
+        (0x20, 0x2e)
+    };
+
+    e.write(out, shoff_offset, word, shoff as u64);
+    e.write(out, shentsize_offset, 2, header_size as u64);
+    e.write(out, shentsize_offset + 2, 2, headers.len() as u64);
+    e.write(
+        out,
+        shentsize_offset + 4,
+        2,
+        mapping[shstrndx].unwrap_or(0) as u64,
+    );
+}
+
+fn elf_debug_file(data: &[u8], elf: &goblin::elf::Elf) -> Result<Vec<u8>, String> {
+    use goblin::elf::program_header::{PT_NOTE, PT_PHDR};
+    use goblin::elf::section_header::{SHF_ALLOC, SHT_NOBITS, SHT_NOTE};
+
+    let e = Endian {
+        little: elf.little_endian,
+    };
+    let sections = &elf.section_headers;
+
+    if sections.is_empty() {
+        return Err("ELF binary has no section headers".to_string());
+    }
+
+    let phoff = elf.header.e_phoff as usize;
+    let phentsize = elf.header.e_phentsize as usize;
+    let header_end = std::cmp::max(
+        elf.header.e_ehsize as usize,
+        phoff + elf.header.e_phnum as usize * phentsize,
+    );
+
+    let mut out = data
+        .get(0..header_end)
+        .ok_or_else(|| "ELF data truncated".to_string())?
+        .to_vec();
+
+    let mut headers = Vec::new();
+
+    for (i, sh) in sections.iter().enumerate() {
+        let mut sh = sh.clone();
+
+        let keep_data = i != 0
+            && sh.sh_type != SHT_NOBITS
+            && (sh.sh_flags & u64::from(SHF_ALLOC) == 0 || sh.sh_type == SHT_NOTE);
+
+        if keep_data {
+            let start = sh.sh_offset as usize;
+            let size = sh.sh_size as usize;
+
+            pad_to(&mut out, sh.sh_addralign as usize);
+            sh.sh_offset = out.len() as u64;
+            out.extend(
+                data.get(start..start + size)
+                    .ok_or_else(|| "section data out of bounds".to_string())?,
+            );
+        } else if i != 0 && sh.sh_flags & u64::from(SHF_ALLOC) != 0 {
+            sh.sh_type = SHT_NOBITS;
+            sh.sh_offset = out.len() as u64;
+        }
+
+        headers.push(sh);
+    }
+
+    // Segments no longer have data in the file, except for the program
+    // headers themselves and notes, which moved along with their sections.
+    let (offset_field, filesz_field, word) = if elf.is_64 { (8, 32, 8) } else { (4, 16, 4) };
+
+    for (i, ph) in elf.program_headers.iter().enumerate() {
+        let header = phoff + i * phentsize;
+
+        if ph.p_type == PT_PHDR {
+            continue;
+        }
+
+        let note = headers
+            .iter()
+            .find(|sh| ph.p_type == PT_NOTE && sh.sh_type == SHT_NOTE && sh.sh_addr == ph.p_vaddr);
+
+        match note {
+            Some(sh) => e.write(&mut out, header + offset_field, word, sh.sh_offset),
+            None => e.write(&mut out, header + filesz_field, word, 0),
+        }
+    }
+
+    let mapping: Vec<Option<usize>> = (0..headers.len()).map(Some).collect();
+    write_elf_section_headers(&mut out, elf, &headers, &mapping);
+
+    Ok(out)
+}
+
+/// Compute the checksum of a PE image.
+fn pe_checksum(data: &[u8], checksum_offset: usize) -> u32 {
+    let mut sum: u64 = 0;
+
+    for (i, chunk) in data.chunks(2).enumerate() {
+        if i * 2 == checksum_offset || i * 2 == checksum_offset + 2 {
+            continue;
+        }
+
+        let word = u64::from(chunk[0]) | (u64::from(*chunk.get(1).unwrap_or(&0)) << 8);
+        sum += word;
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    sum = (sum & 0xffff) + (sum >> 16);
+
+    (sum as u32).wrapping_add(data.len() as u32)
+}
+
+fn strip_pe(data: &[u8], mode: StripMode) -> Result<Vec<u8>, String> {
+    let e = Endian { little: true };
+
+    let pe_offset = e.read(data, 0x3c, 4)? as usize;
+    let coff = pe_offset + 4;
+    let section_count = e.read(data, coff + 2, 2)? as usize;
+    let symbols_offset = e.read(data, coff + 8, 4)? as usize;
+    let symbol_count = e.read(data, coff + 12, 4)? as usize;
+    let optional_size = e.read(data, coff + 16, 2)? as usize;
+
+    let optional = coff + 20;
+    let (directories, directory_count) = match e.read(data, optional, 2)? {
+        0x10b => (optional + 96, e.read(data, optional + 92, 4)? as usize),
+        0x20b => (optional + 112, e.read(data, optional + 108, 4)? as usize),
+        magic => return Err(format!("unknown PE optional header magic: {:#x}", magic)),
+    };
+    let section_alignment = e.read(data, optional + 32, 4)?;
+    let headers_size = e.read(data, optional + 60, 4)? as usize;
+    let checksum = e.read(data, optional + 64, 4)?;
+
+    // IMAGE_DIRECTORY_ENTRY_SECURITY
+    if directory_count > 4 && e.read(data, directories + 4 * 8 + 4, 4)? != 0 {
+        return Err("binary is code signed; strip it before signing".to_string());
+    }
+
+    // The string table holding long section names follows the symbols.
+    let strings_offset = symbols_offset + symbol_count * 18;
+    let strings_end = if symbols_offset != 0 {
+        strings_offset + e.read(data, strings_offset, 4)? as usize
+    } else {
+        0
+    };
+
+    // (name, virtual address, virtual size, raw data offset, raw data size)
+    let mut sections = Vec::new();
+    let section_table = optional + optional_size;
+
+    for i in 0..section_count {
+        let header = section_table + i * 40;
+        let raw_name = data
+            .get(header..header + 8)
+            .ok_or_else(|| "PE section table truncated".to_string())?;
+        let raw_name = &raw_name[0..raw_name.iter().position(|b| *b == 0).unwrap_or(8)];
+        let raw_name = String::from_utf8_lossy(raw_name).to_string();
+
+        let name = if raw_name.starts_with('/') && symbols_offset != 0 {
+            let offset = raw_name[1..].parse::<usize>().unwrap_or(0);
+            let names = data.get(strings_offset + offset..).unwrap_or(&[]);
+            let end = names.iter().position(|b| *b == 0).unwrap_or(0);
+            String::from_utf8_lossy(&names[0..end]).to_string()
+        } else {
+            raw_name.clone()
+        };
+
+        sections.push((
+            name,
+            raw_name,
+            e.read(data, header + 12, 4)?,
+            e.read(data, header + 8, 4)?,
+            e.read(data, header + 20, 4)? as usize,
+            e.read(data, header + 16, 4)? as usize,
+        ));
+    }
+
+    // End of the raw data of sections, or the headers if there are none.
+    let raw_end = |sections: &[(String, String, u64, u64, usize, usize)]| {
+        sections
+            .iter()
+            .map(|s| s.4 + s.5)
+            .chain(vec![headers_size])
+            .max()
+            .unwrap_or(0)
+    };
+
+    // Linkers pad the file to the file alignment; anything else, like an
+    // appended installer payload, would be lost.
+    let end = std::cmp::max(raw_end(&sections), strings_end);
+    if data.len() > end && data[end..].iter().any(|b| *b != 0) {
+        return Err("PE binary has trailing data; refusing to strip".to_string());
+    }
+
+    // Debug sections can only be removed from the end of the image.
+    let mut keep = section_count;
+    while keep > 1 && sections[keep - 1].0.starts_with(".debug") {
+        keep -= 1;
+    }
+
+    // Keep sections referenced by data directories.
+    if keep < section_count {
+        let removed_start = sections[keep].2;
+
+        for i in 0..directory_count {
+            // The security directory holds a file offset, not an address.
+            if i == 4 {
+                continue;
+            }
+
+            let rva = e.read(data, directories + i * 8, 4)?;
+            let size = e.read(data, directories + i * 8 + 4, 4)?;
+
+            if size != 0 && rva >= removed_start {
+                keep = section_count;
+                break;
+            }
+        }
+    }
+
+    // Removed sections must follow all retained data in the file.
+    if sections[keep..]
+        .iter()
+        .any(|s| s.5 > 0 && s.4 < raw_end(&sections[0..keep]))
+    {
+        keep = section_count;
+    }
+
+    let keep_symbols = mode == StripMode::Debug;
+    let need_strings = keep_symbols || sections[0..keep].iter().any(|s| s.1.starts_with('/'));
+
+    if keep == section_count && (symbols_offset == 0 || keep_symbols) {
+        return Ok(data.to_vec());
+    }
+
+    let mut out = data[0..raw_end(&sections[0..keep])].to_vec();
+
+    if symbols_offset != 0 && need_strings {
+        let start = if keep_symbols {
+            symbols_offset
+        } else {
+            strings_offset
+        };
+
+        let pointer = out.len() as u64;
+        e.write(&mut out, coff + 8, 4, pointer);
+        e.write(
+            &mut out,
+            coff + 12,
+            4,
+            if keep_symbols { symbol_count as u64 } else { 0 },
+        );
+
+        let tables = data
+            .get(start..strings_end)
+            .ok_or_else(|| "PE symbol table out of bounds".to_string())?;
+        out.extend(tables);
+    } else {
+        e.write(&mut out, coff + 8, 4, 0);
+        e.write(&mut out, coff + 12, 4, 0);
+    }
+
+    if keep < section_count {
+        e.write(&mut out, coff + 2, 2, keep as u64);
+
+        for i in keep..section_count {
+            let header = section_table + i * 40;
+            for b in &mut out[header..header + 40] {
+                *b = 0;
+            }
+        }
+
+        let last = &sections[keep - 1];
+        let image_end = last.2 + std::cmp::max(last.3, last.5 as u64);
+        let image_size =
+            (image_end + section_alignment - 1) / section_alignment * section_alignment;
+        e.write(&mut out, optional + 56, 4, image_size);
+    }
+
+    if checksum != 0 {
+        let checksum = pe_checksum(&out, optional + 64);
+        e.write(&mut out, optional + 64, 4, u64::from(checksum));
+    }
+
+    Ok(out)
+}
+
+/// Mach-O file types that can be stripped.
+const MH_EXECUTE: u64 = 0x2;
+const MH_DYLIB: u64 = 0x6;
+const MH_BUNDLE: u64 = 0x8;
+
+/// Mach-O load commands referenced when stripping.
+const LC_SEGMENT: u32 = 0x1;
+const LC_SYMTAB: u32 = 0x2;
+const LC_DYSYMTAB: u32 = 0xb;
+const LC_SEGMENT_64: u32 = 0x19;
+const LC_CODE_SIGNATURE: u32 = 0x1d;
+const LC_DYLD_INFO: u32 = 0x22;
+const LC_DYLD_INFO_ONLY: u32 = 0x8000_0022;
+
+/// Load commands whose payload is a ``linkedit_data_command``.
+const LINKEDIT_DATA_COMMANDS: &[u32] = &[
+    0x1e,        // LC_SEGMENT_SPLIT_INFO
+    0x26,        // LC_FUNCTION_STARTS
+    0x29,        // LC_DATA_IN_CODE
+    0x2b,        // LC_DYLIB_CODE_SIGN_DRS
+    0x2e,        // LC_LINKER_OPTIMIZATION_HINT
+    0x8000_0033, // LC_DYLD_EXPORTS_TRIE
+    0x8000_0034, // LC_DYLD_CHAINED_FIXUPS
+];
+
+/// Symbol type bits marking debugging (stab) entries.
+const N_STAB: u8 = 0xe0;
+const N_EXT: u8 = 0x01;
+
+/// Indirect symbol table entries not referring to a symbol.
+const INDIRECT_SYMBOL_LOCAL: u32 = 0x8000_0000;
+const INDIRECT_SYMBOL_ABS: u32 = 0x4000_0000;
+
+fn strip_macho(data: &[u8], mode: StripMode) -> Result<Vec<u8>, String> {
+    let e = match LittleEndian::read_u32(&data[0..4]) {
+        0xfeed_face | 0xfeed_facf => Endian { little: true },
+        _ => Endian { little: false },
+    };
+    let is_64 = e.read(data, 0, 4)? == 0xfeed_facf;
+    let (word, nlist_size) = if is_64 { (8, 16) } else { (4, 12) };
+
+    // Object files have relocations in sections referring to symbols by
+    // index, which aren't rewritten.
+    match e.read(data, 12, 4)? {
+        MH_EXECUTE | MH_DYLIB | MH_BUNDLE => {}
+        filetype => {
+            return Err(format!(
+                "stripping Mach-O file type {} not supported",
+                filetype
+            ));
+        }
+    }
+
+    let ncmds = e.read(data, 16, 4)? as usize;
+    let mut offset = if is_64 { 32 } else { 28 };
+
+    let mut symtab = None;
+    let mut dysymtab = None;
+    let mut linkedit = None;
+    // End of LINKEDIT data other than the symbol tables.
+    let mut other_end = 0;
+
+    for _ in 0..ncmds {
+        let cmd = e.read(data, offset, 4)? as u32;
+        let cmdsize = e.read(data, offset + 4, 4)? as usize;
+
+        match cmd {
+            LC_CODE_SIGNATURE => {
+                return Err("binary is code signed; strip it before signing".to_string());
+            }
+            LC_SYMTAB => symtab = Some(offset),
+            LC_DYSYMTAB => dysymtab = Some(offset),
+            LC_SEGMENT | LC_SEGMENT_64 => {
+                let name = data
+                    .get(offset + 8..offset + 24)
+                    .ok_or_else(|| "Mach-O load commands truncated".to_string())?;
+                if name.starts_with(b"__LINKEDIT\0") {
+                    linkedit = Some(offset);
+                }
+            }
+            LC_DYLD_INFO | LC_DYLD_INFO_ONLY => {
+                for field in 0..5 {
+                    let data_offset = e.read(data, offset + 8 + field * 8, 4)?;
+                    let data_size = e.read(data, offset + 12 + field * 8, 4)?;
+                    other_end = std::cmp::max(other_end, data_offset + data_size);
+                }
+            }
+            cmd if LINKEDIT_DATA_COMMANDS.contains(&cmd) => {
+                let data_offset = e.read(data, offset + 8, 4)?;
+                let data_size = e.read(data, offset + 12, 4)?;
+                other_end = std::cmp::max(other_end, data_offset + data_size);
+            }
+            _ => {}
+        }
+
+        offset += cmdsize;
+    }
+
+    let symtab = match symtab {
+        Some(symtab) => symtab,
+        None => return Ok(data.to_vec()),
+    };
+
+    let symoff = e.read(data, symtab + 8, 4)? as usize;
+    let nsyms = e.read(data, symtab + 12, 4)? as usize;
+    let stroff = e.read(data, symtab + 16, 4)? as usize;
+    let strsize = e.read(data, symtab + 20, 4)? as usize;
+
+    // (index, count) of local, defined external, and undefined symbols.
+    let mut ranges = vec![(0, nsyms)];
+    let mut indirect = (0, 0);
+    let mut relocations = Vec::new();
+
+    if let Some(dysymtab) = dysymtab {
+        ranges = (0..3)
+            .map(|i| {
+                Ok((
+                    e.read(data, dysymtab + 8 + i * 8, 4)? as usize,
+                    e.read(data, dysymtab + 12 + i * 8, 4)? as usize,
+                ))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        // Tables of content, module tables, and external reference tables
+        // only exist in ancient dylibs.
+        for field in &[36, 44, 52] {
+            if e.read(data, dysymtab + field, 4)? != 0 {
+                return Err("Mach-O symbol table layout not supported".to_string());
+            }
+        }
+
+        indirect = (
+            e.read(data, dysymtab + 56, 4)? as usize,
+            e.read(data, dysymtab + 60, 4)? as usize,
+        );
+
+        for field in &[64, 72] {
+            let reloff = e.read(data, dysymtab + field, 4)? as usize;
+            let nrel = e.read(data, dysymtab + field + 4, 4)? as usize;
+
+            other_end = std::cmp::max(other_end, (reloff + nrel * 8) as u64);
+            relocations.extend((0..nrel).map(|i| reloff + i * 8));
+        }
+    }
+
+    // Symbols referenced by index must be retained.
+    let mut referenced = BTreeSet::new();
+
+    for i in 0..indirect.1 {
+        let value = e.read(data, indirect.0 + i * 4, 4)? as u32;
+        if value & (INDIRECT_SYMBOL_LOCAL | INDIRECT_SYMBOL_ABS) == 0 {
+            referenced.insert(value as usize);
+        }
+    }
+
+    for reloc in &relocations {
+        // Scattered relocations don't refer to symbols.
+        if e.read(data, *reloc, 4)? & 0x8000_0000 != 0 {
+            continue;
+        }
+
+        let info = e.read(data, reloc + 4, 4)? as u32;
+        let (symbol, external) = if e.little {
+            (info & 0x00ff_ffff, info & (1 << 27) != 0)
+        } else {
+            (info >> 8, info & (1 << 4) != 0)
+        };
+
+        if external {
+            referenced.insert(symbol as usize);
+        }
+    }
+
+    // Build the new symbol and string tables.
+    let mut mapping = vec![None; nsyms];
+    let mut symbols = Vec::new();
+    let mut strings = b" \0".to_vec();
+    let mut string_offsets: HashMap<&[u8], u32> = HashMap::new();
+
+    for (i, new_index) in mapping.iter_mut().enumerate() {
+        let entry = symoff + i * nlist_size;
+        let strx = e.read(data, entry, 4)? as usize;
+        let n_type = *data
+            .get(entry + 4)
+            .ok_or_else(|| "Mach-O symbol table truncated".to_string())?;
+
+        let local = match dysymtab {
+            Some(_) => i >= ranges[0].0 && i < ranges[0].0 + ranges[0].1,
+            None => n_type & N_EXT == 0,
+        };
+
+        let remove =
+            !referenced.contains(&i) && (n_type & N_STAB != 0 || (mode == StripMode::All && local));
+
+        if remove {
+            continue;
+        }
+
+        let mut symbol = data
+            .get(entry..entry + nlist_size)
+            .ok_or_else(|| "Mach-O symbol table truncated".to_string())?
+            .to_vec();
+
+        if strx != 0 {
+            let name = data
+                .get(stroff + strx..stroff + strsize)
+                .ok_or_else(|| "Mach-O string table out of bounds".to_string())?;
+            let name = &name[0..name.iter().position(|b| *b == 0).unwrap_or(0)];
+
+            let new_strx = match string_offsets.get(name) {
+                Some(v) => *v,
+                None => {
+                    let v = strings.len() as u32;
+                    strings.extend(name);
+                    strings.push(0);
+                    string_offsets.insert(name, v);
+                    v
+                }
+            };
+
+            e.write(&mut symbol, 0, 4, u64::from(new_strx));
+        }
+
+        *new_index = Some(symbols.len() / nlist_size);
+        symbols.extend(symbol);
+    }
+
+    if symbols.len() / nlist_size == nsyms {
+        return Ok(data.to_vec());
+    }
+
+    pad_to(&mut strings, word);
+
+    // Names are deduplicated, so this only happens with unusual tables.
+    if strings.len() > strsize {
+        return Ok(data.to_vec());
+    }
+
+    let new_index = |index: usize| -> Result<u64, String> {
+        mapping
+            .get(index)
+            .and_then(|v| *v)
+            .map(|v| v as u64)
+            .ok_or_else(|| format!("referenced symbol {} was removed", index))
+    };
+
+    let mut out = data.to_vec();
+
+    for i in 0..indirect.1 {
+        let offset = indirect.0 + i * 4;
+        let value = e.read(data, offset, 4)? as u32;
+        if value & (INDIRECT_SYMBOL_LOCAL | INDIRECT_SYMBOL_ABS) == 0 {
+            e.write(&mut out, offset, 4, new_index(value as usize)?);
+        }
+    }
+
+    for reloc in &relocations {
+        if e.read(data, *reloc, 4)? & 0x8000_0000 != 0 {
+            continue;
+        }
+
+        let info = e.read(data, reloc + 4, 4)? as u32;
+        let info = if e.little && info & (1 << 27) != 0 {
+            (info & 0xff00_0000) | new_index((info & 0x00ff_ffff) as usize)? as u32
+        } else if !e.little && info & (1 << 4) != 0 {
+            (info & 0xff) | (new_index((info >> 8) as usize)? as u32) << 8
+        } else {
+            info
+        };
+
+        e.write(&mut out, reloc + 4, 4, u64::from(info));
+    }
+
+    if let Some(dysymtab) = dysymtab {
+        for (i, (start, count)) in ranges.iter().enumerate() {
+            let kept: Vec<usize> = mapping[*start..start + count]
+                .iter()
+                .filter_map(|v| *v)
+                .collect();
+            let removed_before = mapping[0..*start].iter().filter(|v| v.is_none()).count();
+
+            e.write(
+                &mut out,
+                dysymtab + 8 + i * 8,
+                4,
+                (start - removed_before) as u64,
+            );
+            e.write(&mut out, dysymtab + 12 + i * 8, 4, kept.len() as u64);
+        }
+    }
+
+    // If the symbol tables are the last data in the file, they are
+    // rewritten contiguously and the file is truncated. Otherwise they are
+    // rewritten in place.
+    let indirect_size = indirect.1 * 4;
+    let region_start = *[symoff, stroff, indirect.0]
+        .iter()
+        .filter(|v| **v != 0)
+        .min()
+        .unwrap_or(&symoff);
+    let region_end = [
+        symoff + nsyms * nlist_size,
+        stroff + strsize,
+        indirect.0 + indirect_size,
+    ]
+    .iter()
+    .cloned()
+    .max()
+    .unwrap_or(0);
+
+    let relayout = match linkedit {
+        Some(linkedit) if other_end <= region_start as u64 && region_end == data.len() => {
+            Some(linkedit)
+        }
+        _ => None,
+    };
+
+    let (new_symoff, new_indirectoff, new_stroff) = if let Some(linkedit) = relayout {
+        let indirect_data = out[indirect.0..indirect.0 + indirect_size].to_vec();
+
+        out.truncate(region_start);
+        let new_symoff = out.len();
+        out.extend(&symbols);
+        let new_indirectoff = out.len();
+        out.extend(indirect_data);
+        let new_stroff = out.len();
+        out.extend(&strings);
+
+        let fileoff = e.read(data, linkedit + if is_64 { 40 } else { 32 }, word)?;
+        let filesize = out.len() as u64 - fileoff;
+        e.write(
+            &mut out,
+            linkedit + if is_64 { 48 } else { 36 },
+            word,
+            filesize,
+        );
+
+        (new_symoff, new_indirectoff, new_stroff)
+    } else {
+        for b in &mut out[symoff..symoff + nsyms * nlist_size] {
+            *b = 0;
+        }
+        out[symoff..symoff + symbols.len()].copy_from_slice(&symbols);
+
+        for b in &mut out[stroff..stroff + strsize] {
+            *b = 0;
+        }
+        out[stroff..stroff + strings.len()].copy_from_slice(&strings);
+
+        (symoff, indirect.0, stroff)
+    };
+
+    e.write(&mut out, symtab + 8, 4, new_symoff as u64);
+    e.write(
+        &mut out,
+        symtab + 12,
+        4,
+        (symbols.len() / nlist_size) as u64,
+    );
+    e.write(&mut out, symtab + 16, 4, new_stroff as u64);
+    e.write(&mut out, symtab + 20, 4, strings.len() as u64);
+
+    if let Some(dysymtab) = dysymtab {
+        if indirect.1 > 0 {
+            e.write(&mut out, dysymtab + 56, 4, new_indirectoff as u64);
+        }
+    }
+
+    Ok(out)
+}
+
+fn strip_fat_macho(data: &[u8], mode: StripMode) -> Result<Vec<u8>, String> {
+    let e = Endian { little: false };
+    let count = e.read(data, 4, 4)? as usize;
+
+    let mut out = data
+        .get(0..8 + count * 20)
+        .ok_or_else(|| "universal binary header truncated".to_string())?
+        .to_vec();
+
+    for i in 0..count {
+        let entry = 8 + i * 20;
+        let offset = e.read(data, entry + 8, 4)? as usize;
+        let size = e.read(data, entry + 12, 4)? as usize;
+        let align = e.read(data, entry + 16, 4)?;
+
+        let slice = data
+            .get(offset..offset + size)
+            .ok_or_else(|| "universal binary architecture out of bounds".to_string())?;
+        let stripped = strip_macho(slice, mode)?;
+
+        pad_to(&mut out, 1usize << align);
+        let offset = out.len() as u64;
+        e.write(&mut out, entry + 8, 4, offset);
+        e.write(&mut out, entry + 12, 4, stripped.len() as u64);
+        out.extend(stripped);
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::section_header::{SHF_ALLOC, SHT_NOBITS, SHT_NOTE, SHT_SYMTAB};
+    use std::collections::BTreeMap;
+
+    // See testdata/strip/generate.sh for how these are produced.
+    const ELF: &[u8] = include_bytes!("../testdata/strip/hello-elf");
+    const PE: &[u8] = include_bytes!("../testdata/strip/min.exe");
+    const MACHO: &[u8] = include_bytes!("../testdata/strip/min-macho");
+
+    const MODES: &[StripMode] = &[StripMode::Debug, StripMode::All];
+
+    fn parse_elf(data: &[u8]) -> goblin::elf::Elf {
+        goblin::elf::Elf::parse(data).expect("stripped ELF binary should parse")
+    }
+
+    /// Obtain ELF sections by name as (header, data) tuples.
+    fn elf_sections<'a>(
+        elf: &goblin::elf::Elf,
+        data: &'a [u8],
+    ) -> BTreeMap<String, (goblin::elf::SectionHeader, &'a [u8])> {
+        elf.section_headers
+            .iter()
+            .skip(1)
+            .map(|sh| {
+                let name = elf.shdr_strtab.get(sh.sh_name).unwrap().unwrap();
+                let content = if sh.sh_type == SHT_NOBITS {
+                    &data[0..0]
+                } else {
+                    &data[sh.sh_offset as usize..(sh.sh_offset + sh.sh_size) as usize]
+                };
+
+                (name.to_string(), (sh.clone(), content))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn elf_loaded_content_unchanged() {
+        let original = parse_elf(ELF);
+        let original_sections = elf_sections(&original, ELF);
+
+        assert!(original_sections.contains_key(".debug_info"));
+
+        for mode in MODES {
+            let data = strip_binary(ELF, *mode, None).unwrap();
+            assert!(data.len() < ELF.len());
+
+            let elf = parse_elf(&data);
+            let sections = elf_sections(&elf, &data);
+
+            assert!(elf.header == original.header || elf.header.e_shnum < original.header.e_shnum);
+            assert!(elf.program_headers == original.program_headers);
+            assert_eq!(elf.entry, original.entry);
+
+            let dyns = |elf: &goblin::elf::Elf| elf.dynamic.as_ref().unwrap().dyns.clone();
+            assert!(!dyns(&original).is_empty());
+            assert!(dyns(&elf) == dyns(&original));
+
+            for (name, (sh, content)) in &original_sections {
+                if sh.sh_flags & u64::from(SHF_ALLOC) == 0 {
+                    continue;
+                }
+
+                let (new_sh, new_content) = &sections[name];
+                assert_eq!(new_sh.sh_addr, sh.sh_addr, "{}", name);
+                assert_eq!(new_sh.sh_type, sh.sh_type, "{}", name);
+                assert!(new_content == content, "{}", name);
+            }
+
+            assert!(!sections.keys().any(|name| name.starts_with(".debug")));
+
+            match mode {
+                StripMode::Debug => {
+                    assert_eq!(sections[".symtab"].0.sh_type, SHT_SYMTAB);
+                    assert_eq!(elf.syms.len(), original.syms.len());
+                }
+                StripMode::All => {
+                    assert!(!sections.contains_key(".symtab"));
+                    assert!(!sections.contains_key(".strtab"));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn elf_debug_link() {
+        let debug = debug_file(ELF).unwrap();
+        let data = strip_binary(ELF, StripMode::All, Some(("hello-elf.debug", &debug))).unwrap();
+
+        let elf = parse_elf(&data);
+        let sections = elf_sections(&elf, &data);
+
+        let mut expected = b"hello-elf.debug\0".to_vec();
+        let mut crc = [0; 4];
+        LittleEndian::write_u32(&mut crc, crc32(&debug));
+        expected.extend(&crc);
+
+        assert_eq!(sections[".gnu_debuglink"].1, expected.as_slice());
+    }
+
+    #[test]
+    fn elf_debug_file_content() {
+        let original = parse_elf(ELF);
+        let original_sections = elf_sections(&original, ELF);
+
+        let data = debug_file(ELF).unwrap();
+        assert!(data.len() < ELF.len());
+
+        // goblin follows the dynamic segment, which has no data in a debug
+        // file (as with objcopy --only-keep-debug), so parse only sections.
+        let mut data = data;
+        data[0x38..0x3a].copy_from_slice(&[0, 0]);
+
+        let elf = parse_elf(&data);
+        let sections = elf_sections(&elf, &data);
+
+        assert_eq!(sections.len(), original_sections.len());
+
+        for (name, (sh, content)) in &original_sections {
+            let (new_sh, new_content) = &sections[name];
+            assert_eq!(new_sh.sh_addr, sh.sh_addr, "{}", name);
+            assert_eq!(new_sh.sh_size, sh.sh_size, "{}", name);
+
+            if sh.sh_flags & u64::from(SHF_ALLOC) != 0 && sh.sh_type != SHT_NOTE {
+                assert_eq!(new_sh.sh_type, SHT_NOBITS, "{}", name);
+            } else {
+                assert_eq!(new_sh.sh_type, sh.sh_type, "{}", name);
+                assert!(new_content == content, "{}", name);
+            }
+        }
+
+        assert!(sections.contains_key(".debug_info"));
+        assert!(sections.contains_key(".note.gnu.build-id"));
+        assert_eq!(elf.syms.len(), original.syms.len());
+    }
+
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    #[test]
+    fn elf_stripped_runs() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempdir::TempDir::new("pyoxidizer-strip-test").unwrap();
+
+        for mode in MODES {
+            let path = temp_dir.path().join(format!("hello-{:?}", mode));
+            std::fs::write(&path, strip_binary(ELF, *mode, None).unwrap()).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+            let output = std::process::Command::new(&path).output().unwrap();
+            assert!(output.status.success());
+            assert_eq!(output.stdout, b"42\n");
+        }
+    }
+
+    fn pe_section_name(section: &goblin::pe::section_table::SectionTable) -> String {
+        match &section.real_name {
+            Some(name) => name.clone(),
+            None => section.name().unwrap().to_string(),
+        }
+    }
+
+    fn pe_section_data<'a>(
+        data: &'a [u8],
+        section: &goblin::pe::section_table::SectionTable,
+    ) -> &'a [u8] {
+        let start = section.pointer_to_raw_data as usize;
+        &data[start..start + section.size_of_raw_data as usize]
+    }
+
+    #[test]
+    fn pe_loaded_content_unchanged() {
+        let original = goblin::pe::PE::parse(PE).unwrap();
+        let original_header = original.header.optional_header.unwrap();
+
+        assert!(original
+            .sections
+            .iter()
+            .any(|s| pe_section_name(s).starts_with(".debug")));
+        assert_ne!(original.header.coff_header.number_of_symbol_table, 0);
+
+        for mode in MODES {
+            let data = strip_binary(PE, *mode, None).unwrap();
+            assert!(data.len() < PE.len());
+
+            let pe = goblin::pe::PE::parse(&data).expect("stripped PE binary should parse");
+            let header = pe.header.optional_header.unwrap();
+
+            assert_eq!(pe.entry, original.entry);
+            assert_eq!(header.data_directories, original_header.data_directories);
+            assert!(
+                header.windows_fields.size_of_image < original_header.windows_fields.size_of_image
+            );
+
+            let kept: Vec<_> = original
+                .sections
+                .iter()
+                .filter(|s| !pe_section_name(s).starts_with(".debug"))
+                .collect();
+
+            assert_eq!(pe.sections.len(), kept.len());
+
+            for (section, original_section) in pe.sections.iter().zip(kept) {
+                assert_eq!(pe_section_name(section), pe_section_name(original_section));
+                assert_eq!(section.virtual_address, original_section.virtual_address);
+                assert_eq!(section.virtual_size, original_section.virtual_size);
+                assert!(pe_section_data(&data, section) == pe_section_data(PE, original_section));
+            }
+
+            let symbols = pe.header.coff_header.number_of_symbol_table;
+
+            match mode {
+                StripMode::Debug => {
+                    assert_eq!(symbols, original.header.coff_header.number_of_symbol_table);
+                }
+                StripMode::All => assert_eq!(symbols, 0),
+            }
+        }
+    }
+
+    /// Obtain the names and types of the symbols of a Mach-O binary.
+    fn macho_symbols(macho: &goblin::mach::MachO) -> Vec<(String, u8)> {
+        macho
+            .symbols()
+            .map(|symbol| {
+                let (name, nlist) = symbol.unwrap();
+                (name.to_string(), nlist.n_type)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn macho_loaded_content_unchanged() {
+        let original = goblin::mach::MachO::parse(MACHO, 0).unwrap();
+        let original_symbols = macho_symbols(&original);
+
+        assert!(original_symbols
+            .iter()
+            .any(|(_, n_type)| n_type & N_STAB != 0));
+        assert!(original_symbols
+            .iter()
+            .any(|(_, n_type)| n_type & N_EXT == 0));
+
+        for mode in MODES {
+            let data = strip_binary(MACHO, *mode, None).unwrap();
+            assert!(data.len() <= MACHO.len());
+
+            let macho =
+                goblin::mach::MachO::parse(&data, 0).expect("stripped Mach-O binary should parse");
+
+            assert_eq!(macho.entry, original.entry);
+            assert_eq!(macho.segments.len(), original.segments.len());
+
+            for (segment, original_segment) in macho.segments.iter().zip(original.segments.iter()) {
+                let name = segment.name().unwrap();
+                assert_eq!(name, original_segment.name().unwrap());
+                assert_eq!(segment.vmaddr, original_segment.vmaddr);
+
+                if name == "__LINKEDIT" {
+                    continue;
+                }
+
+                // The header and load commands at the start of __TEXT change,
+                // so only section contents are compared.
+                assert_eq!(segment.fileoff, original_segment.fileoff);
+                assert_eq!(segment.filesize, original_segment.filesize);
+
+                let sections = segment.sections().unwrap();
+                let original_sections = original_segment.sections().unwrap();
+                assert_eq!(sections.len(), original_sections.len());
+
+                for ((section, content), (original_section, original_content)) in
+                    sections.iter().zip(original_sections.iter())
+                {
+                    assert_eq!(section.addr, original_section.addr);
+                    assert!(content == original_content);
+                }
+            }
+
+            let symbols = macho_symbols(&macho);
+
+            assert!(!symbols.iter().any(|(_, n_type)| n_type & N_STAB != 0));
+            assert!(symbols.iter().any(|(name, _)| name == "_start"));
+
+            let externals = |symbols: &[(String, u8)]| {
+                symbols
+                    .iter()
+                    .filter(|(_, n_type)| n_type & N_EXT != 0)
+                    .cloned()
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(externals(&symbols), externals(&original_symbols));
+
+            let locals = symbols
+                .iter()
+                .filter(|(_, n_type)| n_type & (N_EXT | N_STAB) == 0)
+                .count();
+
+            match mode {
+                StripMode::Debug => assert_ne!(locals, 0),
+                StripMode::All => assert_eq!(locals, 0),
+            }
+        }
+    }
+}
@@ -0,0 +1,24 @@
+#!/bin/sh
+# Regenerate the binaries used by tests of the strip module.
+#
+# The ELF binary needs a Linux x86_64 C compiler. The PE and Mach-O
+# binaries need a nightly Rust toolchain (for #![no_core]) and rust-lld.
+
+set -e
+
+cd "$(dirname "$0")"
+
+LLD="$(rustc --print sysroot)/lib/rustlib/$(rustc -vV | sed -n 's/^host: //p')/bin/rust-lld"
+
+gcc -g -O0 -o hello-elf hello.c
+
+rustc +nightly --target x86_64-pc-windows-gnu -C debuginfo=2 -C panic=abort \
+    --emit=obj -o min-pe.o min.rs
+"$LLD" -flavor gnu -m i386pep --entry start --subsystem console -o min.exe min-pe.o
+
+rustc +nightly --target x86_64-apple-darwin -C debuginfo=2 -C panic=abort \
+    --emit=obj -o min-macho.o min.rs
+"$LLD" -flavor darwin -arch x86_64 -platform_version macos 10.15 10.15 \
+    -e _start -o min-macho min-macho.o
+
+rm min-pe.o min-macho.o
@@ -0,0 +1,12 @@
+#include <stdio.h>
+
+int counter = 7;
+
+static int helper(int x) {
+    return x + counter;
+}
+
+int main(void) {
+    printf("%d\n", helper(35));
+    return 0;
+}
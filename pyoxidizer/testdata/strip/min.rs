@@ -0,0 +1,39 @@
+// Minimal program without a runtime, used to produce PE and Mach-O fixtures
+// with debug information without needing the platform's SDK.
+
+#![feature(no_core, lang_items)]
+#![no_core]
+#![no_main]
+#![crate_type = "lib"]
+
+#[lang = "pointee_sized"]
+pub trait PointeeSized {}
+#[lang = "meta_sized"]
+pub trait MetaSized: PointeeSized {}
+#[lang = "sized"]
+pub trait Sized: MetaSized {}
+#[lang = "copy"]
+pub trait Copy {}
+impl Copy for u32 {}
+#[lang = "drop_glue"]
+pub unsafe fn drop_glue<T: PointeeSized>(_: *mut T) {}
+#[lang = "legacy_receiver"]
+pub trait LegacyReceiver {}
+
+#[no_mangle]
+pub static mut COUNTER: u32 = 7;
+
+#[inline(never)]
+fn local_helper(x: u32) -> u32 {
+    x
+}
+
+#[no_mangle]
+pub extern "C" fn helper(x: u32) -> u32 {
+    local_helper(x)
+}
+
+#[no_mangle]
+pub extern "C" fn start() -> u32 {
+    helper(42)
+}